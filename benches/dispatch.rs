@@ -0,0 +1,67 @@
+#[macro_use]
+extern crate criterion;
+extern crate impostor;
+
+use criterion::Criterion;
+
+use impostor::mos6502::asm::assemble;
+use impostor::mos6502::MOS6502;
+use impostor::ram::{FixedRam, Ram};
+use impostor::{AddressBusIO, Clock, Debug};
+
+fn program<B: AddressBusIO<u16, u8>>(bus: B) -> MOS6502<B> {
+    let program = assemble(
+        "
+        .org $0000
+start:  ldx #$00
+loop:   lda $0200,x
+        clc
+        adc $10
+        sta $10
+        inx
+        bne loop
+        jmp start
+        ",
+    )
+    .unwrap();
+    let mut cpu = MOS6502::new(bus);
+    for (address, byte) in program.bytes.iter().enumerate() {
+        cpu.inject(address as u16, *byte);
+    }
+    cpu
+}
+
+// run once plain and once with --features match_dispatch (or unchecked),
+// criterion reports the change against the previous run
+fn step(c: &mut Criterion) {
+    let mut cpu = program(Ram::new(0x10000));
+    c.bench_function("mos6502 step", |b| {
+        b.iter(|| {
+            for _ in 0..1000 {
+                cpu.step();
+            }
+        })
+    });
+
+    let mut cpu = program(Ram::new(0x10000));
+    cpu.enable_decode_cache();
+    c.bench_function("mos6502 step cached", |b| {
+        b.iter(|| {
+            for _ in 0..1000 {
+                cpu.step();
+            }
+        })
+    });
+
+    let mut cpu = program(FixedRam::<u8, 0x10000>::new());
+    c.bench_function("mos6502 step fixed ram", |b| {
+        b.iter(|| {
+            for _ in 0..1000 {
+                cpu.step();
+            }
+        })
+    });
+}
+
+criterion_group!(benches, step);
+criterion_main!(benches);
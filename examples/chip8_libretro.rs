@@ -0,0 +1,9 @@
+#[macro_use]
+extern crate impostor;
+
+use impostor::chip8::{load, Chip8};
+use impostor::ram::Ram;
+
+// cargo build --example chip8_libretro --features libretro
+// retroarch -L target/debug/examples/libchip8_libretro.so breakout_carmelo_cortez_1979.ch8
+libretro_core!(Chip8<Ram<u8>>, load, "impostor chip8", "0.1.0", "ch8");
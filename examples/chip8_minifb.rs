@@ -0,0 +1,34 @@
+extern crate impostor;
+
+use impostor::chip8::{Chip8, FONTS};
+use impostor::frontend::minifb;
+#[cfg(feature = "lua")]
+use impostor::machine::script::Scripted;
+use impostor::ram::Ram;
+
+use std::env;
+use std::fs;
+
+// cargo run --example chip8_minifb --features minifb -- examples/breakout_carmelo_cortez_1979.ch8
+// with --features minifb,lua a lua script can be given after the rom
+fn main() {
+    let args: Vec<String> = env::args().collect();
+
+    let mut ram = Ram::new(4096);
+    ram.fill(FONTS.to_vec(), 0x000);
+    ram.fill(fs::read(&*args[1]).unwrap(), 0x200);
+
+    let mut chip8 = Chip8::new(ram);
+
+    #[cfg(feature = "lua")]
+    {
+        if let Some(filename) = args.get(2) {
+            let mut scripted = Scripted::new(chip8);
+            scripted.load_file(filename).unwrap();
+            minifb::run(&mut scripted, "chip8", 16).unwrap();
+            return;
+        }
+    }
+
+    minifb::run(&mut chip8, "chip8", 16).unwrap();
+}
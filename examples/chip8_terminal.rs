@@ -0,0 +1,21 @@
+extern crate impostor;
+
+use impostor::chip8::{Chip8, FONTS};
+use impostor::frontend::terminal;
+use impostor::ram::Ram;
+
+use std::env;
+use std::fs;
+
+// cargo run --example chip8_terminal --features crossterm -- examples/breakout_carmelo_cortez_1979.ch8
+fn main() {
+    let args: Vec<String> = env::args().collect();
+
+    let mut ram = Ram::new(4096);
+    ram.fill(FONTS.to_vec(), 0x000);
+    ram.fill(fs::read(&*args[1]).unwrap(), 0x200);
+
+    let mut chip8 = Chip8::new(ram);
+
+    terminal::run(&mut chip8, true).unwrap();
+}
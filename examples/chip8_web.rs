@@ -0,0 +1,27 @@
+extern crate impostor;
+extern crate wasm_bindgen;
+
+use impostor::chip8::{Chip8, FONTS};
+use impostor::frontend::web;
+use impostor::ram::Ram;
+
+use wasm_bindgen::prelude::*;
+
+// cargo build --example chip8_web --features web --target wasm32-unknown-unknown
+// wasm-bindgen --target web --out-dir pkg target/wasm32-unknown-unknown/debug/examples/chip8_web.wasm
+//
+// <canvas id="screen" style="width: 640px; image-rendering: pixelated"></canvas>
+// <script type="module">
+//   import init, { start } from "./pkg/chip8_web.js";
+//   const rom = await fetch("breakout_carmelo_cortez_1979.ch8");
+//   await init();
+//   start("screen", new Uint8Array(await rom.arrayBuffer()));
+// </script>
+#[wasm_bindgen]
+pub fn start(canvas_id: &str, rom: &[u8]) -> Result<(), JsValue> {
+    let mut ram = Ram::new(4096);
+    ram.fill(FONTS.to_vec(), 0x000);
+    ram.fill(rom.to_vec(), 0x200);
+
+    web::run(Chip8::new(ram), canvas_id)
+}
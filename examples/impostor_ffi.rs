@@ -0,0 +1,5 @@
+extern crate impostor;
+
+// a shared library exporting the C ABI of include/impostor.h
+// cargo build --example impostor_ffi --features ffi
+pub use impostor::ffi::*;
@@ -0,0 +1,64 @@
+/* C ABI of the impostor ffi module, build it with
+ * cargo build --release --example impostor_ffi --features ffi
+ * and link against target/release/examples/libimpostor_ffi.so */
+
+#ifndef IMPOSTOR_H
+#define IMPOSTOR_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* printable ascii characters are their own key codes */
+#define IMPOSTOR_KEY_UP 0x100
+#define IMPOSTOR_KEY_DOWN 0x101
+#define IMPOSTOR_KEY_LEFT 0x102
+#define IMPOSTOR_KEY_RIGHT 0x103
+#define IMPOSTOR_KEY_ENTER 0x104
+#define IMPOSTOR_KEY_ESCAPE 0x105
+#define IMPOSTOR_KEY_BACKSPACE 0x106
+#define IMPOSTOR_KEY_TAB 0x107
+#define IMPOSTOR_KEY_SHIFT 0x108
+#define IMPOSTOR_KEY_CONTROL 0x109
+#define IMPOSTOR_KEY_ALT 0x10a
+#define IMPOSTOR_KEY_FUNCTION(n) (0x200 + (n))
+#define IMPOSTOR_KEY_BUTTON(n) (0x300 + (n))
+
+typedef struct impostor_machine impostor_machine;
+
+/* a machine by name ("chip8"), NULL when unknown. handles are not thread
+ * safe and must be released with impostor_destroy */
+impostor_machine *impostor_create(const char *name);
+void impostor_destroy(impostor_machine *machine);
+
+/* (re)starts the machine with a rom, the data is copied */
+bool impostor_load_rom(impostor_machine *machine, const uint8_t *data, size_t size);
+
+void impostor_step_frame(impostor_machine *machine);
+uint32_t impostor_frame_rate(impostor_machine *machine);
+
+/* returned buffers belong to the handle and stay valid until the next call
+ * on it: rgb pixels, width * height * 3 bytes */
+const uint8_t *impostor_framebuffer(impostor_machine *machine, size_t *width, size_t *height);
+
+/* mono samples in [-1, 1] produced since the last call, 0 rate without audio */
+uint32_t impostor_sample_rate(impostor_machine *machine);
+const float *impostor_audio(impostor_machine *machine, size_t *count);
+
+/* false on unknown key codes or without a loaded rom */
+bool impostor_set_input(impostor_machine *machine, uint32_t key, bool pressed);
+
+/* returns the state size (0 if not supported), buffer is written only when
+ * size is large enough */
+size_t impostor_save_state(impostor_machine *machine, uint8_t *buffer, size_t size);
+bool impostor_load_state(impostor_machine *machine, const uint8_t *data, size_t size);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
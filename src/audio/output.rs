@@ -0,0 +1,134 @@
+extern crate cpal;
+
+use self::cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use self::cpal::{SampleFormat, Stream, StreamConfig};
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use audio::resample::Resampler;
+use audio::ring::SampleRing;
+
+// plays the samples of any ring on the default output device, queued audio
+// beyond the requested latency is dropped (the oldest first) to keep the
+// output in sync with the emulation
+pub struct AudioOutput {
+    _stream: Stream,
+    queue: Arc<Mutex<VecDeque<f32>>>,
+    resampler: Option<(u32, Resampler)>,
+    buffer: Vec<f32>,
+    resampled: Vec<f32>,
+    pub sample_rate: u32,
+    max_queued: usize,
+}
+
+fn fill<T: Copy, F: Fn(f32) -> T>(
+    data: &mut [T],
+    channels: usize,
+    queue: &Mutex<VecDeque<f32>>,
+    convert: F,
+) {
+    let mut queue = queue.lock().unwrap();
+    for frame in data.chunks_mut(channels) {
+        // silence on underrun
+        let sample = convert(queue.pop_front().unwrap_or(0.0));
+        for slot in frame.iter_mut() {
+            *slot = sample;
+        }
+    }
+}
+
+impl AudioOutput {
+    pub fn new(latency: Duration) -> Result<AudioOutput, String> {
+        let host = cpal::default_host();
+        let device = host
+            .default_output_device()
+            .ok_or_else(|| "no audio output device".to_string())?;
+        let supported = device
+            .default_output_config()
+            .map_err(|err| err.to_string())?;
+        let format = supported.sample_format();
+        let config: StreamConfig = supported.into();
+        let channels = usize::from(config.channels);
+        let sample_rate = config.sample_rate;
+
+        let queue = Arc::new(Mutex::new(VecDeque::new()));
+        let stream_queue = Arc::clone(&queue);
+        let on_error = |err| eprintln!("audio output error: {}", err);
+
+        let stream = match format {
+            SampleFormat::F32 => device.build_output_stream(
+                config,
+                move |data: &mut [f32], _: &cpal::OutputCallbackInfo| {
+                    fill(data, channels, &stream_queue, |sample| sample)
+                },
+                on_error,
+                None,
+            ),
+            SampleFormat::I16 => device.build_output_stream(
+                config,
+                move |data: &mut [i16], _: &cpal::OutputCallbackInfo| {
+                    fill(data, channels, &stream_queue, |sample| {
+                        (sample.clamp(-1.0, 1.0) * f32::from(i16::MAX)) as i16
+                    })
+                },
+                on_error,
+                None,
+            ),
+            SampleFormat::U16 => device.build_output_stream(
+                config,
+                move |data: &mut [u16], _: &cpal::OutputCallbackInfo| {
+                    fill(data, channels, &stream_queue, |sample| {
+                        ((sample.clamp(-1.0, 1.0) + 1.0) * 32767.5) as u16
+                    })
+                },
+                on_error,
+                None,
+            ),
+            format => return Err(format!("unsupported sample format {:?}", format)),
+        }
+        .map_err(|err| err.to_string())?;
+        stream.play().map_err(|err| err.to_string())?;
+
+        let max_queued = (f64::from(sample_rate) * latency.as_secs_f64()) as usize;
+        Ok(AudioOutput {
+            _stream: stream,
+            queue,
+            resampler: None,
+            buffer: vec![0.0; 4096],
+            resampled: Vec::new(),
+            sample_rate,
+            max_queued: max_queued.max(1),
+        })
+    }
+
+    // moves everything in the ring to the device
+    pub fn feed(&mut self, ring: &mut SampleRing) {
+        let input_rate = ring.sample_rate;
+        let rebuild = match self.resampler {
+            Some((rate, _)) => rate != input_rate,
+            None => true,
+        };
+        if rebuild {
+            self.resampler = Some((input_rate, Resampler::new(input_rate, self.sample_rate)));
+        }
+
+        self.resampled.clear();
+        loop {
+            let amount = ring.drain(&mut self.buffer);
+            if amount == 0 {
+                break;
+            }
+            if let Some((_, ref mut resampler)) = self.resampler {
+                resampler.process(&self.buffer[0..amount], &mut self.resampled);
+            }
+        }
+
+        let mut queue = self.queue.lock().unwrap();
+        queue.extend(self.resampled.iter());
+        while queue.len() > self.max_queued {
+            queue.pop_front();
+        }
+    }
+}
@@ -0,0 +1,33 @@
+// linear interpolation between consecutive input samples, the position is
+// kept between calls so chunk boundaries do not click
+pub struct Resampler {
+    step: f64,
+    position: f64,
+    previous: f32,
+    current: f32,
+}
+
+impl Resampler {
+    pub fn new(input_rate: u32, output_rate: u32) -> Resampler {
+        Resampler {
+            step: f64::from(input_rate) / f64::from(output_rate),
+            position: 0.0,
+            previous: 0.0,
+            current: 0.0,
+        }
+    }
+
+    pub fn process(&mut self, input: &[f32], output: &mut Vec<f32>) {
+        for sample in input {
+            self.previous = self.current;
+            self.current = *sample;
+            // emit every output sample falling between previous and current
+            while self.position < 1.0 {
+                let t = self.position as f32;
+                output.push(self.previous + (self.current - self.previous) * t);
+                self.position += self.step;
+            }
+            self.position -= 1.0;
+        }
+    }
+}
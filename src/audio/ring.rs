@@ -0,0 +1,47 @@
+use std::collections::VecDeque;
+
+// mono samples produced by the emulated devices and consumed by the host
+// audio backend, the oldest ones are dropped when the consumer is too slow
+pub struct SampleRing {
+    samples: VecDeque<f32>,
+    capacity: usize,
+    pub sample_rate: u32,
+}
+
+impl SampleRing {
+    pub fn new(sample_rate: u32, capacity: usize) -> SampleRing {
+        SampleRing {
+            samples: VecDeque::with_capacity(capacity),
+            capacity,
+            sample_rate,
+        }
+    }
+
+    pub fn push(&mut self, sample: f32) {
+        if self.samples.len() == self.capacity {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+    }
+
+    // returns the number of samples copied in buffer
+    pub fn drain(&mut self, buffer: &mut [f32]) -> usize {
+        let amount = buffer.len().min(self.samples.len());
+        for (slot, sample) in buffer.iter_mut().zip(self.samples.drain(..amount)) {
+            *slot = sample;
+        }
+        amount
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.samples.clear();
+    }
+}
@@ -0,0 +1,31 @@
+use audio::resample::Resampler;
+use audio::ring::SampleRing;
+
+#[test]
+fn ring_drops_oldest() {
+    let mut ring = SampleRing::new(44100, 3);
+    for sample in &[0.1, 0.2, 0.3, 0.4] {
+        ring.push(*sample);
+    }
+    let mut buffer = [0.0; 8];
+    assert_eq!(ring.drain(&mut buffer), 3);
+    assert_eq!(&buffer[0..3], &[0.2, 0.3, 0.4]);
+    assert!(ring.is_empty());
+}
+
+#[test]
+fn resample_double_rate() {
+    let mut resampler = Resampler::new(22050, 44100);
+    let mut output = Vec::new();
+    resampler.process(&[1.0, 1.0], &mut output);
+    resampler.process(&[0.0], &mut output);
+    assert_eq!(output, vec![0.0, 0.5, 1.0, 1.0, 1.0, 0.5]);
+}
+
+#[test]
+fn resample_half_rate() {
+    let mut resampler = Resampler::new(44100, 22050);
+    let mut output = Vec::new();
+    resampler.process(&[0.1, 0.2, 0.3, 0.4, 0.5], &mut output);
+    assert_eq!(output.len(), 3);
+}
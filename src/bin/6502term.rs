@@ -28,9 +28,10 @@ fn main() {
     memory_controller.map(0x8000, 0x8fff, &mut rom);
     memory_controller.map(0x2000, 0x2007, &mut term);
 
-    let mut cpu = MOS6502::new(memory_controller);
-    cpu.pc = 0x8000;
-    cpu.debug = true;
+    let mut cpu = MOS6502::builder(memory_controller)
+        .pc(0x8000)
+        .debug(true)
+        .build();
 
     loop {
         cpu.step();
@@ -5,8 +5,10 @@ use clap::{App, Arg};
 
 use impostor::adapter::BusAdapter;
 use impostor::audio::Piano;
+use impostor::examples::Demo;
 use impostor::memcontroller::MemoryControllerSmart;
-use impostor::mos6502::MOS6502;
+use impostor::mos6502::stack::StackViolation;
+use impostor::mos6502::{InterruptBreakpoint, MOS6502};
 use impostor::ram::Ram;
 use impostor::random::Random;
 use impostor::rom::Rom;
@@ -26,10 +28,13 @@ use impostor::Debug;
 use impostor::Interrupt;
 
 use impostor::debugger::debugger;
+use impostor::debugger::remote::RemoteServer;
 
 use std::cell::RefCell;
 use std::fs;
 use std::rc::Rc;
+use std::thread;
+use std::time::Duration;
 
 #[derive(Copy, Clone)]
 struct Sprite {
@@ -447,11 +452,62 @@ fn main() {
                 .value_name("address")
                 .help("set breakpoint to the specified comma separated list of addresses"),
         )
-        .arg(Arg::with_name("romfile").index(1).required(true))
+        .arg(
+            Arg::with_name("interrupt-breakpoint")
+                .required(false)
+                .long("interrupt-breakpoint")
+                .takes_value(true)
+                .value_name("kinds")
+                .help("break on entry of the comma separated list of nmi,irq,brk,reset"),
+        )
+        .arg(
+            Arg::with_name("uninitialized-reads")
+                .long("uninitialized-reads")
+                .help("break when the program reads ram never written since reset"),
+        )
+        .arg(
+            Arg::with_name("self-modifying")
+                .long("self-modifying")
+                .help("report writes to addresses already executed as code"),
+        )
+        .arg(
+            Arg::with_name("step-diff")
+                .long("step-diff")
+                .help("print registers, flags and memory changed by each instruction"),
+        )
+        .arg(
+            Arg::with_name("remote")
+                .required(false)
+                .long("remote")
+                .takes_value(true)
+                .value_name("address:port")
+                .help("serve the tcp remote debugging protocol"),
+        )
+        .arg(
+            Arg::with_name("idle-skip")
+                .long("idle-skip")
+                .help("skip to the next vblank when the CPU is spinning in an idle loop"),
+        )
+        .arg(
+            Arg::with_name("stack-guard")
+                .required(false)
+                .long("stack-guard")
+                .takes_value(true)
+                .value_name("sp")
+                .help("break on stack wraps, smashed return addresses or sp below the threshold"),
+        )
+        .arg(
+            Arg::with_name("demo")
+                .required(false)
+                .long("demo")
+                .takes_value(true)
+                .value_name("name")
+                .possible_values(&["memtest", "hello", "beep"])
+                .help("run a built-in program instead of a rom file"),
+        )
+        .arg(Arg::with_name("romfile").index(1).required_unless("demo"))
         .get_matches();
 
-    let romfile = matches.value_of("romfile").unwrap();
-
     let pc: u16 = match to_number(matches.value_of("pc").unwrap()) {
         Ok(value) => value,
         Err(_) => panic!("invalid address format for pc"),
@@ -485,7 +541,10 @@ fn main() {
         }
     }
 
-    let mut rom = Rom::new(fs::read(romfile).unwrap());
+    let mut rom = match matches.value_of("demo") {
+        Some(name) => Rom::new(Demo::from_name(name).unwrap().rom()),
+        None => Rom::new(fs::read(matches.value_of("romfile").unwrap()).unwrap()),
+    };
 
     let ram = Rc::new(RefCell::new(Ram::new(4096)));
 
@@ -539,43 +598,138 @@ fn main() {
         memory_controller.map_shared(0x200a, 0x200d, borrowed_dma);
     }
 
-    let mut cpu = MOS6502::new(memory_controller);
-    cpu.pc = pc;
-    cpu.debug = matches.is_present("debug");
+    let mut cpu = MOS6502::builder(memory_controller)
+        .pc(pc)
+        .debug(matches.is_present("debug"))
+        .build();
 
     let block_nmi = matches.is_present("no-vblank");
 
-    let mut last_ticks: u64 = 0;
-
     let ticks_per_frame = hz / vsync;
 
     let mut in_debugger = false;
 
     cpu.set_code_breakpoint(matches.is_present("code-breakpoint"));
 
+    if matches.is_present("interrupt-breakpoint") {
+        for kind in matches.value_of("interrupt-breakpoint").unwrap().split(',') {
+            match kind {
+                "nmi" => cpu.set_interrupt_breakpoint(InterruptBreakpoint::Nmi, true),
+                "irq" => cpu.set_interrupt_breakpoint(InterruptBreakpoint::Irq, true),
+                "brk" => cpu.set_interrupt_breakpoint(InterruptBreakpoint::Brk, true),
+                "reset" => cpu.set_interrupt_breakpoint(InterruptBreakpoint::Reset, true),
+                _ => panic!("invalid interrupt breakpoint {}", kind),
+            }
+        }
+    }
+
+    if matches.is_present("uninitialized-reads") {
+        cpu.enable_uninitialized_guard(0x0000, 0x1fff, true);
+    }
+
+    if matches.is_present("self-modifying") {
+        cpu.enable_coverage();
+    }
+
+    if matches.is_present("step-diff") {
+        cpu.enable_step_diffs();
+    }
+
+    if matches.is_present("idle-skip") {
+        cpu.enable_idle_detection(16);
+    }
+
+    if matches.is_present("stack-guard") {
+        match to_number(matches.value_of("stack-guard").unwrap()) {
+            Ok(threshold) => cpu.enable_stack_guard(threshold),
+            Err(_) => panic!("invalid number format for stack-guard"),
+        }
+    }
+
+    let mut remote: Option<RemoteServer<u16>> = match matches.value_of("remote") {
+        Some(address) => match RemoteServer::bind(address) {
+            Ok(server) => Some(server),
+            Err(err) => panic!("unable to listen on {}: {}", address, err),
+        },
+        None => None,
+    };
+
     loop {
         let mut ticks_counter = i64::from(ticks_per_frame);
         while ticks_counter > 0 {
             if cpu.is_code_breakpoint_requested() || breakpoints.contains(&cpu.pc) {
                 in_debugger = true;
             }
-            if in_debugger {
+            for read in cpu.take_uninitialized_reads() {
+                println!(
+                    "uninitialized read of ${:04X} at ${:04X}",
+                    read.address, read.pc
+                );
+            }
+            for modification in cpu.take_self_modifications() {
+                println!(
+                    "code at ${:04X} modified at ${:04X}",
+                    modification.address, modification.pc
+                );
+            }
+            for violation in cpu.take_stack_violations() {
+                match violation {
+                    StackViolation::Overflow { pc } => println!("stack overflow at ${:04X}", pc),
+                    StackViolation::Underflow { pc } => {
+                        println!("stack underflow at ${:04X}", pc)
+                    }
+                    StackViolation::Threshold { pc, sp } => {
+                        println!("stack pointer ${:02X} below threshold at ${:04X}", sp, pc)
+                    }
+                    StackViolation::Smash { pc, address } => println!(
+                        "return address at ${:04X} overwritten at ${:04X}",
+                        address, pc
+                    ),
+                }
+                in_debugger = true;
+            }
+            if let Some(ref mut remote) = remote {
+                remote.check(&mut cpu, in_debugger);
+                in_debugger = false;
+                while remote.is_paused() {
+                    thread::sleep(Duration::from_millis(10));
+                    remote.poll(&mut cpu);
+                }
+            } else if in_debugger {
                 in_debugger = debugger(&mut cpu);
             }
 
-            cpu.step();
+            let cycles = cpu.step();
+            if let Some(fault) = cpu.take_fault() {
+                println!("cpu halted: {}", fault);
+            }
 
-            if let Some(block_device_dma) = dma.as_mut() { block_device_dma.borrow_mut().step() }
+            if let Some(block_device_dma) = dma.as_mut() {
+                block_device_dma.borrow_mut().step();
+            }
 
             if cpu.debug {
                 println!("[{:04X}] {}", cpu.debug_pc, cpu.debug_line);
             }
-            ticks_counter -= (cpu.ticks - last_ticks) as i64;
-            last_ticks = cpu.ticks;
+            if let Some(diff) = cpu.take_step_diff() {
+                println!("{}", diff);
+            }
+            ticks_counter -= cycles as i64;
+
+            // nothing but the vblank can wake the cpu up (unless a dma transfer is in place)
+            if cpu.is_idle() && dma.is_none() && ticks_counter > 0 {
+                cpu.ticks += ticks_counter as u64;
+                ticks_counter = 0;
+            }
         }
         if aiv_framebuffer.borrow_mut().vblank() {
             break;
         }
+        if let Some(ref mut remote) = remote {
+            let aiv_framebuffer = aiv_framebuffer.borrow();
+            let framebuffer = &aiv_framebuffer.framebuffer;
+            remote.set_screenshot(framebuffer.width, framebuffer.height, &framebuffer.pixels);
+        }
         // avoid NMI if the related vector is not in the rom
         if !block_nmi && cpu.read(0xfffb) >= 0xc0 {
             cpu.raise(6);
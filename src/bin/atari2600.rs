@@ -65,12 +65,12 @@ fn main() {
     memory_controller.mirror(0xd000, 0xdfff, 0x1000);
     memory_controller.mirror(0xf000, 0xffff, 0x1000);
 
-    let mut cpu = MOS6502::new(memory_controller);
-    cpu.pc = 0xf000;
-    cpu.debug = true;
+    let mut cpu = MOS6502::builder(memory_controller)
+        .pc(0xf000)
+        .trace(|pc, line| println!("[{:04X}] {}", pc, line))
+        .build();
 
     loop {
         cpu.step();
-        println!("[{:04X}] {}", cpu.debug_pc, cpu.debug_line);
     }
 }
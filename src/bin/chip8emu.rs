@@ -1,7 +1,7 @@
 extern crate impostor;
 
 use impostor::audio::Beeper;
-use impostor::chip8::Chip8;
+use impostor::chip8::{Chip8, FONTS};
 use impostor::graphics::{Framebuffer, Screen, WindowEvent};
 use impostor::input::{ElementState, VirtualKeyCode};
 use impostor::ram::Ram;
@@ -16,26 +16,7 @@ fn main() {
 
     let mut ram = Ram::new(4096);
 
-    let fonts = [
-        0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
-        0x20, 0x60, 0x20, 0x20, 0x70, // 1
-        0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
-        0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
-        0x90, 0x90, 0xF0, 0x10, 0x10, // 4
-        0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
-        0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
-        0xF0, 0x10, 0x20, 0x40, 0x40, // 7
-        0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
-        0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
-        0xF0, 0x90, 0xF0, 0x90, 0x90, // A
-        0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
-        0xF0, 0x80, 0x80, 0x80, 0xF0, // C
-        0xE0, 0x90, 0x90, 0x90, 0xE0, // D
-        0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
-        0xF0, 0x80, 0xF0, 0x80, 0x80, // F
-    ];
-
-    ram.fill(fonts.to_vec(), 0x000);
+    ram.fill(FONTS.to_vec(), 0x000);
 
     ram.fill(fs::read(&*args[1]).unwrap(), 0x200);
 
@@ -0,0 +1,295 @@
+extern crate clap;
+extern crate impostor;
+
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+
+use impostor::debugger::debugger;
+use impostor::machine::{detect, load, Machine, MACHINES};
+use impostor::mos6502::asm::assemble;
+use impostor::mos6502::MOS6502;
+use impostor::ram::Ram;
+use impostor::utils::to_number;
+use impostor::Debug;
+
+use std::fs;
+use std::path::Path;
+use std::process;
+
+fn read(filename: &str) -> Result<Vec<u8>, String> {
+    fs::read(filename).map_err(|err| format!("unable to read {}: {}", filename, err))
+}
+
+fn address(matches: &ArgMatches, name: &str) -> Result<u16, String> {
+    let value = matches.value_of(name).unwrap();
+    to_number::<u16>(value).map_err(|err| format!("invalid {} {}: {}", name, value, err))
+}
+
+fn machine(matches: &ArgMatches) -> Result<Option<Box<dyn Machine>>, String> {
+    let filename = matches.value_of("rom").unwrap();
+    let name = match matches.value_of("machine") {
+        Some(name) => name,
+        None => match detect(filename) {
+            Some(name) => name,
+            None => return Ok(None),
+        },
+    };
+    match load(name, &read(filename)?) {
+        Ok(machine) => Ok(Some(machine)),
+        Err(error) => Err(format!(
+            "{} is not a valid {} rom: {}",
+            filename, name, error
+        )),
+    }
+}
+
+// the first frontend built in, in order of preference
+#[cfg(feature = "minifb")]
+fn frontend<M: Machine>(machine: &mut M, title: &str, scale: u32) -> Result<(), String> {
+    impostor::frontend::minifb::run(machine, title, scale)
+}
+
+#[cfg(all(feature = "sdl2", not(feature = "minifb")))]
+fn frontend<M: Machine>(machine: &mut M, title: &str, scale: u32) -> Result<(), String> {
+    impostor::frontend::sdl::run(machine, title, scale)
+}
+
+#[cfg(all(feature = "crossterm", not(any(feature = "minifb", feature = "sdl2"))))]
+fn frontend<M: Machine>(machine: &mut M, _title: &str, _scale: u32) -> Result<(), String> {
+    impostor::frontend::terminal::run(machine, true)
+}
+
+#[cfg(not(any(feature = "minifb", feature = "sdl2", feature = "crossterm")))]
+fn frontend<M: Machine>(_machine: &mut M, _title: &str, _scale: u32) -> Result<(), String> {
+    Err("built without a frontend, enable the minifb, sdl2 or crossterm feature".to_string())
+}
+
+fn run(matches: &ArgMatches) -> Result<(), String> {
+    let scale = matches
+        .value_of("scale")
+        .unwrap()
+        .parse()
+        .map_err(|err| format!("invalid scale: {}", err))?;
+    match machine(matches)? {
+        Some(mut machine) => frontend(&mut machine, matches.value_of("rom").unwrap(), scale),
+        None => Err(format!(
+            "unable to detect the machine, use --machine ({})",
+            MACHINES
+                .iter()
+                .map(|machine| machine.0)
+                .collect::<Vec<_>>()
+                .join(", ")
+        )),
+    }
+}
+
+// monitor commands: p, w, j, d, s to step, q or r to leave
+fn monitor<V: Debug<u16, u8> + ?Sized>(debugged: &mut V) {
+    while debugger(debugged) {
+        debugged.next();
+    }
+}
+
+#[cfg(feature = "tui")]
+fn tui(matches: &ArgMatches, machine: &mut Option<Box<dyn Machine>>) -> Option<Result<(), String>> {
+    if !matches.is_present("tui") {
+        return None;
+    }
+    Some(match *machine {
+        Some(ref mut machine) => impostor::frontend::tui::run(machine),
+        None => Err("the tui debugger needs a machine, use --machine".to_string()),
+    })
+}
+
+#[cfg(not(feature = "tui"))]
+fn tui(
+    _matches: &ArgMatches,
+    _machine: &mut Option<Box<dyn Machine>>,
+) -> Option<Result<(), String>> {
+    None
+}
+
+fn debug(matches: &ArgMatches) -> Result<(), String> {
+    let mut machine = machine(matches)?;
+    if let Some(result) = tui(matches, &mut machine) {
+        return result;
+    }
+    match machine {
+        Some(mut machine) => match machine.debug() {
+            Some(debugged) => monitor(debugged),
+            None => return Err("the machine cannot be debugged".to_string()),
+        },
+        // anything else is taken as a raw 6502 binary
+        None => {
+            let origin = address(matches, "origin")?;
+            let data = read(matches.value_of("rom").unwrap())?;
+            if usize::from(origin) + data.len() > 0x10000 {
+                return Err("the binary does not fit in 64k from origin".to_string());
+            }
+            let mut ram = Ram::new(0x10000);
+            ram.fill(data, usize::from(origin));
+            let mut cpu = MOS6502::builder(ram).pc(origin).build();
+            monitor(&mut cpu);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(feature = "websocket")]
+fn serve(matches: &ArgMatches) -> Result<(), String> {
+    let machine = match matches.value_of("rom") {
+        Some(_) => machine(matches)?,
+        None => None,
+    };
+    let address = matches.value_of("listen").unwrap();
+    println!("listening on ws://{}", address);
+    impostor::frontend::websocket::serve(address, machine)
+}
+
+fn asm(matches: &ArgMatches) -> Result<(), String> {
+    let filename = matches.value_of("source").unwrap();
+    let source = fs::read_to_string(filename)
+        .map_err(|err| format!("unable to read {}: {}", filename, err))?;
+    let assembly = assemble(&source)?;
+    let output = match matches.value_of("output") {
+        Some(output) => output.to_string(),
+        None => Path::new(filename)
+            .with_extension("bin")
+            .to_string_lossy()
+            .into_owned(),
+    };
+    fs::write(&output, &assembly.bytes)
+        .map_err(|err| format!("unable to write {}: {}", output, err))?;
+    println!(
+        "{}: {} bytes at ${:04X}",
+        output,
+        assembly.bytes.len(),
+        assembly.origin
+    );
+    Ok(())
+}
+
+fn disasm(matches: &ArgMatches) -> Result<(), String> {
+    let origin = address(matches, "origin")?;
+    let data = read(matches.value_of("binary").unwrap())?;
+    let end = usize::from(origin) + data.len();
+    if end > 0x10000 {
+        return Err("the binary does not fit in 64k from origin".to_string());
+    }
+    let mut ram = Ram::new(0x10000);
+    ram.fill(data, usize::from(origin));
+    let mut cpu = MOS6502::new(ram);
+
+    let mut address = usize::from(origin);
+    while address < end {
+        let (text, length) = cpu.disassemble(address as u16);
+        let bytes: Vec<String> = (0..usize::from(length))
+            .map(|offset| format!("{:02x}", cpu.inspect((address + offset) as u16)))
+            .collect();
+        println!("${:04X}  {:<9} {}", address, bytes.join(" "), text);
+        address += usize::from(length);
+    }
+    Ok(())
+}
+
+fn main() {
+    let rom = Arg::with_name("rom").required(true).help("rom to load");
+    let machine = Arg::with_name("machine")
+        .long("machine")
+        .takes_value(true)
+        .value_name("name")
+        .help("machine to use instead of detecting it from the rom extension");
+    let origin = Arg::with_name("origin")
+        .long("origin")
+        .takes_value(true)
+        .value_name("address")
+        .help("load address of raw 6502 binaries")
+        .default_value("0x0000");
+
+    let debug_command = SubCommand::with_name("debug")
+        .about("open a rom in the monitor")
+        .arg(rom.clone())
+        .arg(machine.clone())
+        .arg(origin.clone());
+    #[cfg(feature = "tui")]
+    let debug_command = debug_command.arg(
+        Arg::with_name("tui")
+            .long("tui")
+            .help("use the terminal ui debugger instead of the monitor"),
+    );
+
+    let app = App::new("impostor")
+        .version("0.1")
+        .about("Emulators toolkit")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .subcommand(
+            SubCommand::with_name("run")
+                .about("run a rom")
+                .arg(rom)
+                .arg(machine)
+                .arg(
+                    Arg::with_name("scale")
+                        .long("scale")
+                        .takes_value(true)
+                        .value_name("factor")
+                        .help("window scale factor")
+                        .default_value("8"),
+                ),
+        )
+        .subcommand(debug_command)
+        .subcommand(
+            SubCommand::with_name("asm")
+                .about("assemble 6502 source")
+                .arg(Arg::with_name("source").required(true))
+                .arg(
+                    Arg::with_name("output")
+                        .short("o")
+                        .takes_value(true)
+                        .value_name("file")
+                        .help("output binary (defaults to the source with a .bin extension)"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("disasm")
+                .about("disassemble a raw 6502 binary")
+                .arg(Arg::with_name("binary").required(true))
+                .arg(origin),
+        );
+
+    #[cfg(feature = "websocket")]
+    let app = app.subcommand(
+        SubCommand::with_name("serve")
+            .about("run headless, controlled through a websocket")
+            .arg(Arg::with_name("rom").help("rom to load, clients can load one too"))
+            .arg(
+                Arg::with_name("machine")
+                    .long("machine")
+                    .takes_value(true)
+                    .value_name("name")
+                    .help("machine to use instead of detecting it from the rom extension"),
+            )
+            .arg(
+                Arg::with_name("listen")
+                    .long("listen")
+                    .takes_value(true)
+                    .value_name("address:port")
+                    .default_value("127.0.0.1:8502"),
+            ),
+    );
+
+    let matches = app.get_matches();
+
+    let result = match matches.subcommand() {
+        ("run", Some(matches)) => run(matches),
+        ("debug", Some(matches)) => debug(matches),
+        ("asm", Some(matches)) => asm(matches),
+        ("disasm", Some(matches)) => disasm(matches),
+        #[cfg(feature = "websocket")]
+        ("serve", Some(matches)) => serve(matches),
+        _ => Ok(()),
+    };
+
+    if let Err(err) = result {
+        eprintln!("error: {}", err);
+        process::exit(1);
+    }
+}
@@ -1,5 +1,35 @@
-use rand;
-use {AddressBusIO, Clock};
+use audio::ring::SampleRing;
+use events::{CpuEvent, Observers};
+use machine::{Key, Machine};
+use ram::Ram;
+use sources::{EntropySource, HostEntropy};
+use {read_block, write_block, AddressBusIO, Clock, Debug, Error, Reset};
+
+pub const FONTS: [u8; 80] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
+    0x20, 0x60, 0x20, 0x20, 0x70, // 1
+    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
+    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
+    0x90, 0x90, 0xF0, 0x10, 0x10, // 4
+    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
+    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
+    0xF0, 0x10, 0x20, 0x40, 0x40, // 7
+    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
+    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
+    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
+    0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
+    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
+    0xE0, 0x90, 0x90, 0x90, 0xE0, // D
+    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
+    0xF0, 0x80, 0xF0, 0x80, 0x80, // F
+];
+
+const INSTRUCTIONS_PER_FRAME: usize = 10;
+const SAMPLE_RATE: u32 = 44100;
+const BEEP_FREQUENCY: u32 = 880;
+const MEMORY_SIZE: u16 = 0x1000;
+// registers, index, pc, sp, timers, stack, screen and memory
+const STATE_SIZE: usize = 16 + 2 + 2 + 3 + 16 * 2 + 64 * 32 + MEMORY_SIZE as usize;
 
 pub struct Chip8<T: AddressBusIO<u16, u8>> {
     bus: T,
@@ -20,6 +50,33 @@ pub struct Chip8<T: AddressBusIO<u16, u8>> {
     pub sp: u8,
 
     pub redraw: bool,
+
+    audio: SampleRing,
+    audio_phase: u32,
+
+    entropy: Box<dyn EntropySource>,
+
+    fault: Option<Error>,
+
+    observers: Observers,
+}
+
+// a rom starting at $200 with the fonts at $000
+pub fn load(rom: &[u8]) -> Result<Chip8<Ram<u8>>, Error> {
+    if rom.is_empty() {
+        return Err(Error::LoadError("empty rom".to_string()));
+    }
+    if rom.len() > 4096 - 0x200 {
+        return Err(Error::LoadError(format!(
+            "rom of {} bytes does not fit in {}",
+            rom.len(),
+            4096 - 0x200
+        )));
+    }
+    let mut ram = Ram::new(4096);
+    ram.fill(FONTS.to_vec(), 0x000);
+    ram.fill(rom.to_vec(), 0x200);
+    Ok(Chip8::new(ram))
 }
 
 impl<T: AddressBusIO<u16, u8>> Chip8<T> {
@@ -35,10 +92,36 @@ impl<T: AddressBusIO<u16, u8>> Chip8<T> {
             screen: [0; 64 * 32],
             keys: [false; 16],
             redraw: false,
+            audio: SampleRing::new(SAMPLE_RATE, SAMPLE_RATE as usize / 4),
+            audio_phase: 0,
+            entropy: Box::new(HostEntropy),
+            fault: None,
+            observers: Observers::new(),
             bus: bus,
         }
     }
 
+    // where cxnn draws from, a SeededEntropy makes runs reproducible
+    pub fn set_entropy<E: EntropySource + 'static>(&mut self, entropy: E) {
+        self.entropy = Box::new(entropy);
+    }
+
+    // step reporting invalid opcodes and stack errors, that step silently
+    // stays on
+    pub fn try_step(&mut self) -> Result<(), Error> {
+        self.step();
+        match self.fault.take() {
+            Some(fault) => Err(fault),
+            None => Ok(()),
+        }
+    }
+
+    // the cpu stays on the faulting opcode
+    fn jam(&mut self, pc: u16, fault: Error) {
+        self.pc = pc;
+        self.fault = Some(fault);
+    }
+
     fn read8(&mut self, addr: u16) -> u8 {
         self.bus.read(addr)
     }
@@ -58,6 +141,53 @@ impl<T: AddressBusIO<u16, u8>> Chip8<T> {
         (high << 8) | low
     }
 
+    fn disassemble_opcode(&mut self, address: u16) -> String {
+        let high = u16::from(self.read8(address));
+        let low = u16::from(self.read8(address.wrapping_add(1)));
+        let opcode = (high << 8) | low;
+
+        let nnn = opcode & 0x0fff;
+        let nn = opcode & 0x00ff;
+        let n = opcode & 0x000f;
+        let x = (opcode & 0x0f00) >> 8;
+        let y = (opcode & 0x00f0) >> 4;
+
+        match (opcode & 0xf000, n, nn) {
+            (0x0000, _, 0xe0) => "cls".to_string(),
+            (0x0000, _, 0xee) => "ret".to_string(),
+            (0x1000, _, _) => format!("jp ${:03X}", nnn),
+            (0x2000, _, _) => format!("call ${:03X}", nnn),
+            (0x3000, _, _) => format!("se v{:X}, ${:02X}", x, nn),
+            (0x4000, _, _) => format!("sne v{:X}, ${:02X}", x, nn),
+            (0x5000, 0, _) => format!("se v{:X}, v{:X}", x, y),
+            (0x6000, _, _) => format!("ld v{:X}, ${:02X}", x, nn),
+            (0x7000, _, _) => format!("add v{:X}, ${:02X}", x, nn),
+            (0x8000, 0, _) => format!("ld v{:X}, v{:X}", x, y),
+            (0x8000, 1, _) => format!("or v{:X}, v{:X}", x, y),
+            (0x8000, 2, _) => format!("and v{:X}, v{:X}", x, y),
+            (0x8000, 3, _) => format!("xor v{:X}, v{:X}", x, y),
+            (0x8000, 4, _) => format!("add v{:X}, v{:X}", x, y),
+            (0x8000, 5, _) => format!("sub v{:X}, v{:X}", x, y),
+            (0x8000, 6, _) => format!("shr v{:X}", x),
+            (0x9000, 0, _) => format!("sne v{:X}, v{:X}", x, y),
+            (0xa000, _, _) => format!("ld i, ${:03X}", nnn),
+            (0xb000, _, _) => format!("jp v0, ${:03X}", nnn),
+            (0xc000, _, _) => format!("rnd v{:X}, ${:02X}", x, nn),
+            (0xd000, _, _) => format!("drw v{:X}, v{:X}, {}", x, y, n),
+            (0xe000, _, 0x9e) => format!("skp v{:X}", x),
+            (0xe000, _, 0xa1) => format!("sknp v{:X}", x),
+            (0xf000, _, 0x07) => format!("ld v{:X}, dt", x),
+            (0xf000, _, 0x0a) => format!("ld v{:X}, k", x),
+            (0xf000, _, 0x15) => format!("ld dt, v{:X}", x),
+            (0xf000, _, 0x18) => format!("ld st, v{:X}", x),
+            (0xf000, _, 0x1e) => format!("add i, v{:X}", x),
+            (0xf000, _, 0x29) => format!("ld f, v{:X}", x),
+            (0xf000, _, 0x33) => format!("ld b, v{:X}", x),
+            (0xf000, _, 0x65) => format!("ld v{:X}, [i]", x),
+            _ => format!(".word ${:04X}", opcode),
+        }
+    }
+
     fn advance_pc(&mut self) -> u16 {
         let pc = self.pc;
         self.pc += 1;
@@ -66,7 +196,9 @@ impl<T: AddressBusIO<u16, u8>> Chip8<T> {
 }
 
 impl<T: AddressBusIO<u16, u8>> Clock for Chip8<T> {
-    fn step(&mut self) {
+    // no clock below the instruction, each one counts as a cycle
+    fn step(&mut self) -> u64 {
+        let pc = self.pc;
         let opcode = self.read16_from_pc();
 
         let nnn = opcode & 0x0fff;
@@ -78,21 +210,17 @@ impl<T: AddressBusIO<u16, u8>> Clock for Chip8<T> {
         match opcode & 0xf000 {
             0x0000 => match opcode & 0x00ff {
                 0x00e0 => self.screen = [0; 64 * 32],
+                0x00ee if self.sp == 0x0f => self.jam(pc, Error::StackUnderflow { pc }),
                 0x00ee => {
-                    if self.sp == 0x0f {
-                        panic!("stack out of bounds");
-                    }
                     self.sp += 1;
                     self.pc = self.stack[self.sp as usize];
                 }
-                _ => panic!("invalid opcode ${:04X}", opcode),
+                _ => self.jam(pc, Error::InvalidOpcode { pc, opcode }),
             },
             0x1000 => self.pc = nnn,
+            0x2000 if self.sp == 0 => self.jam(pc, Error::StackOverflow { pc }),
             0x2000 => {
                 let sp = self.sp;
-                if sp == 0 {
-                    panic!("stack out of bounds");
-                }
                 self.stack[sp as usize] = self.pc;
                 self.sp -= 1;
                 self.pc = nnn;
@@ -139,7 +267,7 @@ impl<T: AddressBusIO<u16, u8>> Clock for Chip8<T> {
                     self.reg[0xf] = self.reg[x] & 0x01;
                     self.reg[x] >>= 1;
                 }
-                _ => panic!("invalid opcode ${:04X}", opcode),
+                _ => self.jam(pc, Error::InvalidOpcode { pc, opcode }),
             },
             0x9000 => {
                 if self.reg[x] != self.reg[y] {
@@ -148,14 +276,15 @@ impl<T: AddressBusIO<u16, u8>> Clock for Chip8<T> {
             }
             0xa000 => self.index = nnn,
             0xb000 => self.pc = nnn + u16::from(self.reg[0]),
-            0xc000 => self.reg[x] = rand::random::<u8>() & nn,
+            0xc000 => self.reg[x] = self.entropy.next_u64() as u8 & nn,
             0xd000 => {
                 self.redraw = true;
                 // first clear collision reg
                 self.reg[0xf] = 0x00;
+                let mut sprite = [0; 15];
+                read_block(&mut self.bus, self.index, &mut sprite[..usize::from(n)]);
                 for i in 0..n {
-                    let index = self.index + u16::from(i);
-                    let pixels = self.read8(index);
+                    let pixels = sprite[usize::from(i)];
                     let pixel_y = self.reg[y] + i;
                     if pixel_y >= 32 {
                         break;
@@ -187,7 +316,7 @@ impl<T: AddressBusIO<u16, u8>> Clock for Chip8<T> {
                         self.pc += 2
                     }
                 }
-                _ => panic!("invalid opcode ${:04X}", opcode),
+                _ => self.jam(pc, Error::InvalidOpcode { pc, opcode }),
             },
             0xf000 => match opcode & 0x00ff {
                 0x0007 => self.reg[x] = self.delay_timer,
@@ -210,21 +339,214 @@ impl<T: AddressBusIO<u16, u8>> Clock for Chip8<T> {
                     self.write8(index + 1, (value / 10) % 10);
                     self.write8(index + 2, (value % 100) % 10);
                 }
-                0x0065 => {
-                    let mut index = self.index;
-                    for i in 0..=x {
-                        self.reg[i] = self.read8(index);
-                        index += 1;
-                    }
-                }
+                0x0065 => read_block(&mut self.bus, self.index, &mut self.reg[..=x]),
                 0x001e => self.index += u16::from(self.reg[x]),
                 0x0029 => {
                     let offset = u16::from(self.reg[x]);
                     self.index = offset * 5;
                 }
-                _ => panic!("invalid opcode ${:04X}", opcode),
+                _ => self.jam(pc, Error::InvalidOpcode { pc, opcode }),
             },
-            _ => panic!("invalid opcode ${:04X}", opcode),
+            _ => self.jam(pc, Error::InvalidOpcode { pc, opcode }),
+        }
+        self.observers.emit(&CpuEvent::InstructionRetired {
+            pc,
+            opcode,
+            cycles: 1,
+        });
+        1
+    }
+}
+
+// the program and the fonts stay in memory
+impl<T: AddressBusIO<u16, u8>> Reset for Chip8<T> {
+    fn reset(&mut self) {
+        self.reg = [0; 16];
+        self.pc = 0x200;
+        self.stack = [0; 16];
+        self.sp = 0xf;
+        self.index = 0;
+        self.delay_timer = 0;
+        self.sound_timer = 0;
+        self.screen = [0; 64 * 32];
+        self.keys = [false; 16];
+        self.redraw = true;
+        self.audio.clear();
+        self.audio_phase = 0;
+        self.fault = None;
+    }
+}
+
+impl<T: AddressBusIO<u16, u8>> Machine for Chip8<T> {
+    fn resolution(&self) -> (usize, usize) {
+        (64, 32)
+    }
+
+    fn render(&self, pixels: &mut [u8]) {
+        for (i, pixel) in self.screen.iter().enumerate() {
+            let color = *pixel * 255;
+            pixels[i * 3] = color;
+            pixels[i * 3 + 1] = color;
+            pixels[i * 3 + 2] = color;
+        }
+    }
+
+    fn run_frame(&mut self) {
+        #[cfg(feature = "tracing")]
+        let _span = ::tracing::trace_span!("frame", pc = self.pc).entered();
+
+        for _ in 0..INSTRUCTIONS_PER_FRAME {
+            self.step();
+            if self.fault.is_some() {
+                break;
+            }
+        }
+
+        // timers run at 60hz, the frame rate
+        if self.delay_timer > 0 {
+            self.delay_timer -= 1;
+        }
+
+        let samples = SAMPLE_RATE / self.frame_rate();
+        let half_period = SAMPLE_RATE / BEEP_FREQUENCY / 2;
+        for _ in 0..samples {
+            let sample = if self.sound_timer == 0 {
+                0.0
+            } else if self.audio_phase % (half_period * 2) < half_period {
+                0.25
+            } else {
+                -0.25
+            };
+            self.audio_phase = self.audio_phase.wrapping_add(1);
+            self.audio.push(sample);
+        }
+
+        if self.sound_timer > 0 {
+            self.sound_timer -= 1;
+        }
+
+        self.observers.emit(&CpuEvent::FrameComplete);
+    }
+
+    // 1 2 3 C / 4 5 6 D / 7 8 9 E / A 0 B F as their own hex digits
+    fn key(&mut self, key: Key, pressed: bool) {
+        if let Key::Char(c) = key {
+            if let Some(digit) = c.to_digit(16) {
+                self.keys[digit as usize] = pressed;
+            }
+        }
+    }
+
+    fn audio(&mut self) -> Option<&mut SampleRing> {
+        Some(&mut self.audio)
+    }
+
+    fn debug(&mut self) -> Option<&mut dyn Debug<u16, u8>> {
+        Some(self)
+    }
+
+    fn observers(&mut self) -> Option<&mut Observers> {
+        Some(&mut self.observers)
+    }
+
+    fn save_state(&mut self) -> Vec<u8> {
+        let mut state = Vec::with_capacity(STATE_SIZE);
+        state.extend_from_slice(&self.reg);
+        state.extend_from_slice(&self.index.to_le_bytes());
+        state.extend_from_slice(&self.pc.to_le_bytes());
+        state.push(self.sp);
+        state.push(self.delay_timer);
+        state.push(self.sound_timer);
+        for address in self.stack.iter() {
+            state.extend_from_slice(&address.to_le_bytes());
+        }
+        state.extend_from_slice(&self.screen);
+        let memory = state.len();
+        state.resize(STATE_SIZE, 0);
+        read_block(&mut self.bus, 0, &mut state[memory..]);
+        state
+    }
+
+    fn load_state(&mut self, state: &[u8]) -> bool {
+        if state.len() != STATE_SIZE {
+            return false;
+        }
+        let word = |offset: usize| u16::from_le_bytes([state[offset], state[offset + 1]]);
+        self.reg.copy_from_slice(&state[0..16]);
+        self.index = word(16);
+        self.pc = word(18);
+        self.sp = state[20];
+        self.delay_timer = state[21];
+        self.sound_timer = state[22];
+        for (i, address) in self.stack.iter_mut().enumerate() {
+            *address = word(23 + i * 2);
+        }
+        self.screen.copy_from_slice(&state[55..55 + 64 * 32]);
+        write_block(&mut self.bus, 0, &state[55 + 64 * 32..]);
+        self.redraw = true;
+        true
+    }
+
+    fn take_fault(&mut self) -> Option<Error> {
+        self.fault.take()
+    }
+}
+
+impl<T: AddressBusIO<u16, u8>> Debug<u16, u8> for Chip8<T> {
+    fn address_str(&self, address: u16) -> String {
+        format!("${:03X}", address)
+    }
+
+    fn data_str(&self, data: u8) -> String {
+        format!("${:02X}", data)
+    }
+
+    fn inspect(&mut self, address: u16) -> u8 {
+        self.read8(address)
+    }
+
+    fn inject(&mut self, address: u16, data: u8) {
+        self.write8(address, data);
+    }
+
+    fn get_cursor(&self) -> u16 {
+        self.pc
+    }
+
+    fn next(&mut self) {
+        self.step();
+    }
+
+    fn set_cursor(&mut self, address: u16) {
+        self.pc = address;
+    }
+
+    // no code driven breakpoints on chip8
+    fn set_code_breakpoint(&mut self, _enable: bool) {}
+
+    fn is_code_breakpoint_requested(&mut self) -> bool {
+        false
+    }
+
+    fn disassemble(&mut self, address: u16) -> (String, u16) {
+        (self.disassemble_opcode(address), 2)
+    }
+
+    fn registers(&self) -> Vec<(&'static str, String)> {
+        const NAMES: [&str; 16] = [
+            "V0", "V1", "V2", "V3", "V4", "V5", "V6", "V7", "V8", "V9", "VA", "VB", "VC", "VD",
+            "VE", "VF",
+        ];
+        let mut registers = vec![
+            ("PC", format!("${:03X}", self.pc)),
+            ("I", format!("${:03X}", self.index)),
+            ("SP", format!("${:X}", self.sp)),
+            ("DT", format!("${:02X}", self.delay_timer)),
+            ("ST", format!("${:02X}", self.sound_timer)),
+        ];
+        for (name, value) in NAMES.iter().zip(self.reg.iter()) {
+            registers.push((*name, format!("${:02X}", value)));
         }
+        registers
     }
 }
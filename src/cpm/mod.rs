@@ -0,0 +1,110 @@
+use alloc::vec::Vec;
+
+// where a .com program is loaded and starts
+pub const TPA: u16 = 0x0100;
+// the bdos entry point and the warm boot the program ends with
+pub const BDOS: u16 = 0x0005;
+pub const WARM_BOOT: u16 = 0x0000;
+
+// what an 8080 or z80 core has to expose to run cp/m programs
+pub trait CpmCpu {
+    fn pc(&self) -> u16;
+    fn set_pc(&mut self, pc: u16);
+    fn sp(&self) -> u16;
+    fn set_sp(&mut self, sp: u16);
+    // the bdos function number
+    fn c(&self) -> u8;
+    fn e(&self) -> u8;
+    fn de(&self) -> u16;
+    fn read(&mut self, address: u16) -> u8;
+    fn write(&mut self, address: u16, value: u8);
+    fn step(&mut self);
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Exit {
+    // the program jumped to the warm boot
+    WarmBoot,
+    TimedOut,
+    // a bdos function the stub does not have
+    Unsupported(u8),
+}
+
+pub struct Run {
+    pub exit: Exit,
+    pub steps: u64,
+    pub output: Vec<u8>,
+}
+
+fn pop<C: CpmCpu>(cpu: &mut C) -> u16 {
+    let sp = cpu.sp();
+    let low = u16::from(cpu.read(sp));
+    let high = u16::from(cpu.read(sp.wrapping_add(1)));
+    cpu.set_sp(sp.wrapping_add(2));
+    high << 8 | low
+}
+
+// only the console output functions (2 and 9) that zexdoc and zexall use,
+// the bdos call returns without running any code in low memory
+pub fn run<C: CpmCpu>(cpu: &mut C, program: &[u8], max_steps: u64) -> Run {
+    for (i, byte) in program.iter().enumerate() {
+        cpu.write(TPA.wrapping_add(i as u16), *byte);
+    }
+    // the top of the tpa, where zex looks for the stack
+    cpu.write(0x0006, 0x00);
+    cpu.write(0x0007, 0xf0);
+    cpu.set_sp(0xf000);
+    cpu.set_pc(TPA);
+
+    let mut output = Vec::new();
+    for steps in 0..max_steps {
+        match cpu.pc() {
+            WARM_BOOT => {
+                return Run {
+                    exit: Exit::WarmBoot,
+                    steps,
+                    output,
+                }
+            }
+            BDOS => {
+                match cpu.c() {
+                    2 => output.push(cpu.e()),
+                    9 => {
+                        let mut address = cpu.de();
+                        loop {
+                            let c = cpu.read(address);
+                            if c == b'$' {
+                                break;
+                            }
+                            output.push(c);
+                            address = address.wrapping_add(1);
+                        }
+                    }
+                    function => {
+                        return Run {
+                            exit: Exit::Unsupported(function),
+                            steps,
+                            output,
+                        }
+                    }
+                }
+                let address = pop(cpu);
+                cpu.set_pc(address);
+            }
+            _ => cpu.step(),
+        }
+    }
+    Run {
+        exit: Exit::TimedOut,
+        steps: max_steps,
+        output,
+    }
+}
+
+// zexdoc and zexall print "ERROR" for every failing group
+pub fn zex_passed(output: &[u8]) -> bool {
+    !output.windows(5).any(|window| window == b"ERROR") && output.ends_with(b"Tests complete\r\n")
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,95 @@
+use cpm::{self, CpmCpu, Exit};
+
+// just ld c,n, ld de,nn, call nn and jp nn
+struct Tiny {
+    memory: Vec<u8>,
+    pc: u16,
+    sp: u16,
+    c: u8,
+    de: u16,
+}
+
+impl Tiny {
+    fn word(&mut self) -> u16 {
+        let pc = usize::from(self.pc);
+        let word = u16::from(self.memory[pc + 1]) | u16::from(self.memory[pc + 2]) << 8;
+        self.pc += 3;
+        word
+    }
+}
+
+impl CpmCpu for Tiny {
+    fn pc(&self) -> u16 {
+        self.pc
+    }
+    fn set_pc(&mut self, pc: u16) {
+        self.pc = pc;
+    }
+    fn sp(&self) -> u16 {
+        self.sp
+    }
+    fn set_sp(&mut self, sp: u16) {
+        self.sp = sp;
+    }
+    fn c(&self) -> u8 {
+        self.c
+    }
+    fn e(&self) -> u8 {
+        self.de as u8
+    }
+    fn de(&self) -> u16 {
+        self.de
+    }
+    fn read(&mut self, address: u16) -> u8 {
+        self.memory[usize::from(address)]
+    }
+    fn write(&mut self, address: u16, value: u8) {
+        self.memory[usize::from(address)] = value;
+    }
+    fn step(&mut self) {
+        match self.memory[usize::from(self.pc)] {
+            0x0e => {
+                self.c = self.memory[usize::from(self.pc) + 1];
+                self.pc += 2;
+            }
+            0x11 => self.de = self.word(),
+            0xcd => {
+                let target = self.word();
+                let pc = self.pc;
+                self.sp -= 2;
+                self.write(self.sp, pc as u8);
+                self.write(self.sp + 1, (pc >> 8) as u8);
+                self.pc = target;
+            }
+            0xc3 => self.pc = self.word(),
+            opcode => panic!("unexpected opcode {:02X}", opcode),
+        }
+    }
+}
+
+#[test]
+fn bdos_console_output() {
+    let mut cpu = Tiny {
+        memory: vec![0; 0x10000],
+        pc: 0,
+        sp: 0,
+        c: 0,
+        de: 0,
+    };
+    let mut program = vec![
+        0x0e, 0x09, // ld c,9
+        0x11, 0x13, 0x01, // ld de,message
+        0xcd, 0x05, 0x00, // call 5
+        0x0e, 0x02, // ld c,2
+        0x11, 0x21, 0x00, // ld de,'!'
+        0xcd, 0x05, 0x00, // call 5
+        0xc3, 0x00, 0x00, // jp 0
+    ];
+    program.extend(b"Tests complete\r\n$");
+    let run = cpm::run(&mut cpu, &program, 100);
+    assert_eq!(run.exit, Exit::WarmBoot);
+    assert_eq!(run.output, b"Tests complete\r\n!");
+
+    assert!(cpm::zex_passed(b"daa.......OK\r\nTests complete\r\n"));
+    assert!(!cpm::zex_passed(b"daa.......ERROR\r\nTests complete\r\n"));
+}
@@ -0,0 +1,74 @@
+use {Address, Data, Debug};
+
+// longest instruction we expect from any core, bounds the backward resync
+const MAX_INSTRUCTION_LENGTH: u32 = 4;
+
+pub struct ContextLine<T: Address> {
+    pub address: T,
+    pub text: String,
+    pub current: bool,
+}
+
+fn decode_from<T: Address, U: Data, V: Debug<T, U> + ?Sized>(
+    debugged: &mut V,
+    start: T,
+    stop: T,
+) -> Option<Vec<ContextLine<T>>> {
+    let mut lines = Vec::new();
+    let mut address = start;
+    while address < stop {
+        let (text, length) = debugged.disassemble(address);
+        lines.push(ContextLine {
+            address,
+            text,
+            current: false,
+        });
+        address = address.checked_add(&length)?;
+    }
+    // only a stream landing exactly on stop is in sync with it
+    if address == stop {
+        Some(lines)
+    } else {
+        None
+    }
+}
+
+// instructions have variable length so the backward part is found by decoding
+// from increasingly closer addresses until one lands exactly on pc
+pub fn context<T: Address, U: Data, V: Debug<T, U> + ?Sized>(
+    debugged: &mut V,
+    pc: T,
+    before: usize,
+    after: usize,
+) -> Vec<ContextLine<T>> {
+    let mut best: Vec<ContextLine<T>> = Vec::new();
+    let mut distance = before as u32 * MAX_INSTRUCTION_LENGTH;
+    while distance > 0 && best.len() < before {
+        let start = T::from(distance).and_then(|offset| pc.checked_sub(&offset));
+        if let Some(start) = start {
+            if let Some(previous) = decode_from(debugged, start, pc) {
+                if previous.len() > best.len() {
+                    best = previous;
+                }
+            }
+        }
+        distance -= 1;
+    }
+    let skip = best.len().saturating_sub(before);
+    let mut lines = best.split_off(skip);
+
+    let mut address = pc;
+    for i in 0..=after {
+        let (text, length) = debugged.disassemble(address);
+        lines.push(ContextLine {
+            address,
+            text,
+            current: i == 0,
+        });
+        match address.checked_add(&length) {
+            Some(next) => address = next,
+            None => break,
+        }
+    }
+    lines
+}
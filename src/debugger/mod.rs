@@ -1,15 +1,33 @@
+#[cfg(not(target_arch = "wasm32"))]
 extern crate rustyline;
+#[cfg(not(target_arch = "wasm32"))]
 use self::rustyline::Editor;
 
+#[cfg(not(target_arch = "wasm32"))]
 use {Address, Data, Debug};
 
+#[cfg(not(target_arch = "wasm32"))]
 use std::num::ParseIntError;
+#[cfg(not(target_arch = "wasm32"))]
 use utils::to_number;
 
+pub mod context;
+pub mod remote;
+#[cfg(feature = "lua")]
+pub mod script;
+pub mod search;
+pub mod viewer;
+
+#[cfg(not(target_arch = "wasm32"))]
+use self::context::context;
+#[cfg(not(target_arch = "wasm32"))]
+use self::viewer::hexdump;
+
+#[cfg(not(target_arch = "wasm32"))]
 pub fn debugger<
     T: Address<FromStrRadixErr = ParseIntError>,
     U: Data<FromStrRadixErr = ParseIntError>,
-    V: Debug<T, U>,
+    V: Debug<T, U> + ?Sized,
 >(
     debugged: &mut V,
 ) -> bool {
@@ -27,26 +45,22 @@ pub fn debugger<
                             Ok(address) => match iter.next() {
                                 Some(amount) => match to_number::<T>(amount) {
                                     Ok(qt) => {
-                                        let mut counter = T::zero();
-                                        let mut next_line = 0;
-                                        print!("{}: ", debugged.address_str(address + counter));
-                                        while counter < qt {
-                                            let data = debugged.inspect(address + counter);
-                                            print!("{} ", debugged.data_str(data));
-                                            if next_line == 15 {
-                                                println!("");
-                                                print!(
-                                                    "{}: ",
-                                                    debugged
-                                                        .address_str(address + counter + T::one())
-                                                );
-                                                next_line = 0;
-                                            } else {
-                                                next_line += 1;
+                                        if qt > T::zero() {
+                                            // clamped to the end of the address space
+                                            let end = match address.checked_add(&(qt - T::one())) {
+                                                Some(end) => end,
+                                                None => {
+                                                    println!(
+                                                        "Range clamped to {}",
+                                                        debugged.address_str(T::max_value())
+                                                    );
+                                                    T::max_value()
+                                                }
+                                            };
+                                            for line in hexdump(debugged, address, end) {
+                                                println!("{}", line);
                                             }
-                                            counter += T::one();
                                         }
-                                        println!("");
                                     }
                                     Err(err) => println!("Error: {}", err),
                                 },
@@ -80,6 +94,41 @@ pub fn debugger<
                         },
                         _ => println!("syntax: j <address>"),
                     },
+                    Some("d") => {
+                        let address = match iter.next() {
+                            Some(value) => match to_number::<T>(value) {
+                                Ok(address) => address,
+                                Err(err) => {
+                                    println!("Error: {}", err);
+                                    continue;
+                                }
+                            },
+                            None => debugged.get_cursor(),
+                        };
+                        for line in context(debugged, address, 5, 5) {
+                            let marker = if line.current { ">" } else { " " };
+                            println!(
+                                "{} {}: {}",
+                                marker,
+                                debugged.address_str(line.address),
+                                line.text
+                            );
+                        }
+                    }
+                    #[cfg(feature = "lua")]
+                    Some("x") => match iter.next() {
+                        Some(filename) => {
+                            let mut host = script::ScriptHost::new();
+                            let result = host.run_file(debugged, filename);
+                            for line in host.take_log() {
+                                println!("{}", line);
+                            }
+                            if let Err(err) = result {
+                                println!("Error: {}", err);
+                            }
+                        }
+                        _ => println!("syntax: x <script.lua>"),
+                    },
                     Some("q") => return false,
                     Some("r") => return false,
                     Some("s") => return true,
@@ -94,3 +143,6 @@ pub fn debugger<
         }
     }
 }
+
+#[cfg(all(test, feature = "mos6502"))]
+mod tests;
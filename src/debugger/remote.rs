@@ -0,0 +1,283 @@
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::num::ParseIntError;
+
+use utils::to_number;
+use {Address, Data, Debug};
+
+// line based protocol, every command gets a single "ok ..." or "error ..."
+// line back, while running a "break <address>" line is sent when stopping
+//
+// peek <address> [count]    poke <address> <value>    regs
+// pc                        jump <address>            step [count]
+// break <address>           clear <address>           breakpoints
+// disasm <address> [count]  pause                     continue
+// screenshot (replies with width, height and the rgb pixels in hex)
+pub struct RemoteServer<T: Address> {
+    listener: TcpListener,
+    client: Option<TcpStream>,
+    pending: String,
+    breakpoints: Vec<T>,
+    paused: bool,
+    // a breakpoint at the address we resumed from must not stop us again
+    resumed_at: Option<T>,
+    screenshot: Option<(usize, usize, Vec<u8>)>,
+    checks: u32,
+}
+
+// checking the socket on every step would slow the emulation down a lot
+const POLL_INTERVAL: u32 = 1024;
+
+impl<T> RemoteServer<T>
+where
+    T: Address<FromStrRadixErr = ParseIntError>,
+{
+    pub fn bind<A: ToSocketAddrs>(address: A) -> Result<RemoteServer<T>, String> {
+        let listener = TcpListener::bind(address).map_err(|err| err.to_string())?;
+        listener
+            .set_nonblocking(true)
+            .map_err(|err| err.to_string())?;
+        Ok(RemoteServer {
+            listener,
+            client: None,
+            pending: String::new(),
+            breakpoints: Vec::new(),
+            paused: false,
+            resumed_at: None,
+            screenshot: None,
+            checks: 0,
+        })
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn breakpoints(&self) -> &[T] {
+        &self.breakpoints
+    }
+
+    pub fn set_screenshot(&mut self, width: usize, height: usize, pixels: &[u8]) {
+        self.screenshot = Some((width, height, pixels.to_vec()));
+    }
+
+    fn send(&mut self, line: &str) {
+        let mut failed = false;
+        if let Some(ref mut client) = self.client {
+            // replies can be big (screenshots), do not give up on a full buffer
+            failed = client.set_nonblocking(false).is_err()
+                || client.write_all(line.as_bytes()).is_err()
+                || client.write_all(b"\n").is_err()
+                || client.set_nonblocking(true).is_err();
+        }
+        if failed {
+            self.disconnect();
+        }
+    }
+
+    fn disconnect(&mut self) {
+        self.client = None;
+        self.pending.clear();
+        // nobody is left to resume us
+        self.paused = false;
+    }
+
+    // to be called before every step, stops on breakpoints (or when requested
+    // by the host) and serves the connected client
+    pub fn check<U, V>(&mut self, debugged: &mut V, requested: bool)
+    where
+        U: Data<FromStrRadixErr = ParseIntError>,
+        V: Debug<T, U> + ?Sized,
+    {
+        let address = debugged.get_cursor();
+        let resumed = self.resumed_at.take() == Some(address);
+        let hit = !resumed && self.breakpoints.contains(&address);
+        if !self.paused && self.client.is_some() && (requested || hit) {
+            self.paused = true;
+            let line = format!("break {}", debugged.address_str(address));
+            self.send(&line);
+        }
+        self.checks += 1;
+        if self.paused || self.checks >= POLL_INTERVAL {
+            self.checks = 0;
+            self.poll(debugged);
+        }
+    }
+
+    pub fn poll<U, V>(&mut self, debugged: &mut V)
+    where
+        U: Data<FromStrRadixErr = ParseIntError>,
+        V: Debug<T, U> + ?Sized,
+    {
+        if let Ok((stream, _)) = self.listener.accept() {
+            if stream.set_nonblocking(true).is_ok() {
+                // a new client replaces the old one
+                self.disconnect();
+                self.client = Some(stream);
+            }
+        }
+
+        let mut closed = false;
+        if let Some(ref mut client) = self.client {
+            let mut buffer = [0; 1024];
+            loop {
+                match client.read(&mut buffer) {
+                    Ok(0) => {
+                        closed = true;
+                        break;
+                    }
+                    Ok(size) => self
+                        .pending
+                        .push_str(&String::from_utf8_lossy(&buffer[0..size])),
+                    Err(ref err) if err.kind() == ErrorKind::WouldBlock => break,
+                    Err(_) => {
+                        closed = true;
+                        break;
+                    }
+                }
+            }
+        }
+
+        while let Some(position) = self.pending.find('\n') {
+            let line: String = self.pending.drain(..=position).collect();
+            let reply = self.handle(debugged, line.trim());
+            self.send(&reply);
+        }
+
+        if closed {
+            self.disconnect();
+        }
+    }
+
+    pub fn handle<U, V>(&mut self, debugged: &mut V, line: &str) -> String
+    where
+        U: Data<FromStrRadixErr = ParseIntError>,
+        V: Debug<T, U> + ?Sized,
+    {
+        match self.execute(debugged, line) {
+            Ok(reply) => {
+                if reply.is_empty() {
+                    "ok".to_string()
+                } else {
+                    format!("ok {}", reply)
+                }
+            }
+            Err(err) => format!("error {}", err),
+        }
+    }
+
+    fn execute<U, V>(&mut self, debugged: &mut V, line: &str) -> Result<String, String>
+    where
+        U: Data<FromStrRadixErr = ParseIntError>,
+        V: Debug<T, U> + ?Sized,
+    {
+        let mut iter = line.split_whitespace();
+        let command = iter.next().unwrap_or("");
+        let arguments: Vec<&str> = iter.collect();
+
+        let address = |index: usize| -> Result<T, String> {
+            match arguments.get(index) {
+                Some(value) => to_number::<T>(value).map_err(|err| err.to_string()),
+                None => Err(format!("missing argument for {}", command)),
+            }
+        };
+        let count = |index: usize| -> Result<u32, String> {
+            match arguments.get(index) {
+                Some(value) => to_number::<u32>(value).map_err(|err| err.to_string()),
+                None => Ok(1),
+            }
+        };
+
+        match command {
+            "peek" => {
+                let mut address = address(0)?;
+                let mut values = Vec::new();
+                for _ in 0..count(1)? {
+                    let value = debugged.inspect(address);
+                    values.push(debugged.data_str(value));
+                    match address.checked_add(&T::one()) {
+                        Some(next) => address = next,
+                        None => break,
+                    }
+                }
+                Ok(values.join(" "))
+            }
+            "poke" => {
+                let address = address(0)?;
+                let value = match arguments.get(1) {
+                    Some(value) => to_number::<U>(value).map_err(|err| err.to_string())?,
+                    None => return Err("missing argument for poke".to_string()),
+                };
+                debugged.inject(address, value);
+                Ok(String::new())
+            }
+            "regs" => Ok(debugged
+                .registers()
+                .iter()
+                .map(|(name, value)| format!("{}={}", name, value))
+                .collect::<Vec<String>>()
+                .join(" ")),
+            "pc" => Ok(debugged.address_str(debugged.get_cursor())),
+            "jump" => {
+                debugged.set_cursor(address(0)?);
+                Ok(String::new())
+            }
+            "step" => {
+                for _ in 0..count(0)? {
+                    debugged.next();
+                }
+                Ok(debugged.address_str(debugged.get_cursor()))
+            }
+            "break" => {
+                let address = address(0)?;
+                if !self.breakpoints.contains(&address) {
+                    self.breakpoints.push(address);
+                }
+                Ok(String::new())
+            }
+            "clear" => {
+                let address = address(0)?;
+                self.breakpoints.retain(|breakpoint| *breakpoint != address);
+                Ok(String::new())
+            }
+            "breakpoints" => Ok(self
+                .breakpoints
+                .iter()
+                .map(|breakpoint| debugged.address_str(*breakpoint))
+                .collect::<Vec<String>>()
+                .join(" ")),
+            "disasm" => {
+                let mut address = address(0)?;
+                let mut lines = Vec::new();
+                for _ in 0..count(1)? {
+                    let (text, length) = debugged.disassemble(address);
+                    lines.push(format!("{}: {}", debugged.address_str(address), text));
+                    match address.checked_add(&length) {
+                        Some(next) => address = next,
+                        None => break,
+                    }
+                }
+                Ok(lines.join(" | "))
+            }
+            "pause" => {
+                self.paused = true;
+                Ok(debugged.address_str(debugged.get_cursor()))
+            }
+            "continue" => {
+                self.paused = false;
+                self.resumed_at = Some(debugged.get_cursor());
+                Ok(String::new())
+            }
+            "screenshot" => match self.screenshot {
+                Some((width, height, ref pixels)) => {
+                    let hex: Vec<String> =
+                        pixels.iter().map(|byte| format!("{:02x}", byte)).collect();
+                    Ok(format!("{} {} {}", width, height, hex.concat()))
+                }
+                None => Err("no screen available".to_string()),
+            },
+            "" => Err("empty command".to_string()),
+            _ => Err(format!("unknown command {}", command)),
+        }
+    }
+}
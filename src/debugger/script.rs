@@ -0,0 +1,176 @@
+extern crate mlua;
+
+use self::mlua::{Error, Lua, Variadic};
+use std::cell::RefCell;
+use std::fs;
+
+use {Address, Data, Debug};
+
+fn from_lua<T: Address>(value: i64) -> Result<T, Error> {
+    T::from(value).ok_or_else(|| Error::RuntimeError(format!("value {} out of range", value)))
+}
+
+fn to_lua<T: Address>(value: T) -> i64 {
+    value.to_i64().unwrap_or(0)
+}
+
+// runs lua scripts against anything implementing Debug, breakpoints set by a
+// script survive between runs
+pub struct ScriptHost<T: Address> {
+    lua: Lua,
+    breakpoints: Vec<T>,
+    log: Vec<String>,
+}
+
+impl<T: Address> Default for ScriptHost<T> {
+    fn default() -> ScriptHost<T> {
+        ScriptHost::new()
+    }
+}
+
+impl<T: Address> ScriptHost<T> {
+    pub fn new() -> ScriptHost<T> {
+        ScriptHost {
+            lua: Lua::new(),
+            breakpoints: Vec::new(),
+            log: Vec::new(),
+        }
+    }
+
+    pub fn breakpoints(&self) -> &[T] {
+        &self.breakpoints
+    }
+
+    pub fn take_log(&mut self) -> Vec<String> {
+        self.log.split_off(0)
+    }
+
+    pub fn run_file<U: Data, V: Debug<T, U> + ?Sized>(
+        &mut self,
+        debugged: &mut V,
+        filename: &str,
+    ) -> Result<(), String> {
+        match fs::read_to_string(filename) {
+            Ok(source) => self.run(debugged, &source),
+            Err(err) => Err(format!("unable to read {}: {}", filename, err)),
+        }
+    }
+
+    pub fn run<U: Data, V: Debug<T, U> + ?Sized>(
+        &mut self,
+        debugged: &mut V,
+        source: &str,
+    ) -> Result<(), String> {
+        let debugged = RefCell::new(debugged);
+        let breakpoints = RefCell::new(&mut self.breakpoints);
+        let log = RefCell::new(&mut self.log);
+        let lua = &self.lua;
+
+        let result = lua.scope(|scope| {
+            let globals = lua.globals();
+
+            globals.set(
+                "peek",
+                scope.create_function(|_, address: i64| {
+                    let data = debugged.borrow_mut().inspect(from_lua(address)?);
+                    Ok(to_lua(data))
+                })?,
+            )?;
+
+            globals.set(
+                "poke",
+                scope.create_function(|_, (address, value): (i64, i64)| {
+                    let address: T = from_lua(address)?;
+                    let value: U = from_lua(value)?;
+                    debugged.borrow_mut().inject(address, value);
+                    Ok(())
+                })?,
+            )?;
+
+            globals.set(
+                "pc",
+                scope.create_function(|_, ()| Ok(to_lua(debugged.borrow().get_cursor())))?,
+            )?;
+
+            globals.set(
+                "jump",
+                scope.create_function(|_, address: i64| {
+                    debugged.borrow_mut().set_cursor(from_lua(address)?);
+                    Ok(())
+                })?,
+            )?;
+
+            globals.set(
+                "step",
+                scope.create_function(|_, count: Option<u64>| {
+                    let mut debugged = debugged.borrow_mut();
+                    for _ in 0..count.unwrap_or(1) {
+                        debugged.next();
+                    }
+                    Ok(to_lua(debugged.get_cursor()))
+                })?,
+            )?;
+
+            // steps until a breakpoint (or a code-driven one) is hit, at most max_steps times
+            globals.set(
+                "run",
+                scope.create_function(|_, max_steps: Option<u64>| {
+                    let mut debugged = debugged.borrow_mut();
+                    let breakpoints = breakpoints.borrow();
+                    for _ in 0..max_steps.unwrap_or(1_000_000) {
+                        debugged.next();
+                        if debugged.is_code_breakpoint_requested()
+                            || breakpoints.contains(&debugged.get_cursor())
+                        {
+                            break;
+                        }
+                    }
+                    Ok(to_lua(debugged.get_cursor()))
+                })?,
+            )?;
+
+            globals.set(
+                "break_at",
+                scope.create_function(|_, address: i64| {
+                    let address = from_lua(address)?;
+                    let mut breakpoints = breakpoints.borrow_mut();
+                    if !breakpoints.contains(&address) {
+                        breakpoints.push(address);
+                    }
+                    Ok(())
+                })?,
+            )?;
+
+            globals.set(
+                "clear",
+                scope.create_function(|_, address: i64| {
+                    let address: T = from_lua(address)?;
+                    breakpoints
+                        .borrow_mut()
+                        .retain(|breakpoint| *breakpoint != address);
+                    Ok(())
+                })?,
+            )?;
+
+            globals.set(
+                "disasm",
+                scope.create_function(|_, address: i64| {
+                    let (text, length) = debugged.borrow_mut().disassemble(from_lua(address)?);
+                    Ok((text, to_lua(length)))
+                })?,
+            )?;
+
+            globals.set(
+                "log",
+                scope.create_function(|_, values: Variadic<String>| {
+                    log.borrow_mut().push(values.join(" "));
+                    Ok(())
+                })?,
+            )?;
+
+            lua.load(source).exec()
+        });
+
+        result.map_err(|err| err.to_string())
+    }
+}
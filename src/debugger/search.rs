@@ -0,0 +1,92 @@
+use {Address, Data, Debug};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Refinement<U: Data> {
+    Equal(U),
+    Changed,
+    Unchanged,
+    Increased,
+    Decreased,
+}
+
+impl<U: Data> Refinement<U> {
+    fn matches(&self, previous: U, current: U) -> bool {
+        match *self {
+            Refinement::Equal(value) => current == value,
+            Refinement::Changed => current != previous,
+            Refinement::Unchanged => current == previous,
+            Refinement::Increased => current > previous,
+            Refinement::Decreased => current < previous,
+        }
+    }
+}
+
+// iterative cheat finder: the first refinement scans the whole (inclusive) range,
+// the following ones only re-read the surviving candidates
+pub struct MemorySearch<T: Address, U: Data> {
+    start: T,
+    end: T,
+    candidates: Vec<(T, U)>,
+    started: bool,
+}
+
+impl<T: Address, U: Data> MemorySearch<T, U> {
+    pub fn new(start: T, end: T) -> MemorySearch<T, U> {
+        MemorySearch {
+            start,
+            end,
+            candidates: Vec::new(),
+            started: false,
+        }
+    }
+
+    pub fn search<V: Debug<T, U> + ?Sized>(&mut self, debugged: &mut V, value: U) -> usize {
+        self.refine(debugged, Refinement::Equal(value))
+    }
+
+    pub fn refine<V: Debug<T, U> + ?Sized>(
+        &mut self,
+        debugged: &mut V,
+        refinement: Refinement<U>,
+    ) -> usize {
+        if !self.started {
+            self.started = true;
+            let mut address = self.start;
+            loop {
+                let value = debugged.inspect(address);
+                // without a previous snapshot only an exact value can filter anything
+                let keep = match refinement {
+                    Refinement::Equal(expected) => value == expected,
+                    _ => true,
+                };
+                if keep {
+                    self.candidates.push((address, value));
+                }
+                if address >= self.end {
+                    break;
+                }
+                address += T::one();
+            }
+            return self.candidates.len();
+        }
+
+        let mut survivors = Vec::new();
+        for &(address, previous) in &self.candidates {
+            let value = debugged.inspect(address);
+            if refinement.matches(previous, value) {
+                survivors.push((address, value));
+            }
+        }
+        self.candidates = survivors;
+        self.candidates.len()
+    }
+
+    pub fn results(&self) -> &[(T, U)] {
+        &self.candidates
+    }
+
+    pub fn reset(&mut self) {
+        self.candidates.clear();
+        self.started = false;
+    }
+}
@@ -0,0 +1,131 @@
+use debugger::context::context;
+use debugger::remote::RemoteServer;
+use debugger::search::{MemorySearch, Refinement};
+use debugger::viewer::{hexdump, MemoryView};
+use mos6502::MOS6502;
+use ram::Ram;
+use Debug;
+
+#[test]
+fn search_value_then_changed() {
+    let mut ram = Ram::new(1024);
+    ram.fill(vec![3, 1, 3, 3], 0x0200);
+    let mut cpu = MOS6502::new(ram);
+    let mut search = MemorySearch::new(0x0200, 0x02ff);
+    assert_eq!(search.search(&mut cpu, 3), 3);
+    cpu.inject(0x0202, 2);
+    assert_eq!(search.refine(&mut cpu, Refinement::Changed), 1);
+    assert_eq!(search.results(), &[(0x0202, 2)]);
+}
+
+#[test]
+fn search_unknown_value_decreased() {
+    let mut ram = Ram::new(1024);
+    ram.fill(vec![5, 5, 5], 0x0010);
+    let mut cpu = MOS6502::new(ram);
+    let mut search = MemorySearch::new(0x0010, 0x0012);
+    assert_eq!(search.refine(&mut cpu, Refinement::Unchanged), 3);
+    cpu.inject(0x0011, 4);
+    cpu.inject(0x0012, 6);
+    assert_eq!(search.refine(&mut cpu, Refinement::Decreased), 1);
+    assert_eq!(search.results(), &[(0x0011, 4)]);
+    search.reset();
+    assert_eq!(search.results().len(), 0);
+}
+
+#[test]
+fn hexdump_lines() {
+    let mut ram = Ram::new(1024);
+    ram.fill(vec![0xaa, 0xbb], 0x0100);
+    let mut cpu = MOS6502::new(ram);
+    let lines = hexdump(&mut cpu, 0x0100, 0x0110);
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].starts_with("$0100: $AA $BB $00"));
+    assert_eq!(lines[1], "$0110: $00");
+}
+
+#[test]
+fn view_dirty_flags() {
+    let mut cpu = MOS6502::new(Ram::new(1024));
+    let mut view = MemoryView::new();
+    assert!(view
+        .view(&mut cpu, 0x0020, 0x0023)
+        .iter()
+        .all(|cell| cell.dirty));
+    cpu.inject(0x0021, 7);
+    let cells = view.view(&mut cpu, 0x0020, 0x0023);
+    let dirty: Vec<u16> = cells
+        .iter()
+        .filter(|c| c.dirty)
+        .map(|c| c.address)
+        .collect();
+    assert_eq!(dirty, vec![0x0021]);
+    assert_eq!(cells[1].value, 7);
+}
+
+#[test]
+fn context_around_pc() {
+    let mut ram = Ram::new(1024);
+    // lda #$01, sta $0200, inx, jmp $0000
+    ram.fill(
+        vec![0xa9, 0x01, 0x8d, 0x00, 0x02, 0xe8, 0x4c, 0x00, 0x00],
+        0,
+    );
+    let mut cpu = MOS6502::new(ram);
+    let lines = context(&mut cpu, 0x0005, 2, 1);
+    let addresses: Vec<u16> = lines.iter().map(|line| line.address).collect();
+    assert_eq!(addresses, vec![0x0000, 0x0002, 0x0005, 0x0006]);
+    assert!(lines[2].current);
+    assert_eq!(lines[1].text, "sta $0200");
+    assert_eq!(lines[3].text, "jmp $0000");
+}
+
+#[cfg(feature = "lua")]
+#[test]
+fn script_breakpoint_and_memory() {
+    use debugger::script::ScriptHost;
+
+    let mut ram = Ram::new(1024);
+    // inx, inx, inx, jmp $0000
+    ram.fill(vec![0xe8, 0xe8, 0xe8, 0x4c, 0x00, 0x00], 0);
+    let mut cpu = MOS6502::new(ram);
+    let mut host = ScriptHost::new();
+    let script = "
+        break_at(0x0003)
+        log('stopped at', run())
+        poke(0x0100, peek(0x0001) + 1)
+    ";
+    assert!(host.run(&mut cpu, script).is_ok());
+    assert_eq!(host.take_log(), vec!["stopped at 3".to_string()]);
+    assert_eq!(host.breakpoints(), &[0x0003]);
+    assert_eq!(cpu.x, 3);
+    assert_eq!(cpu.inspect(0x0100), 0xe9);
+    assert!(host.run(&mut cpu, "poke(0x10000, 1)").is_err());
+}
+
+#[test]
+fn remote_commands() {
+    let mut ram = Ram::new(1024);
+    // lda #$42, sta $0200
+    ram.fill(vec![0xa9, 0x42, 0x8d, 0x00, 0x02], 0);
+    let mut cpu = MOS6502::new(ram);
+    let mut remote: RemoteServer<u16> = RemoteServer::bind("127.0.0.1:0").unwrap();
+    assert_eq!(remote.handle(&mut cpu, "break $0002"), "ok");
+    assert_eq!(remote.handle(&mut cpu, "breakpoints"), "ok $0002");
+    assert_eq!(remote.handle(&mut cpu, "step 2"), "ok $0005");
+    assert_eq!(remote.handle(&mut cpu, "peek $0200 2"), "ok $42 $00");
+    assert_eq!(remote.handle(&mut cpu, "poke $0201 7"), "ok");
+    assert_eq!(remote.handle(&mut cpu, "peek $0201"), "ok $07");
+    assert_eq!(
+        remote.handle(&mut cpu, "disasm 0 2"),
+        "ok $0000: lda #$42 | $0002: sta $0200"
+    );
+    assert!(remote
+        .handle(&mut cpu, "regs")
+        .starts_with("ok PC=$0005 A=$42"));
+    assert_eq!(
+        remote.handle(&mut cpu, "jump"),
+        "error missing argument for jump"
+    );
+    assert_eq!(remote.handle(&mut cpu, "fly"), "error unknown command fly");
+}
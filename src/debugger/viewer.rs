@@ -0,0 +1,98 @@
+use {Address, Data, Debug};
+
+const BYTES_PER_LINE: usize = 16;
+
+// formats the inclusive range as lines of 16 cells prefixed by their address
+pub fn hexdump<T: Address, U: Data, V: Debug<T, U> + ?Sized>(
+    debugged: &mut V,
+    start: T,
+    end: T,
+) -> Vec<String> {
+    let mut lines = Vec::new();
+    if start > end {
+        return lines;
+    }
+    let mut line = String::new();
+    let mut counter = 0;
+    let mut address = start;
+    loop {
+        if counter % BYTES_PER_LINE == 0 {
+            if !line.is_empty() {
+                lines.push(line);
+            }
+            line = format!("{}:", debugged.address_str(address));
+        }
+        let data = debugged.inspect(address);
+        line.push(' ');
+        line.push_str(&debugged.data_str(data));
+        counter += 1;
+        if address >= end {
+            break;
+        }
+        address += T::one();
+    }
+    lines.push(line);
+    lines
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct ViewCell<T: Address, U: Data> {
+    pub address: T,
+    pub value: U,
+    pub dirty: bool,
+}
+
+// keeps only the last viewed range, so a frontend polling a window of memory
+// every frame gets the changed cells without snapshotting the whole bus
+pub struct MemoryView<T: Address, U: Data> {
+    start: T,
+    previous: Vec<U>,
+}
+
+impl<T: Address, U: Data> Default for MemoryView<T, U> {
+    fn default() -> MemoryView<T, U> {
+        MemoryView::new()
+    }
+}
+
+impl<T: Address, U: Data> MemoryView<T, U> {
+    pub fn new() -> MemoryView<T, U> {
+        MemoryView {
+            start: T::zero(),
+            previous: Vec::new(),
+        }
+    }
+
+    pub fn view<V: Debug<T, U> + ?Sized>(
+        &mut self,
+        debugged: &mut V,
+        start: T,
+        end: T,
+    ) -> Vec<ViewCell<T, U>> {
+        let mut cells = Vec::new();
+        if start > end {
+            return cells;
+        }
+        // a different window has no baseline, so everything is reported as dirty
+        let same_window = start == self.start;
+        let mut address = start;
+        let mut index = 0;
+        loop {
+            let value = debugged.inspect(address);
+            let dirty = !same_window || self.previous.get(index) != Some(&value);
+            cells.push(ViewCell {
+                address,
+                value,
+                dirty,
+            });
+            index += 1;
+            if address >= end {
+                break;
+            }
+            address += T::one();
+        }
+        self.start = start;
+        self.previous = cells.iter().map(|cell| cell.value).collect();
+        cells
+    }
+}
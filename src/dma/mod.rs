@@ -1,7 +1,7 @@
 use std::cell::RefCell;
 use std::rc::Rc;
 use storage::BlockDevice;
-use {Address, AddressBusBlockIO, AddressBusIO, As, Clock};
+use {read_block, write_block, Address, AddressBusBlockIO, AddressBusIO, Clock, Reset};
 
 pub struct DmaBlock<T: Address> {
     block_device: BlockDevice,
@@ -32,10 +32,11 @@ impl<T: Address> DmaBlock<T> {
     }
 }
 
-impl<T: Address + As<usize>> Clock for DmaBlock<T> {
-    fn step(&mut self) {
+impl<T: Address> Clock for DmaBlock<T> {
+    // a block per cycle
+    fn step(&mut self) -> u64 {
         if self.blocks_to_transfer == 0 {
-            return;
+            return 1;
         }
 
         let current_block = self.block;
@@ -45,23 +46,30 @@ impl<T: Address + As<usize>> Clock for DmaBlock<T> {
 
         if self.flags & 0x01 == 1 {
             // bus to block
-            for i in 0..self.block_device.block_size {
-                cache_block[i] = self.bus.borrow_mut().read(address + T::from(i).unwrap());
-            }
+            read_block(&mut *self.bus.borrow_mut(), address, &mut cache_block);
             self.block_device.write(current_block, &cache_block);
         } else {
             // block to bus
             self.block_device.read(current_block, &mut cache_block);
-            for i in 0..self.block_device.block_size {
-                self.bus
-                    .borrow_mut()
-                    .write(address + T::from(i).unwrap(), cache_block[i]);
-            }
+            write_block(&mut *self.bus.borrow_mut(), address, &cache_block);
         }
 
         self.blocks_to_transfer -= 1;
         self.block += T::one();
         self.address += T::from(self.block_device.block_size).unwrap();
+        1
+    }
+}
+
+// a transfer in progress is dropped
+impl<T: Address> Reset for DmaBlock<T> {
+    fn reset(&mut self) {
+        self.block = T::zero();
+        self.blocks_to_transfer = 0;
+        self.address = T::zero();
+        self.flags = 0;
+        self.block_counter = 0;
+        self.address_counter = 0;
     }
 }
 
@@ -0,0 +1,31 @@
+use alloc::string::String;
+use core::fmt;
+
+// what the cpus, buses and loaders report instead of panicking
+#[derive(Clone, PartialEq, Debug)]
+pub enum Error {
+    // the cpu stays on the opcode until reset or moved
+    InvalidOpcode { pc: u16, opcode: u16 },
+    // widened to fit every address type
+    UnmappedAddress(u64),
+    StackOverflow { pc: u16 },
+    StackUnderflow { pc: u16 },
+    LoadError(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::InvalidOpcode { pc, opcode } => {
+                write!(f, "invalid opcode ${:02X} at ${:04X}", opcode, pc)
+            }
+            Error::UnmappedAddress(address) => write!(f, "unknown mapping ${:X}", address),
+            Error::StackOverflow { pc } => write!(f, "stack overflow at ${:04X}", pc),
+            Error::StackUnderflow { pc } => write!(f, "stack underflow at ${:04X}", pc),
+            Error::LoadError(ref message) => write!(f, "{}", message),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl ::std::error::Error for Error {}
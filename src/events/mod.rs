@@ -0,0 +1,63 @@
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+// what the cores and machines report to whoever is watching
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum CpuEvent {
+    // pc is where the instruction was fetched from
+    InstructionRetired { pc: u16, opcode: u16, cycles: u64 },
+    // handler is where the cpu went to
+    InterruptTaken { handler: u16 },
+    // a write into one of the regions marked as io
+    IoWrite { address: u16, value: u8 },
+    // once at the end of run_frame
+    FrameComplete,
+}
+
+pub type Observer = Box<dyn FnMut(&CpuEvent) + Send + Sync>;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ObserverId(usize);
+
+#[derive(Default)]
+pub struct Observers {
+    observers: Vec<(ObserverId, Observer)>,
+    next: usize,
+}
+
+impl Observers {
+    pub fn new() -> Observers {
+        Observers::default()
+    }
+
+    pub fn subscribe<F: FnMut(&CpuEvent) + Send + Sync + 'static>(
+        &mut self,
+        observer: F,
+    ) -> ObserverId {
+        let id = ObserverId(self.next);
+        self.next += 1;
+        self.observers.push((id, Box::new(observer)));
+        id
+    }
+
+    // false when it was not subscribed
+    pub fn unsubscribe(&mut self, id: ObserverId) -> bool {
+        let before = self.observers.len();
+        self.observers.retain(|&(other, _)| other != id);
+        self.observers.len() != before
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.observers.is_empty()
+    }
+
+    // in the order they subscribed
+    pub fn emit(&mut self, event: &CpuEvent) {
+        for &mut (_, ref mut observer) in self.observers.iter_mut() {
+            observer(event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,29 @@
+use std::sync::{Arc, Mutex};
+
+use events::{CpuEvent, Observers};
+
+#[test]
+fn observers() {
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let mut observers = Observers::new();
+    let first = {
+        let seen = Arc::clone(&seen);
+        observers.subscribe(move |event| seen.lock().unwrap().push((1, *event)))
+    };
+    {
+        let seen = Arc::clone(&seen);
+        observers.subscribe(move |event| seen.lock().unwrap().push((2, *event)));
+    }
+    observers.emit(&CpuEvent::FrameComplete);
+    assert!(observers.unsubscribe(first));
+    assert!(!observers.unsubscribe(first));
+    observers.emit(&CpuEvent::InterruptTaken { handler: 0x9000 });
+    assert_eq!(
+        *seen.lock().unwrap(),
+        vec![
+            (1, CpuEvent::FrameComplete),
+            (2, CpuEvent::FrameComplete),
+            (2, CpuEvent::InterruptTaken { handler: 0x9000 }),
+        ]
+    );
+}
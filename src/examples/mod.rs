@@ -0,0 +1,159 @@
+use mos6502::asm::{assemble, Assembly};
+use mos6502::MOS6502;
+use {AddressBusIO, Debug};
+
+// the aivmachine memory map the demos are written for: ram at $0000, the
+// terminal (stdout) at $2001, the piano at $2004 and the rom at $c000
+pub const TERMINAL: u16 = 0x2001;
+pub const PIANO: u16 = 0x2004;
+pub const ORIGIN: u16 = 0xc000;
+// $01 when the memory test passed, $ff when it did not
+pub const RESULT: u16 = 0x0000;
+
+const HELLO: &str = "
+TERMINAL = $2001
+        .org $c000
+start:  ldx #$00
+print:  lda message,x
+        beq done
+        sta TERMINAL
+        inx
+        bne print
+done:   jmp done
+nmi:    rti
+message:
+        .byte 'H', 'e', 'l', 'l', 'o', ' ', 'f', 'r', 'o', 'm', ' ', 'i', 'm', 'p', 'o', 's', 't', 'o', 'r', '!', $0a, $00
+";
+
+// every byte of $0200-$0fff gets its low address byte xor its page, then
+// they are all read back
+const MEMORY_TEST: &str = "
+TERMINAL = $2001
+RESULT = $00
+POINTER = $10
+        .org $c000
+start:  lda #$00
+        sta POINTER
+        lda #$02
+        sta POINTER + 1
+        ldy #$00
+fill:   tya
+        eor POINTER + 1
+        sta (POINTER),y
+        iny
+        bne fill
+        inc POINTER + 1
+        lda POINTER + 1
+        cmp #$10
+        bne fill
+        lda #$02
+        sta POINTER + 1
+check:  tya
+        eor POINTER + 1
+        cmp (POINTER),y
+        bne bad
+        iny
+        bne check
+        inc POINTER + 1
+        lda POINTER + 1
+        cmp #$10
+        bne check
+        lda #$01
+        sta RESULT
+        ldx #$00
+good:   lda passed,x
+        beq done
+        sta TERMINAL
+        inx
+        bne good
+bad:    lda #$ff
+        sta RESULT
+        ldx #$00
+fail:   lda failed,x
+        beq done
+        sta TERMINAL
+        inx
+        bne fail
+done:   jmp done
+nmi:    rti
+passed: .byte 'R', 'A', 'M', ' ', 'O', 'K', $0a, $00
+failed: .byte 'R', 'A', 'M', ' ', 'B', 'A', 'D', $0a, $00
+";
+
+// the piano queues the notes, each one lasts --piano-speed
+const BEEP: &str = "
+PIANO = $2004
+        .org $c000
+start:  ldx #$00
+play:   lda notes,x
+        bmi done
+        sta PIANO
+        inx
+        bne play
+done:   jmp done
+nmi:    rti
+notes:  .byte 12, 16, 19, 24, 19, 16, 12, $ff
+";
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Demo {
+    MemoryTest,
+    Hello,
+    Beep,
+}
+
+pub const DEMOS: [Demo; 3] = [Demo::MemoryTest, Demo::Hello, Demo::Beep];
+
+impl Demo {
+    pub fn name(self) -> &'static str {
+        match self {
+            Demo::MemoryTest => "memtest",
+            Demo::Hello => "hello",
+            Demo::Beep => "beep",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Demo> {
+        DEMOS.iter().cloned().find(|demo| demo.name() == name)
+    }
+
+    pub fn source(self) -> &'static str {
+        match self {
+            Demo::MemoryTest => MEMORY_TEST,
+            Demo::Hello => HELLO,
+            Demo::Beep => BEEP,
+        }
+    }
+
+    pub fn assemble(self) -> Assembly {
+        assemble(self.source()).unwrap()
+    }
+
+    // the 16k aivmachine rom, with the reset vector on start and nmi (the
+    // vblank) on a plain rti
+    pub fn rom(self) -> Vec<u8> {
+        let program = self.assemble();
+        let mut rom = vec![0; 0x4000];
+        let offset = usize::from(program.origin - ORIGIN);
+        rom[offset..offset + program.bytes.len()].copy_from_slice(&program.bytes);
+        let vectors = [program.labels["nmi"], program.labels["start"]];
+        for (i, vector) in vectors.iter().enumerate() {
+            rom[0x3ffa + i * 2] = *vector as u8;
+            rom[0x3ffb + i * 2] = (*vector >> 8) as u8;
+        }
+        rom
+    }
+}
+
+// the bus has to be writable at the origin (a Rom can take Demo::rom
+// instead), the cpu is left at the start of the program
+pub fn load_demo<T: AddressBusIO<u16, u8>>(cpu: &mut MOS6502<T>, demo: Demo) {
+    let program = demo.assemble();
+    for (i, byte) in program.bytes.iter().enumerate() {
+        cpu.inject(program.origin + i as u16, *byte);
+    }
+    cpu.pc = program.labels["start"];
+}
+
+#[cfg(test)]
+mod tests;
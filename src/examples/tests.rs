@@ -0,0 +1,66 @@
+use examples::{load_demo, Demo, DEMOS, ORIGIN, PIANO, RESULT, TERMINAL};
+use mos6502::MOS6502;
+use rom::Rom;
+use AddressBusIO;
+use Clock;
+
+// flat memory keeping what reaches the terminal and the piano
+struct Console {
+    memory: Vec<u8>,
+    text: Vec<u8>,
+    notes: Vec<u8>,
+}
+
+impl AddressBusIO<u16, u8> for Console {
+    fn read(&mut self, address: u16) -> u8 {
+        self.memory[usize::from(address)]
+    }
+
+    fn write(&mut self, address: u16, value: u8) {
+        match address {
+            TERMINAL => self.text.push(value),
+            PIANO => self.notes.push(value),
+            _ => self.memory[usize::from(address)] = value,
+        }
+    }
+}
+
+fn run(demo: Demo) -> MOS6502<Console> {
+    let mut cpu = MOS6502::new(Console {
+        memory: vec![0; 0x10000],
+        text: Vec::new(),
+        notes: Vec::new(),
+    });
+    load_demo(&mut cpu, demo);
+    for _ in 0..100_000 {
+        cpu.step();
+    }
+    cpu
+}
+
+#[test]
+fn demos_run() {
+    let hello = run(Demo::Hello);
+    assert_eq!(hello.bus().text, b"Hello from impostor!\n");
+
+    let mut memtest = run(Demo::MemoryTest);
+    assert_eq!(memtest.bus().text, b"RAM OK\n");
+    assert_eq!(memtest.bus_mut().read(RESULT), 0x01);
+
+    let beep = run(Demo::Beep);
+    assert_eq!(beep.bus().notes, [12, 16, 19, 24, 19, 16, 12]);
+
+    for demo in DEMOS.iter() {
+        assert_eq!(Demo::from_name(demo.name()), Some(*demo));
+    }
+}
+
+#[test]
+fn demo_rom_vectors() {
+    let mut rom = Rom::new(Demo::Hello.rom());
+    let start = u16::from(rom.read(0x3ffcu16)) | u16::from(rom.read(0x3ffdu16)) << 8;
+    assert_eq!(start, ORIGIN);
+    let nmi = u16::from(rom.read(0x3ffau16)) | u16::from(rom.read(0x3ffbu16)) << 8;
+    // rti
+    assert_eq!(rom.read(nmi - ORIGIN), 0x40);
+}
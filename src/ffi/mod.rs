@@ -0,0 +1,196 @@
+// the safety contract of every entry point is in include/impostor.h
+#![allow(clippy::missing_safety_doc)]
+
+use std::ffi::CStr;
+use std::os::raw::c_char;
+use std::ptr;
+use std::slice;
+
+use machine::{Key, Loader, Machine, MACHINES};
+
+// printable ascii is itself, everything else is numbered by group, the
+// IMPOSTOR_KEY_* values in the header
+pub fn key_from_code(code: u32) -> Option<Key> {
+    let key = match code {
+        0x20 => Key::Space,
+        0x21..=0x7e => Key::Char((code as u8 as char).to_ascii_lowercase()),
+        0x100 => Key::Up,
+        0x101 => Key::Down,
+        0x102 => Key::Left,
+        0x103 => Key::Right,
+        0x104 => Key::Enter,
+        0x105 => Key::Escape,
+        0x106 => Key::Backspace,
+        0x107 => Key::Tab,
+        0x108 => Key::Shift,
+        0x109 => Key::Control,
+        0x10a => Key::Alt,
+        0x200..=0x2ff => Key::Function((code - 0x200) as u8),
+        0x300..=0x3ff => Key::Button((code - 0x300) as u8),
+        _ => return None,
+    };
+    Some(key)
+}
+
+// what a C caller holds, buffers handed out stay valid until the next call
+// on the same handle
+pub struct Handle {
+    load: Loader,
+    machine: Option<Box<dyn Machine>>,
+    pixels: Vec<u8>,
+    samples: Vec<f32>,
+}
+
+unsafe fn bytes<'a>(data: *const u8, size: usize) -> &'a [u8] {
+    if data.is_null() {
+        &[]
+    } else {
+        slice::from_raw_parts(data, size)
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn impostor_create(name: *const c_char) -> *mut Handle {
+    if name.is_null() {
+        return ptr::null_mut();
+    }
+    let name = CStr::from_ptr(name).to_string_lossy();
+    match MACHINES.iter().find(|machine| machine.0 == name) {
+        Some(&(_, _, load)) => Box::into_raw(Box::new(Handle {
+            load,
+            machine: None,
+            pixels: Vec::new(),
+            samples: Vec::new(),
+        })),
+        None => ptr::null_mut(),
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn impostor_destroy(handle: *mut Handle) {
+    if !handle.is_null() {
+        drop(Box::from_raw(handle));
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn impostor_load_rom(
+    handle: *mut Handle,
+    data: *const u8,
+    size: usize,
+) -> bool {
+    let handle = &mut *handle;
+    handle.machine = (handle.load)(bytes(data, size)).ok();
+    handle.machine.is_some()
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn impostor_step_frame(handle: *mut Handle) {
+    if let Some(ref mut machine) = (*handle).machine {
+        machine.run_frame();
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn impostor_frame_rate(handle: *mut Handle) -> u32 {
+    match (*handle).machine {
+        Some(ref machine) => machine.frame_rate(),
+        None => 0,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn impostor_framebuffer(
+    handle: *mut Handle,
+    width: *mut usize,
+    height: *mut usize,
+) -> *const u8 {
+    let handle = &mut *handle;
+    let (w, h) = match handle.machine {
+        Some(ref machine) => {
+            let (w, h) = machine.resolution();
+            handle.pixels.resize(w * h * 3, 0);
+            machine.render(&mut handle.pixels);
+            (w, h)
+        }
+        None => (0, 0),
+    };
+    if !width.is_null() {
+        *width = w;
+    }
+    if !height.is_null() {
+        *height = h;
+    }
+    if w == 0 {
+        ptr::null()
+    } else {
+        handle.pixels.as_ptr()
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn impostor_sample_rate(handle: *mut Handle) -> u32 {
+    match (*handle).machine {
+        Some(ref mut machine) => machine.audio().map_or(0, |ring| ring.sample_rate),
+        None => 0,
+    }
+}
+
+// mono samples produced since the last call
+#[no_mangle]
+pub unsafe extern "C" fn impostor_audio(handle: *mut Handle, count: *mut usize) -> *const f32 {
+    let handle = &mut *handle;
+    handle.samples.clear();
+    if let Some(ring) = handle.machine.as_mut().and_then(|machine| machine.audio()) {
+        handle.samples.resize(ring.len(), 0.0);
+        let amount = ring.drain(&mut handle.samples);
+        handle.samples.truncate(amount);
+    }
+    if !count.is_null() {
+        *count = handle.samples.len();
+    }
+    handle.samples.as_ptr()
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn impostor_set_input(handle: *mut Handle, key: u32, pressed: bool) -> bool {
+    match (key_from_code(key), (*handle).machine.as_mut()) {
+        (Some(key), Some(machine)) => {
+            machine.key(key, pressed);
+            true
+        }
+        _ => false,
+    }
+}
+
+// returns the state size, the buffer is filled only when it is large enough
+#[no_mangle]
+pub unsafe extern "C" fn impostor_save_state(
+    handle: *mut Handle,
+    buffer: *mut u8,
+    size: usize,
+) -> usize {
+    let state = match (*handle).machine {
+        Some(ref mut machine) => machine.save_state(),
+        None => return 0,
+    };
+    if !buffer.is_null() && size >= state.len() {
+        ptr::copy_nonoverlapping(state.as_ptr(), buffer, state.len());
+    }
+    state.len()
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn impostor_load_state(
+    handle: *mut Handle,
+    data: *const u8,
+    size: usize,
+) -> bool {
+    match (*handle).machine {
+        Some(ref mut machine) => machine.load_state(bytes(data, size)),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,46 @@
+use ffi::*;
+use machine::Key;
+use std::ptr;
+
+#[test]
+fn key_codes() {
+    assert_eq!(key_from_code('C' as u32), Some(Key::Char('c')));
+    assert_eq!(key_from_code(0x20), Some(Key::Space));
+    assert_eq!(key_from_code(0x103), Some(Key::Right));
+    assert_eq!(key_from_code(0x301), Some(Key::Button(1)));
+    assert_eq!(key_from_code(0x1000), None);
+}
+
+#[cfg(feature = "chip8")]
+#[test]
+fn ffi_roundtrip() {
+    unsafe {
+        assert!(impostor_create(b"nes\0".as_ptr() as *const _).is_null());
+        let handle = impostor_create(b"chip8\0".as_ptr() as *const _);
+        assert!(!handle.is_null());
+        assert!(!impostor_set_input(handle, 'a' as u32, true));
+
+        // ld v0, 0x0a / ld f, v0 / drw v1, v1, 5 / jp 0x206
+        let rom = [0x60, 0x0a, 0xf0, 0x29, 0xd1, 0x15, 0x12, 0x06];
+        assert!(impostor_load_rom(handle, rom.as_ptr(), rom.len()));
+        impostor_step_frame(handle);
+
+        let (mut width, mut height) = (0, 0);
+        let pixels = impostor_framebuffer(handle, &mut width, &mut height);
+        assert_eq!((width, height), (64, 32));
+        assert_eq!(*pixels, 255);
+
+        let mut count = 0;
+        impostor_audio(handle, &mut count);
+        assert_eq!(count as u32, impostor_sample_rate(handle) / 60);
+
+        let size = impostor_save_state(handle, ptr::null_mut(), 0);
+        let mut state = vec![0; size];
+        assert_eq!(impostor_save_state(handle, state.as_mut_ptr(), size), size);
+        assert!(impostor_load_rom(handle, rom.as_ptr(), rom.len()));
+        assert!(impostor_load_state(handle, state.as_ptr(), size));
+        assert!(!impostor_load_state(handle, state.as_ptr(), size - 1));
+
+        impostor_destroy(handle);
+    }
+}
@@ -0,0 +1,129 @@
+extern crate gilrs;
+
+pub use self::gilrs::Button;
+use self::gilrs::{Axis, EventType, Gilrs};
+
+use machine::{Key, Machine};
+
+// how far a stick has to be pushed to count as a d-pad direction
+const DEADZONE: f32 = 0.5;
+
+// physical button to machine key, the layout follows the snes/xbox naming
+// used by gilrs (south is A on xbox pads, B on nintendo ones)
+pub struct Bindings {
+    buttons: Vec<(Button, Key)>,
+    pub stick_as_dpad: bool,
+}
+
+impl Default for Bindings {
+    fn default() -> Bindings {
+        Bindings {
+            buttons: vec![
+                (Button::DPadUp, Key::Up),
+                (Button::DPadDown, Key::Down),
+                (Button::DPadLeft, Key::Left),
+                (Button::DPadRight, Key::Right),
+                (Button::South, Key::Button(0)),
+                (Button::East, Key::Button(1)),
+                (Button::West, Key::Button(2)),
+                (Button::North, Key::Button(3)),
+                (Button::Start, Key::Enter),
+                (Button::Select, Key::Escape),
+            ],
+            stick_as_dpad: true,
+        }
+    }
+}
+
+impl Bindings {
+    pub fn empty() -> Bindings {
+        Bindings {
+            buttons: Vec::new(),
+            stick_as_dpad: false,
+        }
+    }
+
+    pub fn bind(&mut self, button: Button, key: Key) {
+        self.unbind(button);
+        self.buttons.push((button, key));
+    }
+
+    pub fn unbind(&mut self, button: Button) {
+        self.buttons.retain(|&(bound, _)| bound != button);
+    }
+
+    pub fn key(&self, button: Button) -> Option<Key> {
+        self.buttons
+            .iter()
+            .find(|&&(bound, _)| bound == button)
+            .map(|&(_, key)| key)
+    }
+}
+
+// all of the connected pads drive the same machine
+pub struct Gamepads {
+    gilrs: Gilrs,
+    pub bindings: Bindings,
+    // negative and positive direction currently held for each stick axis
+    stick_x: (bool, bool),
+    stick_y: (bool, bool),
+}
+
+fn stick<M: Machine>(
+    machine: &mut M,
+    value: f32,
+    state: &mut (bool, bool),
+    negative: Key,
+    positive: Key,
+) {
+    let new_state = (value < -DEADZONE, value > DEADZONE);
+    if new_state.0 != state.0 {
+        machine.key(negative, new_state.0);
+    }
+    if new_state.1 != state.1 {
+        machine.key(positive, new_state.1);
+    }
+    *state = new_state;
+}
+
+impl Gamepads {
+    pub fn new(bindings: Bindings) -> Result<Gamepads, String> {
+        let gilrs = Gilrs::new().map_err(|err| err.to_string())?;
+        Ok(Gamepads {
+            gilrs,
+            bindings,
+            stick_x: (false, false),
+            stick_y: (false, false),
+        })
+    }
+
+    // to be called once per frame, before running the machine
+    pub fn poll<M: Machine>(&mut self, machine: &mut M) {
+        while let Some(event) = self.gilrs.next_event() {
+            match event.event {
+                EventType::ButtonPressed(button, _) => {
+                    if let Some(key) = self.bindings.key(button) {
+                        machine.key(key, true);
+                    }
+                }
+                EventType::ButtonReleased(button, _) => {
+                    if let Some(key) = self.bindings.key(button) {
+                        machine.key(key, false);
+                    }
+                }
+                EventType::AxisChanged(Axis::LeftStickX, value, _)
+                    if self.bindings.stick_as_dpad =>
+                {
+                    stick(machine, value, &mut self.stick_x, Key::Left, Key::Right)
+                }
+                // gilrs reports up as positive
+                EventType::AxisChanged(Axis::LeftStickY, value, _)
+                    if self.bindings.stick_as_dpad =>
+                {
+                    stick(machine, value, &mut self.stick_y, Key::Down, Key::Up)
+                }
+                _ => (),
+            }
+        }
+    }
+}
@@ -0,0 +1,304 @@
+extern crate eframe;
+
+use self::eframe::egui;
+use self::eframe::egui::{Color32, ColorImage, RichText, TextureHandle, TextureOptions};
+
+use std::time::Duration;
+
+use debugger::context::context;
+use debugger::viewer::MemoryView;
+use machine::{Key, Machine};
+use utils::to_number;
+use Debug;
+
+// instructions executed by "run to breakpoint" before giving up
+const RUN_LIMIT: usize = 1_000_000;
+const MEMORY_ROWS: u16 = 16;
+
+fn translate_key(key: egui::Key) -> Option<Key> {
+    let key = match key {
+        egui::Key::ArrowUp => Key::Up,
+        egui::Key::ArrowDown => Key::Down,
+        egui::Key::ArrowLeft => Key::Left,
+        egui::Key::ArrowRight => Key::Right,
+        egui::Key::Enter => Key::Enter,
+        egui::Key::Space => Key::Space,
+        egui::Key::Escape => Key::Escape,
+        egui::Key::Backspace => Key::Backspace,
+        egui::Key::Tab => Key::Tab,
+        _ => {
+            let name = key.name();
+            if name.len() > 1 && name.starts_with('F') {
+                return name[1..].parse().ok().map(Key::Function);
+            }
+            let mut chars = name.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => Key::Char(c.to_ascii_lowercase()),
+                _ => return None,
+            }
+        }
+    };
+    Some(key)
+}
+
+// registers, disassembly, memory and breakpoints windows for anything
+// implementing Debug, usable from any egui host
+pub struct DebuggerPanel {
+    view: MemoryView<u16, u8>,
+    memory_address: String,
+    breakpoint_address: String,
+    pub breakpoints: Vec<u16>,
+    pub paused: bool,
+    // set when "step frame" is clicked, the host is expected to reset it
+    pub frame_step: bool,
+    message: String,
+}
+
+impl Default for DebuggerPanel {
+    fn default() -> DebuggerPanel {
+        DebuggerPanel::new()
+    }
+}
+
+impl DebuggerPanel {
+    pub fn new() -> DebuggerPanel {
+        DebuggerPanel {
+            view: MemoryView::new(),
+            memory_address: "$0000".to_string(),
+            breakpoint_address: String::new(),
+            breakpoints: Vec::new(),
+            paused: false,
+            frame_step: false,
+            message: String::new(),
+        }
+    }
+
+    fn run_to_breakpoint(&mut self, debugged: &mut dyn Debug<u16, u8>) {
+        for _ in 0..RUN_LIMIT {
+            debugged.next();
+            let pc = debugged.get_cursor();
+            if debugged.is_code_breakpoint_requested() || self.breakpoints.contains(&pc) {
+                self.message = format!("stopped at {}", debugged.address_str(pc));
+                return;
+            }
+        }
+        self.message = format!("no breakpoint hit in {} instructions", RUN_LIMIT);
+    }
+
+    fn controls(&mut self, ui: &mut egui::Ui, debugged: &mut dyn Debug<u16, u8>) {
+        ui.horizontal(|ui| {
+            let label = if self.paused { "Resume" } else { "Pause" };
+            if ui.button(label).clicked() {
+                self.paused = !self.paused;
+                self.message.clear();
+            }
+            if ui.button("Step").clicked() {
+                self.paused = true;
+                debugged.next();
+            }
+            if ui.button("Step frame").clicked() {
+                self.paused = true;
+                self.frame_step = true;
+            }
+            if ui.button("Run to breakpoint").clicked() {
+                self.paused = true;
+                self.run_to_breakpoint(debugged);
+            }
+        });
+        if !self.message.is_empty() {
+            ui.label(&self.message);
+        }
+    }
+
+    fn registers(&mut self, ui: &mut egui::Ui, debugged: &mut dyn Debug<u16, u8>) {
+        egui::Grid::new("registers").show(ui, |ui| {
+            for (name, value) in debugged.registers() {
+                ui.monospace(name);
+                ui.monospace(value);
+                ui.end_row();
+            }
+        });
+    }
+
+    fn disassembly(&mut self, ui: &mut egui::Ui, debugged: &mut dyn Debug<u16, u8>) {
+        let pc = debugged.get_cursor();
+        for line in context(debugged, pc, 8, 16) {
+            let marker = if self.breakpoints.contains(&line.address) {
+                "*"
+            } else {
+                " "
+            };
+            let text = format!(
+                "{}{} {}",
+                marker,
+                debugged.address_str(line.address),
+                line.text
+            );
+            let text = RichText::new(text).monospace();
+            if line.current {
+                ui.label(text.color(Color32::YELLOW));
+            } else {
+                ui.label(text);
+            }
+        }
+    }
+
+    fn memory(&mut self, ui: &mut egui::Ui, debugged: &mut dyn Debug<u16, u8>) {
+        ui.horizontal(|ui| {
+            ui.label("address");
+            ui.text_edit_singleline(&mut self.memory_address);
+        });
+        let start = match to_number::<u16>(self.memory_address.trim()) {
+            Ok(address) => address & 0xfff0,
+            Err(_) => {
+                ui.label("invalid address");
+                return;
+            }
+        };
+        let end = start.saturating_add(MEMORY_ROWS * 16 - 1);
+        let cells = self.view.view(debugged, start, end);
+        egui::Grid::new("memory").show(ui, |ui| {
+            for row in cells.chunks(16) {
+                ui.monospace(debugged.address_str(row[0].address));
+                for cell in row {
+                    let text = RichText::new(format!("{:02X}", cell.value)).monospace();
+                    // recently changed bytes stand out
+                    if cell.dirty {
+                        ui.label(text.color(Color32::LIGHT_RED));
+                    } else {
+                        ui.label(text);
+                    }
+                }
+                ui.end_row();
+            }
+        });
+    }
+
+    fn breakpoint_list(&mut self, ui: &mut egui::Ui, debugged: &mut dyn Debug<u16, u8>) {
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut self.breakpoint_address);
+            if ui.button("Add").clicked() {
+                match to_number::<u16>(self.breakpoint_address.trim()) {
+                    Ok(address) => {
+                        if !self.breakpoints.contains(&address) {
+                            self.breakpoints.push(address);
+                        }
+                        self.breakpoint_address.clear();
+                    }
+                    Err(err) => self.message = format!("invalid breakpoint: {}", err),
+                }
+            }
+        });
+        let mut removed = None;
+        for breakpoint in &self.breakpoints {
+            ui.horizontal(|ui| {
+                ui.monospace(debugged.address_str(*breakpoint));
+                if ui.button("Remove").clicked() {
+                    removed = Some(*breakpoint);
+                }
+            });
+        }
+        if let Some(removed) = removed {
+            self.breakpoints.retain(|breakpoint| *breakpoint != removed);
+        }
+    }
+
+    pub fn show(&mut self, ctx: &egui::Context, debugged: &mut dyn Debug<u16, u8>) {
+        egui::Window::new("Controls").show(ctx, |ui| self.controls(ui, debugged));
+        egui::Window::new("Registers").show(ctx, |ui| self.registers(ui, debugged));
+        egui::Window::new("Disassembly").show(ctx, |ui| self.disassembly(ui, debugged));
+        egui::Window::new("Memory").show(ctx, |ui| self.memory(ui, debugged));
+        egui::Window::new("Breakpoints").show(ctx, |ui| self.breakpoint_list(ui, debugged));
+    }
+}
+
+struct GuiApp<M: Machine> {
+    machine: M,
+    panel: DebuggerPanel,
+    pixels: Vec<u8>,
+    screen: Option<TextureHandle>,
+    scale: f32,
+}
+
+impl<M: Machine> GuiApp<M> {
+    fn keys(&mut self, ctx: &egui::Context) {
+        // typing in the debugger fields must not reach the machine
+        if ctx.wants_keyboard_input() {
+            return;
+        }
+        let events = ctx.input(|input| input.events.clone());
+        for event in events {
+            if let egui::Event::Key {
+                key,
+                pressed,
+                repeat: false,
+                ..
+            } = event
+            {
+                if let Some(key) = translate_key(key) {
+                    self.machine.key(key, pressed);
+                }
+            }
+        }
+    }
+}
+
+impl<M: Machine> eframe::App for GuiApp<M> {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.keys(ctx);
+
+        if !self.panel.paused || self.panel.frame_step {
+            self.machine.run_frame();
+            self.panel.frame_step = false;
+        }
+        // no audio here, do not let it pile up
+        if let Some(ring) = self.machine.audio() {
+            ring.clear();
+        }
+
+        let (width, height) = self.machine.resolution();
+        self.machine.render(&mut self.pixels);
+        let image = ColorImage::from_rgb([width, height], &self.pixels);
+        match self.screen {
+            Some(ref mut screen) => screen.set(image, TextureOptions::NEAREST),
+            None => self.screen = Some(ctx.load_texture("screen", image, TextureOptions::NEAREST)),
+        }
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            if let Some(ref screen) = self.screen {
+                let size = egui::vec2(width as f32 * self.scale, height as f32 * self.scale);
+                ui.add(egui::Image::new(egui::load::SizedTexture::new(
+                    screen.id(),
+                    size,
+                )));
+            }
+        });
+
+        match self.machine.debug() {
+            Some(debugged) => self.panel.show(ctx, debugged),
+            None => {
+                egui::Window::new("Debugger").show(ctx, |ui| {
+                    ui.label("this machine does not support debugging");
+                });
+            }
+        }
+
+        let frame_rate = self.machine.frame_rate().max(1);
+        ctx.request_repaint_after(Duration::from_secs(1) / frame_rate);
+    }
+}
+
+// a window with the machine screen and the debugger panels around it
+pub fn run<M: Machine>(machine: M, title: &str, scale: u32) -> Result<(), String> {
+    let (width, height) = machine.resolution();
+    let app = GuiApp {
+        machine,
+        panel: DebuggerPanel::new(),
+        pixels: vec![0; width * height * 3],
+        screen: None,
+        scale: scale as f32,
+    };
+    let options = eframe::NativeOptions::default();
+    eframe::run_native(title, options, Box::new(move |_| Ok(Box::new(app))))
+        .map_err(|err| err.to_string())
+}
@@ -0,0 +1,487 @@
+use std::os::raw::{c_char, c_void};
+use std::ptr;
+use std::sync::Mutex;
+
+use machine::{Key, Machine};
+use Error;
+
+pub const API_VERSION: u32 = 1;
+
+const ENVIRONMENT_SET_PIXEL_FORMAT: u32 = 10;
+const ENVIRONMENT_SET_KEYBOARD_CALLBACK: u32 = 12;
+const PIXEL_FORMAT_XRGB8888: u32 = 1;
+const DEVICE_JOYPAD: u32 = 1;
+
+// joypad ids in libretro order, mapped to the gilrs-like numbering of
+// Key::Button (0 south, 1 east, 2 west, 3 north)
+const JOYPAD: [(u32, Key); 10] = [
+    (0, Key::Button(0)),
+    (1, Key::Button(2)),
+    (2, Key::Escape),
+    (3, Key::Enter),
+    (4, Key::Up),
+    (5, Key::Down),
+    (6, Key::Left),
+    (7, Key::Right),
+    (8, Key::Button(1)),
+    (9, Key::Button(3)),
+];
+
+#[repr(C)]
+pub struct SystemInfo {
+    pub library_name: *const c_char,
+    pub library_version: *const c_char,
+    pub valid_extensions: *const c_char,
+    pub need_fullpath: bool,
+    pub block_extract: bool,
+}
+
+#[repr(C)]
+pub struct GameGeometry {
+    pub base_width: u32,
+    pub base_height: u32,
+    pub max_width: u32,
+    pub max_height: u32,
+    pub aspect_ratio: f32,
+}
+
+#[repr(C)]
+pub struct SystemTiming {
+    pub fps: f64,
+    pub sample_rate: f64,
+}
+
+#[repr(C)]
+pub struct SystemAvInfo {
+    pub geometry: GameGeometry,
+    pub timing: SystemTiming,
+}
+
+#[repr(C)]
+pub struct GameInfo {
+    pub path: *const c_char,
+    pub data: *const c_void,
+    pub size: usize,
+    pub meta: *const c_char,
+}
+
+#[repr(C)]
+struct KeyboardCallback {
+    callback: extern "C" fn(bool, u32, u32, u16),
+}
+
+pub type EnvironmentFn = extern "C" fn(u32, *mut c_void) -> bool;
+pub type VideoRefreshFn = extern "C" fn(*const c_void, u32, u32, usize);
+pub type AudioSampleFn = extern "C" fn(i16, i16);
+pub type AudioSampleBatchFn = extern "C" fn(*const i16, usize) -> usize;
+pub type InputPollFn = extern "C" fn();
+pub type InputStateFn = extern "C" fn(u32, u32, u32, u32) -> i16;
+
+// the keyboard callback can be invoked while the core is running (from the
+// input poll), so events are queued apart from the core state
+static KEYBOARD: Mutex<Vec<(Key, bool)>> = Mutex::new(Vec::new());
+
+pub fn translate_keycode(keycode: u32) -> Option<Key> {
+    let key = match keycode {
+        8 => Key::Backspace,
+        9 => Key::Tab,
+        13 => Key::Enter,
+        27 => Key::Escape,
+        32 => Key::Space,
+        33..=126 => Key::Char((keycode as u8 as char).to_ascii_lowercase()),
+        273 => Key::Up,
+        274 => Key::Down,
+        275 => Key::Right,
+        276 => Key::Left,
+        282..=293 => Key::Function((keycode - 281) as u8),
+        303 | 304 => Key::Shift,
+        305 | 306 => Key::Control,
+        307 | 308 => Key::Alt,
+        _ => return None,
+    };
+    Some(key)
+}
+
+extern "C" fn keyboard_event(down: bool, keycode: u32, _character: u32, _modifiers: u16) {
+    if let Some(key) = translate_keycode(keycode) {
+        KEYBOARD.lock().unwrap().push((key, down));
+    }
+}
+
+// everything behind the retro_* entry points, the libretro_core macro keeps
+// a single instance of it
+pub struct Core<M: Machine> {
+    load: fn(&[u8]) -> Result<M, Error>,
+    machine: Option<M>,
+    game: Vec<u8>,
+    environment: Option<EnvironmentFn>,
+    video_refresh: Option<VideoRefreshFn>,
+    audio_batch: Option<AudioSampleBatchFn>,
+    input_poll: Option<InputPollFn>,
+    input_state: Option<InputStateFn>,
+    joypad: [bool; 10],
+    pixels: Vec<u8>,
+    xrgb: Vec<u32>,
+    samples: Vec<f32>,
+    stereo: Vec<i16>,
+}
+
+impl<M: Machine> Core<M> {
+    pub fn new(load: fn(&[u8]) -> Result<M, Error>) -> Core<M> {
+        Core {
+            load,
+            machine: None,
+            game: Vec::new(),
+            environment: None,
+            video_refresh: None,
+            audio_batch: None,
+            input_poll: None,
+            input_state: None,
+            joypad: [false; 10],
+            pixels: Vec::new(),
+            xrgb: Vec::new(),
+            samples: vec![0.0; 4096],
+            stereo: Vec::new(),
+        }
+    }
+
+    pub fn set_environment(&mut self, environment: EnvironmentFn) {
+        self.environment = Some(environment);
+        let mut keyboard = KeyboardCallback {
+            callback: keyboard_event,
+        };
+        environment(
+            ENVIRONMENT_SET_KEYBOARD_CALLBACK,
+            &mut keyboard as *mut KeyboardCallback as *mut c_void,
+        );
+    }
+
+    pub fn set_video_refresh(&mut self, callback: VideoRefreshFn) {
+        self.video_refresh = Some(callback);
+    }
+
+    pub fn set_audio_sample_batch(&mut self, callback: AudioSampleBatchFn) {
+        self.audio_batch = Some(callback);
+    }
+
+    pub fn set_input_poll(&mut self, callback: InputPollFn) {
+        self.input_poll = Some(callback);
+    }
+
+    pub fn set_input_state(&mut self, callback: InputStateFn) {
+        self.input_state = Some(callback);
+    }
+
+    // resolution and timing are known only once a game is loaded, the
+    // machine defaults are reported before
+    pub fn av_info(&mut self) -> SystemAvInfo {
+        let (width, height, fps, sample_rate) = match self.machine {
+            Some(ref mut machine) => {
+                let (width, height) = machine.resolution();
+                let sample_rate = machine.audio().map_or(44100, |ring| ring.sample_rate);
+                (width, height, machine.frame_rate(), sample_rate)
+            }
+            None => (320, 240, 60, 44100),
+        };
+        SystemAvInfo {
+            geometry: GameGeometry {
+                base_width: width as u32,
+                base_height: height as u32,
+                max_width: width as u32,
+                max_height: height as u32,
+                aspect_ratio: 0.0,
+            },
+            timing: SystemTiming {
+                fps: f64::from(fps),
+                sample_rate: f64::from(sample_rate),
+            },
+        }
+    }
+
+    pub fn load_game(&mut self, data: &[u8]) -> bool {
+        if let Some(environment) = self.environment {
+            let mut format = PIXEL_FORMAT_XRGB8888;
+            if !environment(
+                ENVIRONMENT_SET_PIXEL_FORMAT,
+                &mut format as *mut u32 as *mut c_void,
+            ) {
+                return false;
+            }
+        }
+        self.game = data.to_vec();
+        self.machine = (self.load)(data).ok();
+        KEYBOARD.lock().unwrap().clear();
+        self.joypad = [false; 10];
+        self.machine.is_some()
+    }
+
+    pub fn unload_game(&mut self) {
+        self.machine = None;
+        self.game.clear();
+    }
+
+    // machines have no reset line, the game is just loaded again
+    pub fn reset(&mut self) {
+        self.machine = (self.load)(&self.game).ok();
+    }
+
+    pub fn serialize(&mut self) -> Vec<u8> {
+        match self.machine {
+            Some(ref mut machine) => machine.save_state(),
+            None => Vec::new(),
+        }
+    }
+
+    pub fn unserialize(&mut self, state: &[u8]) -> bool {
+        match self.machine {
+            Some(ref mut machine) => machine.load_state(state),
+            None => false,
+        }
+    }
+
+    fn input(&mut self) {
+        let machine = match self.machine {
+            Some(ref mut machine) => machine,
+            None => return,
+        };
+        if let Some(input_poll) = self.input_poll {
+            input_poll();
+        }
+        for (key, pressed) in KEYBOARD.lock().unwrap().drain(..) {
+            machine.key(key, pressed);
+        }
+        if let Some(input_state) = self.input_state {
+            for (i, &(id, key)) in JOYPAD.iter().enumerate() {
+                let pressed = input_state(0, DEVICE_JOYPAD, 0, id) != 0;
+                if pressed != self.joypad[i] {
+                    machine.key(key, pressed);
+                    self.joypad[i] = pressed;
+                }
+            }
+        }
+    }
+
+    pub fn run(&mut self) {
+        self.input();
+        let machine = match self.machine {
+            Some(ref mut machine) => machine,
+            None => return,
+        };
+
+        machine.run_frame();
+
+        let (width, height) = machine.resolution();
+        self.pixels.resize(width * height * 3, 0);
+        self.xrgb.resize(width * height, 0);
+        machine.render(&mut self.pixels);
+        for (pixel, rgb) in self.xrgb.iter_mut().zip(self.pixels.chunks(3)) {
+            *pixel = u32::from(rgb[0]) << 16 | u32::from(rgb[1]) << 8 | u32::from(rgb[2]);
+        }
+        if let Some(video_refresh) = self.video_refresh {
+            video_refresh(
+                self.xrgb.as_ptr() as *const c_void,
+                width as u32,
+                height as u32,
+                width * 4,
+            );
+        }
+
+        if let Some(ring) = machine.audio() {
+            loop {
+                let amount = ring.drain(&mut self.samples);
+                if amount == 0 {
+                    break;
+                }
+                // mono machines, stereo frontends
+                self.stereo.clear();
+                for sample in &self.samples[0..amount] {
+                    let value = (sample.clamp(-1.0, 1.0) * f32::from(i16::MAX)) as i16;
+                    self.stereo.push(value);
+                    self.stereo.push(value);
+                }
+                if let Some(audio_batch) = self.audio_batch {
+                    audio_batch(self.stereo.as_ptr(), amount);
+                }
+            }
+        }
+    }
+}
+
+pub fn system_info(
+    name: *const c_char,
+    version: *const c_char,
+    extensions: *const c_char,
+) -> SystemInfo {
+    SystemInfo {
+        library_name: name,
+        library_version: version,
+        valid_extensions: extensions,
+        need_fullpath: false,
+        block_extract: false,
+    }
+}
+
+pub fn null() -> *mut c_void {
+    ptr::null_mut()
+}
+
+// exports all of the retro_* symbols for a machine type, the loader builds a
+// machine from the game data:
+//
+// libretro_core!(Chip8<Ram<u8>>, load, "chip8", "0.1.0", "ch8");
+#[macro_export]
+macro_rules! libretro_core {
+    ($machine:ty, $load:expr, $name:expr, $version:expr, $extensions:expr) => {
+        use std::os::raw::{c_char, c_void};
+        use std::sync::Mutex;
+        use $crate::frontend::libretro as retro;
+
+        static CORE: Mutex<Option<retro::Core<$machine>>> = Mutex::new(None);
+
+        fn with_core<R, F: FnOnce(&mut retro::Core<$machine>) -> R>(f: F) -> R {
+            let mut core = CORE.lock().unwrap();
+            if core.is_none() {
+                *core = Some(retro::Core::new($load));
+            }
+            f(core.as_mut().unwrap())
+        }
+
+        #[no_mangle]
+        pub extern "C" fn retro_api_version() -> u32 {
+            retro::API_VERSION
+        }
+
+        #[no_mangle]
+        pub extern "C" fn retro_init() {
+            with_core(|_| ());
+        }
+
+        #[no_mangle]
+        pub extern "C" fn retro_deinit() {
+            *CORE.lock().unwrap() = None;
+        }
+
+        #[no_mangle]
+        pub unsafe extern "C" fn retro_get_system_info(info: *mut retro::SystemInfo) {
+            *info = retro::system_info(
+                concat!($name, "\0").as_ptr() as *const c_char,
+                concat!($version, "\0").as_ptr() as *const c_char,
+                concat!($extensions, "\0").as_ptr() as *const c_char,
+            );
+        }
+
+        #[no_mangle]
+        pub unsafe extern "C" fn retro_get_system_av_info(info: *mut retro::SystemAvInfo) {
+            *info = with_core(|core| core.av_info());
+        }
+
+        #[no_mangle]
+        pub extern "C" fn retro_set_environment(callback: retro::EnvironmentFn) {
+            with_core(|core| core.set_environment(callback));
+        }
+
+        #[no_mangle]
+        pub extern "C" fn retro_set_video_refresh(callback: retro::VideoRefreshFn) {
+            with_core(|core| core.set_video_refresh(callback));
+        }
+
+        #[no_mangle]
+        pub extern "C" fn retro_set_audio_sample(_callback: retro::AudioSampleFn) {}
+
+        #[no_mangle]
+        pub extern "C" fn retro_set_audio_sample_batch(callback: retro::AudioSampleBatchFn) {
+            with_core(|core| core.set_audio_sample_batch(callback));
+        }
+
+        #[no_mangle]
+        pub extern "C" fn retro_set_input_poll(callback: retro::InputPollFn) {
+            with_core(|core| core.set_input_poll(callback));
+        }
+
+        #[no_mangle]
+        pub extern "C" fn retro_set_input_state(callback: retro::InputStateFn) {
+            with_core(|core| core.set_input_state(callback));
+        }
+
+        #[no_mangle]
+        pub extern "C" fn retro_set_controller_port_device(_port: u32, _device: u32) {}
+
+        #[no_mangle]
+        pub extern "C" fn retro_reset() {
+            with_core(|core| core.reset());
+        }
+
+        #[no_mangle]
+        pub extern "C" fn retro_run() {
+            with_core(|core| core.run());
+        }
+
+        #[no_mangle]
+        pub extern "C" fn retro_serialize_size() -> usize {
+            with_core(|core| core.serialize().len())
+        }
+
+        #[no_mangle]
+        pub unsafe extern "C" fn retro_serialize(data: *mut c_void, size: usize) -> bool {
+            let state = with_core(|core| core.serialize());
+            if state.is_empty() || state.len() > size {
+                return false;
+            }
+            ::std::ptr::copy_nonoverlapping(state.as_ptr(), data as *mut u8, state.len());
+            true
+        }
+
+        #[no_mangle]
+        pub unsafe extern "C" fn retro_unserialize(data: *const c_void, size: usize) -> bool {
+            let state = ::std::slice::from_raw_parts(data as *const u8, size);
+            with_core(|core| core.unserialize(state))
+        }
+
+        #[no_mangle]
+        pub extern "C" fn retro_cheat_reset() {}
+
+        #[no_mangle]
+        pub extern "C" fn retro_cheat_set(_index: u32, _enabled: bool, _code: *const c_char) {}
+
+        #[no_mangle]
+        pub unsafe extern "C" fn retro_load_game(game: *const retro::GameInfo) -> bool {
+            // the frontend owns the game buffer, no game is an empty one
+            let data = match game.as_ref() {
+                Some(game) if !game.data.is_null() => {
+                    ::std::slice::from_raw_parts(game.data as *const u8, game.size)
+                }
+                _ => &[],
+            };
+            with_core(|core| core.load_game(data))
+        }
+
+        #[no_mangle]
+        pub extern "C" fn retro_load_game_special(
+            _game_type: u32,
+            _info: *const retro::GameInfo,
+            _num_info: usize,
+        ) -> bool {
+            false
+        }
+
+        #[no_mangle]
+        pub extern "C" fn retro_unload_game() {
+            with_core(|core| core.unload_game());
+        }
+
+        #[no_mangle]
+        pub extern "C" fn retro_get_region() -> u32 {
+            0
+        }
+
+        #[no_mangle]
+        pub extern "C" fn retro_get_memory_data(_id: u32) -> *mut c_void {
+            retro::null()
+        }
+
+        #[no_mangle]
+        pub extern "C" fn retro_get_memory_size(_id: u32) -> usize {
+            0
+        }
+    };
+}
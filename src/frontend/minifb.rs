@@ -0,0 +1,148 @@
+extern crate minifb;
+
+use self::minifb::{Key as MinifbKey, KeyRepeat, Scale, Window, WindowOptions};
+
+#[cfg(feature = "gilrs")]
+use frontend::gamepad::{Bindings, Gamepads};
+use frontend::pacing::{Pacer, MAX_SKIP};
+use frontend::FrontendAudio;
+use machine::{Key, Machine};
+
+fn translate_key(key: MinifbKey) -> Option<Key> {
+    let key = match key {
+        MinifbKey::Key0 => Key::Char('0'),
+        MinifbKey::Key1 => Key::Char('1'),
+        MinifbKey::Key2 => Key::Char('2'),
+        MinifbKey::Key3 => Key::Char('3'),
+        MinifbKey::Key4 => Key::Char('4'),
+        MinifbKey::Key5 => Key::Char('5'),
+        MinifbKey::Key6 => Key::Char('6'),
+        MinifbKey::Key7 => Key::Char('7'),
+        MinifbKey::Key8 => Key::Char('8'),
+        MinifbKey::Key9 => Key::Char('9'),
+        MinifbKey::A => Key::Char('a'),
+        MinifbKey::B => Key::Char('b'),
+        MinifbKey::C => Key::Char('c'),
+        MinifbKey::D => Key::Char('d'),
+        MinifbKey::E => Key::Char('e'),
+        MinifbKey::F => Key::Char('f'),
+        MinifbKey::G => Key::Char('g'),
+        MinifbKey::H => Key::Char('h'),
+        MinifbKey::I => Key::Char('i'),
+        MinifbKey::J => Key::Char('j'),
+        MinifbKey::K => Key::Char('k'),
+        MinifbKey::L => Key::Char('l'),
+        MinifbKey::M => Key::Char('m'),
+        MinifbKey::N => Key::Char('n'),
+        MinifbKey::O => Key::Char('o'),
+        MinifbKey::P => Key::Char('p'),
+        MinifbKey::Q => Key::Char('q'),
+        MinifbKey::R => Key::Char('r'),
+        MinifbKey::S => Key::Char('s'),
+        MinifbKey::T => Key::Char('t'),
+        MinifbKey::U => Key::Char('u'),
+        MinifbKey::V => Key::Char('v'),
+        MinifbKey::W => Key::Char('w'),
+        MinifbKey::X => Key::Char('x'),
+        MinifbKey::Y => Key::Char('y'),
+        MinifbKey::Z => Key::Char('z'),
+        MinifbKey::Up => Key::Up,
+        MinifbKey::Down => Key::Down,
+        MinifbKey::Left => Key::Left,
+        MinifbKey::Right => Key::Right,
+        MinifbKey::Enter => Key::Enter,
+        MinifbKey::Space => Key::Space,
+        MinifbKey::Escape => Key::Escape,
+        MinifbKey::Backspace => Key::Backspace,
+        MinifbKey::Tab => Key::Tab,
+        MinifbKey::LeftShift | MinifbKey::RightShift => Key::Shift,
+        MinifbKey::LeftCtrl | MinifbKey::RightCtrl => Key::Control,
+        MinifbKey::LeftAlt | MinifbKey::RightAlt => Key::Alt,
+        MinifbKey::F1 => Key::Function(1),
+        MinifbKey::F2 => Key::Function(2),
+        MinifbKey::F3 => Key::Function(3),
+        MinifbKey::F4 => Key::Function(4),
+        MinifbKey::F5 => Key::Function(5),
+        MinifbKey::F6 => Key::Function(6),
+        MinifbKey::F7 => Key::Function(7),
+        MinifbKey::F8 => Key::Function(8),
+        MinifbKey::F9 => Key::Function(9),
+        MinifbKey::F10 => Key::Function(10),
+        MinifbKey::F11 => Key::Function(11),
+        MinifbKey::F12 => Key::Function(12),
+        _ => return None,
+    };
+    Some(key)
+}
+
+// minifb only supports power of two scales
+fn translate_scale(scale: u32) -> Scale {
+    match scale {
+        0..=1 => Scale::X1,
+        2..=3 => Scale::X2,
+        4..=7 => Scale::X4,
+        8..=15 => Scale::X8,
+        16..=31 => Scale::X16,
+        _ => Scale::X32,
+    }
+}
+
+// same as the sdl frontend, without gamepads (audio needs the cpal feature)
+pub fn run<M: Machine>(machine: &mut M, title: &str, scale: u32) -> Result<(), String> {
+    let (width, height) = machine.resolution();
+    let options = WindowOptions {
+        scale: translate_scale(scale),
+        ..WindowOptions::default()
+    };
+    let mut window = Window::new(title, width, height, options).map_err(|err| err.to_string())?;
+
+    let mut pixels = vec![0; width * height * 3];
+    let mut buffer: Vec<u32> = vec![0; width * height];
+    let mut pacer = Pacer::new(machine.frame_rate());
+    pacer.set_max_skip(MAX_SKIP);
+    let mut audio = FrontendAudio::new();
+    #[cfg(feature = "gilrs")]
+    let mut gamepads = Gamepads::new(Bindings::default()).ok();
+
+    while window.is_open() {
+        for key in window.get_keys_pressed(KeyRepeat::No) {
+            if let Some(key) = translate_key(key) {
+                machine.key(key, true);
+            }
+        }
+        for key in window.get_keys_released() {
+            if let Some(key) = translate_key(key) {
+                machine.key(key, false);
+            }
+        }
+
+        #[cfg(feature = "gilrs")]
+        {
+            if let Some(ref mut gamepads) = gamepads {
+                gamepads.poll(machine);
+            }
+        }
+
+        machine.run_frame();
+
+        if pacer.render() {
+            machine.render(&mut pixels);
+            for (pixel, rgb) in buffer.iter_mut().zip(pixels.chunks(3)) {
+                *pixel = u32::from(rgb[0]) << 16 | u32::from(rgb[1]) << 8 | u32::from(rgb[2]);
+            }
+            window
+                .update_with_buffer(&buffer, width, height)
+                .map_err(|err| err.to_string())?;
+        } else {
+            // keeps the keyboard state fresh
+            window.update();
+        }
+
+        if let Some(ring) = machine.audio() {
+            audio.play(ring);
+        }
+
+        pacer.wait(None);
+    }
+    Ok(())
+}
@@ -0,0 +1,94 @@
+use std::time::Duration;
+
+#[cfg(feature = "cpal")]
+use audio::output::AudioOutput;
+use audio::ring::SampleRing;
+use sources::{HostClock, TimeSource};
+
+#[cfg(feature = "gilrs")]
+pub mod gamepad;
+#[cfg(feature = "gui")]
+pub mod gui;
+#[cfg(feature = "libretro")]
+pub mod libretro;
+#[cfg(feature = "minifb")]
+pub mod minifb;
+pub mod pacing;
+#[cfg(feature = "sdl2")]
+pub mod sdl;
+#[cfg(feature = "crossterm")]
+pub mod terminal;
+#[cfg(feature = "tui")]
+pub mod tui;
+#[cfg(feature = "web")]
+pub mod web;
+#[cfg(feature = "websocket")]
+pub mod websocket;
+
+// keeps a frontend loop at the machine frame rate, late frames are not
+// recovered (the emulation just slows down)
+pub struct FramePacer {
+    clock: Box<dyn TimeSource>,
+    frame: Duration,
+    next: Duration,
+}
+
+impl FramePacer {
+    pub fn new(frame_rate: u32) -> FramePacer {
+        FramePacer::with_clock(frame_rate, HostClock::new())
+    }
+
+    pub fn with_clock<C: TimeSource + 'static>(frame_rate: u32, clock: C) -> FramePacer {
+        let mut clock = Box::new(clock);
+        FramePacer {
+            frame: Duration::from_secs(1) / frame_rate.max(1),
+            next: clock.now(),
+            clock,
+        }
+    }
+
+    pub fn wait(&mut self) {
+        self.next += self.frame;
+        let now = self.clock.now();
+        if self.next > now {
+            self.clock.sleep(self.next - now);
+        } else {
+            self.next = now;
+        }
+    }
+}
+
+// audio for the frontends without an audio api of their own, samples are
+// just dropped when cpal is not enabled (or no device is available)
+pub struct FrontendAudio {
+    #[cfg(feature = "cpal")]
+    output: Option<AudioOutput>,
+}
+
+impl Default for FrontendAudio {
+    fn default() -> FrontendAudio {
+        FrontendAudio::new()
+    }
+}
+
+impl FrontendAudio {
+    pub fn new() -> FrontendAudio {
+        FrontendAudio {
+            #[cfg(feature = "cpal")]
+            output: AudioOutput::new(Duration::from_millis(100)).ok(),
+        }
+    }
+
+    pub fn play(&mut self, ring: &mut SampleRing) {
+        #[cfg(feature = "cpal")]
+        {
+            if let Some(ref mut output) = self.output {
+                output.feed(ring);
+            }
+        }
+        ring.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,130 @@
+use std::time::Duration;
+
+use sources::{HostClock, TimeSource};
+
+// frames in a row the built in frontends leave undrawn at most
+pub const MAX_SKIP: u32 = 3;
+
+// what the frontend loop follows, the audio cadence keeps about buffered
+// audio queued in the device so it never underruns nor drifts
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Cadence {
+    Clock,
+    Audio { buffered: Duration },
+}
+
+// refreshed about once a second
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct PacingStats {
+    pub emulated_fps: f64,
+    pub rendered_fps: f64,
+    pub skipped: u64,
+}
+
+// like FramePacer, but frames can be left undrawn when the host falls behind:
+// the machine (and its audio) still runs every frame
+pub struct Pacer {
+    clock: Box<dyn TimeSource>,
+    frame: Duration,
+    next: Duration,
+    cadence: Cadence,
+    queued: Option<Duration>,
+    max_skip: u32,
+    skipped: u32,
+    stats: PacingStats,
+    window: Duration,
+    emulated: u32,
+    rendered: u32,
+}
+
+impl Pacer {
+    pub fn new(frame_rate: u32) -> Pacer {
+        Pacer::with_clock(frame_rate, HostClock::new())
+    }
+
+    // a ManualClock for tests and replays
+    pub fn with_clock<C: TimeSource + 'static>(frame_rate: u32, clock: C) -> Pacer {
+        let mut clock = Box::new(clock);
+        let now = clock.now();
+        Pacer {
+            clock,
+            frame: Duration::from_secs(1) / frame_rate.max(1),
+            next: now,
+            cadence: Cadence::Clock,
+            queued: None,
+            max_skip: 0,
+            skipped: 0,
+            stats: PacingStats::default(),
+            window: now,
+            emulated: 0,
+            rendered: 0,
+        }
+    }
+
+    // frames in a row that can be skipped, 0 draws them all
+    pub fn set_max_skip(&mut self, frames: u32) {
+        self.max_skip = frames;
+    }
+
+    pub fn set_cadence(&mut self, cadence: Cadence) {
+        self.cadence = cadence;
+    }
+
+    pub fn stats(&self) -> PacingStats {
+        self.stats
+    }
+
+    fn behind(&mut self) -> bool {
+        match (self.cadence, self.queued) {
+            (Cadence::Audio { buffered }, Some(queued)) => queued < buffered / 2,
+            _ => self.clock.now() > self.next + self.frame,
+        }
+    }
+
+    // after running a frame, false when it should not be drawn
+    pub fn render(&mut self) -> bool {
+        self.emulated += 1;
+        if self.skipped < self.max_skip && self.behind() {
+            self.skipped += 1;
+            self.stats.skipped += 1;
+            return false;
+        }
+        self.skipped = 0;
+        self.rendered += 1;
+        true
+    }
+
+    // queued is the audio still waiting in the device, if the frontend knows
+    pub fn wait(&mut self, queued: Option<Duration>) {
+        self.queued = queued;
+        match (self.cadence, queued) {
+            (Cadence::Audio { buffered }, Some(queued)) => {
+                if queued > buffered {
+                    self.clock.sleep(queued - buffered);
+                }
+                self.next = self.clock.now();
+            }
+            _ => {
+                self.next += self.frame;
+                let now = self.clock.now();
+                if self.next > now {
+                    self.clock.sleep(self.next - now);
+                } else if now - self.next > self.frame * (self.max_skip + 1) {
+                    // too late to catch up by skipping, slow down instead
+                    self.next = now;
+                }
+            }
+        }
+
+        let now = self.clock.now();
+        let elapsed = now - self.window;
+        if elapsed >= Duration::from_secs(1) {
+            let seconds = elapsed.as_secs_f64();
+            self.stats.emulated_fps = f64::from(self.emulated) / seconds;
+            self.stats.rendered_fps = f64::from(self.rendered) / seconds;
+            self.window = now;
+            self.emulated = 0;
+            self.rendered = 0;
+        }
+    }
+}
@@ -0,0 +1,184 @@
+extern crate sdl2;
+
+use self::sdl2::audio::{AudioQueue, AudioSpecDesired};
+use self::sdl2::controller::Button;
+use self::sdl2::event::Event;
+use self::sdl2::keyboard::Keycode;
+use self::sdl2::pixels::PixelFormatEnum;
+
+use frontend::pacing::{Cadence, Pacer, MAX_SKIP};
+use machine::{Key, Machine};
+
+use std::time::Duration;
+
+fn translate_keycode(keycode: Keycode) -> Option<Key> {
+    let key = match keycode {
+        Keycode::Up => Key::Up,
+        Keycode::Down => Key::Down,
+        Keycode::Left => Key::Left,
+        Keycode::Right => Key::Right,
+        Keycode::Return => Key::Enter,
+        Keycode::Space => Key::Space,
+        Keycode::Escape => Key::Escape,
+        Keycode::Backspace => Key::Backspace,
+        Keycode::Tab => Key::Tab,
+        Keycode::LShift | Keycode::RShift => Key::Shift,
+        Keycode::LCtrl | Keycode::RCtrl => Key::Control,
+        Keycode::LAlt | Keycode::RAlt => Key::Alt,
+        Keycode::F1 => Key::Function(1),
+        Keycode::F2 => Key::Function(2),
+        Keycode::F3 => Key::Function(3),
+        Keycode::F4 => Key::Function(4),
+        Keycode::F5 => Key::Function(5),
+        Keycode::F6 => Key::Function(6),
+        Keycode::F7 => Key::Function(7),
+        Keycode::F8 => Key::Function(8),
+        Keycode::F9 => Key::Function(9),
+        Keycode::F10 => Key::Function(10),
+        Keycode::F11 => Key::Function(11),
+        Keycode::F12 => Key::Function(12),
+        _ => {
+            // printable keys are named after their character
+            let name = keycode.name();
+            let mut chars = name.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => Key::Char(c.to_ascii_lowercase()),
+                _ => return None,
+            }
+        }
+    };
+    Some(key)
+}
+
+fn translate_button(button: Button) -> Option<Key> {
+    let key = match button {
+        Button::A => Key::Button(0),
+        Button::B => Key::Button(1),
+        Button::X => Key::Button(2),
+        Button::Y => Key::Button(3),
+        Button::DPadUp => Key::Up,
+        Button::DPadDown => Key::Down,
+        Button::DPadLeft => Key::Left,
+        Button::DPadRight => Key::Right,
+        Button::Start => Key::Enter,
+        Button::Back => Key::Escape,
+        _ => return None,
+    };
+    Some(key)
+}
+
+// runs the machine in a window scaled by scale until it is closed
+pub fn run<M: Machine>(machine: &mut M, title: &str, scale: u32) -> Result<(), String> {
+    let context = sdl2::init()?;
+    let video = context.video()?;
+    let (width, height) = machine.resolution();
+
+    let window = video
+        .window(title, width as u32 * scale, height as u32 * scale)
+        .position_centered()
+        .build()
+        .map_err(|err| err.to_string())?;
+    let mut canvas = window
+        .into_canvas()
+        .build()
+        .map_err(|err| err.to_string())?;
+    let texture_creator = canvas.texture_creator();
+    let mut texture = texture_creator
+        .create_texture_streaming(PixelFormatEnum::RGB24, width as u32, height as u32)
+        .map_err(|err| err.to_string())?;
+
+    let audio = context.audio()?;
+    let queue: Option<AudioQueue<f32>> = match machine.audio() {
+        Some(ring) => {
+            let desired = AudioSpecDesired {
+                freq: Some(ring.sample_rate as i32),
+                channels: Some(1),
+                samples: None,
+            };
+            let queue = audio.open_queue(None, &desired)?;
+            queue.resume();
+            Some(queue)
+        }
+        None => None,
+    };
+
+    let controllers = context.game_controller()?;
+    let mut gamepads = Vec::new();
+    let mut events = context.event_pump()?;
+
+    let mut pixels = vec![0; width * height * 3];
+    let mut samples = vec![0.0; 8192];
+    let mut pacer = Pacer::new(machine.frame_rate());
+    pacer.set_max_skip(MAX_SKIP);
+    if queue.is_some() {
+        pacer.set_cadence(Cadence::Audio {
+            buffered: Duration::from_millis(60),
+        });
+    }
+
+    loop {
+        for event in events.poll_iter() {
+            match event {
+                Event::Quit { .. } => return Ok(()),
+                Event::KeyDown {
+                    keycode: Some(keycode),
+                    repeat: false,
+                    ..
+                } => {
+                    if let Some(key) = translate_keycode(keycode) {
+                        machine.key(key, true);
+                    }
+                }
+                Event::KeyUp {
+                    keycode: Some(keycode),
+                    ..
+                } => {
+                    if let Some(key) = translate_keycode(keycode) {
+                        machine.key(key, false);
+                    }
+                }
+                Event::ControllerDeviceAdded { which, .. } => {
+                    // the controller stops reporting events when dropped
+                    if let Ok(gamepad) = controllers.open(which) {
+                        gamepads.push(gamepad);
+                    }
+                }
+                Event::ControllerButtonDown { button, .. } => {
+                    if let Some(key) = translate_button(button) {
+                        machine.key(key, true);
+                    }
+                }
+                Event::ControllerButtonUp { button, .. } => {
+                    if let Some(key) = translate_button(button) {
+                        machine.key(key, false);
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        machine.run_frame();
+
+        if pacer.render() {
+            machine.render(&mut pixels);
+            texture
+                .update(None, &pixels, width * 3)
+                .map_err(|err| err.to_string())?;
+            canvas.copy(&texture, None, None)?;
+            canvas.present();
+        }
+
+        if let Some(ref queue) = queue {
+            if let Some(ring) = machine.audio() {
+                let amount = ring.drain(&mut samples);
+                queue.queue_audio(&samples[0..amount])?;
+            }
+        }
+
+        // mono f32 samples
+        let queued = queue.as_ref().map(|queue| {
+            Duration::from_secs_f64(f64::from(queue.size() / 4) / f64::from(queue.spec().freq))
+        });
+        pacer.wait(queued);
+    }
+}
@@ -0,0 +1,175 @@
+extern crate crossterm;
+
+use self::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use self::crossterm::style::{Color, Print, ResetColor, SetBackgroundColor, SetForegroundColor};
+use self::crossterm::{cursor, queue, terminal};
+
+use std::io::{stdout, Stdout, Write};
+use std::time::Duration;
+
+#[cfg(feature = "gilrs")]
+use frontend::gamepad::{Bindings, Gamepads};
+use frontend::{FramePacer, FrontendAudio};
+use machine::{Key, Machine};
+
+// terminals only report presses, keys are released after this many frames
+const HOLD_FRAMES: u32 = 6;
+
+fn translate_keycode(code: KeyCode) -> Option<Key> {
+    let key = match code {
+        KeyCode::Char(' ') => Key::Space,
+        KeyCode::Char(c) => Key::Char(c.to_ascii_lowercase()),
+        KeyCode::Up => Key::Up,
+        KeyCode::Down => Key::Down,
+        KeyCode::Left => Key::Left,
+        KeyCode::Right => Key::Right,
+        KeyCode::Enter => Key::Enter,
+        KeyCode::Esc => Key::Escape,
+        KeyCode::Backspace => Key::Backspace,
+        KeyCode::Tab => Key::Tab,
+        KeyCode::F(n) => Key::Function(n),
+        _ => return None,
+    };
+    Some(key)
+}
+
+fn io_error(err: ::std::io::Error) -> String {
+    err.to_string()
+}
+
+// two framebuffer rows per character cell, the upper one as foreground
+fn draw_half_blocks(
+    out: &mut Stdout,
+    pixels: &[u8],
+    width: usize,
+    height: usize,
+) -> Result<(), String> {
+    let (columns, rows) = terminal::size().map_err(io_error)?;
+    let columns = usize::from(columns).max(1);
+    let rows = usize::from(rows).max(1) * 2;
+    // integer downscaling keeps the aspect ratio
+    let step = width.div_ceil(columns).max(height.div_ceil(rows)).max(1);
+    let rgb = |x: usize, y: usize| {
+        let offset = (y * width + x) * 3;
+        Color::Rgb {
+            r: pixels[offset],
+            g: pixels[offset + 1],
+            b: pixels[offset + 2],
+        }
+    };
+
+    queue!(out, cursor::MoveTo(0, 0)).map_err(io_error)?;
+    let mut y = 0;
+    while y < height {
+        let mut x = 0;
+        while x < width {
+            let bottom = if y + step < height {
+                rgb(x, y + step)
+            } else {
+                Color::Black
+            };
+            queue!(
+                out,
+                SetForegroundColor(rgb(x, y)),
+                SetBackgroundColor(bottom),
+                Print('\u{2580}')
+            )
+            .map_err(io_error)?;
+            x += step;
+        }
+        queue!(out, ResetColor, Print("\r\n")).map_err(io_error)?;
+        y += step * 2;
+    }
+    Ok(())
+}
+
+fn run_loop<M: Machine>(
+    machine: &mut M,
+    out: &mut Stdout,
+    framebuffer: bool,
+) -> Result<(), String> {
+    let (width, height) = machine.resolution();
+    let mut pixels = vec![0; width * height * 3];
+    let mut held: Vec<(Key, u32)> = Vec::new();
+    let mut pacer = FramePacer::new(machine.frame_rate());
+    let mut audio = FrontendAudio::new();
+    #[cfg(feature = "gilrs")]
+    let mut gamepads = Gamepads::new(Bindings::default()).ok();
+
+    loop {
+        while event::poll(Duration::from_millis(0)).map_err(io_error)? {
+            if let Event::Key(key_event) = event::read().map_err(io_error)? {
+                if key_event.kind == KeyEventKind::Release {
+                    continue;
+                }
+                // raw mode swallows the signal, ctrl-c is ours
+                if key_event.modifiers.contains(KeyModifiers::CONTROL)
+                    && key_event.code == KeyCode::Char('c')
+                {
+                    return Ok(());
+                }
+                if let Some(key) = translate_keycode(key_event.code) {
+                    held.retain(|&(held_key, _)| held_key != key);
+                    held.push((key, HOLD_FRAMES));
+                    machine.key(key, true);
+                }
+            }
+        }
+
+        #[cfg(feature = "gilrs")]
+        {
+            if let Some(ref mut gamepads) = gamepads {
+                gamepads.poll(machine);
+            }
+        }
+
+        machine.run_frame();
+
+        for &mut (key, ref mut frames) in held.iter_mut() {
+            *frames -= 1;
+            if *frames == 0 {
+                machine.key(key, false);
+            }
+        }
+        held.retain(|&(_, frames)| frames > 0);
+
+        if framebuffer {
+            machine.render(&mut pixels);
+            draw_half_blocks(out, &pixels, width, height)?;
+        } else {
+            let text = machine.take_text();
+            for byte in text {
+                // raw mode does not translate newlines
+                if byte == b'\n' {
+                    queue!(out, Print("\r\n")).map_err(io_error)?;
+                } else {
+                    queue!(out, Print(byte as char)).map_err(io_error)?;
+                }
+            }
+        }
+        out.flush().map_err(io_error)?;
+
+        if let Some(ring) = machine.audio() {
+            audio.play(ring);
+        }
+
+        pacer.wait();
+    }
+}
+
+// shows the text terminal of the machine (or its framebuffer drawn with half
+// block characters) until ctrl-c is pressed
+pub fn run<M: Machine>(machine: &mut M, framebuffer: bool) -> Result<(), String> {
+    let mut out = stdout();
+    terminal::enable_raw_mode().map_err(io_error)?;
+    if framebuffer {
+        queue!(out, terminal::EnterAlternateScreen, cursor::Hide).map_err(io_error)?;
+    }
+    let result = run_loop(machine, &mut out, framebuffer);
+    if framebuffer {
+        let _ = queue!(out, terminal::LeaveAlternateScreen, cursor::Show);
+    }
+    let _ = out.flush();
+    let _ = terminal::disable_raw_mode();
+    result
+}
@@ -0,0 +1,157 @@
+#[cfg(feature = "gilrs")]
+#[test]
+fn gamepad_bindings() {
+    use frontend::gamepad::{Bindings, Button};
+    use machine::Key;
+
+    let mut bindings = Bindings::default();
+    assert_eq!(bindings.key(Button::South), Some(Key::Button(0)));
+    bindings.bind(Button::South, Key::Space);
+    assert_eq!(bindings.key(Button::South), Some(Key::Space));
+    bindings.unbind(Button::DPadUp);
+    assert_eq!(bindings.key(Button::DPadUp), None);
+    assert_eq!(Bindings::empty().key(Button::Start), None);
+}
+
+#[cfg(all(feature = "libretro", feature = "chip8"))]
+#[test]
+fn libretro_core() {
+    use chip8::Chip8;
+    use frontend::libretro::{translate_keycode, Core};
+    use machine::Key;
+    use ram::Ram;
+    use Error;
+
+    assert_eq!(translate_keycode('A' as u32), Some(Key::Char('a')));
+    assert_eq!(translate_keycode(273), Some(Key::Up));
+    assert_eq!(translate_keycode(283), Some(Key::Function(2)));
+    assert_eq!(translate_keycode(0), None);
+
+    fn load(game: &[u8]) -> Result<Chip8<Ram<u8>>, Error> {
+        let mut ram = Ram::new(4096);
+        ram.fill(game.to_vec(), 0x200);
+        Ok(Chip8::new(ram))
+    }
+
+    let mut core = Core::new(load);
+    assert_eq!(core.av_info().geometry.base_width, 320);
+    assert!(core.load_game(&[0x12, 0x00]));
+    assert_eq!(core.av_info().geometry.base_width, 64);
+    core.run();
+    core.reset();
+    core.unload_game();
+    core.run();
+}
+
+#[cfg(all(feature = "websocket", feature = "chip8"))]
+#[test]
+fn websocket_control() {
+    use frontend::websocket::Control;
+    use utils::base64;
+
+    let mut control = Control::new(None);
+    assert_eq!(control.handle("status"), "ok empty 0");
+    assert_eq!(control.handle("step"), "error no machine loaded");
+    assert_eq!(control.handle("load nes AAAA"), "error unknown machine nes");
+
+    // ld v0, 0x0a / ld f, v0 / drw v1, v1, 5 / jp 0x206
+    let rom = base64::encode(&[0x60, 0x0a, 0xf0, 0x29, 0xd1, 0x15, 0x12, 0x06]);
+    assert_eq!(control.handle(&format!("load chip8 {}", rom)), "ok");
+    assert!(control.is_running());
+    assert_eq!(control.handle("step 3"), "ok 3");
+    assert_eq!(control.handle("status"), "ok paused 3");
+    assert_eq!(control.handle("key a down"), "ok");
+    assert_eq!(control.handle("key nope down"), "error unknown key nope");
+
+    let frame = control.handle("frame");
+    assert!(frame.starts_with("frame 3 iVBORw0KGgo"));
+    let png = base64::decode(frame.split(' ').nth(2).unwrap()).unwrap();
+    assert_eq!(&png[1..4], b"PNG");
+
+    assert_eq!(control.handle("continue"), "ok");
+    assert_eq!(control.handle("dance"), "error unknown command dance");
+}
+
+#[cfg(all(feature = "tui", feature = "mos6502"))]
+#[test]
+fn tui_debugger() {
+    extern crate ratatui;
+    use frontend::tui::{evaluate, TuiDebugger};
+    use mos6502::MOS6502;
+    use ram::Ram;
+
+    // lda #$42 / sta $10 / jmp $0000
+    let mut ram = Ram::new(0x10000);
+    ram.fill(vec![0xa9, 0x42, 0x85, 0x10, 0x4c, 0x00, 0x00], 0);
+    let mut cpu = MOS6502::new(ram);
+    let mut tui = TuiDebugger::new();
+
+    assert!(tui.command(&mut cpu, "break $0004").is_ok());
+    assert_eq!(tui.breakpoints(), &[4]);
+    assert_eq!(
+        tui.command(&mut cpu, "step 100"),
+        Ok("pc = $0004".to_string())
+    );
+    assert_eq!(evaluate(&mut cpu, "a"), Ok("$42".to_string()));
+    assert_eq!(evaluate(&mut cpu, "$10"), Ok("$42".to_string()));
+    assert_eq!(evaluate(&mut cpu, "0x03:w"), Ok("$4C10".to_string()));
+    assert!(evaluate(&mut cpu, "nope").is_err());
+
+    assert!(tui.command(&mut cpu, "watch $10").is_ok());
+    assert!(tui.command(&mut cpu, "watch nope").is_err());
+    assert_eq!(tui.watches(), &["$10".to_string()]);
+    assert!(tui.command(&mut cpu, "unwatch 1").is_err());
+    assert!(tui.command(&mut cpu, "poke $10 7").is_ok());
+    assert!(tui.command(&mut cpu, "dance").is_err());
+
+    let backend = ratatui::backend::TestBackend::new(80, 30);
+    let mut terminal = ratatui::Terminal::new(backend).unwrap();
+    terminal.draw(|frame| tui.draw(frame, &mut cpu)).unwrap();
+    let screen: String = terminal
+        .backend()
+        .buffer()
+        .content
+        .iter()
+        .map(|cell| cell.symbol())
+        .collect();
+    assert!(screen.contains("registers"));
+    assert!(screen.contains("0 $10 = $07"));
+    assert!(screen.contains("*> $0004: jmp $0000"));
+}
+
+#[test]
+fn pacing_skips_frames_when_behind() {
+    use frontend::pacing::Pacer;
+    use std::thread;
+    use std::time::Duration;
+
+    let mut pacer = Pacer::new(1000);
+    assert!(pacer.render());
+    thread::sleep(Duration::from_millis(10));
+    // skipping is off by default
+    assert!(pacer.render());
+    pacer.set_max_skip(2);
+    assert!(!pacer.render());
+    assert!(!pacer.render());
+    assert!(pacer.render());
+    assert_eq!(pacer.stats().skipped, 2);
+}
+
+#[test]
+fn pacing_on_a_manual_clock() {
+    use frontend::pacing::Pacer;
+    use sources::{ManualClock, TimeSource};
+    use std::time::Duration;
+
+    let clock = ManualClock::new();
+    let now = || clock.clone().now();
+    let mut pacer = Pacer::with_clock(100, clock.clone());
+    pacer.set_max_skip(1);
+    assert!(pacer.render());
+    // the pacer sleeps through the rest of the frame on the same clock
+    pacer.wait(None);
+    assert_eq!(now(), Duration::from_millis(10));
+    clock.advance(Duration::from_millis(25));
+    assert!(!pacer.render());
+    assert!(pacer.render());
+}
@@ -0,0 +1,407 @@
+extern crate ratatui;
+
+use self::ratatui::backend::CrosstermBackend;
+use self::ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind};
+use self::ratatui::crossterm::{execute, terminal};
+use self::ratatui::layout::{Constraint, Direction, Layout, Rect};
+use self::ratatui::style::{Modifier, Style};
+use self::ratatui::text::Line;
+use self::ratatui::widgets::{Block, Borders, Paragraph};
+use self::ratatui::{Frame, Terminal};
+
+use std::io::{stdout, Stdout};
+use std::time::Duration;
+
+use debugger::context::context;
+use debugger::viewer::hexdump;
+use machine::Machine;
+use utils::to_number;
+use Debug;
+
+// instructions run between two redraws while the machine is running
+const STEPS_PER_DRAW: usize = 10000;
+const BYTES_PER_LINE: u16 = 16;
+
+fn io_error<E: ::std::fmt::Display>(err: E) -> String {
+    err.to_string()
+}
+
+fn address(argument: Option<&str>) -> Result<u16, String> {
+    let argument = argument.ok_or("missing address")?;
+    to_number::<u16>(argument).map_err(|err| format!("invalid address {}: {}", argument, err))
+}
+
+fn attach<M: Machine>(machine: &mut M) -> Result<&mut dyn Debug<u16, u8>, String> {
+    machine
+        .debug()
+        .ok_or_else(|| "the machine cannot be debugged".to_string())
+}
+
+// a watch is a register name, an address (a byte) or an address followed by
+// :w (a little endian word)
+pub fn evaluate(debugged: &mut dyn Debug<u16, u8>, expression: &str) -> Result<String, String> {
+    let register = debugged
+        .registers()
+        .into_iter()
+        .find(|&(name, _)| name.eq_ignore_ascii_case(expression));
+    if let Some((_, value)) = register {
+        return Ok(value);
+    }
+    let (text, word) = match expression.strip_suffix(":w") {
+        Some(text) => (text, true),
+        None => (expression, false),
+    };
+    let address =
+        to_number::<u16>(text).map_err(|_| format!("unknown expression {}", expression))?;
+    let low = debugged.inspect(address);
+    if word {
+        let high = debugged.inspect(address.wrapping_add(1));
+        Ok(format!("${:04X}", u16::from(low) | u16::from(high) << 8))
+    } else {
+        Ok(format!("${:02X}", low))
+    }
+}
+
+fn pane(title: &str) -> Block<'_> {
+    Block::default().borders(Borders::ALL).title(title)
+}
+
+// panes for the disassembly around pc, the registers, the watches, the
+// breakpoints and a memory window, with a vi like command line at the bottom
+pub struct TuiDebugger {
+    breakpoints: Vec<u16>,
+    watches: Vec<String>,
+    memory: u16,
+    input: Option<String>,
+    status: String,
+    running: bool,
+    quit: bool,
+}
+
+impl Default for TuiDebugger {
+    fn default() -> TuiDebugger {
+        TuiDebugger::new()
+    }
+}
+
+impl TuiDebugger {
+    pub fn new() -> TuiDebugger {
+        TuiDebugger {
+            breakpoints: Vec::new(),
+            watches: Vec::new(),
+            memory: 0,
+            input: None,
+            status: "s step, c continue, f frame, b breakpoint, : command, q quit".to_string(),
+            running: false,
+            quit: false,
+        }
+    }
+
+    pub fn breakpoints(&self) -> &[u16] {
+        &self.breakpoints
+    }
+
+    pub fn watches(&self) -> &[String] {
+        &self.watches
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running
+    }
+
+    fn toggle_breakpoint(&mut self, address: u16) {
+        if self.breakpoints.contains(&address) {
+            self.breakpoints.retain(|&breakpoint| breakpoint != address);
+        } else {
+            self.breakpoints.push(address);
+        }
+    }
+
+    // runs until a breakpoint (ours or a code-driven one) or for at most
+    // the given number of instructions
+    pub fn resume(&mut self, debugged: &mut dyn Debug<u16, u8>, steps: usize) {
+        for _ in 0..steps {
+            debugged.next();
+            let pc = debugged.get_cursor();
+            if debugged.is_code_breakpoint_requested() || self.breakpoints.contains(&pc) {
+                self.running = false;
+                self.status = format!("break at {}", debugged.address_str(pc));
+                return;
+            }
+        }
+    }
+
+    pub fn command(
+        &mut self,
+        debugged: &mut dyn Debug<u16, u8>,
+        line: &str,
+    ) -> Result<String, String> {
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("break") | Some("b") => {
+                let address = address(words.next())?;
+                if !self.breakpoints.contains(&address) {
+                    self.breakpoints.push(address);
+                }
+                Ok(format!("breakpoint at {}", debugged.address_str(address)))
+            }
+            Some("clear") => {
+                let address = address(words.next())?;
+                self.breakpoints.retain(|&breakpoint| breakpoint != address);
+                Ok(format!("cleared {}", debugged.address_str(address)))
+            }
+            Some("watch") => {
+                let expression = words.next().ok_or("syntax: watch <register|address[:w]>")?;
+                evaluate(debugged, expression)?;
+                self.watches.push(expression.to_string());
+                Ok(format!("watching {}", expression))
+            }
+            Some("unwatch") => {
+                let index: usize = words
+                    .next()
+                    .and_then(|index| index.parse().ok())
+                    .ok_or("syntax: unwatch <index>")?;
+                if index >= self.watches.len() {
+                    return Err(format!("no watch {}", index));
+                }
+                Ok(format!("removed {}", self.watches.remove(index)))
+            }
+            Some("mem") | Some("m") => {
+                self.memory = address(words.next())?;
+                Ok(format!("memory at {}", debugged.address_str(self.memory)))
+            }
+            Some("poke") => {
+                let address = address(words.next())?;
+                let value = words.next().ok_or("syntax: poke <address> <value>")?;
+                let value = to_number::<u8>(value)
+                    .map_err(|err| format!("invalid value {}: {}", value, err))?;
+                debugged.inject(address, value);
+                Ok(format!(
+                    "{} = {}",
+                    debugged.address_str(address),
+                    debugged.data_str(value)
+                ))
+            }
+            Some("jump") | Some("j") => {
+                let address = address(words.next())?;
+                debugged.set_cursor(address);
+                Ok(format!("pc = {}", debugged.address_str(address)))
+            }
+            Some("step") | Some("s") => {
+                let steps = match words.next() {
+                    Some(steps) => steps
+                        .parse()
+                        .map_err(|_| format!("invalid count {}", steps))?,
+                    None => 1,
+                };
+                self.resume(debugged, steps);
+                Ok(format!(
+                    "pc = {}",
+                    debugged.address_str(debugged.get_cursor())
+                ))
+            }
+            Some("quit") | Some("q") => {
+                self.quit = true;
+                Ok("bye".to_string())
+            }
+            Some(command) => Err(format!("unknown command {}", command)),
+            None => Ok(String::new()),
+        }
+    }
+
+    fn key<M: Machine>(&mut self, machine: &mut M, key: KeyEvent) -> Result<(), String> {
+        if let Some(mut input) = self.input.take() {
+            match key.code {
+                KeyCode::Enter => {
+                    let debugged = attach(machine)?;
+                    self.status = match self.command(debugged, &input) {
+                        Ok(reply) => reply,
+                        Err(err) => format!("error: {}", err),
+                    };
+                }
+                KeyCode::Esc => (),
+                KeyCode::Backspace => {
+                    input.pop();
+                    self.input = Some(input);
+                }
+                KeyCode::Char(c) => {
+                    input.push(c);
+                    self.input = Some(input);
+                }
+                _ => self.input = Some(input),
+            }
+            return Ok(());
+        }
+
+        match key.code {
+            KeyCode::Char(':') => self.input = Some(String::new()),
+            KeyCode::Char('q') => self.quit = true,
+            KeyCode::Char('c') | KeyCode::F(5) => {
+                self.running = !self.running;
+                if self.running {
+                    // leave the breakpoint we are sitting on
+                    let debugged = attach(machine)?;
+                    self.resume(debugged, 1);
+                }
+            }
+            _ if self.running => (),
+            KeyCode::Char('s') | KeyCode::F(11) => {
+                let debugged = attach(machine)?;
+                self.resume(debugged, 1);
+            }
+            // a whole frame, so timers and devices advance too
+            KeyCode::Char('f') => machine.run_frame(),
+            KeyCode::Char('b') | KeyCode::F(9) => {
+                let pc = attach(machine)?.get_cursor();
+                self.toggle_breakpoint(pc);
+            }
+            KeyCode::PageUp => self.memory = self.memory.wrapping_sub(BYTES_PER_LINE * 8),
+            KeyCode::PageDown => self.memory = self.memory.wrapping_add(BYTES_PER_LINE * 8),
+            _ => (),
+        }
+        Ok(())
+    }
+
+    fn draw_disassembly(&self, frame: &mut Frame, area: Rect, debugged: &mut dyn Debug<u16, u8>) {
+        let rows = usize::from(area.height.saturating_sub(2)).max(1);
+        let pc = debugged.get_cursor();
+        let lines: Vec<Line> = context(debugged, pc, rows / 3, rows)
+            .into_iter()
+            .take(rows)
+            .map(|line| {
+                let marker = if self.breakpoints.contains(&line.address) {
+                    '*'
+                } else {
+                    ' '
+                };
+                let text = format!(
+                    "{}{} {}: {}",
+                    marker,
+                    if line.current { '>' } else { ' ' },
+                    debugged.address_str(line.address),
+                    line.text
+                );
+                if line.current {
+                    Line::styled(text, Style::default().add_modifier(Modifier::REVERSED))
+                } else {
+                    Line::from(text)
+                }
+            })
+            .collect();
+        frame.render_widget(Paragraph::new(lines).block(pane("disassembly")), area);
+    }
+
+    fn draw_memory(&self, frame: &mut Frame, area: Rect, debugged: &mut dyn Debug<u16, u8>) {
+        let rows = area.height.saturating_sub(2).max(1);
+        let end = self
+            .memory
+            .saturating_add((rows * BYTES_PER_LINE).saturating_sub(1));
+        let lines: Vec<Line> = hexdump(debugged, self.memory, end)
+            .into_iter()
+            .map(Line::from)
+            .collect();
+        frame.render_widget(Paragraph::new(lines).block(pane("memory")), area);
+    }
+
+    pub fn draw(&self, frame: &mut Frame, debugged: &mut dyn Debug<u16, u8>) {
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Min(8),
+                Constraint::Length(10),
+                Constraint::Length(1),
+            ])
+            .split(frame.area());
+        let columns = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(60), Constraint::Percentage(40)])
+            .split(rows[0]);
+        let side = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Percentage(40),
+                Constraint::Percentage(30),
+                Constraint::Percentage(30),
+            ])
+            .split(columns[1]);
+
+        self.draw_disassembly(frame, columns[0], debugged);
+
+        let registers: Vec<Line> = debugged
+            .registers()
+            .into_iter()
+            .map(|(name, value)| Line::from(format!("{:<4} {}", name, value)))
+            .collect();
+        frame.render_widget(Paragraph::new(registers).block(pane("registers")), side[0]);
+
+        let watches: Vec<Line> = self
+            .watches
+            .iter()
+            .enumerate()
+            .map(|(index, expression)| {
+                let value = evaluate(debugged, expression).unwrap_or_else(|err| err);
+                Line::from(format!("{} {} = {}", index, expression, value))
+            })
+            .collect();
+        frame.render_widget(Paragraph::new(watches).block(pane("watches")), side[1]);
+
+        let breakpoints: Vec<Line> = self
+            .breakpoints
+            .iter()
+            .map(|&address| Line::from(debugged.address_str(address)))
+            .collect();
+        frame.render_widget(
+            Paragraph::new(breakpoints).block(pane("breakpoints")),
+            side[2],
+        );
+
+        self.draw_memory(frame, rows[1], debugged);
+
+        let status = match self.input {
+            Some(ref input) => format!(":{}", input),
+            None if self.running => "running, c to pause".to_string(),
+            None => self.status.clone(),
+        };
+        frame.render_widget(Paragraph::new(status), rows[2]);
+    }
+
+    fn run_loop<M: Machine>(
+        &mut self,
+        machine: &mut M,
+        terminal: &mut Terminal<CrosstermBackend<Stdout>>,
+    ) -> Result<(), String> {
+        while !self.quit {
+            {
+                let debugged = attach(machine)?;
+                if self.running {
+                    self.resume(debugged, STEPS_PER_DRAW);
+                }
+                terminal
+                    .draw(|frame| self.draw(frame, debugged))
+                    .map_err(io_error)?;
+            }
+            let timeout = if self.running { 0 } else { 50 };
+            if event::poll(Duration::from_millis(timeout)).map_err(io_error)? {
+                if let Event::Key(key) = event::read().map_err(io_error)? {
+                    if key.kind != KeyEventKind::Release {
+                        self.key(machine, key)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// takes over the terminal until q is pressed (or :quit is entered)
+pub fn run<M: Machine>(machine: &mut M) -> Result<(), String> {
+    attach(machine)?;
+    terminal::enable_raw_mode().map_err(io_error)?;
+    let result = execute!(stdout(), terminal::EnterAlternateScreen)
+        .map_err(io_error)
+        .and_then(|_| Terminal::new(CrosstermBackend::new(stdout())).map_err(io_error))
+        .and_then(|mut terminal| TuiDebugger::new().run_loop(machine, &mut terminal));
+    let _ = execute!(stdout(), terminal::LeaveAlternateScreen);
+    let _ = terminal::disable_raw_mode();
+    result
+}
@@ -0,0 +1,219 @@
+extern crate wasm_bindgen;
+extern crate web_sys;
+
+use self::wasm_bindgen::prelude::*;
+use self::wasm_bindgen::{Clamped, JsCast};
+use self::web_sys::KeyboardEvent;
+use self::web_sys::{AudioContext, CanvasRenderingContext2d, HtmlCanvasElement, ImageData};
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use machine::{Key, Machine};
+
+type AnimationCallback = Rc<RefCell<Option<Closure<dyn FnMut(f64)>>>>;
+
+// frames run in a single animation frame callback when the tab lagged behind
+const MAX_FRAMES_PER_CALLBACK: u32 = 4;
+// audio scheduled further than this in the future is dropped
+const MAX_AUDIO_LATENCY: f64 = 0.2;
+
+fn translate_key(key: &str) -> Option<Key> {
+    let key = match key {
+        "ArrowUp" => Key::Up,
+        "ArrowDown" => Key::Down,
+        "ArrowLeft" => Key::Left,
+        "ArrowRight" => Key::Right,
+        "Enter" => Key::Enter,
+        " " => Key::Space,
+        "Escape" => Key::Escape,
+        "Backspace" => Key::Backspace,
+        "Tab" => Key::Tab,
+        "Shift" => Key::Shift,
+        "Control" => Key::Control,
+        "Alt" => Key::Alt,
+        _ => {
+            if key.starts_with('F') && key.len() > 1 {
+                if let Ok(n) = key[1..].parse() {
+                    return Some(Key::Function(n));
+                }
+            }
+            let mut chars = key.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => Key::Char(c.to_ascii_lowercase()),
+                _ => return None,
+            }
+        }
+    };
+    Some(key)
+}
+
+struct WebFrontend<M: Machine> {
+    machine: M,
+    context: CanvasRenderingContext2d,
+    rgb: Vec<u8>,
+    rgba: Vec<u8>,
+    samples: Vec<f32>,
+    audio: Option<AudioContext>,
+    audio_time: f64,
+    last_timestamp: Option<f64>,
+    elapsed: f64,
+}
+
+impl<M: Machine> WebFrontend<M> {
+    fn draw(&mut self) -> Result<(), JsValue> {
+        let (width, height) = self.machine.resolution();
+        self.machine.render(&mut self.rgb);
+        for (rgba, rgb) in self.rgba.chunks_mut(4).zip(self.rgb.chunks(3)) {
+            rgba[0..3].copy_from_slice(rgb);
+            rgba[3] = 0xff;
+        }
+        let image = ImageData::new_with_u8_clamped_array_and_sh(
+            Clamped(&self.rgba),
+            width as u32,
+            height as u32,
+        )?;
+        self.context.put_image_data(&image, 0.0, 0.0)
+    }
+
+    // browsers have no blocking audio queue, every frame gets its own buffer
+    // scheduled right after the previous one
+    fn play(&mut self) -> Result<(), JsValue> {
+        let audio = match self.audio {
+            Some(ref audio) => audio,
+            None => return Ok(()),
+        };
+        let ring = match self.machine.audio() {
+            Some(ring) => ring,
+            None => return Ok(()),
+        };
+        let amount = ring.drain(&mut self.samples);
+        let sample_rate = ring.sample_rate;
+        let now = audio.current_time();
+        if amount == 0 || self.audio_time - now > MAX_AUDIO_LATENCY {
+            return Ok(());
+        }
+        let buffer = audio.create_buffer(1, amount as u32, sample_rate as f32)?;
+        buffer.copy_to_channel(&self.samples[0..amount], 0)?;
+        let source = audio.create_buffer_source()?;
+        source.set_buffer(Some(&buffer));
+        source.connect_with_audio_node(&audio.destination())?;
+        let start = self.audio_time.max(now);
+        source.start_with_when(start)?;
+        self.audio_time = start + amount as f64 / f64::from(sample_rate);
+        Ok(())
+    }
+
+    fn animation_frame(&mut self, timestamp: f64) -> Result<(), JsValue> {
+        let frame = 1000.0 / f64::from(self.machine.frame_rate());
+        if let Some(last_timestamp) = self.last_timestamp {
+            self.elapsed += timestamp - last_timestamp;
+        } else {
+            self.elapsed = frame;
+        }
+        self.last_timestamp = Some(timestamp);
+
+        let mut frames = 0;
+        while self.elapsed >= frame && frames < MAX_FRAMES_PER_CALLBACK {
+            self.machine.run_frame();
+            self.play()?;
+            self.elapsed -= frame;
+            frames += 1;
+        }
+        if frames == MAX_FRAMES_PER_CALLBACK {
+            self.elapsed = 0.0;
+        }
+        if frames > 0 {
+            self.draw()?;
+        }
+        Ok(())
+    }
+}
+
+fn window() -> Result<web_sys::Window, JsValue> {
+    web_sys::window().ok_or_else(|| JsValue::from_str("no window available"))
+}
+
+fn listen_keys<M: Machine + 'static>(
+    frontend: &Rc<RefCell<WebFrontend<M>>>,
+    event: &str,
+    pressed: bool,
+) -> Result<(), JsValue> {
+    let frontend = Rc::clone(frontend);
+    let closure = Closure::wrap(Box::new(move |event: KeyboardEvent| {
+        if let Some(key) = translate_key(&event.key()) {
+            let mut frontend = frontend.borrow_mut();
+            // audio can only start after a user gesture
+            if let Some(ref audio) = frontend.audio {
+                let _ = audio.resume();
+            }
+            frontend.machine.key(key, pressed);
+            event.prevent_default();
+        }
+    }) as Box<dyn FnMut(KeyboardEvent)>);
+    let document = window()?
+        .document()
+        .ok_or_else(|| JsValue::from_str("no document available"))?;
+    document.add_event_listener_with_callback(event, closure.as_ref().unchecked_ref())?;
+    // the listener lives as long as the page
+    closure.forget();
+    Ok(())
+}
+
+// attaches the machine to the canvas with the given id and keeps it running
+// from requestAnimationFrame, the canvas is sized to the machine resolution
+// (scale it with css, image-rendering: pixelated)
+pub fn run<M: Machine + 'static>(mut machine: M, canvas_id: &str) -> Result<(), JsValue> {
+    let document = window()?
+        .document()
+        .ok_or_else(|| JsValue::from_str("no document available"))?;
+    let canvas: HtmlCanvasElement = document
+        .get_element_by_id(canvas_id)
+        .ok_or_else(|| JsValue::from_str("canvas not found"))?
+        .dyn_into()?;
+    let (width, height) = machine.resolution();
+    canvas.set_width(width as u32);
+    canvas.set_height(height as u32);
+    let context: CanvasRenderingContext2d = canvas
+        .get_context("2d")?
+        .ok_or_else(|| JsValue::from_str("2d context not available"))?
+        .dyn_into()?;
+
+    let audio = match machine.audio() {
+        Some(_) => AudioContext::new().ok(),
+        None => None,
+    };
+
+    let frontend = Rc::new(RefCell::new(WebFrontend {
+        machine,
+        context,
+        rgb: vec![0; width * height * 3],
+        rgba: vec![0; width * height * 4],
+        samples: vec![0.0; 8192],
+        audio,
+        audio_time: 0.0,
+        last_timestamp: None,
+        elapsed: 0.0,
+    }));
+
+    listen_keys(&frontend, "keydown", true)?;
+    listen_keys(&frontend, "keyup", false)?;
+
+    // the callback has to reschedule itself, so it needs a handle to itself
+    let callback: AnimationCallback = Rc::new(RefCell::new(None));
+    let next_callback = Rc::clone(&callback);
+    *callback.borrow_mut() = Some(Closure::wrap(Box::new(move |timestamp: f64| {
+        if let Err(err) = frontend.borrow_mut().animation_frame(timestamp) {
+            web_sys::console::error_1(&err);
+            return;
+        }
+        if let (Ok(window), Some(ref closure)) = (window(), next_callback.borrow().as_ref()) {
+            let _ = window.request_animation_frame(closure.as_ref().unchecked_ref());
+        }
+    }) as Box<dyn FnMut(f64)>));
+
+    if let Some(ref closure) = *callback.borrow() {
+        window()?.request_animation_frame(closure.as_ref().unchecked_ref())?;
+    }
+    Ok(())
+}
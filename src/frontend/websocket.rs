@@ -0,0 +1,278 @@
+extern crate tungstenite;
+
+use self::tungstenite::{Message, WebSocket};
+use std::io::ErrorKind;
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::str::FromStr;
+
+use frontend::FramePacer;
+use machine::{load, parse_key, Machine};
+use utils::{base64, png};
+
+// the text protocol shared by every client, replies are "ok ..." or
+// "error ...", frames are "frame <number> <base64 png>"
+pub struct Control {
+    machine: Option<Box<dyn Machine>>,
+    paused: bool,
+    frame: u64,
+    pixels: Vec<u8>,
+}
+
+fn argument<T: FromStr>(argument: Option<&str>, default: T) -> Result<T, String> {
+    match argument {
+        Some(value) => value
+            .parse()
+            .map_err(|_| format!("invalid argument {}", value)),
+        None => Ok(default),
+    }
+}
+
+impl Control {
+    pub fn new(machine: Option<Box<dyn Machine>>) -> Control {
+        Control {
+            machine,
+            paused: false,
+            frame: 0,
+            pixels: Vec::new(),
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.machine.is_some() && !self.paused
+    }
+
+    pub fn frame_rate(&self) -> u32 {
+        self.machine
+            .as_ref()
+            .map_or(60, |machine| machine.frame_rate())
+    }
+
+    pub fn frame_count(&self) -> u64 {
+        self.frame
+    }
+
+    pub fn run_frame(&mut self) {
+        if let Some(ref mut machine) = self.machine {
+            machine.run_frame();
+            // nobody listens to a headless machine
+            if let Some(ring) = machine.audio() {
+                ring.clear();
+            }
+            self.frame += 1;
+        }
+    }
+
+    pub fn screenshot(&mut self) -> Option<String> {
+        let machine = self.machine.as_ref()?;
+        let (width, height) = machine.resolution();
+        self.pixels.resize(width * height * 3, 0);
+        machine.render(&mut self.pixels);
+        Some(format!(
+            "frame {} {}",
+            self.frame,
+            base64::encode(&png::encode(width, height, &self.pixels))
+        ))
+    }
+
+    pub fn handle(&mut self, line: &str) -> String {
+        match self.command(line) {
+            Ok(reply) => reply,
+            Err(err) => format!("error {}", err),
+        }
+    }
+
+    fn command(&mut self, line: &str) -> Result<String, String> {
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("load") => {
+                let name = words.next().ok_or("syntax: load <machine> <base64 rom>")?;
+                let rom = words
+                    .next()
+                    .and_then(base64::decode)
+                    .ok_or("syntax: load <machine> <base64 rom>")?;
+                let machine = load(name, &rom).map_err(|error| error.to_string())?;
+                self.machine = Some(machine);
+                self.frame = 0;
+                Ok("ok".to_string())
+            }
+            Some("status") => {
+                let status = match self.machine {
+                    None => "empty",
+                    Some(_) if self.paused => "paused",
+                    Some(_) => "running",
+                };
+                Ok(format!("ok {} {}", status, self.frame))
+            }
+            Some("pause") => {
+                self.paused = true;
+                Ok("ok".to_string())
+            }
+            Some("continue") => {
+                self.paused = false;
+                Ok("ok".to_string())
+            }
+            Some("step") => {
+                if self.machine.is_none() {
+                    return Err("no machine loaded".to_string());
+                }
+                let frames = argument(words.next(), 1)?;
+                self.paused = true;
+                for _ in 0..frames {
+                    self.run_frame();
+                }
+                Ok(format!("ok {}", self.frame))
+            }
+            Some("key") => {
+                let name = words.next().ok_or("syntax: key <name> down|up")?;
+                let key = parse_key(name).ok_or(format!("unknown key {}", name))?;
+                let pressed = match words.next() {
+                    Some("down") => true,
+                    Some("up") => false,
+                    _ => return Err("syntax: key <name> down|up".to_string()),
+                };
+                let machine = self.machine.as_mut().ok_or("no machine loaded")?;
+                machine.key(key, pressed);
+                Ok("ok".to_string())
+            }
+            Some("frame") => self
+                .screenshot()
+                .ok_or_else(|| "no machine loaded".to_string()),
+            Some(command) => Err(format!("unknown command {}", command)),
+            None => Err("empty command".to_string()),
+        }
+    }
+}
+
+struct Client {
+    socket: WebSocket<TcpStream>,
+    subscribed: bool,
+}
+
+fn would_block(err: &tungstenite::Error) -> bool {
+    match *err {
+        tungstenite::Error::Io(ref err) => err.kind() == ErrorKind::WouldBlock,
+        _ => false,
+    }
+}
+
+// a nonblocking websocket endpoint, "subscribe" makes a client receive every
+// new frame without asking for it
+pub struct WebSocketServer {
+    listener: TcpListener,
+    clients: Vec<Client>,
+}
+
+impl WebSocketServer {
+    pub fn bind<A: ToSocketAddrs>(address: A) -> Result<WebSocketServer, String> {
+        let listener = TcpListener::bind(address).map_err(|err| err.to_string())?;
+        listener
+            .set_nonblocking(true)
+            .map_err(|err| err.to_string())?;
+        Ok(WebSocketServer {
+            listener,
+            clients: Vec::new(),
+        })
+    }
+
+    pub fn local_addr(&self) -> Option<String> {
+        self.listener
+            .local_addr()
+            .ok()
+            .map(|address| address.to_string())
+    }
+
+    fn accept(&mut self) {
+        while let Ok((stream, _)) = self.listener.accept() {
+            // the handshake is short, only the frames are nonblocking
+            if stream.set_nonblocking(false).is_err() {
+                continue;
+            }
+            if let Ok(socket) = tungstenite::accept(stream) {
+                if socket.get_ref().set_nonblocking(true).is_ok() {
+                    self.clients.push(Client {
+                        socket,
+                        subscribed: false,
+                    });
+                }
+            }
+        }
+    }
+
+    pub fn poll(&mut self, control: &mut Control) {
+        self.accept();
+        self.clients.retain_mut(|client| loop {
+            let message = match client.socket.read() {
+                Ok(message) => message,
+                // nothing more to read, push out whatever is still pending
+                Err(ref err) if would_block(err) => {
+                    break match client.socket.flush() {
+                        Err(ref err) => would_block(err),
+                        Ok(_) => true,
+                    }
+                }
+                Err(_) => break false,
+            };
+            if message.is_close() {
+                break false;
+            }
+            let line = match message.to_text() {
+                Ok(line) if message.is_text() => line.trim().to_string(),
+                _ => continue,
+            };
+            let reply = match line.as_str() {
+                "subscribe" => {
+                    client.subscribed = true;
+                    "ok".to_string()
+                }
+                "unsubscribe" => {
+                    client.subscribed = false;
+                    "ok".to_string()
+                }
+                _ => control.handle(&line),
+            };
+            match client.socket.send(Message::text(reply)) {
+                Err(ref err) if !would_block(err) => break false,
+                _ => (),
+            }
+        });
+    }
+
+    pub fn broadcast_frame(&mut self, control: &mut Control) {
+        if !self.clients.iter().any(|client| client.subscribed) {
+            return;
+        }
+        let frame = match control.screenshot() {
+            Some(frame) => frame,
+            None => return,
+        };
+        self.clients.retain_mut(|client| {
+            !client.subscribed
+                || match client.socket.send(Message::text(frame.clone())) {
+                    Ok(_) => true,
+                    Err(ref err) => would_block(err),
+                }
+        });
+    }
+}
+
+// runs a machine (or waits for a "load") with no window, driven only by the
+// websocket clients
+pub fn serve<A: ToSocketAddrs>(
+    address: A,
+    machine: Option<Box<dyn Machine>>,
+) -> Result<(), String> {
+    let mut server = WebSocketServer::bind(address)?;
+    let mut control = Control::new(machine);
+    let mut pacer = FramePacer::new(control.frame_rate());
+    loop {
+        let frame = control.frame_count();
+        server.poll(&mut control);
+        if control.is_running() {
+            control.run_frame();
+        }
+        if control.frame_count() != frame {
+            server.broadcast_frame(&mut control);
+        }
+        pacer.wait();
+    }
+}
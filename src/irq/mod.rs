@@ -0,0 +1,114 @@
+use alloc::rc::Rc;
+use core::cell::Cell;
+
+use {Address, Interrupt};
+
+// what a device drives, the line is active while asserted
+pub trait InterruptLine {
+    fn set(&mut self, asserted: bool);
+
+    fn assert(&mut self) {
+        self.set(true);
+    }
+
+    fn release(&mut self) {
+        self.set(false);
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Trigger {
+    // taken for as long as any source asserts it, an irq
+    Level,
+    // taken once when the line goes active, an nmi
+    Edge,
+}
+
+#[derive(Default)]
+struct Wire {
+    // a bit for each source
+    asserted: Cell<u64>,
+    edge: Cell<bool>,
+}
+
+// open collector outputs tied together: active when any of the sources is
+pub struct WiredOr {
+    trigger: Trigger,
+    wire: Rc<Wire>,
+    sources: u32,
+}
+
+impl WiredOr {
+    pub fn new(trigger: Trigger) -> WiredOr {
+        WiredOr {
+            trigger,
+            wire: Rc::new(Wire::default()),
+            sources: 0,
+        }
+    }
+
+    // each device gets its own, up to 64 of them
+    pub fn source(&mut self) -> Source {
+        assert!(self.sources < 64, "too many sources on the line");
+        let source = Source {
+            wire: Rc::clone(&self.wire),
+            bit: 1 << self.sources,
+        };
+        self.sources += 1;
+        source
+    }
+
+    pub fn trigger(&self) -> Trigger {
+        self.trigger
+    }
+
+    pub fn is_asserted(&self) -> bool {
+        self.wire.asserted.get() != 0
+    }
+
+    // whether the cpu is to take the interrupt, an edge is consumed by it
+    pub fn pending(&mut self) -> bool {
+        match self.trigger {
+            Trigger::Level => self.is_asserted(),
+            Trigger::Edge => self.wire.edge.replace(false),
+        }
+    }
+
+    // raises line on the cpu when pending, once per instruction for a level
+    pub fn poll<T: Address, I: Interrupt<T> + ?Sized>(&mut self, cpu: &mut I, line: T) -> bool {
+        let pending = self.pending();
+        if pending {
+            cpu.raise(line);
+        }
+        pending
+    }
+}
+
+pub struct Source {
+    wire: Rc<Wire>,
+    bit: u64,
+}
+
+impl Source {
+    pub fn is_asserted(&self) -> bool {
+        self.wire.asserted.get() & self.bit != 0
+    }
+}
+
+impl InterruptLine for Source {
+    fn set(&mut self, asserted: bool) {
+        let before = self.wire.asserted.get();
+        let after = if asserted {
+            before | self.bit
+        } else {
+            before & !self.bit
+        };
+        if before == 0 && after != 0 {
+            self.wire.edge.set(true);
+        }
+        self.wire.asserted.set(after);
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,42 @@
+use irq::{InterruptLine, Trigger, WiredOr};
+
+#[test]
+fn wired_or_level() {
+    let mut irq = WiredOr::new(Trigger::Level);
+    let (mut via, mut ppu) = (irq.source(), irq.source());
+    assert!(!irq.pending());
+    via.assert();
+    ppu.assert();
+    via.release();
+    assert!(irq.pending());
+    // still asserted until every source lets go
+    assert!(irq.pending());
+    assert!(ppu.is_asserted() && !via.is_asserted());
+    ppu.release();
+    assert!(!irq.pending());
+}
+
+#[cfg(feature = "mos6502")]
+#[test]
+fn wired_or_edge() {
+    use mos6502::MOS6502;
+    use ram::Ram;
+
+    let mut ram = Ram::new(0x10000);
+    ram.fill(vec![0x00, 0x03], 0xfffa);
+    let mut cpu = MOS6502::new(ram);
+    cpu.pc = 0x0200;
+
+    let mut nmi = WiredOr::new(Trigger::Edge);
+    let (mut vblank, mut button) = (nmi.source(), nmi.source());
+    vblank.assert();
+    // the second source does not make another edge
+    button.assert();
+    assert!(nmi.poll(&mut cpu, 6));
+    assert_eq!(cpu.pc, 0x0300);
+    assert!(!nmi.poll(&mut cpu, 6));
+    vblank.release();
+    button.release();
+    button.assert();
+    assert!(nmi.pending());
+}
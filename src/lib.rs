@@ -1,19 +1,81 @@
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+// alloc and core paths work the same with and without std
+#[cfg(not(any(feature = "std", test)))]
+#[macro_use]
+extern crate alloc;
+#[cfg(any(feature = "std", test))]
+extern crate core;
+#[cfg(any(feature = "std", test))]
+extern crate std as alloc;
+
 extern crate num_traits;
+#[cfg(feature = "std")]
 extern crate rand;
+#[cfg(feature = "tracing")]
+extern crate tracing;
 
+pub use error::Error;
 pub use num_traits::AsPrimitive as As;
 use num_traits::{NumAssign, PrimInt};
 
-use std::fmt::{Display, LowerHex, UpperHex};
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::{Display, LowerHex, UpperHex};
+
+// what code generic over the bus widths needs beyond PrimInt, the
+// conversions truncate and the arithmetic wraps as the hardware does
+pub trait BusWidth: Copy {
+    const BITS: u32;
+    fn from_usize(value: usize) -> Self;
+    fn to_usize(self) -> usize;
+    fn wrapping_add(self, other: Self) -> Self;
+    fn wrapping_sub(self, other: Self) -> Self;
+    fn wrapping_shl(self, bits: u32) -> Self;
+    fn wrapping_shr(self, bits: u32) -> Self;
+}
+
+macro_rules! bus_width {
+    ($($width:ty),*) => {
+        $(
+            impl BusWidth for $width {
+                const BITS: u32 = <$width>::BITS;
+                fn from_usize(value: usize) -> $width {
+                    value as $width
+                }
+                fn to_usize(self) -> usize {
+                    self as usize
+                }
+                fn wrapping_add(self, other: $width) -> $width {
+                    <$width>::wrapping_add(self, other)
+                }
+                fn wrapping_sub(self, other: $width) -> $width {
+                    <$width>::wrapping_sub(self, other)
+                }
+                fn wrapping_shl(self, bits: u32) -> $width {
+                    <$width>::wrapping_shl(self, bits)
+                }
+                fn wrapping_shr(self, bits: u32) -> $width {
+                    <$width>::wrapping_shr(self, bits)
+                }
+            }
+        )*
+    };
+}
+
+bus_width!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize);
 
 pub trait Address:
-    PrimInt + NumAssign + Display + LowerHex + UpperHex + Sync + Send + 'static
+    PrimInt + NumAssign + BusWidth + Display + LowerHex + UpperHex + Sync + Send + 'static
 {
 }
 
 pub trait Data: Address {}
 
-impl<T: PrimInt + NumAssign + Display + LowerHex + UpperHex + Sync + Send + 'static> Address for T {}
+impl<T: PrimInt + NumAssign + BusWidth + Display + LowerHex + UpperHex + Sync + Send + 'static>
+    Address for T
+{
+}
 impl<T: Address> Data for T {}
 
 pub trait AddressBusIO<T: Address, U: Data> {
@@ -21,6 +83,40 @@ pub trait AddressBusIO<T: Address, U: Data> {
         U::zero()
     }
     fn write(&mut self, _address: T, _value: U) {}
+    // plain memory can hand out address..address + length directly, the fast
+    // path of block transfers
+    fn as_contiguous(&mut self, _address: T, _length: usize) -> Option<&mut [U]> {
+        None
+    }
+}
+
+// through as_contiguous when the bus allows it, a byte at a time otherwise
+pub fn read_block<T: Address, U: Data, B: AddressBusIO<T, U> + ?Sized>(
+    bus: &mut B,
+    address: T,
+    buffer: &mut [U],
+) {
+    if let Some(cells) = bus.as_contiguous(address, buffer.len()) {
+        buffer.copy_from_slice(cells);
+        return;
+    }
+    for (offset, data) in buffer.iter_mut().enumerate() {
+        *data = bus.read(address + T::from(offset).unwrap());
+    }
+}
+
+pub fn write_block<T: Address, U: Data, B: AddressBusIO<T, U> + ?Sized>(
+    bus: &mut B,
+    address: T,
+    buffer: &[U],
+) {
+    if let Some(cells) = bus.as_contiguous(address, buffer.len()) {
+        cells.copy_from_slice(buffer);
+        return;
+    }
+    for (offset, data) in buffer.iter().enumerate() {
+        bus.write(address + T::from(offset).unwrap(), *data);
+    }
 }
 
 pub trait AddressBusBlockIO<T: Address, U: Data> {
@@ -29,7 +125,13 @@ pub trait AddressBusBlockIO<T: Address, U: Data> {
 }
 
 pub trait Clock {
-    fn step(&mut self);
+    // returns the master clock cycles the step took
+    fn step(&mut self) -> u64;
+}
+
+// what the reset line does, memory is left as it is
+pub trait Reset {
+    fn reset(&mut self);
 }
 
 pub trait Interrupt<T: Address> {
@@ -46,24 +148,59 @@ pub trait Debug<T: Address, U: Data> {
     fn next(&mut self);
     fn set_code_breakpoint(&mut self, bool);
     fn is_code_breakpoint_requested(&mut self) -> bool;
+    fn disassemble(&mut self, address: T) -> (String, T);
+    fn registers(&self) -> Vec<(&'static str, String)>;
 }
 
 pub mod adapter;
+#[cfg(feature = "std")]
 pub mod audio;
+#[cfg(feature = "chip8")]
 pub mod chip8;
+#[cfg(feature = "cpm")]
+pub mod cpm;
+#[cfg(feature = "std")]
 pub mod debugger;
+#[cfg(feature = "dma")]
 pub mod dma;
+mod error;
+pub mod events;
+#[cfg(all(feature = "std", feature = "mos6502"))]
+pub mod examples;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "std")]
+pub mod frontend;
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
 pub mod graphics;
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
 pub mod input;
+pub mod irq;
+#[cfg(feature = "std")]
+pub mod machine;
 pub mod memcontroller;
+#[cfg(feature = "mos6502")]
 pub mod mos6502;
 pub mod ram;
+#[cfg(feature = "std")]
 pub mod random;
 pub mod rom;
+pub mod scheduler;
+#[cfg(feature = "std")]
+pub mod sources;
+#[cfg(feature = "std")]
 pub mod storage;
+pub mod testbus;
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
 pub mod timer;
+#[cfg(feature = "unixterm")]
 pub mod unixterm;
+#[cfg(feature = "std")]
 pub mod utils;
+#[cfg(feature = "w65c816")]
+pub mod w65c816;
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+pub mod worker;
 
 #[cfg(test)]
 mod tests;
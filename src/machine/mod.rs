@@ -0,0 +1,171 @@
+use audio::ring::SampleRing;
+#[cfg(feature = "chip8")]
+use chip8;
+use events::Observers;
+use std::path::Path;
+use {Debug, Error};
+
+#[cfg(feature = "lua")]
+pub mod script;
+
+// host independent keys, frontends translate their own events to these
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Key {
+    // letters are always lowercase
+    Char(char),
+    Up,
+    Down,
+    Left,
+    Right,
+    Enter,
+    Space,
+    Escape,
+    Backspace,
+    Tab,
+    Shift,
+    Control,
+    Alt,
+    Function(u8),
+    // gamepad buttons, 0 and 1 are the main ones (A and B)
+    Button(u8),
+}
+
+// "a" to "z", "0" to "9", "up", "enter", "f1", "button0" and so on
+pub fn parse_key(name: &str) -> Option<Key> {
+    let name = name.to_lowercase();
+    let key = match name.as_str() {
+        "up" => Key::Up,
+        "down" => Key::Down,
+        "left" => Key::Left,
+        "right" => Key::Right,
+        "enter" => Key::Enter,
+        "space" => Key::Space,
+        "escape" => Key::Escape,
+        "backspace" => Key::Backspace,
+        "tab" => Key::Tab,
+        "shift" => Key::Shift,
+        "control" => Key::Control,
+        "alt" => Key::Alt,
+        _ => {
+            let mut chars = name.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => Key::Char(c),
+                _ if name.starts_with("button") => Key::Button(name[6..].parse().ok()?),
+                _ if name.starts_with('f') => Key::Function(name[1..].parse().ok()?),
+                _ => return None,
+            }
+        }
+    };
+    Some(key)
+}
+
+// a whole emulated system as seen by a frontend
+pub trait Machine {
+    fn resolution(&self) -> (usize, usize);
+    // pixels is resolution width * height * 3 bytes (rgb)
+    fn render(&self, pixels: &mut [u8]);
+    fn run_frame(&mut self);
+    fn key(&mut self, key: Key, pressed: bool);
+    // bytes written to the text terminal since the last call
+    fn take_text(&mut self) -> Vec<u8> {
+        Vec::new()
+    }
+    fn audio(&mut self) -> Option<&mut SampleRing> {
+        None
+    }
+    fn frame_rate(&self) -> u32 {
+        60
+    }
+    // what the debugger tools can attach to
+    fn debug(&mut self) -> Option<&mut dyn Debug<u16, u8>> {
+        None
+    }
+    // profilers, loggers and scripts subscribe here instead of to the cpu
+    fn observers(&mut self) -> Option<&mut Observers> {
+        None
+    }
+    // an opaque snapshot of the whole machine, empty when not supported
+    fn save_state(&mut self) -> Vec<u8> {
+        Vec::new()
+    }
+    // false when the state was not produced by the same kind of machine
+    fn load_state(&mut self, _state: &[u8]) -> bool {
+        false
+    }
+    // why the machine stopped running, cleared once taken
+    fn take_fault(&mut self) -> Option<Error> {
+        None
+    }
+}
+
+impl<M: Machine + ?Sized> Machine for Box<M> {
+    fn resolution(&self) -> (usize, usize) {
+        (**self).resolution()
+    }
+    fn render(&self, pixels: &mut [u8]) {
+        (**self).render(pixels)
+    }
+    fn run_frame(&mut self) {
+        (**self).run_frame()
+    }
+    fn key(&mut self, key: Key, pressed: bool) {
+        (**self).key(key, pressed)
+    }
+    fn take_text(&mut self) -> Vec<u8> {
+        (**self).take_text()
+    }
+    fn audio(&mut self) -> Option<&mut SampleRing> {
+        (**self).audio()
+    }
+    fn frame_rate(&self) -> u32 {
+        (**self).frame_rate()
+    }
+    fn debug(&mut self) -> Option<&mut dyn Debug<u16, u8>> {
+        (**self).debug()
+    }
+    fn observers(&mut self) -> Option<&mut Observers> {
+        (**self).observers()
+    }
+    fn save_state(&mut self) -> Vec<u8> {
+        (**self).save_state()
+    }
+    fn load_state(&mut self, state: &[u8]) -> bool {
+        (**self).load_state(state)
+    }
+    fn take_fault(&mut self) -> Option<Error> {
+        (**self).take_fault()
+    }
+}
+
+pub type Loader = fn(&[u8]) -> Result<Box<dyn Machine>, Error>;
+
+#[cfg(feature = "chip8")]
+fn chip8(rom: &[u8]) -> Result<Box<dyn Machine>, Error> {
+    chip8::load(rom).map(|machine| Box::new(machine) as Box<dyn Machine>)
+}
+
+// name, rom file extensions and loader of every machine frontends can run
+pub const MACHINES: &[(&str, &[&str], Loader)] = &[
+    #[cfg(feature = "chip8")]
+    ("chip8", &["ch8", "c8"], chip8),
+];
+
+pub fn load(name: &str, rom: &[u8]) -> Result<Box<dyn Machine>, Error> {
+    let machine = MACHINES
+        .iter()
+        .find(|machine| machine.0 == name)
+        .ok_or_else(|| Error::LoadError(format!("unknown machine {}", name)))?;
+    (machine.2)(rom)
+}
+
+// by file extension
+pub fn detect(filename: &str) -> Option<&'static str> {
+    let extension = Path::new(filename).extension()?.to_str()?.to_lowercase();
+    MACHINES
+        .iter()
+        .find(|machine| machine.1.contains(&extension.as_str()))
+        .map(|machine| machine.0)
+}
+
+#[cfg(all(test, feature = "chip8"))]
+mod tests;
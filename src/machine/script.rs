@@ -0,0 +1,370 @@
+extern crate mlua;
+
+use self::mlua::{Error, Function, Lua, RegistryKey, Variadic};
+use std::cell::RefCell;
+use std::fs;
+use std::mem;
+
+use audio::ring::SampleRing;
+use events::Observers;
+use machine::{parse_key, Key, Machine};
+use Debug;
+
+// colors are 0xRRGGBB, coordinates are in machine pixels
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Shape {
+    Pixel {
+        x: i64,
+        y: i64,
+        color: u32,
+    },
+    Rect {
+        x: i64,
+        y: i64,
+        width: i64,
+        height: i64,
+        color: u32,
+    },
+    Fill {
+        x: i64,
+        y: i64,
+        width: i64,
+        height: i64,
+        color: u32,
+    },
+}
+
+fn no_debug() -> Error {
+    Error::RuntimeError("machine has no memory access".to_string())
+}
+
+fn key_arg(name: &str) -> Result<Key, Error> {
+    parse_key(name).ok_or_else(|| Error::RuntimeError(format!("unknown key {}", name)))
+}
+
+// lua scripts driving a Machine frame by frame: on_frame callbacks run after
+// every frame and can read and write memory, queue input for the next frame
+// and draw an overlay that lasts until the next frame. the api functions are
+// only valid while the script runs, callbacks must look them up as globals
+pub struct MachineScript {
+    lua: Lua,
+    frame_callbacks: Vec<RegistryKey>,
+    overlay: Vec<Shape>,
+    input: Vec<(Key, bool)>,
+    frame: u64,
+    log: Vec<String>,
+}
+
+impl Default for MachineScript {
+    fn default() -> MachineScript {
+        MachineScript::new()
+    }
+}
+
+impl MachineScript {
+    pub fn new() -> MachineScript {
+        MachineScript {
+            lua: Lua::new(),
+            frame_callbacks: Vec::new(),
+            overlay: Vec::new(),
+            input: Vec::new(),
+            frame: 0,
+            log: Vec::new(),
+        }
+    }
+
+    pub fn overlay(&self) -> &[Shape] {
+        &self.overlay
+    }
+
+    pub fn take_log(&mut self) -> Vec<String> {
+        self.log.split_off(0)
+    }
+
+    pub fn load_file<M: Machine + ?Sized>(
+        &mut self,
+        machine: &mut M,
+        filename: &str,
+    ) -> Result<(), String> {
+        match fs::read_to_string(filename) {
+            Ok(source) => self.load(machine, &source),
+            Err(err) => Err(format!("unable to read {}: {}", filename, err)),
+        }
+    }
+
+    // runs the top level of a script, usually registering callbacks
+    pub fn load<M: Machine + ?Sized>(
+        &mut self,
+        machine: &mut M,
+        source: &str,
+    ) -> Result<(), String> {
+        self.with_api(machine, |lua| lua.load(source).exec())
+    }
+
+    pub fn frame<M: Machine + ?Sized>(&mut self, machine: &mut M) -> Result<(), String> {
+        for (key, pressed) in self.input.drain(..) {
+            machine.key(key, pressed);
+        }
+        machine.run_frame();
+        self.frame += 1;
+        self.overlay.clear();
+
+        // callbacks registered while running are kept after the current ones
+        let callbacks = mem::take(&mut self.frame_callbacks);
+        let frame = self.frame;
+        let result = self.with_api(machine, |lua| {
+            for callback in &callbacks {
+                let callback: Function = lua.registry_value(callback)?;
+                callback.call::<()>(frame)?;
+            }
+            Ok(())
+        });
+        let added = mem::replace(&mut self.frame_callbacks, callbacks);
+        self.frame_callbacks.extend(added);
+        result
+    }
+
+    // pixels as given by Machine::render
+    pub fn draw(&self, pixels: &mut [u8], width: usize, height: usize) {
+        let mut plot = |x: i64, y: i64, color: u32| {
+            if x >= 0 && y >= 0 && (x as usize) < width && (y as usize) < height {
+                let offset = (y as usize * width + x as usize) * 3;
+                pixels[offset] = (color >> 16) as u8;
+                pixels[offset + 1] = (color >> 8) as u8;
+                pixels[offset + 2] = color as u8;
+            }
+        };
+        for shape in &self.overlay {
+            match *shape {
+                Shape::Pixel { x, y, color } => plot(x, y, color),
+                Shape::Rect {
+                    x,
+                    y,
+                    width,
+                    height,
+                    color,
+                } => {
+                    for i in x..x + width {
+                        plot(i, y, color);
+                        plot(i, y + height - 1, color);
+                    }
+                    for j in y..y + height {
+                        plot(x, j, color);
+                        plot(x + width - 1, j, color);
+                    }
+                }
+                Shape::Fill {
+                    x,
+                    y,
+                    width,
+                    height,
+                    color,
+                } => {
+                    for j in y..y + height {
+                        for i in x..x + width {
+                            plot(i, j, color);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn with_api<M: Machine + ?Sized, F: FnOnce(&Lua) -> Result<(), Error>>(
+        &mut self,
+        machine: &mut M,
+        f: F,
+    ) -> Result<(), String> {
+        let machine = RefCell::new(machine);
+        let callbacks = RefCell::new(&mut self.frame_callbacks);
+        let overlay = RefCell::new(&mut self.overlay);
+        let input = RefCell::new(&mut self.input);
+        let log = RefCell::new(&mut self.log);
+        let frame = self.frame;
+        let lua = &self.lua;
+
+        let result = lua.scope(|scope| {
+            let globals = lua.globals();
+
+            globals.set(
+                "peek",
+                scope.create_function(|_, address: u16| match machine.borrow_mut().debug() {
+                    Some(debugged) => Ok(debugged.inspect(address)),
+                    None => Err(no_debug()),
+                })?,
+            )?;
+
+            globals.set(
+                "poke",
+                scope.create_function(|_, (address, value): (u16, u8)| {
+                    match machine.borrow_mut().debug() {
+                        Some(debugged) => {
+                            debugged.inject(address, value);
+                            Ok(())
+                        }
+                        None => Err(no_debug()),
+                    }
+                })?,
+            )?;
+
+            globals.set("frame_count", scope.create_function(|_, ()| Ok(frame))?)?;
+
+            globals.set(
+                "on_frame",
+                scope.create_function(|lua, callback: Function| {
+                    let callback = lua.create_registry_value(callback)?;
+                    callbacks.borrow_mut().push(callback);
+                    Ok(())
+                })?,
+            )?;
+
+            globals.set(
+                "press",
+                scope.create_function(|_, name: String| {
+                    input.borrow_mut().push((key_arg(&name)?, true));
+                    Ok(())
+                })?,
+            )?;
+
+            globals.set(
+                "release",
+                scope.create_function(|_, name: String| {
+                    input.borrow_mut().push((key_arg(&name)?, false));
+                    Ok(())
+                })?,
+            )?;
+
+            globals.set(
+                "pixel",
+                scope.create_function(|_, (x, y, color): (i64, i64, u32)| {
+                    overlay.borrow_mut().push(Shape::Pixel { x, y, color });
+                    Ok(())
+                })?,
+            )?;
+
+            globals.set(
+                "rect",
+                scope.create_function(
+                    |_, (x, y, width, height, color): (i64, i64, i64, i64, u32)| {
+                        overlay.borrow_mut().push(Shape::Rect {
+                            x,
+                            y,
+                            width,
+                            height,
+                            color,
+                        });
+                        Ok(())
+                    },
+                )?,
+            )?;
+
+            globals.set(
+                "fill",
+                scope.create_function(
+                    |_, (x, y, width, height, color): (i64, i64, i64, i64, u32)| {
+                        overlay.borrow_mut().push(Shape::Fill {
+                            x,
+                            y,
+                            width,
+                            height,
+                            color,
+                        });
+                        Ok(())
+                    },
+                )?,
+            )?;
+
+            globals.set(
+                "log",
+                scope.create_function(|_, values: Variadic<String>| {
+                    log.borrow_mut().push(values.join(" "));
+                    Ok(())
+                })?,
+            )?;
+
+            f(lua)
+        });
+
+        result.map_err(|err| err.to_string())
+    }
+}
+
+// a scripted machine is still a Machine, so any frontend can run it: the
+// overlay is drawn over the rendered frame and script errors are collected
+pub struct Scripted<M: Machine> {
+    pub machine: M,
+    pub script: MachineScript,
+    errors: Vec<String>,
+}
+
+impl<M: Machine> Scripted<M> {
+    pub fn new(machine: M) -> Scripted<M> {
+        Scripted {
+            machine,
+            script: MachineScript::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    pub fn load(&mut self, source: &str) -> Result<(), String> {
+        self.script.load(&mut self.machine, source)
+    }
+
+    pub fn load_file(&mut self, filename: &str) -> Result<(), String> {
+        self.script.load_file(&mut self.machine, filename)
+    }
+
+    pub fn take_errors(&mut self) -> Vec<String> {
+        self.errors.split_off(0)
+    }
+}
+
+impl<M: Machine> Machine for Scripted<M> {
+    fn resolution(&self) -> (usize, usize) {
+        self.machine.resolution()
+    }
+
+    fn render(&self, pixels: &mut [u8]) {
+        self.machine.render(pixels);
+        let (width, height) = self.machine.resolution();
+        self.script.draw(pixels, width, height);
+    }
+
+    fn run_frame(&mut self) {
+        if let Err(err) = self.script.frame(&mut self.machine) {
+            self.errors.push(err);
+        }
+    }
+
+    fn key(&mut self, key: Key, pressed: bool) {
+        self.machine.key(key, pressed);
+    }
+
+    fn take_text(&mut self) -> Vec<u8> {
+        self.machine.take_text()
+    }
+
+    fn audio(&mut self) -> Option<&mut SampleRing> {
+        self.machine.audio()
+    }
+
+    fn frame_rate(&self) -> u32 {
+        self.machine.frame_rate()
+    }
+
+    fn debug(&mut self) -> Option<&mut dyn Debug<u16, u8>> {
+        self.machine.debug()
+    }
+
+    fn observers(&mut self) -> Option<&mut Observers> {
+        self.machine.observers()
+    }
+
+    fn save_state(&mut self) -> Vec<u8> {
+        self.machine.save_state()
+    }
+
+    fn load_state(&mut self, state: &[u8]) -> bool {
+        self.machine.load_state(state)
+    }
+}
@@ -0,0 +1,153 @@
+use chip8::{Chip8, FONTS};
+use machine::{parse_key, Key, Machine};
+use ram::Ram;
+
+#[test]
+fn chip8_frame_and_render() {
+    let mut ram = Ram::new(4096);
+    ram.fill(FONTS.to_vec(), 0x000);
+    // ld v0, 0x0a / ld f, v0 / drw v1, v1, 5 / jp 0x206
+    ram.fill(vec![0x60, 0x0a, 0xf0, 0x29, 0xd1, 0x15, 0x12, 0x06], 0x200);
+    let mut chip8 = Chip8::new(ram);
+    chip8.run_frame();
+    let (width, height) = chip8.resolution();
+    let mut pixels = vec![0; width * height * 3];
+    chip8.render(&mut pixels);
+    // top row of the "A" glyph is 0xF0
+    assert_eq!(&pixels[0..12], &[255; 12]);
+    assert_eq!(pixels[4 * 3], 0);
+    assert_eq!(chip8.audio().unwrap().len(), 44100 / 60);
+}
+
+#[test]
+fn chip8_hex_keys() {
+    let mut chip8 = Chip8::new(Ram::new(4096));
+    chip8.key(Key::Char('b'), true);
+    chip8.key(Key::Up, true);
+    assert!(chip8.keys[0xb]);
+    chip8.key(Key::Char('b'), false);
+    assert!(!chip8.keys.iter().any(|key| *key));
+}
+
+#[test]
+fn chip8_debug() {
+    let mut ram = Ram::new(4096);
+    ram.fill(vec![0x60, 0x0a, 0xd1, 0x15, 0x12, 0x00], 0x200);
+    let mut chip8 = Chip8::new(ram);
+    let debugged = chip8.debug().unwrap();
+    assert_eq!(debugged.disassemble(0x200), ("ld v0, $0A".to_string(), 2));
+    assert_eq!(debugged.disassemble(0x202).0, "drw v1, v1, 5");
+    assert_eq!(debugged.disassemble(0x204).0, "jp $200");
+    debugged.next();
+    assert!(debugged.registers().contains(&("V0", "$0A".to_string())));
+}
+
+#[test]
+fn chip8_save_state() {
+    let mut ram = Ram::new(4096);
+    // ld v0, 0x0a / ld i, 0x300 / ld b, v0 / jp 0x206
+    ram.fill(vec![0x60, 0x0a, 0xa3, 0x00, 0xf0, 0x33, 0x12, 0x06], 0x200);
+    let mut chip8 = Chip8::new(ram);
+    chip8.run_frame();
+    let state = chip8.save_state();
+
+    let mut restored = Chip8::new(Ram::new(4096));
+    assert!(!restored.load_state(&state[1..]));
+    assert!(restored.load_state(&state));
+    assert_eq!(restored.reg[0], 0x0a);
+    assert_eq!(restored.index, 0x300);
+    assert_eq!(restored.pc, 0x206);
+    assert_eq!(restored.debug().unwrap().inspect(0x301), 0x01);
+    assert_eq!(restored.save_state(), state);
+}
+
+#[test]
+fn chip8_faults() {
+    use chip8;
+    use machine::load;
+    use Error;
+
+    assert!(chip8::load(&[]).is_err());
+    assert!(chip8::load(&[0; 4096]).is_err());
+    assert_eq!(
+        load("nes", &[0x00]).err(),
+        Some(Error::LoadError("unknown machine nes".to_string()))
+    );
+
+    // ret with nothing on the stack
+    let mut machine = load("chip8", &[0x00, 0xee]).unwrap();
+    machine.run_frame();
+    assert_eq!(
+        machine.take_fault(),
+        Some(Error::StackUnderflow { pc: 0x200 })
+    );
+    assert_eq!(machine.take_fault(), None);
+
+    let mut ram = Ram::new(4096);
+    ram.fill(vec![0x00, 0xee], 0x200);
+    let mut chip8 = Chip8::new(ram);
+    assert!(chip8.try_step().is_err());
+    assert_eq!(chip8.pc, 0x200);
+}
+
+#[test]
+fn chip8_reset() {
+    use Reset;
+
+    let mut ram = Ram::new(4096);
+    // ld v0, 0x0a / ld i, 0x300 / jp 0x204
+    ram.fill(vec![0x60, 0x0a, 0xa3, 0x00, 0x12, 0x04], 0x200);
+    let mut chip8 = Chip8::new(ram);
+    chip8.run_frame();
+    chip8.reset();
+    assert_eq!(chip8.pc, 0x200);
+    assert_eq!(chip8.reg[0], 0);
+    assert_eq!(chip8.index, 0);
+    // the program is still there
+    chip8.run_frame();
+    assert_eq!(chip8.reg[0], 0x0a);
+}
+
+#[test]
+fn key_names() {
+    assert_eq!(parse_key("F3"), Some(Key::Function(3)));
+    assert_eq!(parse_key("button1"), Some(Key::Button(1)));
+    assert_eq!(parse_key("x"), Some(Key::Char('x')));
+    assert_eq!(parse_key("Enter"), Some(Key::Enter));
+    assert_eq!(parse_key("nope"), None);
+}
+
+#[cfg(feature = "lua")]
+#[test]
+fn scripted_machine() {
+    use machine::script::{Scripted, Shape};
+
+    let mut ram = Ram::new(4096);
+    // jp 0x200
+    ram.fill(vec![0x12, 0x00], 0x200);
+    let mut scripted = Scripted::new(Chip8::new(ram));
+    let script = "
+        on_frame(function(frame)
+            poke(0x300, frame)
+            if frame == 1 then press('a') end
+            fill(0, 0, 2, 1, 0xff0000)
+            log('frame', frame, peek(0x300))
+        end)
+    ";
+    assert!(scripted.load(script).is_ok());
+    scripted.run_frame();
+    scripted.run_frame();
+    assert!(scripted.machine.keys[0xa]);
+    assert_eq!(scripted.script.take_log(), vec!["frame 1 1", "frame 2 2"]);
+    assert_eq!(scripted.script.overlay().len(), 1);
+    let mut pixels = vec![0; 64 * 32 * 3];
+    scripted.render(&mut pixels);
+    assert_eq!(&pixels[0..7], &[255, 0, 0, 255, 0, 0, 0]);
+
+    assert!(scripted.load("press('nope')").is_err());
+    assert!(matches!(
+        scripted.script.overlay()[0],
+        Shape::Fill { width: 2, .. }
+    ));
+    assert!(scripted.take_errors().is_empty());
+}
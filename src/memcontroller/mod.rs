@@ -1,8 +1,11 @@
-use std::cell::RefCell;
-use std::rc::Rc;
-use std::sync::{Arc, Mutex};
+use alloc::boxed::Box;
+use alloc::rc::Rc;
+#[cfg(feature = "std")]
+use alloc::sync::{Arc, Mutex};
+use alloc::vec::Vec;
+use core::cell::RefCell;
 
-use {Address, AddressBusIO, Data};
+use {Address, AddressBusIO, Data, Error};
 
 struct AddressMapping<'a, T: Address + 'a, U: Data + 'a> {
     start: T,
@@ -16,10 +19,19 @@ struct MirrorMapping<T: Address> {
     mirror: T,
 }
 
+// address..address + length all inside start..=end
+fn contains<T: Address>(start: T, end: T, address: T, length: usize) -> bool {
+    address >= start
+        && address <= end
+        && (length == 0 || T::from(length - 1).is_some_and(|last| last <= end - address))
+}
+
 pub struct MemoryController<'a, T: Address + 'a, U: Data + 'a> {
     mappings: Vec<AddressMapping<'a, T, U>>,
     mirrors: Vec<MirrorMapping<T>>,
     pub panic_on_no_map: bool,
+    // the first access nothing answered since the last take_unmapped
+    unmapped: Option<Error>,
 }
 
 impl<'a, T: Address, U: Data> MemoryController<'a, T, U> {
@@ -28,6 +40,7 @@ impl<'a, T: Address, U: Data> MemoryController<'a, T, U> {
             mappings: Vec::new(),
             mirrors: Vec::new(),
             panic_on_no_map: false,
+            unmapped: None,
         }
     }
 
@@ -42,6 +55,19 @@ impl<'a, T: Address, U: Data> MemoryController<'a, T, U> {
     pub fn mirror(&mut self, start: T, end: T, mirror: T) {
         self.mirrors.push(MirrorMapping { start, end, mirror });
     }
+
+    pub fn take_unmapped(&mut self) -> Option<Error> {
+        self.unmapped.take()
+    }
+
+    fn no_map(&mut self, address: T) {
+        if self.panic_on_no_map {
+            panic!("unknown mapping ${:X}", address);
+        }
+        if self.unmapped.is_none() {
+            self.unmapped = Some(Error::UnmappedAddress(address.to_u64().unwrap_or(u64::MAX)));
+        }
+    }
 }
 
 impl<'a, T: Address, U: Data> AddressBusIO<T, U> for MemoryController<'a, T, U> {
@@ -59,9 +85,7 @@ impl<'a, T: Address, U: Data> AddressBusIO<T, U> for MemoryController<'a, T, U>
                 return mapping.connection.read(cleaned_address - mapping.start);
             }
         }
-        if self.panic_on_no_map {
-            panic!("unknown mapping ${:X}", address);
-        }
+        self.no_map(address);
         U::zero()
     }
 
@@ -82,9 +106,31 @@ impl<'a, T: Address, U: Data> AddressBusIO<T, U> for MemoryController<'a, T, U>
                 return;
             }
         }
-        if self.panic_on_no_map {
-            panic!("unknown mapping ${:X}", address);
+        self.no_map(address);
+    }
+
+    fn as_contiguous(&mut self, address: T, length: usize) -> Option<&mut [U]> {
+        let mut cleaned_address = address;
+        for mirror in &self.mirrors {
+            if address >= mirror.start && address <= mirror.end {
+                if !contains(mirror.start, mirror.end, address, length) {
+                    return None;
+                }
+                cleaned_address = mirror.mirror + address - mirror.start;
+                break;
+            }
         }
+        for mapping in &mut self.mappings {
+            if cleaned_address >= mapping.start && cleaned_address <= mapping.end {
+                if !contains(mapping.start, mapping.end, cleaned_address, length) {
+                    return None;
+                }
+                return mapping
+                    .connection
+                    .as_contiguous(cleaned_address - mapping.start, length);
+            }
+        }
+        None
     }
 }
 
@@ -132,6 +178,20 @@ impl<T: Address, U: Data> AddressBusIO<T, U> for MemoryControllerBoxed<T, U> {
             }
         }
     }
+
+    fn as_contiguous(&mut self, address: T, length: usize) -> Option<&mut [U]> {
+        for mapping in &mut self.mappings {
+            if address >= mapping.start && address <= mapping.end {
+                if !contains(mapping.start, mapping.end, address, length) {
+                    return None;
+                }
+                return mapping
+                    .connection
+                    .as_contiguous(address - mapping.start, length);
+            }
+        }
+        None
+    }
 }
 
 struct AddressMappingShared<T: Address, U: Data> {
@@ -186,16 +246,19 @@ impl<T: Address, U: Data> AddressBusIO<T, U> for MemoryControllerShared<T, U> {
     }
 }
 
+#[cfg(feature = "std")]
 struct AddressMappingThreadSafe<T: Address, U: Data> {
     start: T,
     end: T,
     connection: Arc<Mutex<dyn AddressBusIO<T, U> + Send + Sync>>,
 }
 
+#[cfg(feature = "std")]
 pub struct MemoryControllerThreadSafe<T: Address, U: Data> {
     mappings: Vec<AddressMappingThreadSafe<T, U>>,
 }
 
+#[cfg(feature = "std")]
 impl<T: Address, U: Data> MemoryControllerThreadSafe<T, U> {
     pub fn new() -> MemoryControllerThreadSafe<T, U> {
         MemoryControllerThreadSafe {
@@ -217,6 +280,7 @@ impl<T: Address, U: Data> MemoryControllerThreadSafe<T, U> {
     }
 }
 
+#[cfg(feature = "std")]
 impl<T: Address, U: Data> AddressBusIO<T, U> for MemoryControllerThreadSafe<T, U> {
     fn read(&mut self, address: T) -> U {
         for mapping in &mut self.mappings {
@@ -314,4 +378,19 @@ impl<'a, T: Address, U: Data> AddressBusIO<T, U> for MemoryControllerSmart<'a, T
             }
         }
     }
+
+    // shared mappings cannot lend their memory past the borrow
+    fn as_contiguous(&mut self, address: T, length: usize) -> Option<&mut [U]> {
+        for mapping in &mut self.mappings {
+            if address >= mapping.start && address <= mapping.end {
+                if !contains(mapping.start, mapping.end, address, length) {
+                    return None;
+                }
+                return mapping
+                    .connection
+                    .as_contiguous(address - mapping.start, length);
+            }
+        }
+        None
+    }
 }
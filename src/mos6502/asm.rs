@@ -0,0 +1,454 @@
+use std::collections::HashMap;
+
+use mos6502::opcodes::{self, length};
+
+// mnemonic, addressing mode and code of every valid opcode
+fn opcode_table() -> Vec<(&'static str, &'static str, u8)> {
+    opcodes::all()
+        .filter(|info| info.legal)
+        .map(|info| (info.mnemonic, info.mode, info.opcode))
+        .collect()
+}
+
+enum Operand {
+    None,
+    Accumulator,
+    Immediate(String),
+    Direct(String),
+    IndexedX(String),
+    IndexedY(String),
+    Indirect(String),
+    IndirectX(String),
+    IndirectY(String),
+}
+
+enum Statement {
+    Org(String),
+    Bytes(Vec<String>),
+    Words(Vec<String>),
+    Constant(String, String),
+    Instruction(String, Operand),
+}
+
+struct Line {
+    number: usize,
+    label: Option<String>,
+    statement: Option<Statement>,
+}
+
+pub struct Assembly {
+    pub origin: u16,
+    pub bytes: Vec<u8>,
+    pub labels: HashMap<String, u16>,
+}
+
+fn is_identifier(text: &str) -> bool {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+            chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        }
+        _ => false,
+    }
+}
+
+// splits on commas outside of quotes
+fn split_list(text: &str) -> Vec<String> {
+    let mut items = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    for c in text.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                current.push(c);
+            }
+            ',' if !quoted => {
+                items.push(current.trim().to_string());
+                current.clear();
+            }
+            _ => current.push(c),
+        }
+    }
+    items.push(current.trim().to_string());
+    items
+}
+
+fn strip_comment(line: &str) -> &str {
+    let mut quoted = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ';' if !quoted => return &line[..i],
+            _ => (),
+        }
+    }
+    line
+}
+
+fn parse_operand(text: &str) -> Operand {
+    let text = text.trim();
+    let lower = text.to_lowercase().replace(' ', "");
+    if text.is_empty() {
+        Operand::None
+    } else if lower == "a" {
+        Operand::Accumulator
+    } else if let Some(value) = text.strip_prefix('#') {
+        Operand::Immediate(value.trim().to_string())
+    } else if lower.starts_with('(') && lower.ends_with(",x)") {
+        Operand::IndirectX(text[1..text.rfind(',').unwrap()].trim().to_string())
+    } else if lower.starts_with('(') && lower.ends_with("),y") {
+        Operand::IndirectY(text[1..text.rfind(')').unwrap()].trim().to_string())
+    } else if lower.starts_with('(') && lower.ends_with(')') {
+        Operand::Indirect(text[1..text.len() - 1].trim().to_string())
+    } else if lower.ends_with(",x") {
+        Operand::IndexedX(text[..text.rfind(',').unwrap()].trim().to_string())
+    } else if lower.ends_with(",y") {
+        Operand::IndexedY(text[..text.rfind(',').unwrap()].trim().to_string())
+    } else {
+        Operand::Direct(text.to_string())
+    }
+}
+
+fn parse_line(text: &str) -> Result<(Option<String>, Option<Statement>), String> {
+    let mut text = strip_comment(text).trim();
+    let mut label = None;
+
+    if let Some(colon) = text.find(':') {
+        if is_identifier(&text[..colon]) {
+            label = Some(text[..colon].to_string());
+            text = text[colon + 1..].trim();
+        }
+    }
+    if text.is_empty() {
+        return Ok((label, None));
+    }
+
+    if let Some(equal) = text.find('=') {
+        let name = text[..equal].trim();
+        if is_identifier(name) {
+            let value = text[equal + 1..].trim().to_string();
+            return Ok((label, Some(Statement::Constant(name.to_string(), value))));
+        }
+    }
+
+    let (word, rest) = match text.find(char::is_whitespace) {
+        Some(space) => (&text[..space], text[space..].trim()),
+        None => (text, ""),
+    };
+    let statement = match word.to_lowercase().as_str() {
+        ".org" => Statement::Org(rest.to_string()),
+        ".byte" | ".db" => Statement::Bytes(split_list(rest)),
+        ".word" | ".dw" => Statement::Words(split_list(rest)),
+        directive if directive.starts_with('.') => {
+            return Err(format!("unknown directive {}", directive))
+        }
+        mnemonic => Statement::Instruction(mnemonic.to_string(), parse_operand(rest)),
+    };
+    Ok((label, Some(statement)))
+}
+
+fn parse_number(text: &str) -> Result<i64, String> {
+    let result = if let Some(hex) = text.strip_prefix('$') {
+        i64::from_str_radix(hex, 16)
+    } else if let Some(binary) = text.strip_prefix('%') {
+        i64::from_str_radix(binary, 2)
+    } else {
+        text.parse()
+    };
+    result.map_err(|_| format!("invalid number {}", text))
+}
+
+// terms joined by + and -, optionally prefixed by < (low byte) or > (high
+// byte). unknown labels give None, they are resolved in the second pass
+fn evaluate(text: &str, symbols: &HashMap<String, u16>, pc: u16) -> Result<Option<i64>, String> {
+    let text = text.trim();
+    if let Some(value) = text.strip_prefix('<') {
+        return Ok(evaluate(value, symbols, pc)?.map(|value| value & 0xff));
+    }
+    if let Some(value) = text.strip_prefix('>') {
+        return Ok(evaluate(value, symbols, pc)?.map(|value| (value >> 8) & 0xff));
+    }
+
+    let mut total = Some(0);
+    let mut sign = 1;
+    let mut term = String::new();
+    let mut quoted = false;
+    for c in text.chars().chain(Some('+')) {
+        if c == '\'' {
+            quoted = !quoted;
+        }
+        if quoted || c == '\'' {
+            term.push(c);
+        } else if (c == '+' || c == '-') && !term.trim().is_empty() {
+            let term_text = term.trim();
+            let value = if term_text == "*" {
+                Some(i64::from(pc))
+            } else if term_text.len() == 3
+                && term_text.starts_with('\'')
+                && term_text.ends_with('\'')
+            {
+                Some(i64::from(term_text.as_bytes()[1]))
+            } else if is_identifier(term_text) {
+                symbols.get(term_text).map(|value| i64::from(*value))
+            } else {
+                Some(parse_number(term_text)?)
+            };
+            total = match (total, value) {
+                (Some(total), Some(value)) => Some(total + sign * value),
+                _ => None,
+            };
+            sign = if c == '-' { -1 } else { 1 };
+            term.clear();
+        } else if c == '-' && term.trim().is_empty() {
+            sign = -sign;
+        } else {
+            term.push(c);
+        }
+    }
+    if total.is_some() && text.is_empty() {
+        return Err("missing value".to_string());
+    }
+    Ok(total)
+}
+
+fn resolve(value: Option<i64>) -> Result<i64, String> {
+    value.ok_or_else(|| "undefined label".to_string())
+}
+
+fn byte(value: i64) -> Result<u8, String> {
+    if (-128..=255).contains(&value) {
+        Ok(value as u8)
+    } else {
+        Err(format!("value {} does not fit in a byte", value))
+    }
+}
+
+fn word(value: i64) -> Result<u16, String> {
+    if (-32768..=65535).contains(&value) {
+        Ok(value as u16)
+    } else {
+        Err(format!("value {} does not fit in a word", value))
+    }
+}
+
+struct Output {
+    origin: Option<u16>,
+    pc: u16,
+    bytes: Vec<u8>,
+}
+
+struct Assembler {
+    table: Vec<(&'static str, &'static str, u8)>,
+    symbols: HashMap<String, u16>,
+    // addressing mode chosen for each instruction in the first pass
+    modes: HashMap<usize, (&'static str, u8)>,
+}
+
+impl Assembler {
+    fn find(&self, mnemonic: &str, mode: &str) -> Option<u8> {
+        self.table
+            .iter()
+            .find(|opcode| opcode.0 == mnemonic && opcode.1 == mode)
+            .map(|opcode| opcode.2)
+    }
+
+    fn choose(
+        &self,
+        mnemonic: &str,
+        operand: &Operand,
+        pc: u16,
+    ) -> Result<(&'static str, u8), String> {
+        let value = |text: &str| evaluate(text, &self.symbols, pc);
+        let zeropage = |text: &str| -> Result<bool, String> {
+            Ok(value(text)?.is_some_and(|value| (0..0x100).contains(&value)))
+        };
+        let candidates: Vec<&'static str> = match *operand {
+            Operand::None => vec!["implied", "accumulator"],
+            Operand::Accumulator => vec!["accumulator"],
+            Operand::Immediate(_) => vec!["immediate"],
+            Operand::Indirect(_) => vec!["indirect"],
+            Operand::IndirectX(_) => vec!["indirect_x"],
+            Operand::IndirectY(_) => vec!["indirect_y"],
+            Operand::Direct(ref text) if zeropage(text)? => {
+                vec!["relative", "zeropage", "absolute"]
+            }
+            Operand::Direct(_) => vec!["relative", "absolute", "zeropage"],
+            Operand::IndexedX(ref text) if zeropage(text)? => vec!["zeropage_x", "absolute_x"],
+            Operand::IndexedX(_) => vec!["absolute_x", "zeropage_x"],
+            Operand::IndexedY(ref text) if zeropage(text)? => vec!["zeropage_y", "absolute_y"],
+            Operand::IndexedY(_) => vec!["absolute_y", "zeropage_y"],
+        };
+        for mode in candidates {
+            if let Some(code) = self.find(mnemonic, mode) {
+                return Ok((mode, code));
+            }
+        }
+        if self.table.iter().any(|opcode| opcode.0 == mnemonic) {
+            Err(format!("invalid addressing mode for {}", mnemonic))
+        } else {
+            Err(format!("unknown instruction {}", mnemonic))
+        }
+    }
+
+    fn encode(&self, mode: &str, code: u8, operand: &Operand, pc: u16) -> Result<Vec<u8>, String> {
+        let text = match *operand {
+            Operand::None | Operand::Accumulator => return Ok(vec![code]),
+            Operand::Immediate(ref text)
+            | Operand::Direct(ref text)
+            | Operand::IndexedX(ref text)
+            | Operand::IndexedY(ref text)
+            | Operand::Indirect(ref text)
+            | Operand::IndirectX(ref text)
+            | Operand::IndirectY(ref text) => text,
+        };
+        let value = resolve(evaluate(text, &self.symbols, pc)?)?;
+        match mode {
+            "relative" => {
+                let offset = value - (i64::from(pc) + 2);
+                if !(-128..=127).contains(&offset) {
+                    return Err(format!("branch out of range ({})", offset));
+                }
+                Ok(vec![code, offset as u8])
+            }
+            _ if length(mode) == 3 => {
+                let value = word(value)?;
+                Ok(vec![code, value as u8, (value >> 8) as u8])
+            }
+            _ => Ok(vec![code, byte(value)?]),
+        }
+    }
+
+    fn data(&self, item: &str, pc: u16, words: bool) -> Result<Vec<u8>, String> {
+        if !words && item.len() >= 2 && item.starts_with('"') && item.ends_with('"') {
+            return Ok(item[1..item.len() - 1].bytes().collect());
+        }
+        let value = resolve(evaluate(item, &self.symbols, pc)?)?;
+        if words {
+            let value = word(value)?;
+            Ok(vec![value as u8, (value >> 8) as u8])
+        } else {
+            Ok(vec![byte(value)?])
+        }
+    }
+
+    fn line(&mut self, line: &Line, emit: bool, output: &mut Output) -> Result<(), String> {
+        let pc = output.pc;
+        if let Some(ref label) = line.label {
+            if !emit && self.symbols.contains_key(label) {
+                return Err(format!("label {} defined twice", label));
+            }
+            self.symbols.insert(label.clone(), pc);
+        }
+        let generated = match line.statement {
+            None => return Ok(()),
+            Some(Statement::Constant(ref name, ref value)) => {
+                if let Some(value) = evaluate(value, &self.symbols, pc)? {
+                    self.symbols.insert(name.clone(), word(value)?);
+                } else if emit {
+                    return Err(format!("constant {} uses an undefined label", name));
+                }
+                return Ok(());
+            }
+            Some(Statement::Org(ref value)) => {
+                let address = word(resolve(evaluate(value, &self.symbols, pc)?)?)?;
+                match output.origin {
+                    None => output.origin = Some(address),
+                    Some(_) if address < pc => return Err("org goes backwards".to_string()),
+                    Some(_) => output
+                        .bytes
+                        .resize(output.bytes.len() + usize::from(address - pc), 0),
+                }
+                output.pc = address;
+                return Ok(());
+            }
+            Some(Statement::Bytes(ref items)) => {
+                let mut generated = Vec::new();
+                for item in items {
+                    if emit {
+                        generated.extend(self.data(item, pc, false)?);
+                    } else if item.starts_with('"') {
+                        generated.resize(generated.len() + item.len().saturating_sub(2), 0);
+                    } else {
+                        generated.push(0);
+                    }
+                }
+                generated
+            }
+            Some(Statement::Words(ref items)) => {
+                let mut generated = Vec::new();
+                for item in items {
+                    if emit {
+                        generated.extend(self.data(item, pc, true)?);
+                    } else {
+                        generated.extend_from_slice(&[0, 0]);
+                    }
+                }
+                generated
+            }
+            Some(Statement::Instruction(ref mnemonic, ref operand)) => {
+                if emit {
+                    let (mode, code) = self.modes[&line.number];
+                    self.encode(mode, code, operand, pc)?
+                } else {
+                    let chosen = self.choose(mnemonic, operand, pc)?;
+                    self.modes.insert(line.number, chosen);
+                    vec![0; usize::from(length(chosen.0))]
+                }
+            }
+        };
+        if output.origin.is_none() {
+            output.origin = Some(pc);
+        }
+        if usize::from(pc) + generated.len() > 0x10000 {
+            return Err("code goes past $FFFF".to_string());
+        }
+        output.pc = pc.wrapping_add(generated.len() as u16);
+        output.bytes.extend(generated);
+        Ok(())
+    }
+
+    // the first pass only computes addresses (emit false), the second one
+    // generates the code with every label known
+    fn pass(&mut self, lines: &[Line], emit: bool) -> Result<Output, String> {
+        let mut output = Output {
+            origin: None,
+            pc: 0,
+            bytes: Vec::new(),
+        };
+        for line in lines {
+            self.line(line, emit, &mut output)
+                .map_err(|err| format!("line {}: {}", line.number, err))?;
+        }
+        Ok(output)
+    }
+}
+
+// a small two-pass assembler: labels (name:), constants (name = value),
+// .org, .byte/.db and .word/.dw, $hex, %binary, 'c', * and < > selectors
+pub fn assemble(source: &str) -> Result<Assembly, String> {
+    let mut lines = Vec::new();
+    for (i, text) in source.lines().enumerate() {
+        let (label, statement) =
+            parse_line(text).map_err(|err| format!("line {}: {}", i + 1, err))?;
+        lines.push(Line {
+            number: i + 1,
+            label,
+            statement,
+        });
+    }
+
+    let mut assembler = Assembler {
+        table: opcode_table(),
+        symbols: HashMap::new(),
+        modes: HashMap::new(),
+    };
+    assembler.pass(&lines, false)?;
+    let output = assembler.pass(&lines, true)?;
+
+    Ok(Assembly {
+        origin: output.origin.unwrap_or(0),
+        bytes: output.bytes,
+        labels: assembler.symbols,
+    })
+}
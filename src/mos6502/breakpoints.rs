@@ -0,0 +1,194 @@
+use alloc::boxed::Box;
+use alloc::string::String;
+
+use mos6502::condition::Condition;
+use mos6502::MOS6502;
+use AddressBusIO;
+
+// hit once an instruction (or an interrupt) leaves the pc at address, so the
+// instruction there has not run yet and resuming runs it
+#[derive(Clone, PartialEq, Debug)]
+pub struct Breakpoint {
+    pub address: u16,
+    pub enabled: bool,
+    // checked only there, the hits are the times it held
+    pub condition: Option<Condition>,
+    pub hits: u64,
+}
+
+impl<T: AddressBusIO<u16, u8>> MOS6502<T> {
+    // an existing one at address is enabled again, its hits kept
+    pub fn add_breakpoint(&mut self, address: u16) {
+        self.insert_breakpoint(address, None);
+    }
+
+    // as add_breakpoint, condition is parsed by Condition::parse
+    pub fn add_conditional_breakpoint(
+        &mut self,
+        address: u16,
+        condition: &str,
+    ) -> Result<(), String> {
+        let condition = Condition::parse(condition)?;
+        self.insert_breakpoint(address, Some(condition));
+        Ok(())
+    }
+
+    fn insert_breakpoint(&mut self, address: u16, condition: Option<Condition>) {
+        match self.breakpoint_index(address) {
+            Some(index) => {
+                self.breakpoints[index].enabled = true;
+                self.breakpoints[index].condition = condition;
+            }
+            None => self.breakpoints.push(Breakpoint {
+                address,
+                enabled: true,
+                condition,
+                hits: 0,
+            }),
+        }
+    }
+
+    pub fn remove_breakpoint(&mut self, address: u16) -> bool {
+        let count = self.breakpoints.len();
+        self.breakpoints
+            .retain(|breakpoint| breakpoint.address != address);
+        self.breakpoints.len() != count
+    }
+
+    // false without a breakpoint at address
+    pub fn enable_breakpoint(&mut self, address: u16, enable: bool) -> bool {
+        match self.breakpoint_index(address) {
+            Some(index) => {
+                self.breakpoints[index].enabled = enable;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.clear();
+    }
+
+    // in the order they were added
+    pub fn breakpoints(&self) -> &[Breakpoint] {
+        &self.breakpoints
+    }
+
+    // the last one hit, reported as a code breakpoint too
+    pub fn take_breakpoint(&mut self) -> Option<Breakpoint> {
+        self.hit_breakpoint.take()
+    }
+
+    fn breakpoint_index(&self, address: u16) -> Option<usize> {
+        self.breakpoints
+            .iter()
+            .position(|breakpoint| breakpoint.address == address)
+    }
+}
+
+// the accesses a watchpoint is for
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Watch {
+    Read,
+    Write,
+    Access,
+}
+
+impl Watch {
+    pub fn matches(self, write: bool) -> bool {
+        match self {
+            Watch::Read => !write,
+            Watch::Write => write,
+            Watch::Access => true,
+        }
+    }
+}
+
+// over start..=end, hit by the bus accesses of the program only, peeks do not
+// count
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Watchpoint {
+    pub start: u16,
+    pub end: u16,
+    pub watch: Watch,
+    pub enabled: bool,
+    pub hits: u64,
+}
+
+// pc is the instruction making the access, it runs to its end before the
+// stop
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct WatchHit {
+    pub pc: u16,
+    pub address: u16,
+    pub value: u8,
+    pub write: bool,
+}
+
+pub type WatchHandler = Box<dyn FnMut(&WatchHit) + Send + Sync>;
+
+impl<T: AddressBusIO<u16, u8>> MOS6502<T> {
+    // one on the same range is replaced, its hits kept
+    pub fn add_watchpoint(&mut self, start: u16, end: u16, watch: Watch) {
+        match self.watchpoint_index(start, end) {
+            Some(index) => {
+                self.watchpoints[index].watch = watch;
+                self.watchpoints[index].enabled = true;
+            }
+            None => self.watchpoints.push(Watchpoint {
+                start,
+                end,
+                watch,
+                enabled: true,
+                hits: 0,
+            }),
+        }
+    }
+
+    pub fn remove_watchpoint(&mut self, start: u16, end: u16) -> bool {
+        let count = self.watchpoints.len();
+        self.watchpoints
+            .retain(|watchpoint| (watchpoint.start, watchpoint.end) != (start, end));
+        self.watchpoints.len() != count
+    }
+
+    pub fn enable_watchpoint(&mut self, start: u16, end: u16, enable: bool) -> bool {
+        match self.watchpoint_index(start, end) {
+            Some(index) => {
+                self.watchpoints[index].enabled = enable;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn clear_watchpoints(&mut self) {
+        self.watchpoints.clear();
+    }
+
+    pub fn watchpoints(&self) -> &[Watchpoint] {
+        &self.watchpoints
+    }
+
+    // the hits go to handler instead of stopping the cpu
+    pub fn on_watchpoint<F: FnMut(&WatchHit) + Send + Sync + 'static>(&mut self, handler: F) {
+        self.on_watchpoint = Some(Box::new(handler));
+    }
+
+    pub fn stop_on_watchpoints(&mut self) {
+        self.on_watchpoint = None;
+    }
+
+    // the first access hit in the instruction, reported as a code breakpoint
+    // too
+    pub fn take_watchpoint(&mut self) -> Option<WatchHit> {
+        self.hit_watchpoint.take()
+    }
+
+    fn watchpoint_index(&self, start: u16, end: u16) -> Option<usize> {
+        self.watchpoints
+            .iter()
+            .position(|watchpoint| (watchpoint.start, watchpoint.end) == (start, end))
+    }
+}
@@ -0,0 +1,230 @@
+use alloc::boxed::Box;
+
+use mos6502::lockstep::Registers;
+use mos6502::port::ProcessorPort;
+use mos6502::power::PowerUpState;
+use mos6502::MOS6502;
+use AddressBusIO;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Variant {
+    Nmos,
+    // the wdc 65c02: its opcodes, the fixed jmp ($xxFF) and the decimal flags
+    Cmos,
+    // the nes cpu, an nmos without the decimal mode
+    Ricoh2A03,
+    // the c64 cpu, an nmos with the processor port at $0000 and $0001
+    Mos6510,
+    // the pc engine cpu, a cmos with its zeropage at $2000 and its own
+    // vectors; its mmu, block transfers and other opcodes come with
+    // MOS6502::huc6280
+    HuC6280,
+}
+
+impl Variant {
+    // the 65c02 and the chips built on it, with the cmos opcodes and timings
+    pub fn is_cmos(self) -> bool {
+        matches!(self, Variant::Cmos | Variant::HuC6280)
+    }
+}
+
+// the behaviors the variants differ on, a new cpu has the ones of the nmos
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct Quirks {
+    // jmp ($xxFF) takes the high byte from $xx00, as the nmos does
+    pub indirect_jump_wrap: bool,
+    // brk, irq and nmi clear the decimal flag, as the cmos does
+    pub clear_decimal_on_interrupt: bool,
+    // adc and sbc work on bcd with the decimal flag set
+    pub decimal_mode: bool,
+    // n and z of a decimal adc or sbc come from its result, for a cycle more,
+    // as the cmos does
+    pub decimal_flags: bool,
+    // the page the zeropage is on, the stack on the one after it
+    pub zeropage: u16,
+    // reset at $FFFE, nmi at $FFFC, irq at $FFF8 and brk at $FFF6, as the
+    // huc6280 has them
+    pub huc6280_vectors: bool,
+}
+
+impl Quirks {
+    pub fn of(variant: Variant) -> Quirks {
+        match variant {
+            Variant::Nmos | Variant::Mos6510 => Quirks {
+                indirect_jump_wrap: true,
+                clear_decimal_on_interrupt: false,
+                decimal_mode: true,
+                decimal_flags: false,
+                zeropage: 0x0000,
+                huc6280_vectors: false,
+            },
+            Variant::Cmos => Quirks {
+                indirect_jump_wrap: false,
+                clear_decimal_on_interrupt: true,
+                decimal_mode: true,
+                decimal_flags: true,
+                zeropage: 0x0000,
+                huc6280_vectors: false,
+            },
+            Variant::Ricoh2A03 => Quirks {
+                decimal_mode: false,
+                ..Quirks::of(Variant::Nmos)
+            },
+            Variant::HuC6280 => Quirks {
+                zeropage: 0x2000,
+                huc6280_vectors: true,
+                ..Quirks::of(Variant::Cmos)
+            },
+        }
+    }
+}
+
+// what happens on an opcode the core does not implement
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum IllegalOpcodes {
+    // halts on the opcode with an InvalidOpcode fault, as a kil does
+    Jam,
+    // a single byte, two cycle nop
+    Nop,
+    // the host goes down with the opcode and its address
+    Panic,
+    // the callback given to on_illegal_opcode gets the address and the
+    // opcode, then it goes on as a nop
+    Callback,
+    // the stable undocumented nmos opcodes run as on the chip (lax, sax,
+    // dcp, isc, slo, rla, sre, rra, anc, alr, arr, sbx, sbc #imm and the
+    // nops), the others jam
+    Execute,
+}
+
+pub type TraceSink = Box<dyn FnMut(u16, &str) + Send + Sync>;
+
+pub type IllegalOpcodeHandler = Box<dyn FnMut(u16, u8) + Send + Sync>;
+
+pub struct Builder<T: AddressBusIO<u16, u8>> {
+    bus: T,
+    variant: Variant,
+    quirks: Quirks,
+    dummy_accesses: bool,
+    accurate_interrupts: bool,
+    illegal_opcodes: IllegalOpcodes,
+    power_up: PowerUpState,
+    debug: bool,
+    trace: Option<TraceSink>,
+    on_illegal_opcode: Option<IllegalOpcodeHandler>,
+}
+
+impl<T: AddressBusIO<u16, u8>> Builder<T> {
+    pub fn new(bus: T) -> Builder<T> {
+        Builder {
+            bus,
+            variant: Variant::Nmos,
+            quirks: Quirks::of(Variant::Nmos),
+            dummy_accesses: false,
+            accurate_interrupts: false,
+            illegal_opcodes: IllegalOpcodes::Jam,
+            power_up: PowerUpState::new(),
+            debug: false,
+            trace: None,
+            on_illegal_opcode: None,
+        }
+    }
+
+    // also sets the quirks of the variant, quirks can be changed after it
+    pub fn variant(mut self, variant: Variant) -> Builder<T> {
+        self.variant = variant;
+        self.quirks = Quirks::of(variant);
+        self
+    }
+
+    pub fn quirks(mut self, quirks: Quirks) -> Builder<T> {
+        self.quirks = quirks;
+        self
+    }
+
+    // the spurious bus accesses of the nmos chip under step too: the reads
+    // from the unfixed address of the indexed modes and the write of the old
+    // value before the new one by the read modify writes, for the memory
+    // mapped registers that react to them
+    pub fn dummy_accesses(mut self, enable: bool) -> Builder<T> {
+        self.dummy_accesses = enable;
+        self
+    }
+
+    // the irq and nmi polled on the last cycle of each instruction: cli, sei
+    // and plp act an instruction late; under tick a branch taken within its
+    // page delays the interrupt by an instruction and an nmi can take over a
+    // brk or an irq already under way
+    pub fn accurate_interrupts(mut self, enable: bool) -> Builder<T> {
+        self.accurate_interrupts = enable;
+        self
+    }
+
+    pub fn illegal_opcodes(mut self, policy: IllegalOpcodes) -> Builder<T> {
+        self.illegal_opcodes = policy;
+        self
+    }
+
+    // sets the Callback policy
+    pub fn on_illegal_opcode<F: FnMut(u16, u8) + Send + Sync + 'static>(
+        mut self,
+        handler: F,
+    ) -> Builder<T> {
+        self.illegal_opcodes = IllegalOpcodes::Callback;
+        self.on_illegal_opcode = Some(Box::new(handler));
+        self
+    }
+
+    pub fn registers(mut self, registers: Registers) -> Builder<T> {
+        self.power_up.registers = registers;
+        self
+    }
+
+    pub fn pc(mut self, pc: u16) -> Builder<T> {
+        self.power_up.registers.pc = pc;
+        self
+    }
+
+    // its registers replace the ones given so far, its memory fill is done by
+    // build and again by each power_on
+    pub fn power_up(mut self, power_up: PowerUpState) -> Builder<T> {
+        self.power_up = power_up;
+        self
+    }
+
+    pub fn debug(mut self, debug: bool) -> Builder<T> {
+        self.debug = debug;
+        self
+    }
+
+    // gets the address and the debug line of every instruction, turns debug on
+    pub fn trace<F: FnMut(u16, &str) + Send + Sync + 'static>(mut self, sink: F) -> Builder<T> {
+        self.debug = true;
+        self.trace = Some(Box::new(sink));
+        self
+    }
+
+    pub fn build(self) -> MOS6502<T> {
+        let mut cpu = MOS6502::new(self.bus);
+        cpu.variant = self.variant;
+        cpu.quirks = self.quirks;
+        cpu.dummy_accesses = self.dummy_accesses;
+        cpu.accurate_interrupts = self.accurate_interrupts;
+        cpu.illegal_opcodes = self.illegal_opcodes;
+        if self.variant == Variant::Mos6510 {
+            cpu.port = Some(ProcessorPort::new());
+        }
+        // the cmos uses some of the undocumented nmos opcodes for its own
+        if self.variant.is_cmos() {
+            cpu.register_cmos_opcodes();
+        } else if self.illegal_opcodes == IllegalOpcodes::Execute {
+            cpu.register_illegal_opcodes();
+        }
+        cpu.power_up = self.power_up;
+        cpu.apply_power_up();
+        cpu.debug = self.debug;
+        cpu.trace = self.trace;
+        cpu.on_illegal_opcode = self.on_illegal_opcode;
+        cpu
+    }
+}
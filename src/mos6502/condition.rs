@@ -0,0 +1,278 @@
+use alloc::boxed::Box;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+
+use mos6502::{Register, CARRY, DECIMAL, INTERRUPT, MOS6502, OVERFLOW, SIGN, ZERO};
+use AddressBusIO;
+
+// from the loosest to the tightest, as in rust so [$2002] & $80 != 0 masks
+// before comparing
+const LEVELS: [&[&str]; 8] = [
+    &["||"],
+    &["&&"],
+    &["==", "!=", "<=", ">=", "<", ">"],
+    &["|"],
+    &["^"],
+    &["&"],
+    &["<<", ">>"],
+    &["+", "-"],
+];
+const FACTORS: [&str; 3] = ["*", "/", "%"];
+
+// the two character ones first
+const OPERATORS: [&str; 21] = [
+    "||", "&&", "==", "!=", "<=", ">=", "<<", ">>", "<", ">", "|", "^", "&", "+", "-", "*", "/",
+    "%", "!", "~", "=",
+];
+
+#[derive(Clone, PartialEq, Debug)]
+enum Token {
+    Number(i64),
+    Name(String),
+    Operator(&'static str),
+    Open(char),
+    Close(char),
+}
+
+#[derive(Clone, PartialEq, Debug)]
+enum Expr {
+    Number(i64),
+    Register(Register),
+    Flag(u8),
+    // the byte at the address, peeked so no guard or watchpoint sees it
+    Memory(Box<Expr>),
+    Unary(&'static str, Box<Expr>),
+    Binary(&'static str, Box<Expr>, Box<Expr>),
+}
+
+// an expression on the registers, the flags (n v d i z c) and the memory
+// ([address]), true when not 0; numbers are decimal, $ff or 0xff
+#[derive(Clone, PartialEq, Debug)]
+pub struct Condition {
+    source: String,
+    expr: Expr,
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+impl Condition {
+    pub fn parse(source: &str) -> Result<Condition, String> {
+        let mut parser = Parser {
+            tokens: tokenize(source)?,
+            position: 0,
+        };
+        let expr = parser.expression(0)?;
+        if let Some(token) = parser.tokens.get(parser.position) {
+            return Err(format!("unexpected {:?}", token));
+        }
+        Ok(Condition {
+            source: source.trim().to_string(),
+            expr,
+        })
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn evaluate<T: AddressBusIO<u16, u8>>(&self, cpu: &mut MOS6502<T>) -> i64 {
+        evaluate(&self.expr, cpu)
+    }
+
+    pub fn test<T: AddressBusIO<u16, u8>>(&self, cpu: &mut MOS6502<T>) -> bool {
+        self.evaluate(cpu) != 0
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '(' || c == '[' {
+            tokens.push(Token::Open(c));
+            i += 1;
+        } else if c == ')' || c == ']' {
+            tokens.push(Token::Close(c));
+            i += 1;
+        } else if c == '$' || c.is_ascii_alphanumeric() || c == '_' {
+            let start = i;
+            i += 1;
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            let word: String = chars[start..i].iter().collect();
+            tokens.push(if c == '$' || c.is_ascii_digit() {
+                Token::Number(number(&word)?)
+            } else {
+                Token::Name(word.to_ascii_lowercase())
+            });
+        } else {
+            let rest: String = chars[i..chars.len().min(i + 2)].iter().collect();
+            match OPERATORS
+                .iter()
+                .find(|operator| rest.starts_with(**operator))
+            {
+                // a lone = is taken for ==
+                Some(&"=") => {
+                    tokens.push(Token::Operator("=="));
+                    i += 1;
+                }
+                Some(&operator) => {
+                    tokens.push(Token::Operator(operator));
+                    i += operator.len();
+                }
+                None => return Err(format!("unexpected character {}", c)),
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+fn number(word: &str) -> Result<i64, String> {
+    let result = if let Some(hex) = word.strip_prefix('$') {
+        i64::from_str_radix(hex, 16)
+    } else if let Some(hex) = word.strip_prefix("0x") {
+        i64::from_str_radix(hex, 16)
+    } else if let Some(binary) = word.strip_prefix("0b") {
+        i64::from_str_radix(binary, 2)
+    } else {
+        word.parse()
+    };
+    result.map_err(|_| format!("invalid number {}", word))
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn operator(&self, operators: &[&str]) -> Option<&'static str> {
+        match self.tokens.get(self.position) {
+            Some(&Token::Operator(operator)) if operators.contains(&operator) => Some(operator),
+            _ => None,
+        }
+    }
+
+    fn expression(&mut self, level: usize) -> Result<Expr, String> {
+        let operators = LEVELS.get(level).copied().unwrap_or(&FACTORS[..]);
+        let next = |parser: &mut Parser| {
+            if level < LEVELS.len() {
+                parser.expression(level + 1)
+            } else {
+                parser.unary()
+            }
+        };
+        let mut left = next(self)?;
+        while let Some(operator) = self.operator(operators) {
+            self.position += 1;
+            let right = next(self)?;
+            left = Expr::Binary(operator, Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        if let Some(operator) = self.operator(&["!", "~", "-"]) {
+            self.position += 1;
+            return Ok(Expr::Unary(operator, Box::new(self.unary()?)));
+        }
+        match self.next() {
+            Some(Token::Number(value)) => Ok(Expr::Number(value)),
+            Some(Token::Name(name)) => name_expr(&name),
+            Some(Token::Open(open)) => {
+                let inner = self.expression(0)?;
+                let close = if open == '(' { ')' } else { ']' };
+                if self.next() != Some(Token::Close(close)) {
+                    return Err(format!("missing {}", close));
+                }
+                Ok(if open == '(' {
+                    inner
+                } else {
+                    Expr::Memory(Box::new(inner))
+                })
+            }
+            Some(token) => Err(format!("unexpected {:?}", token)),
+            None => Err("unexpected end".to_string()),
+        }
+    }
+}
+
+fn name_expr(name: &str) -> Result<Expr, String> {
+    let flag = match name {
+        "n" => SIGN,
+        "v" => OVERFLOW,
+        "d" => DECIMAL,
+        "i" => INTERRUPT,
+        "z" => ZERO,
+        "c" => CARRY,
+        _ => 0,
+    };
+    if flag != 0 {
+        return Ok(Expr::Flag(flag));
+    }
+    Register::from_name(name)
+        .map(Expr::Register)
+        .ok_or_else(|| format!("unknown name {}", name))
+}
+
+fn evaluate<T: AddressBusIO<u16, u8>>(expr: &Expr, cpu: &mut MOS6502<T>) -> i64 {
+    match *expr {
+        Expr::Number(value) => value,
+        Expr::Register(register) => i64::from(cpu.get(register)),
+        Expr::Flag(flag) => i64::from(cpu.status & flag != 0),
+        Expr::Memory(ref address) => {
+            let address = evaluate(address, cpu) as u16;
+            i64::from(cpu.peek8(address))
+        }
+        Expr::Unary(operator, ref operand) => {
+            let value = evaluate(operand, cpu);
+            match operator {
+                "!" => i64::from(value == 0),
+                "~" => !value,
+                _ => value.wrapping_neg(),
+            }
+        }
+        Expr::Binary(operator, ref left, ref right) => {
+            let left = evaluate(left, cpu);
+            // both sides of && and || too, peeks have no side effects
+            let right = evaluate(right, cpu);
+            match operator {
+                "||" => i64::from(left != 0 || right != 0),
+                "&&" => i64::from(left != 0 && right != 0),
+                "==" => i64::from(left == right),
+                "!=" => i64::from(left != right),
+                "<=" => i64::from(left <= right),
+                ">=" => i64::from(left >= right),
+                "<" => i64::from(left < right),
+                ">" => i64::from(left > right),
+                "|" => left | right,
+                "^" => left ^ right,
+                "&" => left & right,
+                "<<" => left.wrapping_shl(right as u32),
+                ">>" => left.wrapping_shr(right as u32),
+                "+" => left.wrapping_add(right),
+                "-" => left.wrapping_sub(right),
+                "*" => left.wrapping_mul(right),
+                // 0 for a division by 0
+                "/" => left.checked_div(right).unwrap_or(0),
+                _ => left.checked_rem(right).unwrap_or(0),
+            }
+        }
+    }
+}
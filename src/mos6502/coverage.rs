@@ -0,0 +1,53 @@
+use alloc::vec::Vec;
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct SelfModification {
+    pub pc: u16,
+    pub address: u16,
+}
+
+// every byte fetched as part of an instruction (opcode and operands) is
+// marked as executed, writes landing on them are reported
+pub struct Coverage {
+    executed: Vec<bool>,
+    modifications: Vec<SelfModification>,
+}
+
+impl Default for Coverage {
+    fn default() -> Coverage {
+        Coverage::new()
+    }
+}
+
+impl Coverage {
+    pub fn new() -> Coverage {
+        Coverage {
+            executed: vec![false; 0x10000],
+            modifications: Vec::new(),
+        }
+    }
+
+    pub fn execute(&mut self, start: u16, length: u16) {
+        for offset in 0..length {
+            self.executed[usize::from(start.wrapping_add(offset))] = true;
+        }
+    }
+
+    pub fn is_executed(&self, address: u16) -> bool {
+        self.executed[usize::from(address)]
+    }
+
+    pub fn executed_count(&self) -> usize {
+        self.executed.iter().filter(|executed| **executed).count()
+    }
+
+    pub fn write(&mut self, pc: u16, address: u16) {
+        if self.executed[usize::from(address)] {
+            self.modifications.push(SelfModification { pc, address });
+        }
+    }
+
+    pub fn take_modifications(&mut self) -> Vec<SelfModification> {
+        self.modifications.split_off(0)
+    }
+}
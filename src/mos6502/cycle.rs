@@ -0,0 +1,603 @@
+use events::CpuEvent;
+use mos6502::{pushed, unpushed, Access, InterruptBreakpoint, BRK, DECIMAL, INTERRUPT, MOS6502};
+use AddressBusIO;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Index {
+    X,
+    Y,
+}
+
+// the bus cycles following the opcode fetch, one kind for each addressing
+// mode and for the instructions doing their own stack traffic
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Sequence {
+    Implied,
+    Immediate,
+    Zeropage,
+    ZeropageIndexed(Index),
+    Absolute,
+    AbsoluteIndexed(Index),
+    IndirectX,
+    IndirectY,
+    ZeropageIndirect,
+    // bbr and bbs, the zeropage byte tested then a branch
+    ZeropageRelative,
+    Branch,
+    Jump,
+    JumpIndirect,
+    // jmp ($xxxx) of the cmos, as jmp ($xxxx,x) without the index
+    CmosJumpIndirect,
+    JumpIndexedIndirect,
+    Jsr,
+    Rts,
+    Rti,
+    Push,
+    Pull,
+    // wai and stp, the three cycles of a push without its write
+    Wait,
+    // run by execute on the fetch, as step does, the cycles it takes after
+    // that spent reading the next opcode; the huc6280 ones and a few nops
+    Whole,
+    // the vector and the break bit of the pushed status, brk and the
+    // interrupts taken between instructions
+    Interrupt(u16, u8),
+}
+
+// where tick is within an instruction, cycle 0 is the fetch of the next one
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TickState {
+    cycle: u8,
+    sequence: Sequence,
+    // the cycle the operand access starts on, 0 until the address is known
+    resolved: u8,
+    // an address or a pointer being put together
+    base: u16,
+    // the cycles the operation counted itself, the decimal one of the cmos
+    // adc and sbc, done once the bus is through with the operand
+    extra: u32,
+    // the interrupt the last poll found, for accurate_interrupts
+    poll: Option<u16>,
+}
+
+impl TickState {
+    pub fn new() -> TickState {
+        TickState {
+            cycle: 0,
+            sequence: Sequence::Implied,
+            resolved: 0,
+            base: 0,
+            extra: 0,
+            poll: None,
+        }
+    }
+}
+
+impl Default for TickState {
+    fn default() -> TickState {
+        TickState::new()
+    }
+}
+
+// worked out once as the opcode is registered, brk runs as an implied
+// opcode when it is a code breakpoint
+pub fn sequence(name: &str, mode: &str, cmos: bool) -> Sequence {
+    match (name, mode) {
+        ("brk", _) => Sequence::Interrupt(0xfffe, BRK),
+        ("jsr", _) => Sequence::Jsr,
+        ("rts", _) => Sequence::Rts,
+        ("rti", _) => Sequence::Rti,
+        ("pha", _) | ("php", _) | ("phx", _) | ("phy", _) => Sequence::Push,
+        ("wai", _) | ("stp", _) => Sequence::Wait,
+        ("pla", _) | ("plp", _) | ("plx", _) | ("ply", _) => Sequence::Pull,
+        // the huc6280 ones
+        ("bsr", _)
+        | ("tam", _)
+        | ("tma", _)
+        | ("st0", _)
+        | ("st1", _)
+        | ("st2", _)
+        | ("sax", "implied")
+        | ("say", _)
+        | ("sxy", _)
+        | ("csl", _)
+        | ("csh", _)
+        | ("set", _) => Sequence::Whole,
+        (_, "immediate_zeropage")
+        | (_, "immediate_zeropage_x")
+        | (_, "immediate_absolute")
+        | (_, "immediate_absolute_x")
+        | (_, "block_transfer") => Sequence::Whole,
+        ("jmp", "indirect") if cmos => Sequence::CmosJumpIndirect,
+        ("jmp", "indirect") => Sequence::JumpIndirect,
+        ("jmp", "indirect_absolute_x") => Sequence::JumpIndexedIndirect,
+        ("jmp", _) => Sequence::Jump,
+        (_, "immediate") => Sequence::Immediate,
+        (_, "zeropage") => Sequence::Zeropage,
+        (_, "zeropage_x") => Sequence::ZeropageIndexed(Index::X),
+        (_, "zeropage_y") => Sequence::ZeropageIndexed(Index::Y),
+        (_, "absolute") => Sequence::Absolute,
+        (_, "absolute_x") => Sequence::AbsoluteIndexed(Index::X),
+        (_, "absolute_y") => Sequence::AbsoluteIndexed(Index::Y),
+        (_, "indirect_x") => Sequence::IndirectX,
+        (_, "indirect_y") => Sequence::IndirectY,
+        (_, "zeropage_indirect") => Sequence::ZeropageIndirect,
+        (_, "zeropage_relative") => Sequence::ZeropageRelative,
+        (_, "relative") => Sequence::Branch,
+        // implied, accumulator and the invalid opcodes
+        _ => Sequence::Implied,
+    }
+}
+
+// a driver doing a single bus cycle per call, in the order of the nmos chip
+// with its dummy reads and writes, for the devices that count cycles; the
+// interrupts are taken on the fetch of the next instruction, and the decode
+// cache, coverage, step diffs, idle detection and debug lines are left to step
+impl<T: AddressBusIO<u16, u8>> MOS6502<T> {
+    pub fn tick(&mut self) {
+        self.ticks += 1;
+        if self.halted || self.stopped || (self.waiting && !self.wake()) {
+            return;
+        }
+        if self.stall != 0 && !self.writes() {
+            self.stall -= 1;
+            return;
+        }
+        let cycle = self.tick_state.cycle;
+        if cycle == 0 {
+            self.fetch_cycle();
+            return;
+        }
+        self.tick_state.cycle = cycle + 1;
+        // the lines are polled as a cycle starts, the one of the last cycle
+        // decides
+        let masked = self.get_flag(INTERRUPT);
+        let poll = self.pending_interrupt(masked);
+        let resolved = self.tick_state.resolved;
+        let done = if self.tick_state.extra != 0 {
+            // a read of the next opcode
+            let pc = self.pc;
+            self.dummy_read8(pc);
+            self.tick_state.extra -= 1;
+            self.tick_state.extra == 0
+        } else if resolved != 0 && cycle >= resolved {
+            self.access_cycle(cycle - resolved)
+        } else {
+            self.sequence_cycle(cycle)
+        };
+        // but a branch taken within its page skips the poll of its last cycle
+        let skipped = done && cycle == 2 && self.tick_state.sequence == Sequence::Branch;
+        if self.accurate_interrupts && !skipped {
+            self.tick_state.poll = poll;
+            if done {
+                self.interrupt_mask = masked;
+            }
+        }
+        if done {
+            self.retire(cycle + 1);
+        }
+    }
+
+    // whether tick stopped in the middle of an instruction
+    pub fn in_instruction(&self) -> bool {
+        self.tick_state.cycle != 0
+    }
+
+    // the cycles left of the instruction tick was in
+    pub fn finish_instruction(&mut self) -> u64 {
+        let ticks = self.ticks;
+        while self.in_instruction() && !self.halted {
+            self.tick();
+        }
+        self.ticks - ticks
+    }
+
+    // whether the coming cycle is a write, rdy does not hold those
+    fn writes(&self) -> bool {
+        let (cycle, resolved) = (self.tick_state.cycle, self.tick_state.resolved);
+        if cycle == 0 {
+            return false;
+        }
+        if resolved != 0 && cycle >= resolved {
+            return match self.opcode.access {
+                Access::Read => false,
+                Access::Write => true,
+                Access::Modify => cycle > resolved,
+            };
+        }
+        matches!(
+            (self.tick_state.sequence, cycle),
+            (Sequence::Push, 2) | (Sequence::Jsr, 3..=4) | (Sequence::Interrupt(..), 2..=4)
+        )
+    }
+
+    fn fetch_cycle(&mut self) {
+        self.debug_pc = self.pc;
+        let vector = if self.accurate_interrupts {
+            self.tick_state.poll
+        } else {
+            self.pending_interrupt(self.get_flag(INTERRUPT))
+        };
+        if vector == Some(0xfffa) {
+            self.nmi_pending = false;
+        }
+        let pc = self.pc;
+        let sequence = match vector {
+            // the opcode is read but dropped
+            Some(vector) => {
+                self.dummy_read8(pc);
+                Sequence::Interrupt(vector, 0)
+            }
+            None => {
+                let opcode = self.fetch8();
+                self.current_opcode = opcode;
+                self.opcode = self.lookup(opcode);
+                match self.opcode.sequence {
+                    Sequence::Interrupt(..) if self.code_breakpoint => Sequence::Implied,
+                    sequence => sequence,
+                }
+            }
+        };
+        let extra = if sequence == Sequence::Whole {
+            let ticks = self.ticks;
+            (self.opcode.execute)(self);
+            let cycles = self.ticks - ticks + u64::from(self.opcode.cycles);
+            self.ticks = ticks;
+            (cycles - 1) as u32
+        } else {
+            0
+        };
+        self.tick_state = TickState {
+            cycle: 1,
+            sequence,
+            resolved: 0,
+            base: 0,
+            extra,
+            poll: None,
+        };
+        if sequence == Sequence::Whole && extra == 0 {
+            self.retire(1);
+        }
+    }
+
+    fn sequence_cycle(&mut self, cycle: u8) -> bool {
+        let (pc, base) = (self.pc, self.tick_state.base);
+        match (self.tick_state.sequence, cycle) {
+            (Sequence::Implied, _) => {
+                self.dummy_read8(pc);
+                if self.opcode.accumulator {
+                    self.value = self.a;
+                }
+                self.operate()
+            }
+            (Sequence::Immediate, _) => {
+                self.value = self.fetch8();
+                self.operate()
+            }
+            (Sequence::Zeropage, _) => {
+                let offset = self.fetch8();
+                self.addr = self.zeropage_address(offset);
+                self.resolve()
+            }
+            (Sequence::ZeropageIndexed(_), 1)
+            | (Sequence::IndirectX, 1)
+            | (Sequence::IndirectY, 1)
+            | (Sequence::ZeropageIndirect, 1)
+            | (Sequence::ZeropageRelative, 1) => {
+                let offset = self.fetch8();
+                self.tick_state.base = self.zeropage_address(offset);
+                false
+            }
+            (Sequence::Absolute, 1)
+            | (Sequence::AbsoluteIndexed(_), 1)
+            | (Sequence::Jump, 1)
+            | (Sequence::JumpIndirect, 1)
+            | (Sequence::CmosJumpIndirect, 1)
+            | (Sequence::JumpIndexedIndirect, 1)
+            | (Sequence::Jsr, 1) => {
+                self.tick_state.base = u16::from(self.fetch8());
+                false
+            }
+            (Sequence::ZeropageIndexed(index), _) => {
+                // the index is added while the unindexed address is read
+                self.dummy_read8(base);
+                let index = self.index(index);
+                self.addr = self.zeropage_address((base as u8).wrapping_add(index));
+                self.resolve()
+            }
+            (Sequence::Absolute, _) => {
+                self.addr = self.fetch_high();
+                self.resolve()
+            }
+            (Sequence::AbsoluteIndexed(_), 2)
+            | (Sequence::JumpIndirect, 2)
+            | (Sequence::CmosJumpIndirect, 2)
+            | (Sequence::JumpIndexedIndirect, 2) => {
+                self.tick_state.base = self.fetch_high();
+                false
+            }
+            (Sequence::AbsoluteIndexed(index), _) => {
+                let index = self.index(index);
+                self.indexed(base, index)
+            }
+            (Sequence::IndirectX, 2) => {
+                self.dummy_read8(base);
+                self.tick_state.base = self.zeropage_address((base as u8).wrapping_add(self.x));
+                false
+            }
+            (Sequence::IndirectX, 3)
+            | (Sequence::IndirectY, 2)
+            | (Sequence::ZeropageIndirect, 2)
+            | (Sequence::CmosJumpIndirect, 4)
+            | (Sequence::JumpIndexedIndirect, 4) => {
+                self.addr = u16::from(self.read8(base));
+                false
+            }
+            (Sequence::IndirectX, _) | (Sequence::ZeropageIndirect, _) => {
+                let high = self.read8(self.zeropage_address((base as u8).wrapping_add(1)));
+                self.addr |= u16::from(high) << 8;
+                self.resolve()
+            }
+            (Sequence::IndirectY, 3) => {
+                let high = self.read8(self.zeropage_address((base as u8).wrapping_add(1)));
+                self.tick_state.base = (u16::from(high) << 8) | self.addr;
+                false
+            }
+            (Sequence::IndirectY, _) => {
+                let y = self.y;
+                self.indexed(base, y)
+            }
+            (Sequence::ZeropageRelative, 2) => {
+                self.value = self.read8(base);
+                false
+            }
+            (Sequence::ZeropageRelative, 3) => {
+                self.dummy_read8(base);
+                false
+            }
+            (Sequence::Branch, 1) | (Sequence::ZeropageRelative, 4) => {
+                let offset = self.fetch8() as i8;
+                let (pc, ticks) = (self.pc, self.ticks);
+                self.addr = pc.wrapping_add(offset as u16);
+                // the operation counts the cycles of a taken branch and moves
+                // pc, it is moved again once those cycles are done
+                (self.opcode.operation)(self);
+                let taken = self.ticks != ticks;
+                self.pc = pc;
+                self.ticks = ticks;
+                !taken
+            }
+            (Sequence::Branch, 2) | (Sequence::ZeropageRelative, 5) => {
+                self.dummy_read8(pc);
+                if pc >> 8 == self.addr >> 8 {
+                    self.pc = self.addr;
+                    return true;
+                }
+                false
+            }
+            (Sequence::Branch, _) | (Sequence::ZeropageRelative, _) => {
+                // still in the page of the next instruction
+                self.dummy_read8((pc & 0xff00) | (self.addr & 0x00ff));
+                self.pc = self.addr;
+                true
+            }
+            (Sequence::Jump, _) => {
+                self.pc = self.fetch_high();
+                true
+            }
+            (Sequence::CmosJumpIndirect, 3) => {
+                self.dummy_read8(pc);
+                false
+            }
+            (Sequence::JumpIndexedIndirect, 3) => {
+                self.dummy_read8(pc);
+                self.tick_state.base = base.wrapping_add(u16::from(self.x));
+                false
+            }
+            (Sequence::CmosJumpIndirect, _) | (Sequence::JumpIndexedIndirect, _) => {
+                let high = self.read8(base.wrapping_add(1));
+                self.pc = (u16::from(high) << 8) | self.addr;
+                true
+            }
+            (Sequence::JumpIndirect, 3) => {
+                self.addr = u16::from(self.read8(base));
+                false
+            }
+            (Sequence::JumpIndirect, _) => {
+                let next = if self.quirks.indirect_jump_wrap {
+                    (base & 0xff00) | (base.wrapping_add(1) & 0x00ff)
+                } else {
+                    base.wrapping_add(1)
+                };
+                let high = self.read8(next);
+                self.pc = (u16::from(high) << 8) | self.addr;
+                true
+            }
+            (Sequence::Push, 1)
+            | (Sequence::Pull, 1)
+            | (Sequence::Wait, 1)
+            | (Sequence::Rts, 1)
+            | (Sequence::Rti, 1) => {
+                self.dummy_read8(pc);
+                false
+            }
+            (Sequence::Pull, 2) | (Sequence::Rts, 2) | (Sequence::Rti, 2) | (Sequence::Jsr, 2) => {
+                let sp = self.stack_address();
+                self.dummy_read8(sp);
+                false
+            }
+            (Sequence::Push, _) | (Sequence::Pull, _) | (Sequence::Wait, _) => self.operate(),
+            // all of it spent as extra cycles
+            (Sequence::Whole, _) => true,
+            (Sequence::Jsr, 3) | (Sequence::Interrupt(..), 2) => {
+                self.push8((pc >> 8) as u8);
+                false
+            }
+            (Sequence::Jsr, 4) | (Sequence::Interrupt(..), 3) => {
+                self.push8(pc as u8);
+                if let Some(ref mut guard) = self.stack_guard {
+                    guard.return_address_pushed(self.sp);
+                }
+                false
+            }
+            (Sequence::Jsr, _) => {
+                self.pc = self.fetch_high();
+                true
+            }
+            (Sequence::Rts, 3) | (Sequence::Rti, 4) => {
+                self.tick_state.base = u16::from(self.pull8());
+                false
+            }
+            (Sequence::Rts, 4) => {
+                let high = self.pull8();
+                self.pc = (u16::from(high) << 8) | base;
+                false
+            }
+            (Sequence::Rts, _) => {
+                self.dummy_read8(pc);
+                self.pc = pc.wrapping_add(1);
+                true
+            }
+            (Sequence::Rti, 3) => {
+                self.status = unpushed(self.pull8());
+                false
+            }
+            (Sequence::Rti, _) => {
+                let high = self.pull8();
+                self.pc = (u16::from(high) << 8) | base;
+                true
+            }
+            (Sequence::Interrupt(_, brk), 1) => {
+                // the byte after a brk is skipped, rti returns past it
+                if brk != 0 {
+                    self.fetch8();
+                } else {
+                    self.dummy_read8(pc);
+                }
+                false
+            }
+            (Sequence::Interrupt(_, brk), 4) => {
+                let status = pushed(self.status, brk);
+                self.push8(status);
+                self.set_flag(INTERRUPT, true);
+                if self.quirks.clear_decimal_on_interrupt {
+                    self.set_flag(DECIMAL, false);
+                }
+                false
+            }
+            (Sequence::Interrupt(vector, brk), 5) => {
+                // an nmi coming before the vector is fetched takes over a brk
+                // or an irq, the break bit pushed stays
+                let vector = if self.accurate_interrupts && vector != 0xfffa && self.nmi_pending {
+                    self.nmi_pending = false;
+                    self.tick_state.sequence = Sequence::Interrupt(0xfffa, brk);
+                    0xfffa
+                } else {
+                    vector
+                };
+                let vector = self.vector(vector, brk);
+                self.addr = u16::from(self.read8(vector));
+                false
+            }
+            (Sequence::Interrupt(vector, brk), _) => {
+                let high = self.read8(self.vector(vector, brk).wrapping_add(1));
+                self.addr |= u16::from(high) << 8;
+                self.pc = self.addr;
+                let kind = match (vector, brk) {
+                    (0xfffa, _) => InterruptBreakpoint::Nmi,
+                    (_, 0) => InterruptBreakpoint::Irq,
+                    _ => InterruptBreakpoint::Brk,
+                };
+                self.interrupt_breakpoint(kind);
+                true
+            }
+        }
+    }
+
+    // step is the number of cycles since the address was known
+    fn access_cycle(&mut self, step: u8) -> bool {
+        let addr = self.addr;
+        match (self.opcode.access, step) {
+            (Access::Read, _) => {
+                self.value = self.read8(addr);
+                self.operate()
+            }
+            (Access::Modify, 0) => {
+                self.value = self.read8(addr);
+                false
+            }
+            (Access::Modify, 1) => {
+                let value = self.value;
+                self.dummy_write8(addr, value);
+                false
+            }
+            (Access::Write, _) | (Access::Modify, _) => self.operate(),
+        }
+    }
+
+    // the operand is accessed from the next cycle on
+    fn resolve(&mut self) -> bool {
+        self.tick_state.resolved = self.tick_state.cycle;
+        false
+    }
+
+    // the address without the carry into the high byte is read first, it is
+    // the operand already when no page is crossed, and the opcodes paying for
+    // the crossing only (the reads, the shifts of the cmos) go on from there
+    fn indexed(&mut self, base: u16, index: u8) -> bool {
+        self.addr = base.wrapping_add(u16::from(index));
+        let partial = (base & 0xff00) | (self.addr & 0x00ff);
+        if partial == self.addr && self.opcode.page_penalty {
+            self.tick_state.resolved = self.tick_state.cycle - 1;
+            return self.access_cycle(0);
+        }
+        self.dummy_read8(partial);
+        self.resolve()
+    }
+
+    fn index(&self, index: Index) -> u8 {
+        match index {
+            Index::X => self.x,
+            Index::Y => self.y,
+        }
+    }
+
+    fn fetch8(&mut self) -> u8 {
+        let pc = self.advance_pc();
+        self.read8(pc)
+    }
+
+    // the high byte of the operand, the low one is in the base
+    fn fetch_high(&mut self) -> u16 {
+        let high = self.fetch8();
+        (u16::from(high) << 8) | self.tick_state.base
+    }
+
+    // the cycles are counted by tick, not by the operation: the ones it adds
+    // are spent as cycles of their own, done once they are
+    fn operate(&mut self) -> bool {
+        let ticks = self.ticks;
+        (self.opcode.operation)(self);
+        self.tick_state.extra = (self.ticks - ticks) as u32;
+        self.ticks = ticks;
+        self.tick_state.extra == 0
+    }
+
+    fn retire(&mut self, cycles: u8) {
+        let sequence = self.tick_state.sequence;
+        self.tick_state.cycle = 0;
+        if let Some(ref mut guard) = self.uninitialized_guard {
+            if guard.commit() && guard.trap {
+                self.requested_code_breakpoint = true;
+            }
+        }
+        if let Sequence::Interrupt(_, 0) = sequence {
+            return;
+        }
+        self.observers.emit(&CpuEvent::InstructionRetired {
+            pc: self.debug_pc,
+            opcode: u16::from(self.current_opcode),
+            cycles: u64::from(cycles),
+        });
+    }
+}
@@ -0,0 +1,100 @@
+use alloc::vec::Vec;
+
+// longest 6502 instruction, opcode and two operand bytes
+const MAX_LENGTH: usize = 3;
+
+#[derive(Clone, Copy)]
+struct Entry {
+    bytes: [u8; MAX_LENGTH],
+    length: u8,
+    complete: bool,
+}
+
+const EMPTY: Entry = Entry {
+    bytes: [0; MAX_LENGTH],
+    length: 0,
+    complete: false,
+};
+
+// the bytes of every executed instruction keyed by the address of its opcode,
+// replayed instructions are not fetched from the bus again until a write (or
+// an explicit invalidation, for dma and bank switching) lands on them
+pub struct DecodeCache {
+    entries: Vec<Entry>,
+    hits: u64,
+    misses: u64,
+}
+
+impl Default for DecodeCache {
+    fn default() -> DecodeCache {
+        DecodeCache::new()
+    }
+}
+
+impl DecodeCache {
+    pub fn new() -> DecodeCache {
+        DecodeCache {
+            entries: vec![EMPTY; 0x10000],
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+
+    // a new instruction at start, fetched from the bus if not complete yet
+    pub fn begin(&mut self, start: u16) {
+        let entry = &mut self.entries[usize::from(start)];
+        if entry.complete {
+            self.hits += 1;
+        } else {
+            *entry = EMPTY;
+            self.misses += 1;
+        }
+    }
+
+    pub fn fetch(&self, start: u16, address: u16) -> Option<u8> {
+        let entry = &self.entries[usize::from(start)];
+        let offset = usize::from(address.wrapping_sub(start));
+        if entry.complete && offset < usize::from(entry.length) {
+            Some(entry.bytes[offset])
+        } else {
+            None
+        }
+    }
+
+    pub fn record(&mut self, start: u16, address: u16, byte: u8) {
+        let entry = &mut self.entries[usize::from(start)];
+        let offset = usize::from(address.wrapping_sub(start));
+        if !entry.complete && offset < MAX_LENGTH {
+            entry.bytes[offset] = byte;
+            entry.length = entry.length.max(offset as u8 + 1);
+        }
+    }
+
+    // an instruction modifying itself (length 0 after the invalidation) is
+    // fetched again the next time
+    pub fn commit(&mut self, start: u16) {
+        let entry = &mut self.entries[usize::from(start)];
+        entry.complete = entry.length > 0;
+    }
+
+    // every instruction overlapping address
+    pub fn write(&mut self, address: u16) {
+        for offset in 0..MAX_LENGTH as u16 {
+            self.entries[usize::from(address.wrapping_sub(offset))] = EMPTY;
+        }
+    }
+
+    pub fn invalidate(&mut self, start: u16, end: u16) {
+        for address in start..=end {
+            self.write(address);
+        }
+    }
+}
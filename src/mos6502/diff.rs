@@ -0,0 +1,100 @@
+use alloc::vec::Vec;
+use core::fmt;
+
+const REGISTERS: [&str; 4] = ["A", "X", "Y", "SP"];
+const FLAGS: [(u8, char); 8] = [
+    (0x80, 'S'),
+    (0x40, 'V'),
+    (0x20, '-'),
+    (0x10, 'B'),
+    (0x08, 'D'),
+    (0x04, 'I'),
+    (0x02, 'Z'),
+    (0x01, 'C'),
+];
+
+// what a single instruction did: registers whose value changed (name, before,
+// after), status bits it set or cleared and every bus write in order
+#[derive(Clone, PartialEq)]
+pub struct StepDiff {
+    pub pc: u16,
+    pub next_pc: u16,
+    pub registers: Vec<(&'static str, u8, u8)>,
+    pub flags_set: u8,
+    pub flags_cleared: u8,
+    pub writes: Vec<(u16, u8)>,
+}
+
+impl fmt::Display for StepDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[{:04X}] -> {:04X}", self.pc, self.next_pc)?;
+        for &(name, before, after) in &self.registers {
+            write!(f, " {}:{:02X}->{:02X}", name, before, after)?;
+        }
+        for &(mask, name) in FLAGS.iter() {
+            if self.flags_set & mask != 0 {
+                write!(f, " +{}", name)?;
+            }
+            if self.flags_cleared & mask != 0 {
+                write!(f, " -{}", name)?;
+            }
+        }
+        for &(address, value) in &self.writes {
+            write!(f, " ${:04X}={:02X}", address, value)?;
+        }
+        Ok(())
+    }
+}
+
+// state is [a, x, y, sp, status] as in the idle detector
+pub struct StepRecorder {
+    before: [u8; 5],
+    writes: Vec<(u16, u8)>,
+    diff: Option<StepDiff>,
+}
+
+impl Default for StepRecorder {
+    fn default() -> StepRecorder {
+        StepRecorder::new()
+    }
+}
+
+impl StepRecorder {
+    pub fn new() -> StepRecorder {
+        StepRecorder {
+            before: [0; 5],
+            writes: Vec::new(),
+            diff: None,
+        }
+    }
+
+    pub fn begin(&mut self, state: [u8; 5]) {
+        self.before = state;
+        self.writes.clear();
+    }
+
+    pub fn write(&mut self, address: u16, value: u8) {
+        self.writes.push((address, value));
+    }
+
+    pub fn end(&mut self, pc: u16, next_pc: u16, state: [u8; 5]) {
+        let mut registers = Vec::new();
+        for (i, name) in REGISTERS.iter().enumerate() {
+            if self.before[i] != state[i] {
+                registers.push((*name, self.before[i], state[i]));
+            }
+        }
+        self.diff = Some(StepDiff {
+            pc,
+            next_pc,
+            registers,
+            flags_set: !self.before[4] & state[4],
+            flags_cleared: self.before[4] & !state[4],
+            writes: self.writes.split_off(0),
+        });
+    }
+
+    pub fn take_diff(&mut self) -> Option<StepDiff> {
+        self.diff.take()
+    }
+}
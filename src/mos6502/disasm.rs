@@ -0,0 +1,112 @@
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+
+use mos6502::opcodes;
+use AddressBusIO;
+
+// an instruction of a listing, the undocumented opcodes and the ones cut
+// short by the end of the input are single .byte lines
+#[derive(Clone, PartialEq, Debug)]
+pub struct Line {
+    pub address: u16,
+    bytes: [u8; 3],
+    length: u8,
+    pub text: String,
+}
+
+impl Line {
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes[..usize::from(self.length)]
+    }
+}
+
+impl fmt::Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut raw = String::new();
+        for (index, byte) in self.bytes().iter().enumerate() {
+            if index > 0 {
+                raw.push(' ');
+            }
+            raw.push_str(&format!("{:02X}", byte));
+        }
+        write!(f, "${:04X}: {:<8}  {}", self.address, raw, self.text)
+    }
+}
+
+// the operands in the syntax of asm, relative targets resolved from address;
+// the modes of the cmos and of the huc6280 too
+pub fn text(address: u16, opcode: u8, mnemonic: &str, mode: &str, operands: &[u8]) -> String {
+    let byte = |index: usize| operands.get(index).copied().unwrap_or(0);
+    let word = |index: usize| u16::from(byte(index + 1)) << 8 | u16::from(byte(index));
+    let (low, high) = (byte(0), byte(1));
+    match mode {
+        "implied" | "accumulator" => mnemonic.to_string(),
+        "immediate" => format!("{} #${:02X}", mnemonic, low),
+        "zeropage" => format!("{} ${:02X}", mnemonic, low),
+        "zeropage_x" => format!("{} ${:02X},X", mnemonic, low),
+        "zeropage_y" => format!("{} ${:02X},Y", mnemonic, low),
+        "indirect_x" => format!("{} (${:02X},X)", mnemonic, low),
+        "indirect_y" => format!("{} (${:02X}),Y", mnemonic, low),
+        "zeropage_indirect" => format!("{} (${:02X})", mnemonic, low),
+        "relative" => {
+            let target = address.wrapping_add(2).wrapping_add(low as i8 as u16);
+            format!("{} ${:04X}", mnemonic, target)
+        }
+        "zeropage_relative" => {
+            let target = address.wrapping_add(3).wrapping_add(high as i8 as u16);
+            format!("{} ${:02X},${:04X}", mnemonic, low, target)
+        }
+        "absolute" => format!("{} ${:04X}", mnemonic, word(0)),
+        "absolute_x" => format!("{} ${:04X},X", mnemonic, word(0)),
+        "absolute_y" => format!("{} ${:04X},Y", mnemonic, word(0)),
+        "indirect" => format!("{} (${:04X})", mnemonic, word(0)),
+        "indirect_absolute_x" => format!("{} (${:04X},X)", mnemonic, word(0)),
+        "immediate_zeropage" => format!("{} #${:02X},${:02X}", mnemonic, low, high),
+        "immediate_zeropage_x" => format!("{} #${:02X},${:02X},X", mnemonic, low, high),
+        "immediate_absolute" => format!("{} #${:02X},${:04X}", mnemonic, low, word(1)),
+        "immediate_absolute_x" => format!("{} #${:02X},${:04X},X", mnemonic, low, word(1)),
+        "block_transfer" => format!(
+            "{} ${:04X},${:04X},${:04X}",
+            mnemonic,
+            word(0),
+            word(2),
+            word(4)
+        ),
+        _ => format!(".byte ${:02X}", opcode),
+    }
+}
+
+// the documented nmos opcodes of bytes, the first one at origin
+pub fn disassemble(bytes: &[u8], origin: u16) -> Vec<Line> {
+    let mut lines = Vec::new();
+    let mut offset = 0;
+    while offset < bytes.len() {
+        let address = origin.wrapping_add(offset as u16);
+        let info = opcodes::info(bytes[offset]);
+        let decoded = info.legal && offset + usize::from(info.length) <= bytes.len();
+        let length = if decoded { info.length } else { 1 };
+        let mut raw = [0; 3];
+        raw[..usize::from(length)].copy_from_slice(&bytes[offset..offset + usize::from(length)]);
+        let text = if decoded {
+            text(address, info.opcode, info.mnemonic, info.mode, &raw[1..])
+        } else {
+            format!(".byte ${:02X}", info.opcode)
+        };
+        lines.push(Line {
+            address,
+            bytes: raw,
+            length,
+            text,
+        });
+        offset += usize::from(length);
+    }
+    lines
+}
+
+// from start to end included, read through the bus so the io registers in it
+// see the reads
+pub fn disassemble_range<T: AddressBusIO<u16, u8>>(bus: &mut T, start: u16, end: u16) -> Vec<Line> {
+    let bytes: Vec<u8> = (start..=end).map(|address| bus.read(address)).collect();
+    disassemble(&bytes, start)
+}
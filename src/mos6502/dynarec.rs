@@ -0,0 +1,456 @@
+extern crate cranelift_codegen;
+extern crate cranelift_frontend;
+extern crate cranelift_jit;
+extern crate cranelift_module;
+
+use self::cranelift_codegen::ir::condcodes::IntCC;
+use self::cranelift_codegen::ir::{types, AbiParam, InstBuilder, MemFlagsData, Value};
+use self::cranelift_codegen::Context;
+use self::cranelift_frontend::{FunctionBuilder, FunctionBuilderContext, Variable};
+use self::cranelift_jit::{JITBuilder, JITModule};
+use self::cranelift_module::{default_libcall_names, Module};
+
+use std::collections::{HashMap, HashSet};
+use std::mem;
+
+use mos6502::{CARRY, DECIMAL, INTERRUPT, MOS6502, OVERFLOW, SIGN, ZERO};
+use {AddressBusIO, Clock};
+
+// instructions compiled at most in one block
+const MAX_BLOCK: usize = 32;
+// a block rewritten this many times is left to the interpreter
+const MAX_RECOMPILES: u32 = 4;
+
+// a, x, y, sp and status, as laid out for the compiled code
+type Registers = [u8; 5];
+const A: usize = 0;
+const X: usize = 1;
+const Y: usize = 2;
+const SP: usize = 3;
+const STATUS: usize = 4;
+
+struct Block {
+    function: extern "C" fn(*mut u8),
+    // the code it was compiled from, checked before every run
+    bytes: Vec<u8>,
+    instructions: u16,
+    // the registered cycles of its instructions
+    cycles: u64,
+    last: u16,
+}
+
+// only instructions working on registers are compiled, everything touching
+// the bus (or the pc, or a decimal adc) ends the block and runs in the
+// interpreter, so there is nothing to fall back from in the middle of a block
+fn compilable(name: &str, mode: &str) -> bool {
+    match mode {
+        "immediate" => matches!(
+            name,
+            "lda" | "ldx" | "ldy" | "and" | "ora" | "eor" | "cmp" | "cpx" | "cpy"
+        ),
+        "accumulator" => matches!(name, "asl" | "lsr" | "rol" | "ror" | "inc" | "dec"),
+        "implied" => matches!(
+            name,
+            "tax"
+                | "tay"
+                | "txa"
+                | "tya"
+                | "tsx"
+                | "txs"
+                | "inx"
+                | "iny"
+                | "dex"
+                | "dey"
+                | "clc"
+                | "sec"
+                | "cli"
+                | "sei"
+                | "clv"
+                | "cld"
+                | "sed"
+                | "nop"
+        ),
+        _ => false,
+    }
+}
+
+struct Emitter<'a, 'b: 'a> {
+    builder: &'a mut FunctionBuilder<'b>,
+    registers: Vec<Variable>,
+}
+
+impl<'a, 'b> Emitter<'a, 'b> {
+    fn get(&mut self, register: usize) -> Value {
+        self.builder.use_var(self.registers[register])
+    }
+
+    fn set(&mut self, register: usize, value: Value) {
+        self.builder.def_var(self.registers[register], value);
+    }
+
+    fn constant(&mut self, value: u8) -> Value {
+        self.builder.ins().iconst(types::I8, i64::from(value))
+    }
+
+    fn flags(&mut self, clear: u8, set: Value) {
+        let status = self.get(STATUS);
+        let status = self.builder.ins().band_imm_u(status, i64::from(!clear));
+        let status = self.builder.ins().bor(status, set);
+        self.set(STATUS, status);
+    }
+
+    fn set_flag(&mut self, flag: u8, enabled: bool) {
+        let status = self.get(STATUS);
+        let status = if enabled {
+            self.builder.ins().bor_imm_u(status, i64::from(flag))
+        } else {
+            self.builder.ins().band_imm_u(status, i64::from(!flag))
+        };
+        self.set(STATUS, status);
+    }
+
+    fn nz(&mut self, value: Value) {
+        let sign = self.builder.ins().band_imm_u(value, i64::from(SIGN));
+        let zero = self.builder.ins().icmp_imm_u(IntCC::Equal, value, 0);
+        let zero = self.builder.ins().ishl_imm_u(zero, 1);
+        let set = self.builder.ins().bor(sign, zero);
+        self.flags(SIGN | ZERO, set);
+    }
+
+    // carry is a 0/1 value
+    fn carry(&mut self, carry: Value) {
+        self.flags(CARRY, carry);
+    }
+
+    fn load(&mut self, register: usize, value: Value) {
+        self.set(register, value);
+        self.nz(value);
+    }
+
+    fn compare(&mut self, register: usize, operand: u8) {
+        let value = self.get(register);
+        let carry = self.builder.ins().icmp_imm_u(
+            IntCC::UnsignedGreaterThanOrEqual,
+            value,
+            i64::from(operand),
+        );
+        let result = self
+            .builder
+            .ins()
+            .iadd_imm_u(value, i64::from(operand.wrapping_neg()));
+        self.carry(carry);
+        self.nz(result);
+    }
+
+    fn instruction(&mut self, name: &str, operand: u8) {
+        match name {
+            "lda" => {
+                let value = self.constant(operand);
+                self.load(A, value);
+            }
+            "ldx" => {
+                let value = self.constant(operand);
+                self.load(X, value);
+            }
+            "ldy" => {
+                let value = self.constant(operand);
+                self.load(Y, value);
+            }
+            "and" | "ora" | "eor" => {
+                let a = self.get(A);
+                let operand = i64::from(operand);
+                let value = match name {
+                    "and" => self.builder.ins().band_imm_u(a, operand),
+                    "ora" => self.builder.ins().bor_imm_u(a, operand),
+                    _ => self.builder.ins().bxor_imm_u(a, operand),
+                };
+                self.load(A, value);
+            }
+            "cmp" => self.compare(A, operand),
+            "cpx" => self.compare(X, operand),
+            "cpy" => self.compare(Y, operand),
+            "tax" | "tay" | "txa" | "tya" | "tsx" => {
+                let (from, to) = match name {
+                    "tax" => (A, X),
+                    "tay" => (A, Y),
+                    "txa" => (X, A),
+                    "tya" => (Y, A),
+                    _ => (SP, X),
+                };
+                let value = self.get(from);
+                self.load(to, value);
+            }
+            "txs" => {
+                let x = self.get(X);
+                self.set(SP, x);
+            }
+            "inx" | "iny" | "dex" | "dey" => {
+                let register = if name.ends_with('x') { X } else { Y };
+                // immediates are zero extended, 0xff wraps to -1
+                let delta = if name.starts_with('i') { 1 } else { 0xff };
+                let value = self.get(register);
+                let value = self.builder.ins().iadd_imm_u(value, delta);
+                self.load(register, value);
+            }
+            // the accumulator ones of the cmos
+            "inc" | "dec" => {
+                let delta = if name == "inc" { 1 } else { 0xff };
+                let a = self.get(A);
+                let value = self.builder.ins().iadd_imm_u(a, delta);
+                self.load(A, value);
+            }
+            "clc" => self.set_flag(CARRY, false),
+            "sec" => self.set_flag(CARRY, true),
+            "cli" => self.set_flag(INTERRUPT, false),
+            "sei" => self.set_flag(INTERRUPT, true),
+            "clv" => self.set_flag(OVERFLOW, false),
+            "cld" => self.set_flag(DECIMAL, false),
+            "sed" => self.set_flag(DECIMAL, true),
+            "asl" | "lsr" | "rol" | "ror" => {
+                let a = self.get(A);
+                let status = self.get(STATUS);
+                let carry_in = self.builder.ins().band_imm_u(status, i64::from(CARRY));
+                let left = name.starts_with("asl") || name.starts_with("rol");
+                let (carry, shifted) = if left {
+                    (
+                        self.builder.ins().ushr_imm_u(a, 7),
+                        self.builder.ins().ishl_imm_u(a, 1),
+                    )
+                } else {
+                    (
+                        self.builder.ins().band_imm_u(a, 1),
+                        self.builder.ins().ushr_imm_u(a, 1),
+                    )
+                };
+                let value = match name {
+                    "rol" => self.builder.ins().bor(shifted, carry_in),
+                    "ror" => {
+                        let carry_in = self.builder.ins().ishl_imm_u(carry_in, 7);
+                        self.builder.ins().bor(shifted, carry_in)
+                    }
+                    _ => shifted,
+                };
+                self.carry(carry);
+                self.load(A, value);
+            }
+            _ => (),
+        }
+    }
+}
+
+// an experimental recompiler for straight-line register code, blocks are
+// checked against the code they came from before each run so self-modifying
+// code just gets recompiled (and eventually interpreted). Compiled blocks skip
+// the cpu guards, the coverage and the debug line.
+pub struct Dynarec {
+    module: JITModule,
+    context: Context,
+    builder: FunctionBuilderContext,
+    blocks: HashMap<u16, Block>,
+    recompiles: HashMap<u16, u32>,
+    interpreted: HashSet<u16>,
+    regions: Vec<(u16, u16)>,
+    compiled_instructions: u64,
+    interpreted_instructions: u64,
+}
+
+impl Dynarec {
+    pub fn new() -> Result<Dynarec, String> {
+        let builder = JITBuilder::new(default_libcall_names()).map_err(|err| err.to_string())?;
+        let module = JITModule::new(builder);
+        Ok(Dynarec {
+            context: module.make_context(),
+            module,
+            builder: FunctionBuilderContext::new(),
+            blocks: HashMap::new(),
+            recompiles: HashMap::new(),
+            interpreted: HashSet::new(),
+            regions: Vec::new(),
+            compiled_instructions: 0,
+            interpreted_instructions: 0,
+        })
+    }
+
+    // i/o heavy code (or anything else) that should never be compiled
+    pub fn interpret_region(&mut self, start: u16, end: u16) {
+        self.regions.push((start, end));
+        self.blocks
+            .retain(|&address, _| address < start || address > end);
+    }
+
+    pub fn blocks(&self) -> usize {
+        self.blocks.len()
+    }
+
+    pub fn compiled_instructions(&self) -> u64 {
+        self.compiled_instructions
+    }
+
+    pub fn interpreted_instructions(&self) -> u64 {
+        self.interpreted_instructions
+    }
+
+    fn is_interpreted(&self, address: u16) -> bool {
+        self.interpreted.contains(&address)
+            || self
+                .regions
+                .iter()
+                .any(|&(start, end)| address >= start && address <= end)
+    }
+
+    fn is_valid<T: AddressBusIO<u16, u8>>(cpu: &mut MOS6502<T>, start: u16, bytes: &[u8]) -> bool {
+        bytes
+            .iter()
+            .enumerate()
+            .all(|(offset, &byte)| cpu.peek8(start.wrapping_add(offset as u16)) == byte)
+    }
+
+    fn compile<T: AddressBusIO<u16, u8>>(
+        &mut self,
+        cpu: &mut MOS6502<T>,
+        start: u16,
+    ) -> Result<Option<Block>, String> {
+        let mut instructions = Vec::new();
+        let mut cycles = 0;
+        let mut address = start;
+        while instructions.len() < MAX_BLOCK {
+            let opcode = cpu.opcodes[usize::from(cpu.peek8(address))];
+            if !compilable(opcode.name, opcode.mode) {
+                break;
+            }
+            let (operand, length) = if opcode.mode == "immediate" {
+                (cpu.peek8(address.wrapping_add(1)), 2)
+            } else {
+                (0, 1)
+            };
+            instructions.push((address, opcode.name, operand));
+            cycles += u64::from(opcode.cycles);
+            match address.checked_add(length) {
+                Some(next) => address = next,
+                None => break,
+            }
+        }
+        let last = match instructions.last() {
+            Some(&(last, _, _)) => last,
+            None => return Ok(None),
+        };
+
+        let pointer = self.module.target_config().pointer_type();
+        self.context.func.signature = self.module.make_signature();
+        self.context
+            .func
+            .signature
+            .params
+            .push(AbiParam::new(pointer));
+        {
+            let mut builder = FunctionBuilder::new(&mut self.context.func, &mut self.builder);
+            let entry = builder.create_block();
+            builder.append_block_params_for_function_params(entry);
+            builder.switch_to_block(entry);
+            let registers_pointer = builder.block_params(entry)[0];
+            let registers: Vec<Variable> = (0..STATUS + 1)
+                .map(|offset| {
+                    let register = builder.declare_var(types::I8);
+                    let value = builder.ins().load(
+                        types::I8,
+                        MemFlagsData::trusted(),
+                        registers_pointer,
+                        offset as i32,
+                    );
+                    builder.def_var(register, value);
+                    register
+                })
+                .collect();
+            {
+                let mut emitter = Emitter {
+                    builder: &mut builder,
+                    registers: registers.clone(),
+                };
+                for &(_, name, operand) in instructions.iter() {
+                    emitter.instruction(name, operand);
+                }
+            }
+            for (offset, register) in registers.iter().enumerate() {
+                let value = builder.use_var(*register);
+                builder.ins().store(
+                    MemFlagsData::trusted(),
+                    value,
+                    registers_pointer,
+                    offset as i32,
+                );
+            }
+            builder.ins().return_(&[]);
+            builder.seal_all_blocks();
+            builder.finalize(self.module.target_config());
+        }
+
+        let id = self
+            .module
+            .declare_anonymous_function(&self.context.func.signature)
+            .map_err(|err| err.to_string())?;
+        let defined = self.module.define_function(id, &mut self.context);
+        self.module.clear_context(&mut self.context);
+        defined.map_err(|err| err.to_string())?;
+        self.module
+            .finalize_definitions()
+            .map_err(|err| err.to_string())?;
+        // the signature above is exactly extern "C" fn(*mut u8)
+        let function = unsafe {
+            mem::transmute::<*const u8, extern "C" fn(*mut u8)>(
+                self.module.get_finalized_function(id),
+            )
+        };
+
+        let bytes = (0..address.wrapping_sub(start))
+            .map(|offset| cpu.peek8(start.wrapping_add(offset)))
+            .collect();
+        Ok(Some(Block {
+            function,
+            bytes,
+            instructions: instructions.len() as u16,
+            cycles,
+            last,
+        }))
+    }
+
+    // runs a whole compiled block, or a single instruction in the interpreter
+    pub fn step<T: AddressBusIO<u16, u8>>(&mut self, cpu: &mut MOS6502<T>) -> Result<(), String> {
+        let start = cpu.pc;
+        if !self.is_interpreted(start) {
+            let stale = match self.blocks.get(&start) {
+                Some(block) => !Dynarec::is_valid(cpu, start, &block.bytes),
+                None => false,
+            };
+            if stale {
+                self.blocks.remove(&start);
+                let recompiles = self.recompiles.entry(start).or_insert(0);
+                *recompiles += 1;
+                if *recompiles >= MAX_RECOMPILES {
+                    self.interpreted.insert(start);
+                }
+            }
+            if !self.blocks.contains_key(&start) && !self.interpreted.contains(&start) {
+                if let Some(block) = self.compile(cpu, start)? {
+                    self.blocks.insert(start, block);
+                }
+            }
+            if let Some(block) = self.blocks.get(&start) {
+                let mut registers: Registers = [cpu.a, cpu.x, cpu.y, cpu.sp, cpu.status];
+                (block.function)(registers.as_mut_ptr());
+                cpu.a = registers[A];
+                cpu.x = registers[X];
+                cpu.y = registers[Y];
+                cpu.sp = registers[SP];
+                cpu.status = registers[STATUS];
+                cpu.debug_pc = block.last;
+                cpu.pc = start.wrapping_add(block.bytes.len() as u16);
+                cpu.ticks += block.cycles;
+                self.compiled_instructions += u64::from(block.instructions);
+                return Ok(());
+            }
+        }
+        cpu.step();
+        self.interpreted_instructions += 1;
+        Ok(())
+    }
+}
@@ -0,0 +1,251 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+use mos6502::{CARRY, DECIMAL, MOS6502, OVERFLOW, SIGN, ZERO};
+use {AddressBusIO, Clock, Debug};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Operation {
+    Adc,
+    Sbc,
+    Cmp,
+    Cpx,
+    Cpy,
+}
+
+impl Operation {
+    // the immediate mode opcode
+    pub fn opcode(self) -> u8 {
+        match self {
+            Operation::Adc => 0x69,
+            Operation::Sbc => 0xe9,
+            Operation::Cmp => 0xc9,
+            Operation::Cpx => 0xe0,
+            Operation::Cpy => 0xc0,
+        }
+    }
+}
+
+// an operand and the flags going in, register is a for adc, sbc and cmp
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Case {
+    pub operation: Operation,
+    pub register: u8,
+    pub value: u8,
+    pub carry: bool,
+    pub overflow: bool,
+    pub decimal: bool,
+}
+
+// the register and the flags coming out
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Outcome {
+    pub register: u8,
+    pub carry: bool,
+    pub overflow: bool,
+    pub zero: bool,
+    pub sign: bool,
+}
+
+impl Outcome {
+    fn binary(register: u8, carry: bool, overflow: bool) -> Outcome {
+        Outcome {
+            register,
+            carry,
+            overflow,
+            zero: register == 0,
+            sign: register & 0x80 != 0,
+        }
+    }
+}
+
+// the documented behavior, written from the carry and overflow relationships
+// instead of the way the core computes them: the nmos decimal mode keeps n, v
+// and z from the binary intermediates
+pub fn expected(case: &Case) -> Outcome {
+    let (a, m, c) = (case.register, case.value, u16::from(case.carry));
+    match case.operation {
+        Operation::Adc if case.decimal => {
+            let mut low = u16::from(a & 0x0f) + u16::from(m & 0x0f) + c;
+            if low >= 0x0a {
+                low = ((low + 0x06) & 0x0f) + 0x10;
+            }
+            let intermediate = u16::from(a & 0xf0) + u16::from(m & 0xf0) + low;
+            let mut result = intermediate;
+            if result >= 0xa0 {
+                result += 0x60;
+            }
+            let binary = u16::from(a) + u16::from(m) + c;
+            Outcome {
+                register: result as u8,
+                carry: result >= 0x100,
+                overflow: (a ^ m) & 0x80 == 0 && (u16::from(a) ^ intermediate) & 0x80 != 0,
+                zero: binary as u8 == 0,
+                sign: intermediate & 0x80 != 0,
+            }
+        }
+        Operation::Adc => {
+            let sum = u16::from(a) + u16::from(m) + c;
+            let result = sum as u8;
+            // both operands with the same sign, the result with the other
+            let overflow = (a ^ m) & 0x80 == 0 && (a ^ result) & 0x80 != 0;
+            Outcome::binary(result, sum > 0xff, overflow)
+        }
+        Operation::Sbc => {
+            let borrow = 1 - c;
+            let binary = u16::from(a).wrapping_sub(u16::from(m)).wrapping_sub(borrow) as u8;
+            // no borrow when a is at least m plus the borrow going in
+            let carry = u16::from(a) >= u16::from(m) + borrow;
+            // operands with different signs, the result with the sign of m
+            let overflow = (a ^ m) & 0x80 != 0 && (a ^ binary) & 0x80 != 0;
+            let mut outcome = Outcome::binary(binary, carry, overflow);
+            if case.decimal {
+                let mut low = i16::from(a & 0x0f) - i16::from(m & 0x0f) - borrow as i16;
+                if low < 0 {
+                    low = ((low - 0x06) & 0x0f) - 0x10;
+                }
+                let mut result = i16::from(a & 0xf0) - i16::from(m & 0xf0) + low;
+                if result < 0 {
+                    result -= 0x60;
+                }
+                outcome.register = result as u8;
+            }
+            outcome
+        }
+        Operation::Cmp | Operation::Cpx | Operation::Cpy => {
+            let difference = a.wrapping_sub(m);
+            Outcome {
+                register: a,
+                carry: a >= m,
+                overflow: case.overflow,
+                zero: a == m,
+                sign: difference & 0x80 != 0,
+            }
+        }
+    }
+}
+
+// the instruction is put on the bus at address, the bus is expected to be
+// plain memory there
+pub fn execute<T: AddressBusIO<u16, u8>>(
+    cpu: &mut MOS6502<T>,
+    case: &Case,
+    address: u16,
+) -> Outcome {
+    cpu.inject(address, case.operation.opcode());
+    cpu.inject(address.wrapping_add(1), case.value);
+    cpu.pc = address;
+    match case.operation {
+        Operation::Cpx => cpu.x = case.register,
+        Operation::Cpy => cpu.y = case.register,
+        _ => cpu.a = case.register,
+    }
+    cpu.set_flag(CARRY, case.carry);
+    cpu.set_flag(OVERFLOW, case.overflow);
+    cpu.set_flag(DECIMAL, case.decimal);
+    cpu.step();
+    Outcome {
+        register: match case.operation {
+            Operation::Cpx => cpu.x,
+            Operation::Cpy => cpu.y,
+            _ => cpu.a,
+        },
+        carry: cpu.get_flag(CARRY),
+        overflow: cpu.get_flag(OVERFLOW),
+        zero: cpu.get_flag(ZERO),
+        sign: cpu.get_flag(SIGN),
+    }
+}
+
+pub struct Mismatch {
+    pub case: Case,
+    pub expected: Outcome,
+    pub got: Outcome,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let flags = |outcome: &Outcome| {
+            [
+                (outcome.sign, 'N'),
+                (outcome.overflow, 'V'),
+                (outcome.zero, 'Z'),
+                (outcome.carry, 'C'),
+            ]
+            .iter()
+            .map(|&(set, name)| if set { name } else { '-' })
+            .collect::<String>()
+        };
+        write!(
+            f,
+            "{:?} ${:02X} with ${:02X} (C={} V={} D={}): expected ${:02X} {}, got ${:02X} {}",
+            self.case.operation,
+            self.case.register,
+            self.case.value,
+            u8::from(self.case.carry),
+            u8::from(self.case.overflow),
+            u8::from(self.case.decimal),
+            self.expected.register,
+            flags(&self.expected),
+            self.got.register,
+            flags(&self.got)
+        )
+    }
+}
+
+// every case that does not match the documented behavior
+pub fn check<T: AddressBusIO<u16, u8>, I: IntoIterator<Item = Case>>(
+    cpu: &mut MOS6502<T>,
+    cases: I,
+    address: u16,
+) -> Vec<Mismatch> {
+    cases
+        .into_iter()
+        .filter_map(|case| {
+            let (expected, got) = (expected(&case), execute(cpu, &case, address));
+            if expected == got {
+                None
+            } else {
+                Some(Mismatch {
+                    case,
+                    expected,
+                    got,
+                })
+            }
+        })
+        .collect()
+}
+
+fn case(operation: Operation, decimal: bool, bits: u32) -> Case {
+    Case {
+        operation,
+        register: (bits >> 8) as u8,
+        value: bits as u8,
+        carry: bits & 0x10000 != 0,
+        // any value, to see it is left alone by the comparisons
+        overflow: bits & 0x20000 != 0,
+        decimal,
+    }
+}
+
+// the 2^18 operand and flag combinations, cheap enough to run them all
+pub fn all_cases(operation: Operation, decimal: bool) -> impl Iterator<Item = Case> {
+    (0..0x40000).map(move |bits| case(operation, decimal, bits))
+}
+
+// count random combinations from seed (not 0), for slower buses and variants
+pub fn random_cases(
+    operation: Operation,
+    decimal: bool,
+    seed: u32,
+    count: usize,
+) -> impl Iterator<Item = Case> {
+    let mut state = seed.max(1);
+    (0..count).map(move |_| {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        case(operation, decimal, state)
+    })
+}
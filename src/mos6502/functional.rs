@@ -0,0 +1,123 @@
+use alloc::collections::VecDeque;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+use mos6502::MOS6502;
+use {AddressBusIO, Clock, Debug};
+
+// where the code starts, the success loop and the current test number of the
+// default build of Klaus Dormann's 6502_functional_test
+pub const START: u16 = 0x0400;
+pub const SUCCESS: u16 = 0x3469;
+pub const TEST_CASE: u16 = 0x0200;
+
+// the test stopped in a jmp * (or branch to self) that is not the success one
+pub struct Trap {
+    pub pc: u16,
+    pub test_case: u8,
+    pub steps: u64,
+    pub timed_out: bool,
+    // the last instructions before the trap, oldest first
+    pub trace: Vec<String>,
+}
+
+impl fmt::Display for Trap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.timed_out {
+            write!(f, "no trap after {} steps", self.steps)?;
+        } else {
+            write!(f, "trapped at ${:04X} after {} steps", self.pc, self.steps)?;
+        }
+        write!(f, " (test case ${:02X})", self.test_case)?;
+        for line in &self.trace {
+            write!(f, "\n{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+pub struct FunctionalTest {
+    pub start: u16,
+    pub success: u16,
+    pub test_case: u16,
+    pub max_steps: u64,
+    pub trace_length: usize,
+}
+
+impl Default for FunctionalTest {
+    fn default() -> FunctionalTest {
+        FunctionalTest {
+            start: START,
+            success: SUCCESS,
+            test_case: TEST_CASE,
+            max_steps: 100_000_000,
+            trace_length: 16,
+        }
+    }
+}
+
+impl FunctionalTest {
+    // the binary is expected to be already on the bus, returns the steps
+    // needed to reach the success loop
+    pub fn run<T: AddressBusIO<u16, u8>>(&self, cpu: &mut MOS6502<T>) -> Result<u64, Trap> {
+        // registers only, the disassembly is done once trapped
+        let mut tail = VecDeque::with_capacity(self.trace_length);
+        cpu.pc = self.start;
+        for steps in 0..self.max_steps {
+            let pc = cpu.pc;
+            if tail.len() == self.trace_length {
+                tail.pop_front();
+            }
+            tail.push_back([
+                cpu.a,
+                cpu.x,
+                cpu.y,
+                cpu.sp,
+                cpu.status,
+                pc as u8,
+                (pc >> 8) as u8,
+            ]);
+            cpu.step();
+            if cpu.pc == pc {
+                if pc == self.success {
+                    return Ok(steps + 1);
+                }
+                return Err(self.trap(cpu, steps + 1, false, &tail));
+            }
+        }
+        Err(self.trap(cpu, self.max_steps, true, &tail))
+    }
+
+    fn trap<T: AddressBusIO<u16, u8>>(
+        &self,
+        cpu: &mut MOS6502<T>,
+        steps: u64,
+        timed_out: bool,
+        tail: &VecDeque<[u8; 7]>,
+    ) -> Trap {
+        let trace = tail
+            .iter()
+            .map(|state| {
+                let pc = u16::from(state[6]) << 8 | u16::from(state[5]);
+                format!(
+                    "${:04X}: {:<24} A=${:02X} X=${:02X} Y=${:02X} SP=${:02X} P=${:02X}",
+                    pc,
+                    cpu.disassemble(pc).0,
+                    state[0],
+                    state[1],
+                    state[2],
+                    state[3],
+                    state[4]
+                )
+            })
+            .collect();
+        Trap {
+            pc: cpu.pc,
+            test_case: cpu.inspect(self.test_case),
+            steps,
+            timed_out,
+            trace,
+        }
+    }
+}
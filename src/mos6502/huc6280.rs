@@ -0,0 +1,334 @@
+use mos6502::builder::{Builder, Variant};
+use mos6502::{MOS6502, OVERFLOW, SIGN, ZERO};
+use AddressBusIO;
+
+// the t flag in place of the unused bit, set by set for the instruction
+// after it only
+const MEMORY_OPERATION: u8 = 0x20;
+
+// the 64k the cpu sees mapped on the 2m of the huc6280 through the eight
+// mprs, one for each 8k; mpr7 is $00 from the start so the vectors are in
+// the first bank
+pub struct Mmu<T: AddressBusIO<u32, u8>> {
+    bus: T,
+    pub mpr: [u8; 8],
+    // set by csh, the 7.16 mhz of the chip instead of the 1.79 of csl
+    pub high_speed: bool,
+}
+
+impl<T: AddressBusIO<u32, u8>> Mmu<T> {
+    pub fn new(bus: T) -> Mmu<T> {
+        Mmu {
+            bus,
+            mpr: [0; 8],
+            high_speed: false,
+        }
+    }
+
+    pub fn physical(&self, address: u16) -> u32 {
+        let bank = u32::from(self.mpr[usize::from(address >> 13)]);
+        bank << 13 | u32::from(address & 0x1fff)
+    }
+
+    pub fn bus(&self) -> &T {
+        &self.bus
+    }
+
+    pub fn bus_mut(&mut self) -> &mut T {
+        &mut self.bus
+    }
+
+    pub fn into_bus(self) -> T {
+        self.bus
+    }
+}
+
+impl<T: AddressBusIO<u32, u8>> AddressBusIO<u16, u8> for Mmu<T> {
+    fn read(&mut self, address: u16) -> u8 {
+        let physical = self.physical(address);
+        self.bus.read(physical)
+    }
+
+    fn write(&mut self, address: u16, value: u8) {
+        let physical = self.physical(address);
+        self.bus.write(physical, value)
+    }
+
+    // only within a bank, the next one can be mapped anywhere
+    fn as_contiguous(&mut self, address: u16, length: usize) -> Option<&mut [u8]> {
+        if usize::from(address & 0x1fff) + length > 0x2000 {
+            return None;
+        }
+        let physical = self.physical(address);
+        self.bus.as_contiguous(physical, length)
+    }
+}
+
+// how a block transfer moves its source or its destination
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Stride {
+    Increment,
+    Decrement,
+    // the address and the one after it in turn, for the data ports
+    Alternate,
+    Fixed,
+}
+
+impl Stride {
+    fn offset(self, index: u32) -> u16 {
+        match self {
+            Stride::Increment => index as u16,
+            Stride::Decrement => (index as u16).wrapping_neg(),
+            Stride::Alternate => (index & 1) as u16,
+            Stride::Fixed => 0,
+        }
+    }
+}
+
+// the 65c02 with the huc6280 opcodes over it, built by build_huc6280; tick
+// runs those whole on the fetch, with the cycles step counts
+impl<T: AddressBusIO<u32, u8>> Builder<Mmu<T>> {
+    // the variant and its quirks are the huc6280 ones
+    pub fn build_huc6280(self) -> MOS6502<Mmu<T>> {
+        let mut cpu = self.variant(Variant::HuC6280).build();
+        cpu.register_huc6280_opcodes();
+        cpu
+    }
+}
+
+impl<T: AddressBusIO<u32, u8>> MOS6502<Mmu<T>> {
+    pub fn huc6280(bus: T) -> MOS6502<Mmu<T>> {
+        MOS6502::builder(Mmu::new(bus)).build_huc6280()
+    }
+
+    fn register_huc6280_opcodes(&mut self) {
+        let cpu = self;
+        huc6280_opcode_table!(register_opcodes!(cpu));
+        // the names of these two are taken by the undocumented nmos sax and
+        // by the register setter
+        cpu.register_opcode(
+            "sax",
+            0x22,
+            "implied",
+            |cpu: &mut MOS6502<Mmu<T>>| {
+                cpu.implied();
+                cpu.operand();
+                cpu.swap_ax();
+            },
+            |cpu: &mut MOS6502<Mmu<T>>| cpu.swap_ax(),
+        );
+        cpu.register_opcode(
+            "set",
+            0xf4,
+            "implied",
+            |cpu: &mut MOS6502<Mmu<T>>| {
+                cpu.implied();
+                cpu.operand();
+                cpu.memory_operation();
+            },
+            |cpu: &mut MOS6502<Mmu<T>>| cpu.memory_operation(),
+        );
+    }
+
+    pub fn mmu(&self) -> &Mmu<T> {
+        self.bus()
+    }
+
+    pub fn mmu_mut(&mut self) -> &mut Mmu<T> {
+        self.bus_mut()
+    }
+
+    // tst #imm,zp and the like, the mask first
+    fn immediate_zeropage(&mut self) {
+        self.value = self.read8_from_pc();
+        let offset = self.read8_from_pc();
+        self.addr = self.zeropage_address(offset);
+        if self.debugging() {
+            let name = self.get_opcode_name();
+            self.debug_line = format!("{} #${:02X},${:02X}", name, self.value, offset);
+        }
+    }
+
+    fn immediate_zeropage_x(&mut self) {
+        self.value = self.read8_from_pc();
+        let offset = self.read8_from_pc();
+        self.addr = self.zeropage_address(offset.wrapping_add(self.x));
+        if self.debugging() {
+            let name = self.get_opcode_name();
+            self.debug_line = format!("{} #${:02X},${:02X},X", name, self.value, offset);
+        }
+    }
+
+    fn immediate_absolute(&mut self) {
+        self.value = self.read8_from_pc();
+        self.addr = self.read16_from_pc();
+        if self.debugging() {
+            let name = self.get_opcode_name();
+            self.debug_line = format!("{} #${:02X},${:04X}", name, self.value, self.addr);
+        }
+    }
+
+    fn immediate_absolute_x(&mut self) {
+        self.value = self.read8_from_pc();
+        let addr = self.read16_from_pc();
+        self.addr = addr.wrapping_add(u16::from(self.x));
+        if self.debugging() {
+            let name = self.get_opcode_name();
+            self.debug_line = format!("{} #${:02X},${:04X},X", name, self.value, addr);
+        }
+    }
+
+    // the source, the destination and the length are fetched by the
+    // transfer as it goes
+    fn block_transfer(&mut self) {}
+
+    fn tst(&mut self) {
+        let mask = self.value;
+        let addr = self.addr;
+        let value = self.read8(addr);
+        self.set_flag(ZERO, value & mask == 0);
+        self.set_flag(SIGN, value & 0x80 != 0);
+        self.set_flag(OVERFLOW, value & 0x40 != 0);
+    }
+
+    fn swap_ax(&mut self) {
+        ::core::mem::swap(&mut self.a, &mut self.x);
+    }
+
+    fn say(&mut self) {
+        ::core::mem::swap(&mut self.a, &mut self.y);
+    }
+
+    fn sxy(&mut self) {
+        ::core::mem::swap(&mut self.x, &mut self.y);
+    }
+
+    fn cla(&mut self) {
+        self.a = 0;
+    }
+
+    fn clx(&mut self) {
+        self.x = 0;
+    }
+
+    fn cly(&mut self) {
+        self.y = 0;
+    }
+
+    fn csh(&mut self) {
+        self.bus_mut().high_speed = true;
+    }
+
+    fn csl(&mut self) {
+        self.bus_mut().high_speed = false;
+    }
+
+    // the mprs with a bit set in the operand get a
+    fn tam(&mut self) {
+        let (mask, a) = (self.value, self.a);
+        let mmu = self.bus_mut();
+        for (bit, mpr) in mmu.mpr.iter_mut().enumerate() {
+            if mask & (1 << bit) != 0 {
+                *mpr = a;
+            }
+        }
+    }
+
+    // a from the first mpr with its bit set
+    fn tma(&mut self) {
+        let mask = self.value;
+        if let Some(bit) = (0..8).find(|&bit| mask & (1 << bit) != 0) {
+            self.a = self.bus().mpr[bit];
+        }
+    }
+
+    // the registers of the video chip, always at the start of the io bank
+    fn store_video(&mut self, register: u32) {
+        let value = self.value;
+        self.bus_mut()
+            .bus_mut()
+            .write(0x001f_e000 | register, value);
+    }
+
+    fn st0(&mut self) {
+        self.store_video(0);
+    }
+
+    fn st1(&mut self) {
+        self.store_video(2);
+    }
+
+    fn st2(&mut self) {
+        self.store_video(3);
+    }
+
+    fn bsr(&mut self) {
+        let pc = self.pc.wrapping_sub(1);
+        self.push_return_address(pc);
+        self.pc = self.addr;
+    }
+
+    // a length of 0 moves 64k, 6 cycles a byte
+    fn transfer(&mut self, source: Stride, destination: Stride) {
+        let from = self.read16_from_pc();
+        let to = self.read16_from_pc();
+        let length = self.read16_from_pc();
+        if self.debugging() {
+            let name = self.get_opcode_name();
+            self.debug_line = format!("{} ${:04X},${:04X},${:04X}", name, from, to, length);
+        }
+        let count = if length == 0 {
+            0x10000
+        } else {
+            u32::from(length)
+        };
+        for index in 0..count {
+            let value = self.read8(from.wrapping_add(source.offset(index)));
+            self.write8(to.wrapping_add(destination.offset(index)), value);
+        }
+        self.ticks += 6 * u64::from(count);
+    }
+
+    fn tii(&mut self) {
+        self.transfer(Stride::Increment, Stride::Increment);
+    }
+
+    fn tdd(&mut self) {
+        self.transfer(Stride::Decrement, Stride::Decrement);
+    }
+
+    fn tin(&mut self) {
+        self.transfer(Stride::Increment, Stride::Fixed);
+    }
+
+    fn tia(&mut self) {
+        self.transfer(Stride::Increment, Stride::Alternate);
+    }
+
+    fn tai(&mut self) {
+        self.transfer(Stride::Alternate, Stride::Increment);
+    }
+
+    // runs the next instruction with the t flag set: adc, and, eor and ora
+    // then work on the zeropage byte at x in place of a, for 3 cycles more
+    fn memory_operation(&mut self) {
+        self.ticks += u64::from(self.opcode.cycles);
+        self.status |= MEMORY_OPERATION;
+        let opcode = self.read8_from_pc();
+        self.current_opcode = opcode;
+        self.opcode = self.lookup(opcode);
+        if matches!(self.opcode.name, "adc" | "and" | "eor" | "ora") {
+            let addr = self.zeropage_address(self.x);
+            let a = self.a;
+            self.a = self.read8(addr);
+            (self.opcode.execute)(self);
+            let result = self.a;
+            self.write8(addr, result);
+            self.a = a;
+            self.ticks += 3;
+        } else {
+            (self.opcode.execute)(self);
+        }
+        self.status &= !MEMORY_OPERATION;
+    }
+}
@@ -0,0 +1,75 @@
+#[derive(Clone, Copy, PartialEq)]
+pub struct IdleLoop {
+    pub start: u16,
+    pub end: u16,
+}
+
+// a loop is idle when two consecutive iterations reach its head with the same
+// registers and without any bus write in between: nothing but an external
+// device can make it progress
+pub struct IdleDetector {
+    window: u16,
+    head: Option<u16>,
+    end: u16,
+    state: [u8; 5],
+    writes: u64,
+    head_writes: u64,
+    idle: bool,
+    event: Option<IdleLoop>,
+}
+
+impl IdleDetector {
+    pub fn new(window: u16) -> IdleDetector {
+        IdleDetector {
+            window,
+            head: None,
+            end: 0,
+            state: [0; 5],
+            writes: 0,
+            head_writes: 0,
+            idle: false,
+            event: None,
+        }
+    }
+
+    pub fn write(&mut self) {
+        self.writes += 1;
+    }
+
+    pub fn step(&mut self, pc: u16, next_pc: u16, state: [u8; 5]) {
+        // only short backward jumps (including branch-to-self) close a loop
+        if next_pc <= pc && pc - next_pc <= self.window {
+            if self.head == Some(next_pc) && self.state == state && self.head_writes == self.writes
+            {
+                if !self.idle {
+                    self.idle = true;
+                    self.event = Some(IdleLoop {
+                        start: next_pc,
+                        end: pc,
+                    });
+                }
+                return;
+            }
+            self.head = Some(next_pc);
+            self.end = pc;
+            self.state = state;
+            self.head_writes = self.writes;
+            self.idle = false;
+            return;
+        }
+        if let Some(head) = self.head {
+            if next_pc < head || next_pc > self.end {
+                self.head = None;
+                self.idle = false;
+            }
+        }
+    }
+
+    pub fn is_idle(&self) -> bool {
+        self.idle
+    }
+
+    pub fn take_event(&mut self) -> Option<IdleLoop> {
+        self.event.take()
+    }
+}
@@ -0,0 +1,173 @@
+use core::fmt;
+
+use mos6502::opcodes;
+use mos6502::{Access, MOS6502};
+use {AddressBusIO, Clock};
+
+// an instruction as it is in memory, read without running it
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Instruction {
+    pub address: u16,
+    pub opcode: u8,
+    pub mnemonic: &'static str,
+    pub mode: &'static str,
+    // up to the 6 of a huc6280 block transfer
+    bytes: [u8; 6],
+    // opcode included, 1 for the invalid ones
+    pub length: u8,
+}
+
+impl Instruction {
+    pub fn operands(&self) -> &[u8] {
+        &self.bytes[..usize::from(self.length - 1)]
+    }
+
+    // the first two operand bytes as a word, the address of most modes
+    pub fn word(&self) -> u16 {
+        u16::from(self.bytes[1]) << 8 | u16::from(self.bytes[0])
+    }
+}
+
+// an instruction as it was run, the bytes are the ones fetched before it
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Executed {
+    pub pc: u16,
+    pub opcode: u8,
+    pub name: &'static str,
+    pub mode: &'static str,
+    // up to the 6 of a huc6280 block transfer
+    bytes: [u8; 6],
+    length: u8,
+    // where the operand was, for the modes reading or writing memory
+    pub address: Option<u16>,
+    // the operand as read and the last value stored to it
+    pub read: Option<u8>,
+    pub written: Option<u8>,
+    pub cycles: u64,
+}
+
+impl Executed {
+    pub fn operands(&self) -> &[u8] {
+        &self.bytes[..usize::from(self.length)]
+    }
+}
+
+impl fmt::Display for Executed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "${:04X}: {:02X}", self.pc, self.opcode)?;
+        for operand in self.operands() {
+            write!(f, " {:02X}", operand)?;
+        }
+        write!(f, " {} ({} cycles)", self.name, self.cycles)
+    }
+}
+
+// runs an instruction per item, ends once the cpu halts, stops or waits with
+// no interrupt to wake it (the fault is left to take_fault)
+pub struct Instructions<'a, T: AddressBusIO<u16, u8>> {
+    cpu: &'a mut MOS6502<T>,
+}
+
+impl<'a, T: AddressBusIO<u16, u8>> Iterator for Instructions<'a, T> {
+    type Item = Executed;
+
+    fn next(&mut self) -> Option<Executed> {
+        loop {
+            let cpu = &mut self.cpu;
+            if cpu.fault.is_some() || cpu.halted || cpu.stopped || (cpu.waiting && !cpu.wake()) {
+                return None;
+            }
+            // past a stall or an instruction tick started
+            if let Some(executed) = cpu.step_traced() {
+                return Some(executed);
+            }
+        }
+    }
+}
+
+// the operand accesses of the instruction run by step_traced
+#[derive(Clone, Copy, Default)]
+pub struct AccessRecorder {
+    read: Option<u8>,
+    written: Option<u8>,
+}
+
+impl AccessRecorder {
+    pub fn read(&mut self, value: u8) {
+        self.read = Some(value);
+    }
+
+    pub fn write(&mut self, value: u8) {
+        self.written = Some(value);
+    }
+}
+
+impl<T: AddressBusIO<u16, u8>> MOS6502<T> {
+    // with the opcodes of the variant, only peeks at the bus so no register,
+    // guard or cache sees it
+    pub fn decode(&mut self, address: u16) -> Instruction {
+        let opcode = self.peek8(address);
+        let decoded = self.lookup(opcode);
+        let length = opcodes::length(decoded.mode);
+        let mut bytes = [0; 6];
+        for (offset, byte) in bytes.iter_mut().take(usize::from(length - 1)).enumerate() {
+            *byte = self.peek8(address.wrapping_add(1 + offset as u16));
+        }
+        Instruction {
+            address,
+            opcode,
+            mnemonic: decoded.name,
+            mode: decoded.mode,
+            bytes,
+            length,
+        }
+    }
+
+    // step with a record of what ran, an interrupt taken first counts with
+    // the instruction it leads to; none when step runs no instruction of its
+    // own, on a halted, stopped or waiting cpu, a stall or the rest of an
+    // instruction tick started
+    pub fn step_traced(&mut self) -> Option<Executed> {
+        if self.halted
+            || self.stopped
+            || (self.waiting && !self.wake())
+            || self.stall != 0
+            || self.in_instruction()
+        {
+            self.step();
+            return None;
+        }
+        let taken = if self.due_interrupt().is_some() {
+            self.step()
+        } else {
+            0
+        };
+        let pc = self.pc;
+        let instruction = self.decode(pc);
+        self.access_recorder = Some(AccessRecorder::default());
+        let cycles = taken + self.step();
+        let recorder = self.access_recorder.take().unwrap_or_default();
+        let memory = self.opcode.operand || self.opcode.access != Access::Read;
+        let address = if memory && !self.opcode.accumulator {
+            Some(self.addr)
+        } else {
+            None
+        };
+        Some(Executed {
+            pc,
+            opcode: instruction.opcode,
+            name: instruction.mnemonic,
+            mode: instruction.mode,
+            bytes: instruction.bytes,
+            length: instruction.length - 1,
+            address,
+            read: recorder.read.filter(|_| address.is_some()),
+            written: recorder.written.filter(|_| address.is_some()),
+            cycles,
+        })
+    }
+
+    pub fn iter_instructions(&mut self) -> Instructions<'_, T> {
+        Instructions { cpu: self }
+    }
+}
@@ -0,0 +1,164 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+use mos6502::diff::StepRecorder;
+use mos6502::MOS6502;
+use {AddressBusIO, Clock, Debug};
+
+// what every 6502 implementation has, for comparing them
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Registers {
+    pub pc: u16,
+    pub a: u8,
+    pub x: u8,
+    pub y: u8,
+    pub sp: u8,
+    pub status: u8,
+}
+
+impl Registers {
+    pub fn of<T: AddressBusIO<u16, u8>>(cpu: &MOS6502<T>) -> Registers {
+        Registers {
+            pc: cpu.pc,
+            a: cpu.a,
+            x: cpu.x,
+            y: cpu.y,
+            sp: cpu.sp,
+            status: cpu.status,
+        }
+    }
+}
+
+impl fmt::Display for Registers {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "PC=${:04X} A=${:02X} X=${:02X} Y=${:02X} SP=${:02X} P=${:02X}",
+            self.pc, self.a, self.x, self.y, self.sp, self.status
+        )
+    }
+}
+
+// another implementation run next to MOS6502, set up with the same program
+// and registers by the caller
+pub trait ReferenceCpu {
+    fn registers(&self) -> Registers;
+    // a whole instruction, returns its bus writes in order
+    fn step(&mut self) -> Vec<(u16, u8)>;
+}
+
+// another instance of the core, for comparing features (dispatch, caches,
+// buses) against the plain interpreter
+impl<T: AddressBusIO<u16, u8>> ReferenceCpu for MOS6502<T> {
+    fn registers(&self) -> Registers {
+        Registers::of(self)
+    }
+
+    fn step(&mut self) -> Vec<(u16, u8)> {
+        self.step_recorder.get_or_insert_with(StepRecorder::new);
+        Clock::step(self);
+        self.take_step_diff()
+            .map(|diff| diff.writes)
+            .unwrap_or_default()
+    }
+}
+
+pub struct Divergence {
+    // instructions run by both before the divergence
+    pub step: u64,
+    // the instruction that diverged, if it is not the initial state
+    pub disassembly: Option<String>,
+    pub expected: Registers,
+    pub got: Registers,
+    pub expected_writes: Vec<(u16, u8)>,
+    pub got_writes: Vec<(u16, u8)>,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "diverged after {} steps", self.step)?;
+        if let Some(ref disassembly) = self.disassembly {
+            write!(f, " at {}", disassembly)?;
+        }
+        write!(f, "\n  expected {}", self.expected)?;
+        for &(address, value) in &self.expected_writes {
+            write!(f, " ${:04X}={:02X}", address, value)?;
+        }
+        write!(f, "\n  got      {}", self.got)?;
+        for &(address, value) in &self.got_writes {
+            write!(f, " ${:04X}={:02X}", address, value)?;
+        }
+        Ok(())
+    }
+}
+
+pub struct Lockstep {
+    pub max_steps: u64,
+    // status bits compared, for references that do not keep B or bit 5
+    pub status_mask: u8,
+}
+
+impl Default for Lockstep {
+    fn default() -> Lockstep {
+        Lockstep {
+            max_steps: 1_000_000,
+            status_mask: 0xff,
+        }
+    }
+}
+
+impl Lockstep {
+    fn matches(&self, expected: &Registers, got: &Registers) -> bool {
+        Registers {
+            status: expected.status & self.status_mask,
+            ..*expected
+        } == Registers {
+            status: got.status & self.status_mask,
+            ..*got
+        }
+    }
+
+    // step diffs are left enabled on cpu, returns max_steps when nothing
+    // diverged
+    pub fn run<T: AddressBusIO<u16, u8>, R: ReferenceCpu>(
+        &self,
+        cpu: &mut MOS6502<T>,
+        reference: &mut R,
+    ) -> Result<u64, Divergence> {
+        let (expected, got) = (reference.registers(), Registers::of(cpu));
+        if !self.matches(&expected, &got) {
+            return Err(Divergence {
+                step: 0,
+                disassembly: None,
+                expected,
+                got,
+                expected_writes: Vec::new(),
+                got_writes: Vec::new(),
+            });
+        }
+        cpu.enable_step_diffs();
+        for step in 0..self.max_steps {
+            let pc = cpu.pc;
+            let expected_writes = reference.step();
+            Clock::step(cpu);
+            let got_writes = cpu
+                .take_step_diff()
+                .map(|diff| diff.writes)
+                .unwrap_or_default();
+            let (expected, got) = (reference.registers(), Registers::of(cpu));
+            if !self.matches(&expected, &got) || expected_writes != got_writes {
+                let (text, _) = cpu.disassemble(pc);
+                return Err(Divergence {
+                    step,
+                    disassembly: Some(format!("${:04X}: {}", pc, text)),
+                    expected,
+                    got,
+                    expected_writes,
+                    got_writes,
+                });
+            }
+        }
+        Ok(self.max_steps)
+    }
+}
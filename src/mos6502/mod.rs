@@ -1,4 +1,52 @@
-use {AddressBusIO, Clock, Debug, Interrupt};
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+
+use {AddressBusIO, Clock, Debug, Error, Interrupt, Reset};
+
+#[cfg(feature = "std")]
+pub mod asm;
+pub mod breakpoints;
+pub mod builder;
+pub mod condition;
+pub mod coverage;
+pub mod cycle;
+pub mod decode;
+pub mod diff;
+pub mod disasm;
+#[cfg(feature = "dynarec")]
+pub mod dynarec;
+pub mod flags;
+pub mod functional;
+pub mod idle;
+pub mod instructions;
+pub mod lockstep;
+#[cfg(feature = "std")]
+pub mod nestest;
+pub mod opcodes;
+pub mod port;
+pub mod power;
+#[cfg(feature = "std")]
+pub mod processor_tests;
+pub mod run;
+pub mod stack;
+pub mod timing;
+pub mod uninit;
+
+use self::breakpoints::{Breakpoint, WatchHandler, WatchHit, Watchpoint};
+use self::builder::{Builder, IllegalOpcodeHandler, IllegalOpcodes, Quirks, TraceSink, Variant};
+use self::coverage::{Coverage, SelfModification};
+use self::cycle::{Sequence, TickState};
+use self::decode::DecodeCache;
+use self::diff::{StepDiff, StepRecorder};
+use self::idle::{IdleDetector, IdleLoop};
+use self::instructions::AccessRecorder;
+use self::port::ProcessorPort;
+use self::power::PowerUpState;
+use self::stack::{StackGuard, StackViolation};
+use self::uninit::{UninitializedGuard, UninitializedRead};
+use events::{CpuEvent, Observers};
+use scheduler::Scheduler;
 
 const CARRY: u8 = 0x01;
 const ZERO: u8 = 0x02;
@@ -9,10 +57,183 @@ const ALWAYS_SET: u8 = 0x20;
 const OVERFLOW: u8 = 0x40;
 const SIGN: u8 = 0x80;
 
+// the sign and zero bits of the status for every result
+const NZ: [u8; 256] = nz_table();
+
+const fn nz_table() -> [u8; 256] {
+    let mut table = [0; 256];
+    let mut value = 0;
+    while value < 256 {
+        table[value] = (value as u8 & SIGN) | if value == 0 { ZERO } else { 0 };
+        value += 1;
+    }
+    table
+}
+
+// what an opcode costs from its addressing mode and what it does with the
+// operand, the stores and read modify writes through an index always pay for
+// the carry into the high byte; the cmos takes a cycle more for jmp ($xxxx)
+// and one less for the shifts through x, when they stay in the page
+fn cycles(name: &str, mode: &str, access: Access, cmos: bool) -> u8 {
+    match (name, mode) {
+        ("jmp", "indirect") if cmos => return 6,
+        ("asl", "absolute_x")
+        | ("lsr", "absolute_x")
+        | ("rol", "absolute_x")
+        | ("ror", "absolute_x")
+            if cmos =>
+        {
+            return 6
+        }
+        ("brk", _) => return 7,
+        ("jsr", _) | ("rts", _) | ("rti", _) => return 6,
+        ("pha", _) | ("php", _) | ("phx", _) | ("phy", _) | ("wai", _) | ("stp", _) => return 3,
+        ("pla", _) | ("plp", _) | ("plx", _) | ("ply", _) => return 4,
+        ("jmp", "absolute") => return 3,
+        (_, "zeropage_relative") => return 5,
+        // the huc6280 ones, a block transfer takes 6 more per byte
+        ("bsr", _) => return 8,
+        ("tam", _) => return 5,
+        ("tma", _) | ("st0", _) | ("st1", _) | ("st2", _) => return 4,
+        ("sax", "implied") | ("say", _) | ("sxy", _) | ("csl", _) | ("csh", _) => return 3,
+        (_, "immediate_zeropage") | (_, "immediate_zeropage_x") => return 7,
+        (_, "immediate_absolute") | (_, "immediate_absolute_x") => return 8,
+        (_, "block_transfer") => return 17,
+        (_, "implied")
+        | (_, "accumulator")
+        | (_, "immediate")
+        | (_, "relative")
+        | (_, "invalid") => return 2,
+        _ => (),
+    }
+    let read = match mode {
+        "zeropage" => 3,
+        "zeropage_x" | "zeropage_y" | "absolute" | "absolute_x" | "absolute_y" => 4,
+        "indirect" | "indirect_y" | "zeropage_indirect" => 5,
+        "indirect_x" | "indirect_absolute_x" => 6,
+        _ => return 0,
+    };
+    let indexed = matches!(mode, "absolute_x" | "absolute_y" | "indirect_y");
+    match access {
+        Access::Read => read,
+        Access::Write => read + u8::from(indexed),
+        Access::Modify => read + 2 + u8::from(indexed),
+    }
+}
+
+fn access(name: &str) -> Access {
+    match name {
+        "sta" | "stx" | "sty" | "stz" | "sax" => Access::Write,
+        "asl" | "lsr" | "rol" | "ror" | "inc" | "dec" | "trb" | "tsb" | "slo" | "rla" | "sre"
+        | "rra" | "dcp" | "isc" => Access::Modify,
+        _ if name.starts_with("rmb") || name.starts_with("smb") => Access::Modify,
+        _ => Access::Read,
+    }
+}
+
+// the reads pay for a page crossed, and so do the shifts of the cmos
+fn page_penalty(name: &str, mode: &str, access: Access, cmos: bool) -> bool {
+    let shift = matches!(name, "asl" | "lsr" | "rol" | "ror");
+    match mode {
+        "absolute_x" => access == Access::Read || (cmos && shift),
+        "absolute_y" | "indirect_y" => access == Access::Read,
+        _ => false,
+    }
+}
+
+// the break and unused bits only exist in the copies of the status pushed on
+// the stack: both set by php and brk, the break bit clear for the interrupts
+fn pushed(status: u8, brk: u8) -> u8 {
+    (status & !BRK) | brk | ALWAYS_SET
+}
+
+// as the live register has them, the unused bit reads as set
+fn unpushed(status: u8) -> u8 {
+    (status & !BRK) | ALWAYS_SET
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum InterruptBreakpoint {
+    Nmi,
+    Irq,
+    Brk,
+    Reset,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Register {
+    A,
+    X,
+    Y,
+    SP,
+    PC,
+    P,
+}
+
+impl Register {
+    // in the order debuggers show them
+    pub const ALL: [Register; 6] = [
+        Register::PC,
+        Register::A,
+        Register::X,
+        Register::Y,
+        Register::SP,
+        Register::P,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Register::A => "A",
+            Register::X => "X",
+            Register::Y => "Y",
+            Register::SP => "SP",
+            Register::PC => "PC",
+            Register::P => "P",
+        }
+    }
+
+    // any case
+    pub fn from_name(name: &str) -> Option<Register> {
+        Register::ALL
+            .iter()
+            .copied()
+            .find(|register| register.name().eq_ignore_ascii_case(name))
+    }
+
+    pub fn is_wide(self) -> bool {
+        self == Register::PC
+    }
+}
+
+// what is done with the operand once its address is known
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Access {
+    Read,
+    Write,
+    // read, the old value written back, then the new one
+    Modify,
+}
+
 struct OpCode<T: AddressBusIO<u16, u8>> {
-    fetch: fn(&mut MOS6502<T>),
-    fun: fn(&mut MOS6502<T>),
+    // addressing mode and operation fused in a single call
+    execute: fn(&mut MOS6502<T>),
+    // the operation alone, for tick doing the addressing a cycle at a time
+    operation: fn(&mut MOS6502<T>),
     name: &'static str,
+    mode: &'static str,
+    // memory operand loaded once the address is resolved
+    operand: bool,
+    access: Access,
+    // the read modify writes work on a instead of memory
+    accumulator: bool,
+    // without the page crossing and the branches taken
+    cycles: u8,
+    // one more cycle when an indexed read crosses a page
+    page_penalty: bool,
+    // the bus cycles of tick
+    sequence: Sequence,
+    // cli, sei and plp, the interrupt flag is changed after the poll
+    late_mask: bool,
 }
 
 // we cannot use derive as the generics in place generates mess
@@ -46,28 +267,383 @@ pub struct MOS6502<T: AddressBusIO<u16, u8>> {
     code_breakpoint: bool,
     requested_code_breakpoint: bool,
 
+    interrupt_breakpoints: Vec<InterruptBreakpoint>,
+    hit_interrupt_breakpoint: Option<InterruptBreakpoint>,
+    breakpoints: Vec<Breakpoint>,
+    hit_breakpoint: Option<Breakpoint>,
+    watchpoints: Vec<Watchpoint>,
+    hit_watchpoint: Option<WatchHit>,
+    on_watchpoint: Option<WatchHandler>,
+
+    stack_guard: Option<StackGuard>,
+    idle_detector: Option<IdleDetector>,
+    uninitialized_guard: Option<UninitializedGuard>,
+    coverage: Option<Coverage>,
+    step_recorder: Option<StepRecorder>,
+    access_recorder: Option<AccessRecorder>,
+    decode_cache: Option<DecodeCache>,
+
+    fault: Option<Error>,
+    // jammed by a kil, only a reset gets it going again
+    halted: bool,
+    // after a wai until an interrupt comes, masked or not
+    waiting: bool,
+    // after a stp until a reset
+    stopped: bool,
+
+    // the irq is held by the devices, the nmi latched on its edge
+    irq_line: bool,
+    nmi_pending: bool,
+    // the polls between instructions as the chip does them, otherwise the
+    // interrupt flag is the one at the start of the next instruction
+    accurate_interrupts: bool,
+    // the interrupt flag as seen by the last poll
+    interrupt_mask: bool,
+    // cycles left with rdy pulled low
+    stall: u64,
+    // the 6510 only
+    port: Option<ProcessorPort>,
+    so_line: bool,
+    power_up: PowerUpState,
+
+    variant: Variant,
+    quirks: Quirks,
+    // the dummy reads of the indexed modes and the write of the old value by
+    // the read modify writes, done by tick anyway
+    dummy_accesses: bool,
+    illegal_opcodes: IllegalOpcodes,
+    on_illegal_opcode: Option<IllegalOpcodeHandler>,
+    trace: Option<TraceSink>,
+
+    observers: Observers,
+    io_regions: Vec<(u16, u16)>,
+
     current_opcode: u8,
     opcode: OpCode<T>,
+    tick_state: TickState,
 
     opcodes: [OpCode<T>; 256],
 }
 
-macro_rules! opcode {
-    ($cpu:ident, $name:ident, $code:expr, $fetch:ident) => (
-        $cpu.register_opcode(stringify!($name), Self::$name, $code, Self::$fetch);
-    );
-    ($cpu:ident, $name:ident, $code:expr, $fetch:ident, $($codeN:expr, $fetchN:ident),+) => (
-        opcode!($cpu, $name, $code, $fetch);
-        opcode!($cpu, $name, $($codeN, $fetchN),+);
-    );
+// the whole instruction set, expanded into a table of fused handlers by
+// register_opcodes! and into a single match by dispatch!
+macro_rules! opcode_table {
+    ($callback:ident!($($arguments:tt)*)) => {
+        $callback!($($arguments)*;
+            adc: 0x69 immediate, 0x65 zeropage, 0x75 zeropage_x, 0x6d absolute, 0x7d absolute_x,
+                0x79 absolute_y, 0x61 indirect_x, 0x71 indirect_y;
+            bit: 0x24 zeropage, 0x2c absolute;
+            and: 0x29 immediate, 0x25 zeropage, 0x35 zeropage_x, 0x2d absolute, 0x3d absolute_x,
+                0x39 absolute_y, 0x21 indirect_x, 0x31 indirect_y;
+            asl: 0x0a accumulator, 0x06 zeropage, 0x16 zeropage_x, 0x0e absolute, 0x1e absolute_x;
+            eor: 0x49 immediate, 0x45 zeropage, 0x55 zeropage_x, 0x4d absolute, 0x5d absolute_x,
+                0x59 absolute_y, 0x41 indirect_x, 0x51 indirect_y;
+            lsr: 0x4a accumulator, 0x46 zeropage, 0x56 zeropage_x, 0x4e absolute, 0x5e absolute_x;
+            ora: 0x09 immediate, 0x05 zeropage, 0x15 zeropage_x, 0x0d absolute, 0x1d absolute_x,
+                0x19 absolute_y, 0x01 indirect_x, 0x11 indirect_y;
+            bpl: 0x10 relative;
+            bmi: 0x30 relative;
+            bvc: 0x50 relative;
+            bvs: 0x70 relative;
+            beq: 0xf0 relative;
+            bne: 0xd0 relative;
+            bcc: 0x90 relative;
+            bcs: 0xb0 relative;
+            brk: 0x00 implied;
+            cmp: 0xc9 immediate, 0xc5 zeropage, 0xd5 zeropage_x, 0xcd absolute, 0xdd absolute_x,
+                0xd9 absolute_y, 0xc1 indirect_x, 0xd1 indirect_y;
+            cpx: 0xe0 immediate, 0xe4 zeropage, 0xec absolute;
+            cpy: 0xc0 immediate, 0xc4 zeropage, 0xcc absolute;
+            dec: 0xc6 zeropage, 0xd6 zeropage_x, 0xce absolute, 0xde absolute_x;
+            inc: 0xe6 zeropage, 0xf6 zeropage_x, 0xee absolute, 0xfe absolute_x;
+            clc: 0x18 implied;
+            sec: 0x38 implied;
+            cli: 0x58 implied;
+            sei: 0x78 implied;
+            clv: 0xb8 implied;
+            cld: 0xd8 implied;
+            sed: 0xf8 implied;
+            jmp: 0x4c absolute, 0x6c indirect;
+            jsr: 0x20 absolute;
+            lda: 0xa9 immediate, 0xa5 zeropage, 0xb5 zeropage_x, 0xad absolute, 0xbd absolute_x,
+                0xb9 absolute_y, 0xa1 indirect_x, 0xb1 indirect_y;
+            ldx: 0xa2 immediate, 0xa6 zeropage, 0xb6 zeropage_y, 0xae absolute, 0xbe absolute_y;
+            ldy: 0xa0 immediate, 0xa4 zeropage, 0xb4 zeropage_x, 0xac absolute, 0xbc absolute_x;
+            nop: 0xea implied;
+            tax: 0xaa implied;
+            txa: 0x8a implied;
+            dex: 0xca implied;
+            inx: 0xe8 implied;
+            tay: 0xa8 implied;
+            tya: 0x98 implied;
+            dey: 0x88 implied;
+            iny: 0xc8 implied;
+            rts: 0x60 implied;
+            rti: 0x40 implied;
+            sbc: 0xe9 immediate, 0xe5 zeropage, 0xf5 zeropage_x, 0xed absolute, 0xfd absolute_x,
+                0xf9 absolute_y, 0xe1 indirect_x, 0xf1 indirect_y;
+            sta: 0x85 zeropage, 0x95 zeropage_x, 0x8d absolute, 0x9d absolute_x,
+                0x99 absolute_y, 0x81 indirect_x, 0x91 indirect_y;
+            stx: 0x86 zeropage, 0x96 zeropage_y, 0x8e absolute;
+            sty: 0x84 zeropage, 0x94 zeropage_x, 0x8c absolute;
+            txs: 0x9a implied;
+            tsx: 0xba implied;
+            pha: 0x48 implied;
+            pla: 0x68 implied;
+            php: 0x08 implied;
+            plp: 0x28 implied;
+            rol: 0x2a accumulator, 0x26 zeropage, 0x36 zeropage_x, 0x2e absolute, 0x3e absolute_x;
+            ror: 0x6a accumulator, 0x66 zeropage, 0x76 zeropage_x, 0x6e absolute, 0x7e absolute_x;
+        )
+    };
+}
+
+// the stable undocumented nmos opcodes, only registered when the builder is
+// told to run them; sbc and nop are the documented handlers
+macro_rules! illegal_opcode_table {
+    ($callback:ident!($($arguments:tt)*)) => {
+        $callback!($($arguments)*;
+            lax: 0xa7 zeropage, 0xb7 zeropage_y, 0xaf absolute, 0xbf absolute_y, 0xa3 indirect_x,
+                0xb3 indirect_y;
+            sax: 0x87 zeropage, 0x97 zeropage_y, 0x8f absolute, 0x83 indirect_x;
+            dcp: 0xc7 zeropage, 0xd7 zeropage_x, 0xcf absolute, 0xdf absolute_x, 0xdb absolute_y,
+                0xc3 indirect_x, 0xd3 indirect_y;
+            isc: 0xe7 zeropage, 0xf7 zeropage_x, 0xef absolute, 0xff absolute_x, 0xfb absolute_y,
+                0xe3 indirect_x, 0xf3 indirect_y;
+            slo: 0x07 zeropage, 0x17 zeropage_x, 0x0f absolute, 0x1f absolute_x, 0x1b absolute_y,
+                0x03 indirect_x, 0x13 indirect_y;
+            rla: 0x27 zeropage, 0x37 zeropage_x, 0x2f absolute, 0x3f absolute_x, 0x3b absolute_y,
+                0x23 indirect_x, 0x33 indirect_y;
+            sre: 0x47 zeropage, 0x57 zeropage_x, 0x4f absolute, 0x5f absolute_x, 0x5b absolute_y,
+                0x43 indirect_x, 0x53 indirect_y;
+            rra: 0x67 zeropage, 0x77 zeropage_x, 0x6f absolute, 0x7f absolute_x, 0x7b absolute_y,
+                0x63 indirect_x, 0x73 indirect_y;
+            anc: 0x0b immediate, 0x2b immediate;
+            alr: 0x4b immediate;
+            arr: 0x6b immediate;
+            sbx: 0xcb immediate;
+            sbc: 0xeb immediate;
+            nop: 0x1a implied, 0x3a implied, 0x5a implied, 0x7a implied, 0xda implied,
+                0xfa implied, 0x80 immediate, 0x82 immediate, 0x89 immediate, 0xc2 immediate,
+                0xe2 immediate, 0x04 zeropage, 0x44 zeropage, 0x64 zeropage, 0x14 zeropage_x,
+                0x34 zeropage_x, 0x54 zeropage_x, 0x74 zeropage_x, 0xd4 zeropage_x,
+                0xf4 zeropage_x, 0x0c absolute, 0x1c absolute_x, 0x3c absolute_x,
+                0x5c absolute_x, 0x7c absolute_x, 0xdc absolute_x, 0xfc absolute_x;
+        )
+    };
+}
+
+macro_rules! register_opcodes {
+    ($cpu:ident; $($name:ident: $($code:literal $fetch:ident),+;)*) => {
+        $($(
+            $cpu.register_opcode(
+                stringify!($name),
+                $code,
+                stringify!($fetch),
+                |cpu: &mut MOS6502<_>| {
+                    cpu.$fetch();
+                    cpu.operand();
+                    cpu.$name();
+                },
+                |cpu: &mut MOS6502<_>| cpu.$name(),
+            );
+        )+)*
+    };
+}
+
+// no indirect calls, every handler can be inlined in its arm
+#[cfg(feature = "match_dispatch")]
+macro_rules! dispatch {
+    ($cpu:ident, $opcode:expr; $($name:ident: $($code:literal $fetch:ident),+;)*) => {
+        match $opcode {
+            $($(
+                $code => {
+                    $cpu.$fetch();
+                    $cpu.operand();
+                    $cpu.$name();
+                }
+            )+)*
+            // the undocumented ones when registered, invalid otherwise
+            _ => ($cpu.opcode.execute)($cpu),
+        }
+    };
+}
+
+// name and addressing mode without a cpu instance, for the opcodes table
+macro_rules! describe {
+    ($code:ident; $($name:ident: $($op:literal $mode:ident),+;)*) => {
+        match $code {
+            $($(
+                $op => (stringify!($name), stringify!($mode)),
+            )+)*
+            _ => ("-", "invalid"),
+        }
+    };
+}
+
+// what the wdc 65c02 adds, registered for the cmos variant, with the bit
+// instructions of the rockwell parts and the undefined opcodes as nops
+macro_rules! cmos_opcode_table {
+    ($callback:ident!($($arguments:tt)*)) => {
+        $callback!($($arguments)*;
+            adc: 0x72 zeropage_indirect;
+            and: 0x32 zeropage_indirect;
+            bbr0: 0x0f zeropage_relative;
+            bbr1: 0x1f zeropage_relative;
+            bbr2: 0x2f zeropage_relative;
+            bbr3: 0x3f zeropage_relative;
+            bbr4: 0x4f zeropage_relative;
+            bbr5: 0x5f zeropage_relative;
+            bbr6: 0x6f zeropage_relative;
+            bbr7: 0x7f zeropage_relative;
+            bbs0: 0x8f zeropage_relative;
+            bbs1: 0x9f zeropage_relative;
+            bbs2: 0xaf zeropage_relative;
+            bbs3: 0xbf zeropage_relative;
+            bbs4: 0xcf zeropage_relative;
+            bbs5: 0xdf zeropage_relative;
+            bbs6: 0xef zeropage_relative;
+            bbs7: 0xff zeropage_relative;
+            bit: 0x89 immediate, 0x34 zeropage_x, 0x3c absolute_x;
+            bra: 0x80 relative;
+            cmp: 0xd2 zeropage_indirect;
+            dec: 0x3a accumulator;
+            eor: 0x52 zeropage_indirect;
+            inc: 0x1a accumulator;
+            jmp: 0x7c indirect_absolute_x;
+            lda: 0xb2 zeropage_indirect;
+            nop: 0x02 immediate, 0x22 immediate, 0x42 immediate, 0x62 immediate, 0x82 immediate,
+                0xc2 immediate, 0xe2 immediate, 0x44 zeropage, 0x54 zeropage_x, 0xd4 zeropage_x,
+                0xf4 zeropage_x, 0x5c absolute, 0xdc absolute, 0xfc absolute, 0x03 implied,
+                0x13 implied, 0x23 implied, 0x33 implied, 0x43 implied, 0x53 implied,
+                0x63 implied, 0x73 implied, 0x83 implied, 0x93 implied, 0xa3 implied,
+                0xb3 implied, 0xc3 implied, 0xd3 implied, 0xe3 implied, 0xf3 implied,
+                0x0b implied, 0x1b implied, 0x2b implied, 0x3b implied, 0x4b implied,
+                0x5b implied, 0x6b implied, 0x7b implied, 0x8b implied, 0x9b implied,
+                0xab implied, 0xbb implied, 0xeb implied, 0xfb implied;
+            ora: 0x12 zeropage_indirect;
+            phx: 0xda implied;
+            phy: 0x5a implied;
+            plx: 0xfa implied;
+            ply: 0x7a implied;
+            rmb0: 0x07 zeropage;
+            rmb1: 0x17 zeropage;
+            rmb2: 0x27 zeropage;
+            rmb3: 0x37 zeropage;
+            rmb4: 0x47 zeropage;
+            rmb5: 0x57 zeropage;
+            rmb6: 0x67 zeropage;
+            rmb7: 0x77 zeropage;
+            sbc: 0xf2 zeropage_indirect;
+            smb0: 0x87 zeropage;
+            smb1: 0x97 zeropage;
+            smb2: 0xa7 zeropage;
+            smb3: 0xb7 zeropage;
+            smb4: 0xc7 zeropage;
+            smb5: 0xd7 zeropage;
+            smb6: 0xe7 zeropage;
+            smb7: 0xf7 zeropage;
+            sta: 0x92 zeropage_indirect;
+            stp: 0xdb implied;
+            stz: 0x64 zeropage, 0x74 zeropage_x, 0x9c absolute, 0x9e absolute_x;
+            trb: 0x14 zeropage, 0x1c absolute;
+            tsb: 0x04 zeropage, 0x0c absolute;
+            wai: 0xcb implied;
+        )
+    };
+}
+
+// the bit instructions for each bit, an operation per opcode
+macro_rules! bit_instructions {
+    ($($bit:literal: $rmb:ident $smb:ident $bbr:ident $bbs:ident;)*) => {
+        impl<T: AddressBusIO<u16, u8>> MOS6502<T> {
+            $(
+                fn $rmb(&mut self) {
+                    self.reset_bit($bit);
+                }
+
+                fn $smb(&mut self) {
+                    self.set_bit($bit);
+                }
+
+                fn $bbr(&mut self) {
+                    self.branch_on_bit($bit, false);
+                }
+
+                fn $bbs(&mut self) {
+                    self.branch_on_bit($bit, true);
+                }
+            )*
+        }
+    };
+}
+
+bit_instructions! {
+    0: rmb0 smb0 bbr0 bbs0;
+    1: rmb1 smb1 bbr1 bbs1;
+    2: rmb2 smb2 bbr2 bbs2;
+    3: rmb3 smb3 bbr3 bbs3;
+    4: rmb4 smb4 bbr4 bbs4;
+    5: rmb5 smb5 bbr5 bbs5;
+    6: rmb6 smb6 bbr6 bbs6;
+    7: rmb7 smb7 bbr7 bbs7;
+}
+
+// what the huc6280 adds over the cmos table, it has no wai and stp
+macro_rules! huc6280_opcode_table {
+    ($callback:ident!($($arguments:tt)*)) => {
+        $callback!($($arguments)*;
+            bsr: 0x44 relative;
+            cla: 0x62 implied;
+            clx: 0x82 implied;
+            cly: 0xc2 implied;
+            csh: 0xd4 implied;
+            csl: 0x54 implied;
+            nop: 0xcb implied, 0xdb implied;
+            say: 0x42 implied;
+            st0: 0x03 immediate;
+            st1: 0x13 immediate;
+            st2: 0x23 immediate;
+            sxy: 0x02 implied;
+            tai: 0xf3 block_transfer;
+            tam: 0x53 immediate;
+            tdd: 0xc3 block_transfer;
+            tia: 0xe3 block_transfer;
+            tii: 0x73 block_transfer;
+            tin: 0xd3 block_transfer;
+            tma: 0x43 immediate;
+            tst: 0x83 immediate_zeropage, 0x93 immediate_absolute, 0xa3 immediate_zeropage_x,
+                0xb3 immediate_absolute_x;
+        )
+    };
+}
+
+// after the opcode table macros, it registers its own with them
+pub mod huc6280;
+
+const fn describe(code: u8) -> (&'static str, &'static str) {
+    opcode_table!(describe!(code))
+}
+
+const fn describe_illegal(code: u8) -> (&'static str, &'static str) {
+    illegal_opcode_table!(describe!(code))
 }
 
 impl<T: AddressBusIO<u16, u8>> MOS6502<T> {
     pub fn new(bus: T) -> MOS6502<T> {
         let noop = OpCode {
-            fetch: MOS6502::invalid,
-            fun: MOS6502::nop,
+            execute: MOS6502::invalid,
+            operation: MOS6502::invalid,
             name: "-",
+            mode: "invalid",
+            operand: false,
+            access: Access::Read,
+            accumulator: false,
+            // the fetch and a read of the next byte, jammed or not
+            cycles: 2,
+            page_penalty: false,
+            sequence: Sequence::Implied,
+            late_mask: false,
         };
 
         let mut cpu = MOS6502 {
@@ -81,6 +657,7 @@ impl<T: AddressBusIO<u16, u8>> MOS6502<T> {
             ticks: 0,
             opcode: noop,
             current_opcode: 0,
+            tick_state: TickState::new(),
 
             debug_pc: 0,
             debug_line: "".to_string(),
@@ -90,6 +667,44 @@ impl<T: AddressBusIO<u16, u8>> MOS6502<T> {
             code_breakpoint: false,
             requested_code_breakpoint: false,
 
+            interrupt_breakpoints: Vec::new(),
+            hit_interrupt_breakpoint: None,
+            breakpoints: Vec::new(),
+            hit_breakpoint: None,
+            watchpoints: Vec::new(),
+            hit_watchpoint: None,
+            on_watchpoint: None,
+
+            stack_guard: None,
+            idle_detector: None,
+            uninitialized_guard: None,
+            coverage: None,
+            step_recorder: None,
+            access_recorder: None,
+            decode_cache: None,
+            fault: None,
+            halted: false,
+            waiting: false,
+            stopped: false,
+            irq_line: false,
+            nmi_pending: false,
+            accurate_interrupts: false,
+            interrupt_mask: true,
+            stall: 0,
+            port: None,
+            so_line: false,
+            power_up: PowerUpState::new(),
+
+            variant: Variant::Nmos,
+            quirks: Quirks::of(Variant::Nmos),
+            dummy_accesses: false,
+            illegal_opcodes: IllegalOpcodes::Jam,
+            on_illegal_opcode: None,
+            trace: None,
+
+            observers: Observers::new(),
+            io_regions: Vec::new(),
+
             debug: false,
 
             status: ALWAYS_SET | INTERRUPT,
@@ -97,162 +712,648 @@ impl<T: AddressBusIO<u16, u8>> MOS6502<T> {
             bus: bus,
         };
 
-        opcode!(
-            cpu, adc, 0x69, immediate, 0x65, zeropage, 0x75, zeropage_x, 0x6d, absolute, 0x7d,
-            absolute_x, 0x79, absolute_y, 0x61, indirect_x, 0x71, indirect_y
-        );
+        opcode_table!(register_opcodes!(cpu));
 
-        opcode!(cpu, bit, 0x24, zeropage, 0x2c, absolute);
+        cpu
+    }
 
-        opcode!(
-            cpu, and, 0x29, immediate, 0x25, zeropage, 0x35, zeropage_x, 0x2d, absolute, 0x3d,
-            absolute_x, 0x39, absolute_y, 0x21, indirect_x, 0x31, indirect_y
-        );
+    pub fn builder(bus: T) -> Builder<T> {
+        Builder::new(bus)
+    }
 
-        opcode!(cpu, asl_a, 0x0a, accumulator);
+    // pc is the only 16 bit one
+    pub fn get(&self, register: Register) -> u16 {
+        match register {
+            Register::A => u16::from(self.a),
+            Register::X => u16::from(self.x),
+            Register::Y => u16::from(self.y),
+            Register::SP => u16::from(self.sp),
+            Register::PC => self.pc,
+            Register::P => u16::from(self.status),
+        }
+    }
 
-        opcode!(cpu, lsr_a, 0x4a, accumulator);
+    // the high byte is dropped for the 8 bit ones, and the break bit of p as
+    // the live register does not have one
+    pub fn set(&mut self, register: Register, value: u16) {
+        match register {
+            Register::A => self.a = value as u8,
+            Register::X => self.x = value as u8,
+            Register::Y => self.y = value as u8,
+            Register::SP => self.sp = value as u8,
+            Register::PC => self.pc = value,
+            Register::P => self.status = unpushed(value as u8),
+        }
+    }
 
-        opcode!(cpu, asl, 0x06, zeropage, 0x16, zeropage_x, 0x0e, absolute, 0x1e, absolute_x);
+    pub fn variant(&self) -> Variant {
+        self.variant
+    }
 
-        opcode!(
-            cpu, eor, 0x49, immediate, 0x45, zeropage, 0x55, zeropage_x, 0x4d, absolute, 0x5d,
-            absolute_x, 0x59, absolute_y, 0x41, indirect_x, 0x51, indirect_y
-        );
+    pub fn quirks(&self) -> Quirks {
+        self.quirks
+    }
 
-        opcode!(cpu, lsr, 0x46, zeropage, 0x56, zeropage_x, 0x4e, absolute, 0x5e, absolute_x);
+    fn register_opcode(
+        &mut self,
+        name: &'static str,
+        code: u8,
+        mode: &'static str,
+        execute: fn(&mut MOS6502<T>),
+        operation: fn(&mut MOS6502<T>),
+    ) {
+        // stores and jumps only need the address, a dummy read would hit
+        // read-sensitive io registers
+        let operand = match mode {
+            // the fetch of zeropage_relative reads the operand between its two
+            // bytes, the ones of the huc6280 tst and block transfers do their
+            // reads themselves
+            "implied"
+            | "accumulator"
+            | "immediate"
+            | "relative"
+            | "zeropage_relative"
+            | "immediate_zeropage"
+            | "immediate_zeropage_x"
+            | "immediate_absolute"
+            | "immediate_absolute_x"
+            | "block_transfer" => false,
+            _ => !matches!(name, "sta" | "stx" | "sty" | "stz" | "sax" | "jmp" | "jsr"),
+        };
+        let access = access(name);
+        let cmos = self.variant.is_cmos();
+        // the one byte nops of the 65c02 are done with their fetch and $5c
+        // takes eight cycles, tick runs those whole
+        let undefined = self.variant == Variant::Cmos && name == "nop";
+        let (cycles, sequence) = match code {
+            _ if undefined && code & 0x03 == 0x03 => (1, Sequence::Whole),
+            0x5c if undefined => (8, Sequence::Whole),
+            _ => (
+                cycles(name, mode, access, cmos),
+                cycle::sequence(name, mode, cmos),
+            ),
+        };
+        self.opcodes[code as usize] = OpCode {
+            execute,
+            operation,
+            name: name,
+            mode,
+            operand,
+            access,
+            accumulator: mode == "accumulator",
+            cycles,
+            page_penalty: page_penalty(name, mode, access, cmos),
+            sequence,
+            late_mask: matches!(name, "cli" | "sei" | "plp"),
+        };
+    }
 
-        opcode!(
-            cpu, ora, 0x09, immediate, 0x05, zeropage, 0x15, zeropage_x, 0x0d, absolute, 0x1d,
-            absolute_x, 0x19, absolute_y, 0x01, indirect_x, 0x11, indirect_y
-        );
+    fn read8(&mut self, addr: u16) -> u8 {
+        if let Some(ref mut guard) = self.uninitialized_guard {
+            guard.read(self.debug_pc, addr);
+        }
+        let value = self.bus_read(addr);
+        if !self.watchpoints.is_empty() {
+            self.watch(addr, value, false);
+        }
+        value
+    }
 
-        opcode!(cpu, bpl, 0x10, relative);
-        opcode!(cpu, bmi, 0x30, relative);
+    // bus access for debugging purposes, not seen by any guard
+    fn peek8(&mut self, addr: u16) -> u8 {
+        self.bus_read(addr)
+    }
 
-        opcode!(cpu, bvc, 0x50, relative);
-        opcode!(cpu, bvs, 0x70, relative);
+    // the accesses the chip makes along the way, straight to the bus as the
+    // guards would take them for the program's own
+    fn dummy_read8(&mut self, addr: u16) {
+        self.bus_read(addr);
+    }
 
-        opcode!(cpu, beq, 0xf0, relative);
-        opcode!(cpu, bne, 0xd0, relative);
+    fn dummy_write8(&mut self, addr: u16, value: u8) {
+        self.bus_write(addr, value);
+    }
 
-        opcode!(cpu, bcc, 0x90, relative);
-        opcode!(cpu, bcs, 0xb0, relative);
+    // the processor port of the 6510 takes its two addresses off the bus
+    fn bus_read(&mut self, addr: u16) -> u8 {
+        match self.port {
+            Some(ref port) if ProcessorPort::decodes(addr) => port.read(addr),
+            _ => self.bus.read(addr),
+        }
+    }
 
-        opcode!(cpu, brk, 0x00, implied);
+    fn bus_write(&mut self, addr: u16, value: u8) {
+        match self.port {
+            Some(ref mut port) if ProcessorPort::decodes(addr) => port.write(addr, value),
+            _ => self.bus.write(addr, value),
+        }
+    }
 
-        opcode!(
-            cpu, cmp, 0xc9, immediate, 0xc5, zeropage, 0xd5, zeropage_x, 0xcd, absolute, 0xdd,
-            absolute_x, 0xd9, absolute_y, 0xc1, indirect_x, 0xd1, indirect_y
-        );
-        opcode!(cpu, cpx, 0xe0, immediate, 0xe4, zeropage, 0xec, absolute);
-        opcode!(cpu, cpy, 0xc0, immediate, 0xc4, zeropage, 0xcc, absolute);
-
-        opcode!(cpu, dec, 0xc6, zeropage, 0xd6, zeropage_x, 0xce, absolute, 0xde, absolute_x);
-        opcode!(cpu, inc, 0xe6, zeropage, 0xf6, zeropage_x, 0xee, absolute, 0xfe, absolute_x);
-
-        opcode!(cpu, clc, 0x18, implied);
-        opcode!(cpu, sec, 0x38, implied);
-        opcode!(cpu, cli, 0x58, implied);
-        opcode!(cpu, sei, 0x78, implied);
-        opcode!(cpu, clv, 0xb8, implied);
-        opcode!(cpu, cld, 0xd8, implied);
-        opcode!(cpu, sed, 0xf8, implied);
-
-        opcode!(cpu, jmp, 0x4c, absolute, 0x6c, indirect);
-        opcode!(cpu, jsr, 0x20, absolute);
-
-        opcode!(
-            cpu, lda, 0xa9, immediate, 0xa5, zeropage, 0xb5, zeropage_x, 0xad, absolute, 0xbd,
-            absolute_x, 0xb9, absolute_y, 0xa1, indirect_x, 0xb1, indirect_y
-        );
-        opcode!(
-            cpu, ldx, 0xa2, immediate, 0xa6, zeropage, 0xb6, zeropage_y, 0xae, absolute, 0xbe,
-            absolute_y
-        );
-        opcode!(
-            cpu, ldy, 0xa0, immediate, 0xa4, zeropage, 0xb4, zeropage_x, 0xac, absolute, 0xbc,
-            absolute_x
-        );
+    pub fn processor_port(&self) -> Option<&ProcessorPort> {
+        self.port.as_ref()
+    }
 
-        opcode!(cpu, nop, 0xea, implied);
+    pub fn processor_port_mut(&mut self) -> Option<&mut ProcessorPort> {
+        self.port.as_mut()
+    }
 
-        opcode!(cpu, tax, 0xaa, implied);
-        opcode!(cpu, txa, 0x8a, implied);
-        opcode!(cpu, dex, 0xca, implied);
-        opcode!(cpu, inx, 0xe8, implied);
-        opcode!(cpu, tay, 0xa8, implied);
-        opcode!(cpu, tya, 0x98, implied);
-        opcode!(cpu, tay, 0xa8, implied);
-        opcode!(cpu, dey, 0x88, implied);
-        opcode!(cpu, iny, 0xc8, implied);
+    fn read16(&mut self, addr: u16) -> u16 {
+        let low = u16::from(self.read8(addr));
+        let high = u16::from(self.read8(addr.wrapping_add(1)));
+        (high << 8) | low
+    }
 
-        opcode!(cpu, rts, 0x60, implied);
+    // a pointer in the zeropage, its high byte wraps to $00 instead of $0100
+    fn read16_zeropage(&mut self, addr: u8) -> u16 {
+        let low = u16::from(self.read8(self.zeropage_address(addr)));
+        let high = u16::from(self.read8(self.zeropage_address(addr.wrapping_add(1))));
+        (high << 8) | low
+    }
 
-        opcode!(cpu, rti, 0x40, implied);
+    fn zeropage_address(&self, offset: u8) -> u16 {
+        self.quirks.zeropage | u16::from(offset)
+    }
 
-        opcode!(
-            cpu, sbc, 0xe9, immediate, 0xe5, zeropage, 0xf5, zeropage_x, 0xed, absolute, 0xfd,
-            absolute_x, 0xf9, absolute_y, 0xe1, indirect_x, 0xf1, indirect_y
-        );
+    fn stack_address(&self) -> u16 {
+        self.quirks.zeropage + 0x100 + u16::from(self.sp)
+    }
+
+    // where the huc6280 has the vector of the 6502 one
+    fn vector(&self, vector: u16, brk: u8) -> u16 {
+        if !self.quirks.huc6280_vectors {
+            return vector;
+        }
+        match vector {
+            0xfffa => 0xfffc,
+            0xfffc => 0xfffe,
+            _ if brk != 0 => 0xfff6,
+            _ => 0xfff8,
+        }
+    }
+
+    fn write8(&mut self, addr: u16, value: u8) {
+        let stack = self.quirks.zeropage + 0x100;
+        if let Some(ref mut guard) = self.stack_guard {
+            guard.write(self.debug_pc, self.sp, stack, addr);
+        }
+        if let Some(ref mut detector) = self.idle_detector {
+            detector.write();
+        }
+        if let Some(ref mut guard) = self.uninitialized_guard {
+            guard.write(addr);
+        }
+        if let Some(ref mut coverage) = self.coverage {
+            coverage.write(self.debug_pc, addr);
+        }
+        if let Some(ref mut recorder) = self.step_recorder {
+            recorder.write(addr, value);
+        }
+        if let Some(ref mut recorder) = self.access_recorder {
+            if addr == self.addr {
+                recorder.write(value);
+            }
+        }
+        if let Some(ref mut cache) = self.decode_cache {
+            cache.write(addr);
+        }
+        if !self.watchpoints.is_empty() {
+            self.watch(addr, value, true);
+        }
+        if !self.io_regions.is_empty() && self.is_io(addr) {
+            #[cfg(feature = "tracing")]
+            ::tracing::debug!(pc = self.debug_pc, address = addr, value, "io write");
+            let event = CpuEvent::IoWrite {
+                address: addr,
+                value,
+            };
+            self.observers.emit(&event);
+        }
+        self.bus_write(addr, value)
+    }
+
+    fn is_io(&self, addr: u16) -> bool {
+        self.io_regions
+            .iter()
+            .any(|&(start, end)| addr >= start && addr <= end)
+    }
+
+    fn push8(&mut self, value: u8) {
+        if let Some(ref mut guard) = self.stack_guard {
+            guard.push(self.debug_pc, self.sp);
+        }
+        let sp = self.stack_address();
+        self.write8(sp, value);
+        self.sp = self.sp.wrapping_sub(1);
+    }
+
+    fn pull8(&mut self) -> u8 {
+        if let Some(ref mut guard) = self.stack_guard {
+            guard.pull(self.debug_pc, self.sp);
+        }
+        self.sp = self.sp.wrapping_add(1);
+        let sp = self.stack_address();
+        self.read8(sp)
+    }
+
+    fn push_return_address(&mut self, address: u16) {
+        self.push8((address >> 8) as u8);
+        self.push8((address & 0x00ff) as u8);
+        if let Some(ref mut guard) = self.stack_guard {
+            guard.return_address_pushed(self.sp);
+        }
+    }
+
+    fn pull_return_address(&mut self) -> u16 {
+        let low = u16::from(self.pull8());
+        let high = u16::from(self.pull8());
+        high << 8 | low
+    }
 
-        opcode!(
-            cpu, sta, 0x85, zeropage, 0x95, zeropage_x, 0x8d, absolute, 0x9d, absolute_x, 0x99,
-            absolute_y, 0x81, indirect_x, 0x91, indirect_y
+    // step reporting an invalid opcode, that step silently stays on (and
+    // every step after it while halted)
+    pub fn try_step(&mut self) -> Result<(), Error> {
+        self.step();
+        match self.fault.take() {
+            Some(fault) => Err(fault),
+            None if self.halted => Err(Error::InvalidOpcode {
+                pc: self.pc,
+                opcode: u16::from(self.current_opcode),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+
+    pub fn is_waiting(&self) -> bool {
+        self.waiting
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.stopped
+    }
+
+    // an irq wakes a waiting cpu up even when masked, it just goes on with
+    // the next instruction then
+    fn wake(&mut self) -> bool {
+        if self.pending_interrupt(false).is_some() {
+            self.waiting = false;
+        }
+        !self.waiting
+    }
+
+    // the last fault since the previous call, for the hosts calling step
+    pub fn take_fault(&mut self) -> Option<Error> {
+        self.fault.take()
+    }
+
+    // unlike raise the interrupt is taken by the next step, between
+    // instructions; the irq stays asserted until released
+    pub fn irq(&mut self, asserted: bool) {
+        self.irq_line = asserted;
+    }
+
+    pub fn nmi(&mut self) {
+        self.nmi_pending = true;
+    }
+
+    // the set overflow input, v is set when it gets asserted (the falling
+    // edge of the pin) and holding it does nothing more
+    pub fn so(&mut self, asserted: bool) {
+        if asserted && !self.so_line {
+            self.set_flag(OVERFLOW, true);
+        }
+        self.so_line = asserted;
+    }
+
+    // rdy held low for cycles, as by a dma: the cpu waits on its reads, step
+    // takes the whole stall before the next instruction while tick only
+    // holds the read cycles, the writes go on as on the chip
+    pub fn stall(&mut self, cycles: u64) {
+        self.stall += cycles;
+    }
+
+    pub fn is_stalled(&self) -> bool {
+        self.stall != 0
+    }
+
+    // the vector of the interrupt to take, the nmi first and the irq unless
+    // masked
+    fn pending_interrupt(&self, masked: bool) -> Option<u16> {
+        if self.nmi_pending {
+            Some(0xfffa)
+        } else if self.irq_line && !masked {
+            Some(0xfffe)
+        } else {
+            None
+        }
+    }
+
+    // cli, sei and plp change the interrupt flag on their last cycle, after
+    // the poll, so it only counts from the instruction after the next one
+    fn polled_mask(&self, before: u8) -> bool {
+        let status = if self.opcode.late_mask {
+            before
+        } else {
+            self.status
+        };
+        status & INTERRUPT != 0
+    }
+
+    // the one step takes before the next instruction
+    fn due_interrupt(&self) -> Option<u16> {
+        let masked = if self.accurate_interrupts {
+            self.interrupt_mask
+        } else {
+            self.get_flag(INTERRUPT)
+        };
+        self.pending_interrupt(masked)
+    }
+
+    // two cycles more than raise for the fetch that was dropped
+    fn service_interrupts(&mut self) -> bool {
+        let vector = match self.due_interrupt() {
+            Some(vector) => vector,
+            None => return false,
+        };
+        self.debug_pc = self.pc;
+        let kind = if vector == 0xfffa {
+            self.nmi_pending = false;
+            InterruptBreakpoint::Nmi
+        } else {
+            InterruptBreakpoint::Irq
+        };
+        self.interrupt(vector, 0);
+        self.interrupt_breakpoint(kind);
+        self.interrupt_mask = true;
+        self.ticks += 7;
+        true
+    }
+
+    // the bus as it is, without going through the guards
+    pub fn bus(&self) -> &T {
+        &self.bus
+    }
+
+    pub fn bus_mut(&mut self) -> &mut T {
+        &mut self.bus
+    }
+
+    pub fn into_bus(self) -> T {
+        self.bus
+    }
+
+    // the text and the length of the instruction at address, only peeks at
+    // the bus so no guard, cache or register sees it
+    pub fn disassemble_at(&mut self, address: u16) -> (String, u8) {
+        let instruction = self.decode(address);
+        let text = disasm::text(
+            address,
+            instruction.opcode,
+            instruction.mnemonic,
+            instruction.mode,
+            instruction.operands(),
         );
+        (text, instruction.length)
+    }
 
-        opcode!(cpu, stx, 0x86, zeropage, 0x96, zeropage_x, 0x8e, absolute);
-        opcode!(cpu, sty, 0x84, zeropage, 0x94, zeropage_x, 0x8c, absolute);
+    pub fn enable_stack_guard(&mut self, threshold: u8) {
+        self.stack_guard = Some(StackGuard::new(threshold));
+    }
 
-        opcode!(cpu, txs, 0x9a, implied);
-        opcode!(cpu, tsx, 0xba, implied);
-        opcode!(cpu, pha, 0x48, implied);
-        opcode!(cpu, pla, 0x68, implied);
-        opcode!(cpu, php, 0x08, implied);
-        opcode!(cpu, plp, 0x28, implied);
+    pub fn disable_stack_guard(&mut self) {
+        self.stack_guard = None;
+    }
 
-        opcode!(cpu, rol_a, 0x2a, accumulator);
+    pub fn take_stack_violations(&mut self) -> Vec<StackViolation> {
+        match self.stack_guard {
+            Some(ref mut guard) => guard.take_violations(),
+            None => Vec::new(),
+        }
+    }
 
-        opcode!(cpu, rol, 0x26, zeropage, 0x36, zeropage_x, 0x2e, absolute, 0x3e, absolute_x);
+    // trap turns the first read of each never-written byte into a code breakpoint
+    pub fn enable_uninitialized_guard(&mut self, start: u16, end: u16, trap: bool) {
+        self.uninitialized_guard = Some(UninitializedGuard::new(start, end, trap));
+    }
 
-        opcode!(cpu, ror_a, 0x6a, accumulator);
+    pub fn disable_uninitialized_guard(&mut self) {
+        self.uninitialized_guard = None;
+    }
 
-        opcode!(cpu, ror, 0x66, zeropage, 0x76, zeropage_x, 0x6e, absolute, 0x7e, absolute_x);
+    // for areas filled by loaders before the cpu starts
+    pub fn mark_initialized(&mut self, start: u16, end: u16) {
+        if let Some(ref mut guard) = self.uninitialized_guard {
+            guard.mark_initialized(start, end);
+        }
+    }
 
-        cpu
+    pub fn take_uninitialized_reads(&mut self) -> Vec<UninitializedRead> {
+        match self.uninitialized_guard {
+            Some(ref mut guard) => guard.take_reads(),
+            None => Vec::new(),
+        }
     }
 
-    fn register_opcode(
-        &mut self,
-        name: &'static str,
-        fun: fn(&mut MOS6502<T>),
-        code: u8,
-        fetch: fn(&mut MOS6502<T>),
-    ) {
-        self.opcodes[code as usize] = OpCode {
-            fetch: fetch,
-            fun: fun,
-            name: name,
+    pub fn enable_coverage(&mut self) {
+        self.coverage = Some(Coverage::new());
+    }
+
+    pub fn disable_coverage(&mut self) {
+        self.coverage = None;
+    }
+
+    pub fn coverage(&self) -> Option<&Coverage> {
+        self.coverage.as_ref()
+    }
+
+    pub fn take_self_modifications(&mut self) -> Vec<SelfModification> {
+        match self.coverage {
+            Some(ref mut coverage) => coverage.take_modifications(),
+            None => Vec::new(),
+        }
+    }
+
+    pub fn enable_decode_cache(&mut self) {
+        self.decode_cache = Some(DecodeCache::new());
+    }
+
+    pub fn disable_decode_cache(&mut self) {
+        self.decode_cache = None;
+    }
+
+    pub fn decode_cache(&self) -> Option<&DecodeCache> {
+        self.decode_cache.as_ref()
+    }
+
+    // for code changed behind the cpu back (dma, bank switching)
+    pub fn invalidate_decode_cache(&mut self, start: u16, end: u16) {
+        if let Some(ref mut cache) = self.decode_cache {
+            cache.invalidate(start, end);
+        }
+    }
+
+    pub fn enable_step_diffs(&mut self) {
+        self.step_recorder = Some(StepRecorder::new());
+    }
+
+    pub fn disable_step_diffs(&mut self) {
+        self.step_recorder = None;
+    }
+
+    // the diff of the last executed instruction
+    pub fn take_step_diff(&mut self) -> Option<StepDiff> {
+        match self.step_recorder {
+            Some(ref mut recorder) => recorder.take_diff(),
+            None => None,
+        }
+    }
+
+    // writes in start..=end are IoWrite events, and "io write" debug events
+    // with tracing
+    pub fn trace_io_region(&mut self, start: u16, end: u16) {
+        self.io_regions.push((start, end));
+    }
+
+    pub fn clear_io_regions(&mut self) {
+        self.io_regions.clear();
+    }
+
+    pub fn observers(&mut self) -> &mut Observers {
+        &mut self.observers
+    }
+
+    pub fn set_interrupt_breakpoint(&mut self, kind: InterruptBreakpoint, enable: bool) {
+        self.interrupt_breakpoints
+            .retain(|breakpoint| *breakpoint != kind);
+        if enable {
+            self.interrupt_breakpoints.push(kind);
+        }
+    }
+
+    pub fn take_interrupt_breakpoint(&mut self) -> Option<InterruptBreakpoint> {
+        self.hit_interrupt_breakpoint.take()
+    }
+
+    // stops at the handler entry, reported as a code breakpoint too so every
+    // debugger loop notices it
+    fn interrupt_breakpoint(&mut self, kind: InterruptBreakpoint) {
+        // every interrupt taken ends up here
+        #[cfg(feature = "tracing")]
+        ::tracing::debug!(?kind, pc = self.debug_pc, handler = self.addr, "interrupt");
+        let handler = self.addr;
+        self.observers.emit(&CpuEvent::InterruptTaken { handler });
+        if self.interrupt_breakpoints.contains(&kind) {
+            self.hit_interrupt_breakpoint = Some(kind);
+            self.requested_code_breakpoint = true;
+        }
+    }
+
+    // the next instruction is at pc, the one that got there is done
+    fn pc_breakpoint(&mut self) {
+        let pc = self.pc;
+        let index = match self
+            .breakpoints
+            .iter()
+            .position(|breakpoint| breakpoint.enabled && breakpoint.address == pc)
+        {
+            Some(index) => index,
+            None => return,
         };
+        // out while it peeks at the cpu
+        let condition = self.breakpoints[index].condition.take();
+        let hit = match condition {
+            Some(ref condition) => condition.test(self),
+            None => true,
+        };
+        self.breakpoints[index].condition = condition;
+        if hit {
+            self.breakpoints[index].hits += 1;
+            self.hit_breakpoint = Some(self.breakpoints[index].clone());
+            self.requested_code_breakpoint = true;
+        }
     }
 
-    fn read8(&mut self, addr: u16) -> u8 {
-        self.bus.read(addr)
+    fn watch(&mut self, address: u16, value: u8, write: bool) {
+        let hit = WatchHit {
+            pc: self.debug_pc,
+            address,
+            value,
+            write,
+        };
+        for watchpoint in self.watchpoints.iter_mut() {
+            let inside = address >= watchpoint.start && address <= watchpoint.end;
+            if !watchpoint.enabled || !inside || !watchpoint.watch.matches(write) {
+                continue;
+            }
+            watchpoint.hits += 1;
+            match self.on_watchpoint {
+                Some(ref mut handler) => handler(&hit),
+                None if self.hit_watchpoint.is_none() => {
+                    self.hit_watchpoint = Some(hit);
+                    self.requested_code_breakpoint = true;
+                }
+                None => {}
+            }
+        }
     }
 
-    fn read16(&mut self, addr: u16) -> u16 {
-        let low = u16::from(self.read8(addr));
-        let high = u16::from(self.read8(addr + 1));
-        (high << 8) | low
+    // window is the maximum distance (in bytes) of a backward jump closing a loop
+    pub fn enable_idle_detection(&mut self, window: u16) {
+        self.idle_detector = Some(IdleDetector::new(window));
     }
 
-    fn write8(&mut self, addr: u16, value: u8) {
-        self.bus.write(addr, value)
+    pub fn disable_idle_detection(&mut self) {
+        self.idle_detector = None;
+    }
+
+    // a waiting cpu is idle without the detector
+    pub fn is_idle(&self) -> bool {
+        if self.waiting {
+            return true;
+        }
+        match self.idle_detector {
+            Some(ref detector) => detector.is_idle(),
+            None => false,
+        }
+    }
+
+    // when spinning in an idle loop jumps the ticks (and the devices) right
+    // before the next scheduled event, but not past limit
+    pub fn fast_forward(&mut self, scheduler: &mut Scheduler, limit: u64) -> bool {
+        if !self.is_idle() {
+            return false;
+        }
+        let target = scheduler.fast_forward(self.ticks, limit);
+        if target <= self.ticks {
+            return false;
+        }
+        self.ticks = target;
+        true
+    }
+
+    pub fn take_idle_loop(&mut self) -> Option<IdleLoop> {
+        match self.idle_detector {
+            Some(ref mut detector) => detector.take_event(),
+            None => None,
+        }
     }
 
     fn read8_from_pc(&mut self) -> u8 {
         let pc = self.advance_pc();
-        self.read8(pc)
+        let start = self.debug_pc;
+        if let Some(ref mut cache) = self.decode_cache {
+            if let Some(byte) = cache.fetch(start, pc) {
+                return byte;
+            }
+        }
+        let byte = self.read8(pc);
+        if let Some(ref mut cache) = self.decode_cache {
+            cache.record(start, pc, byte);
+        }
+        byte
     }
 
     fn read16_from_pc(&mut self) -> u16 {
@@ -261,62 +1362,87 @@ impl<T: AddressBusIO<u16, u8>> MOS6502<T> {
         (high << 8) | low
     }
 
-    fn advance_pc(&mut self) -> u16 {
-        let pc = self.pc;
-        self.pc += 1;
-        pc
+    fn advance_pc(&mut self) -> u16 {
+        let pc = self.pc;
+        self.pc = self.pc.wrapping_add(1);
+        pc
+    }
+
+    fn get_opcode_name(&self) -> &'static str {
+        self.opcode.name
+    }
+
+    // between fetch and execute, pc has just moved past the operands
+    fn operand(&mut self) {
+        if let Some(ref mut coverage) = self.coverage {
+            coverage.execute(self.debug_pc, self.pc.wrapping_sub(self.debug_pc));
+        }
+        if self.opcode.operand {
+            let addr = self.addr;
+            self.value = self.read8(addr);
+            if let Some(ref mut recorder) = self.access_recorder {
+                recorder.read(self.value);
+            }
+        }
+    }
+
+    // the bounds check is usually optimized out already, unchecked makes sure
+    fn lookup(&self, code: u8) -> OpCode<T> {
+        #[cfg(feature = "unchecked")]
+        {
+            // safety: any u8 indexes the 256 entries
+            unsafe { *self.opcodes.get_unchecked(usize::from(code)) }
+        }
+        #[cfg(not(feature = "unchecked"))]
+        self.opcodes[usize::from(code)]
     }
 
-    fn get_opcode_name(&self) -> &'static str {
-        self.opcode.name
+    // always false with strip_debug, the fields stay so callers still build
+    // but none of the formatting is compiled in
+    fn debugging(&self) -> bool {
+        !cfg!(feature = "strip_debug") && self.debug
     }
 
     fn implied(&mut self) {
-        self.ticks += 2;
-        if self.debug {
+        if self.debugging() {
             self.debug_line = self.get_opcode_name().to_string()
         }
     }
 
     fn immediate(&mut self) {
         self.value = self.read8_from_pc();
-        self.ticks += 2;
-        if self.debug {
+        if self.debugging() {
             self.debug_line = format!("{} #${:02X}", self.get_opcode_name(), self.value);
         }
     }
 
     fn accumulator(&mut self) {
-        self.ticks += 2;
-        if self.debug {
+        self.value = self.a;
+        if self.debugging() {
             self.debug_line = self.get_opcode_name().to_string();
         }
     }
 
     fn relative(&mut self) {
         let offset = self.read8_from_pc() as i8;
-        self.ticks += 2;
-        let addr = i32::from(self.pc) + i32::from(offset);
-        self.addr = addr as u16;
-        if self.debug {
+        self.addr = self.pc.wrapping_add(offset as u16);
+        if self.debugging() {
             self.debug_line = format!("{} ${:04X}", self.get_opcode_name(), self.addr);
         }
     }
 
     fn zeropage(&mut self) {
-        let addr = u16::from(self.read8_from_pc());
-        self.addr = addr;
-        self.value = self.read8(addr);
-        self.ticks += 3;
-        self.debug_line = format!("{} ${:02X}", self.get_opcode_name(), self.addr);
+        let offset = self.read8_from_pc();
+        self.addr = self.zeropage_address(offset);
+        if self.debugging() {
+            self.debug_line = format!("{} ${:02X}", self.get_opcode_name(), offset);
+        }
     }
 
     fn absolute(&mut self) {
         let addr = self.read16_from_pc();
         self.addr = addr;
-        self.value = self.read8(addr);
-        self.ticks += 4;
-        if self.debug {
+        if self.debugging() {
             self.debug_line = format!("{} ${:04X}", self.get_opcode_name(), self.addr);
         }
     }
@@ -324,15 +1450,11 @@ impl<T: AddressBusIO<u16, u8>> MOS6502<T> {
     fn absolute_x(&mut self) {
         let addr = self.read16_from_pc();
         let original_addr = addr;
-        let mut boundary = 0;
-        let addr_x = addr + u16::from(self.x);
-        if addr >> 8 != addr_x >> 8 {
-            boundary = 1;
-        }
+        let addr_x = addr.wrapping_add(u16::from(self.x));
         self.addr = addr_x;
-        self.value = self.read8(addr_x);
-        self.ticks += 4 + boundary;
-        if self.debug {
+        self.indexed_dummy_read(addr);
+        self.page_penalty(addr);
+        if self.debugging() {
             self.debug_line = format!(
                 "{} ${:04X},X (absolute addr: ${:04X})",
                 self.get_opcode_name(),
@@ -345,15 +1467,11 @@ impl<T: AddressBusIO<u16, u8>> MOS6502<T> {
     fn absolute_y(&mut self) {
         let addr = self.read16_from_pc();
         let original_addr = addr;
-        let mut boundary = 0;
-        let addr_y = addr + u16::from(self.y);
-        if addr >> 8 != addr_y >> 8 {
-            boundary = 1;
-        }
+        let addr_y = addr.wrapping_add(u16::from(self.y));
         self.addr = addr_y;
-        self.value = self.read8(addr_y);
-        self.ticks += 4 + boundary;
-        if self.debug {
+        self.indexed_dummy_read(addr);
+        self.page_penalty(addr);
+        if self.debugging() {
             self.debug_line = format!(
                 "{} ${:04X},Y (absolute addr: ${:04X})",
                 self.get_opcode_name(),
@@ -364,13 +1482,14 @@ impl<T: AddressBusIO<u16, u8>> MOS6502<T> {
     }
 
     fn zeropage_x(&mut self) {
-        // leave it as u8 to allow overflowing
+        // stays in the zeropage
         let original_addr = self.read8_from_pc();
-        let addr = original_addr + self.x;
-        self.addr = u16::from(addr);
-        self.value = self.read8(u16::from(addr));
-        self.ticks += 3;
-        if self.debug {
+        let addr = original_addr.wrapping_add(self.x);
+        self.addr = self.zeropage_address(addr);
+        if self.dummy_accesses {
+            self.dummy_read8(self.zeropage_address(original_addr));
+        }
+        if self.debugging() {
             self.debug_line = format!(
                 "{} ${:02X},X (zeropage addr: ${:02X})",
                 self.get_opcode_name(),
@@ -381,13 +1500,14 @@ impl<T: AddressBusIO<u16, u8>> MOS6502<T> {
     }
 
     fn zeropage_y(&mut self) {
-        // leave it as u8 to allow overflowing
+        // stays in the zeropage
         let original_addr = self.read8_from_pc();
-        let addr = original_addr + self.y;
-        self.addr = u16::from(addr);
-        self.value = self.read8(u16::from(addr));
-        self.ticks += 3;
-        if self.debug {
+        let addr = original_addr.wrapping_add(self.y);
+        self.addr = self.zeropage_address(addr);
+        if self.dummy_accesses {
+            self.dummy_read8(self.zeropage_address(original_addr));
+        }
+        if self.debugging() {
             self.debug_line = format!(
                 "{} ${:02X},Y (zeropage addr: ${:02X})",
                 self.get_opcode_name(),
@@ -399,12 +1519,15 @@ impl<T: AddressBusIO<u16, u8>> MOS6502<T> {
 
     fn indirect(&mut self) {
         let addr = self.read16_from_pc();
-        let indirect_addr = self.read16(addr) as u16;
+        let indirect_addr = if self.quirks.indirect_jump_wrap && addr & 0xff == 0xff {
+            let low = u16::from(self.read8(addr));
+            let high = u16::from(self.read8(addr & 0xff00));
+            (high << 8) | low
+        } else {
+            self.read16(addr)
+        };
         self.addr = indirect_addr;
-        self.value = self.read8(indirect_addr);
-        self.pc += 1;
-        self.ticks += 2;
-        if self.debug {
+        if self.debugging() {
             self.debug_line = format!(
                 "{} (${:04X}) (indirect addr: ${:04X})",
                 self.get_opcode_name(),
@@ -415,16 +1538,15 @@ impl<T: AddressBusIO<u16, u8>> MOS6502<T> {
     }
 
     fn indirect_x(&mut self) {
-        let pc = self.pc;
-        // leave it as u8 to allow overflowing
-        let original_offset = u16::from(self.read8(pc));
-        let offset = original_offset + 2;
-        let indirect_addr = self.read16(offset);
+        // stays in the zeropage
+        let original_offset = self.read8_from_pc();
+        if self.dummy_accesses {
+            self.dummy_read8(self.zeropage_address(original_offset));
+        }
+        let offset = original_offset.wrapping_add(self.x);
+        let indirect_addr = self.read16_zeropage(offset);
         self.addr = indirect_addr;
-        self.value = self.read8(indirect_addr);
-        self.pc += 1;
-        self.ticks += 3;
-        if self.debug {
+        if self.debugging() {
             self.debug_line = format!(
                 "{} (${:02X},X) (indirect addr: ${:04X})",
                 self.get_opcode_name(),
@@ -435,20 +1557,38 @@ impl<T: AddressBusIO<u16, u8>> MOS6502<T> {
     }
 
     fn indirect_y(&mut self) {
-        let pc = self.pc;
-        // leave it as u8 to allow overflowing
-        let offset = u16::from(self.read8(pc));
-        let indirect_addr = self.read16(offset) + u16::from(self.y);
-        self.value = self.read8(indirect_addr);
+        // the pointer stays in the zeropage, the address does not
+        let offset = self.read8_from_pc();
+        let pointer = self.read16_zeropage(offset);
+        let indirect_addr = pointer.wrapping_add(u16::from(self.y));
         self.addr = indirect_addr;
-        self.pc += 1;
-        self.ticks += 2;
-        if indirect_addr >> 8 != 0 {
+        self.indexed_dummy_read(pointer);
+        self.page_penalty(pointer);
+        if self.debugging() {
+            self.debug_line = format!(
+                "{} (${:02X}),Y (indirect addr: ${:04X})",
+                self.get_opcode_name(),
+                offset,
+                self.addr
+            );
+        }
+    }
+
+    // a cycle more for the carry into the high byte, stores and read modify
+    // writes have it in their cost already
+    fn page_penalty(&mut self, base: u16) {
+        if self.opcode.page_penalty && base >> 8 != self.addr >> 8 {
             self.ticks += 1;
         }
-        if self.debug {
+    }
+
+    // (zp) of the cmos, the pointer without an index
+    fn zeropage_indirect(&mut self) {
+        let offset = self.read8_from_pc();
+        self.addr = self.read16_zeropage(offset);
+        if self.debugging() {
             self.debug_line = format!(
-                "{} (${:02X}),Y (indirect addr: ${:04X})",
+                "{} (${:02X}) (indirect addr: ${:04X})",
                 self.get_opcode_name(),
                 offset,
                 self.addr
@@ -456,10 +1596,56 @@ impl<T: AddressBusIO<u16, u8>> MOS6502<T> {
         }
     }
 
+    // the zeropage address and the branch offset of bbr and bbs, the byte
+    // tested is read in between
+    fn zeropage_relative(&mut self) {
+        let addr = self.read8_from_pc();
+        self.value = self.read8(self.zeropage_address(addr));
+        let offset = self.read8_from_pc() as i8;
+        self.addr = self.pc.wrapping_add(offset as u16);
+        if self.debugging() {
+            self.debug_line = format!(
+                "{} ${:02X},${:04X}",
+                self.get_opcode_name(),
+                addr,
+                self.addr
+            );
+        }
+    }
+
+    // jmp ($xxxx,x) of the cmos, a table of addresses
+    fn indirect_absolute_x(&mut self) {
+        let addr = self.read16_from_pc();
+        let pointer = addr.wrapping_add(u16::from(self.x));
+        self.addr = self.read16(pointer);
+        if self.debugging() {
+            self.debug_line = format!(
+                "{} (${:04X},X) (indirect addr: ${:04X})",
+                self.get_opcode_name(),
+                addr,
+                self.addr
+            );
+        }
+    }
+
+    // from the address without the carry into its high byte, done by the
+    // reads crossing a page and by every store and read modify write
+    fn indexed_dummy_read(&mut self, base: u16) {
+        let addr = self.addr;
+        let partial = (base & 0xff00) | (addr & 0x00ff);
+        if self.dummy_accesses && (partial != addr || !self.opcode.page_penalty) {
+            self.dummy_read8(partial);
+        }
+    }
+
     fn get_flag(&self, flag: u8) -> bool {
         (self.status & flag) != 0
     }
 
+    fn set_nz(&mut self, value: u8) {
+        self.status = (self.status & !(SIGN | ZERO)) | NZ[usize::from(value)];
+    }
+
     fn set_flag(&mut self, flag: u8, enabled: bool) {
         if enabled {
             self.status |= flag;
@@ -473,87 +1659,73 @@ impl<T: AddressBusIO<u16, u8>> MOS6502<T> {
     fn lda(&mut self) {
         let a = self.value;
         self.a = a;
-        self.set_flag(ZERO, a == 0);
-        self.set_flag(SIGN, a >> 7 == 1);
+        self.set_nz(a);
     }
 
     fn tay(&mut self) {
         self.y = self.a;
         let y = self.y;
-        self.set_flag(ZERO, y == 0);
-        self.set_flag(SIGN, y >> 7 == 1);
+        self.set_nz(y);
     }
 
     fn tax(&mut self) {
         self.x = self.a;
         let x = self.x;
-        self.set_flag(ZERO, x == 0);
-        self.set_flag(SIGN, x >> 7 == 1);
+        self.set_nz(x);
     }
 
     fn txa(&mut self) {
         self.a = self.x;
         let a = self.a;
-        self.set_flag(ZERO, a == 0);
-        self.set_flag(SIGN, a >> 7 == 1);
+        self.set_nz(a);
     }
 
     fn tya(&mut self) {
         self.a = self.y;
         let a = self.a;
-        self.set_flag(ZERO, a == 0);
-        self.set_flag(SIGN, a >> 7 == 1);
+        self.set_nz(a);
     }
 
     fn iny(&mut self) {
-        self.y += 1;
+        self.y = self.y.wrapping_add(1);
         let y = self.y;
-        self.set_flag(ZERO, y == 0);
-        self.set_flag(SIGN, y >> 7 == 1);
+        self.set_nz(y);
     }
 
     fn inx(&mut self) {
-        self.x += 1;
+        self.x = self.x.wrapping_add(1);
         let x = self.x;
-        self.set_flag(ZERO, x == 0);
-        self.set_flag(SIGN, x >> 7 == 1);
+        self.set_nz(x);
     }
 
     fn dex(&mut self) {
-        self.x -= 1;
+        self.x = self.x.wrapping_sub(1);
         let x = self.x;
-        self.set_flag(ZERO, x == 0);
-        self.set_flag(SIGN, x >> 7 == 1);
+        self.set_nz(x);
     }
 
     fn dey(&mut self) {
-        self.y -= 1;
+        self.y = self.y.wrapping_sub(1);
         let y = self.y;
-        self.set_flag(ZERO, y == 0);
-        self.set_flag(SIGN, y >> 7 == 1);
+        self.set_nz(y);
     }
 
     fn dec(&mut self) {
-        let value = self.value - 1;
-        let addr = self.addr;
-        self.write8(addr, value);
-        self.set_flag(ZERO, value == 0);
-        self.set_flag(SIGN, value >> 7 == 1);
+        let value = self.value.wrapping_sub(1);
+        self.write_back(value);
+        self.set_nz(value);
     }
 
     fn inc(&mut self) {
-        let value = self.value + 1;
-        let addr = self.addr;
-        self.write8(addr, value);
-        self.set_flag(ZERO, value == 0);
-        self.set_flag(SIGN, value >> 7 == 1);
+        let value = self.value.wrapping_add(1);
+        self.write_back(value);
+        self.set_nz(value);
     }
 
     fn ldy(&mut self) {
         let y = self.value;
         self.y = y;
-        self.set_flag(ZERO, y == 0);
-        self.set_flag(SIGN, y >> 7 == 1);
+        self.set_nz(y);
     }
 
     fn stx(&mut self) {
@@ -568,6 +1740,40 @@ impl<T: AddressBusIO<u16, u8>> MOS6502<T> {
         self.write8(addr, y);
     }
 
+    fn stz(&mut self) {
+        let addr = self.addr;
+        self.write8(addr, 0);
+    }
+
+    // z from the bits a and memory have in common, then those bits cleared
+    // or set in memory
+    fn trb(&mut self) {
+        let (a, value) = (self.a, self.value);
+        self.set_flag(ZERO, a & value == 0);
+        self.write_back(value & !a);
+    }
+
+    fn tsb(&mut self) {
+        let (a, value) = (self.a, self.value);
+        self.set_flag(ZERO, a & value == 0);
+        self.write_back(value | a);
+    }
+
+    fn reset_bit(&mut self, bit: u8) {
+        let value = self.value;
+        self.write_back(value & !(1 << bit));
+    }
+
+    fn set_bit(&mut self, bit: u8) {
+        let value = self.value;
+        self.write_back(value | (1 << bit));
+    }
+
+    fn branch_on_bit(&mut self, bit: u8, set: bool) {
+        let taken = (self.value >> bit) & 1 == u8::from(set);
+        self.branch(taken);
+    }
+
     fn sta(&mut self) {
         let addr = self.addr;
         let a = self.a;
@@ -577,63 +1783,42 @@ impl<T: AddressBusIO<u16, u8>> MOS6502<T> {
     fn ldx(&mut self) {
         self.x = self.value;
         let x = self.x;
-        self.set_flag(ZERO, x == 0);
-        self.set_flag(SIGN, x >> 7 == 1);
+        self.set_nz(x);
     }
 
     fn and(&mut self) {
         self.a &= self.value;
         let a = self.a;
-        self.set_flag(ZERO, a == 0);
-        self.set_flag(SIGN, a >> 7 == 1);
+        self.set_nz(a);
     }
 
     fn bit(&mut self) {
         let a = self.a;
         let value = self.value;
         self.set_flag(ZERO, (a & value) == 0);
+        // bit #imm of the cmos only sets z
+        if self.opcode.mode == "immediate" {
+            return;
+        }
         self.set_flag(SIGN, (value & 0x80) != 0);
         self.set_flag(OVERFLOW, (value & 0x40) != 0);
     }
 
-    fn asl_a(&mut self) {
-        let mut a = self.a;
-        self.set_flag(CARRY, (a >> 7) == 0x01);
-        a <<= 1;
-        self.a = a;
-        self.set_flag(ZERO, a == 0);
-        self.set_flag(SIGN, a >> 7 == 1);
-    }
-
-    fn rol_a(&mut self) {
-        let mut a = self.a;
-        let carry = self.get_flag(CARRY);
-        self.set_flag(CARRY, (a >> 7) == 0x01);
-        a <<= 1;
-        a |= if carry { 1 } else { 0 };
-        self.a = a;
-        self.set_flag(ZERO, a == 0);
-        self.set_flag(SIGN, a >> 7 == 1);
-    }
-
-    fn ror_a(&mut self) {
-        let mut a = self.a;
-        let carry = self.get_flag(CARRY);
-        self.set_flag(CARRY, (a & 0x01) == 0x01);
-        a >>= 1;
-        a |= if carry { 0x80 } else { 0 };
-        self.a = a;
-        self.set_flag(ZERO, a == 0);
-        self.set_flag(SIGN, a >> 7 == 1);
-    }
-
-    fn lsr_a(&mut self) {
-        let mut a = self.a;
-        self.set_flag(CARRY, (a & 0x01) == 0x01);
-        a >>= 1;
-        self.a = a;
-        self.set_flag(ZERO, a == 0);
-        self.set_flag(SIGN, a >> 7 == 1);
+    // where a read modify write puts its result, it is left as the value for
+    // the undocumented opcodes doing another operation on it
+    fn write_back(&mut self, value: u8) {
+        let old = self.value;
+        self.value = value;
+        if self.opcode.accumulator {
+            self.a = value;
+        } else {
+            let addr = self.addr;
+            // tick does its own
+            if self.dummy_accesses && !self.in_instruction() {
+                self.dummy_write8(addr, old);
+            }
+            self.write8(addr, value);
+        }
     }
 
     fn rol(&mut self) {
@@ -642,10 +1827,8 @@ impl<T: AddressBusIO<u16, u8>> MOS6502<T> {
         self.set_flag(CARRY, (value >> 7) == 0x01);
         value <<= 1;
         value |= if carry { 1 } else { 0 };
-        let addr = self.addr;
-        self.write8(addr, value);
-        self.set_flag(ZERO, value == 0);
-        self.set_flag(SIGN, value >> 7 == 1);
+        self.write_back(value);
+        self.set_nz(value);
     }
 
     fn ror(&mut self) {
@@ -654,47 +1837,43 @@ impl<T: AddressBusIO<u16, u8>> MOS6502<T> {
         self.set_flag(CARRY, (value & 0x01) == 0x01);
         value >>= 1;
         value |= if carry { 0x80 } else { 0 };
-        let addr = self.addr;
-        self.write8(addr, value);
-        self.set_flag(ZERO, value == 0);
-        self.set_flag(SIGN, value >> 7 == 1);
+        self.write_back(value);
+        self.set_nz(value);
     }
 
     fn asl(&mut self) {
         let mut value = self.value;
         self.set_flag(CARRY, (value >> 7) == 0x01);
         value <<= 1;
-        let addr = self.addr;
-        self.write8(addr, value);
-        self.set_flag(ZERO, value == 0);
-        self.set_flag(SIGN, value >> 7 == 1);
+        self.write_back(value);
+        self.set_nz(value);
     }
 
     fn lsr(&mut self) {
         let mut value = self.value;
         self.set_flag(CARRY, (value & 0x01) == 0x01);
         value >>= 1;
-        let addr = self.addr;
-        self.write8(addr, value);
-        self.set_flag(ZERO, value == 0);
-        self.set_flag(SIGN, value >> 7 == 1);
+        self.write_back(value);
+        self.set_nz(value);
     }
 
     fn ora(&mut self) {
         self.a |= self.value;
         let a = self.a;
-        self.set_flag(ZERO, a == 0);
-        self.set_flag(SIGN, a >> 7 == 1);
+        self.set_nz(a);
     }
 
     fn eor(&mut self) {
         self.a ^= self.value;
         let a = self.a;
-        self.set_flag(ZERO, a == 0);
-        self.set_flag(SIGN, a >> 7 == 1);
+        self.set_nz(a);
     }
 
     fn sbc(&mut self) {
+        if self.quirks.decimal_mode && self.get_flag(DECIMAL) {
+            self.sbc_decimal();
+            return;
+        }
         // first check for carry
         let carry = if self.get_flag(CARRY) { 0 } else { 1 };
         let orig_a = i16::from(self.a);
@@ -703,16 +1882,19 @@ impl<T: AddressBusIO<u16, u8>> MOS6502<T> {
         self.set_flag(CARRY, result >= 0 && result <= 0xff);
         self.a = result as u8;
         let a = self.a;
-        self.set_flag(ZERO, a == 0);
-        self.set_flag(SIGN, a >> 7 == 1);
-        // if the sign of both inputs is different from the sign of the result
+        self.set_nz(a);
+        // if the inputs have different signs and the result the sign of value
         self.set_flag(
             OVERFLOW,
-            ((orig_a as u8 ^ a) & (value as u8 ^ a)) & 0x80 != 0,
+            ((orig_a as u8 ^ value as u8) & (orig_a as u8 ^ a)) & 0x80 != 0,
         );
     }
 
     fn adc(&mut self) {
+        if self.quirks.decimal_mode && self.get_flag(DECIMAL) {
+            self.adc_decimal();
+            return;
+        }
         // first check for carry
         let carry = if self.get_flag(CARRY) { 1 } else { 0 };
         let orig_a = i16::from(self.a);
@@ -721,8 +1903,7 @@ impl<T: AddressBusIO<u16, u8>> MOS6502<T> {
         self.set_flag(CARRY, result > 0xff);
         self.a = result as u8;
         let a = self.a;
-        self.set_flag(ZERO, a == 0);
-        self.set_flag(SIGN, a >> 7 == 1);
+        self.set_nz(a);
         // if the sign of both inputs is different from the sign of the result
         self.set_flag(
             OVERFLOW,
@@ -730,6 +1911,53 @@ impl<T: AddressBusIO<u16, u8>> MOS6502<T> {
         );
     }
 
+    // a digit at a time, adjusted past 9; the nmos leaves n, v and z as the
+    // binary intermediates have them, the cmos sets n and z from the result
+    // for a cycle more
+    fn adc_decimal(&mut self) {
+        let (a, value) = (u16::from(self.a), u16::from(self.value));
+        let carry = u16::from(self.get_flag(CARRY));
+        let mut low = (a & 0x0f) + (value & 0x0f) + carry;
+        if low > 0x09 {
+            low = ((low + 0x06) & 0x0f) + 0x10;
+        }
+        let high = (a & 0xf0) + (value & 0xf0) + low;
+        let result = if high > 0x9f { high + 0x60 } else { high };
+        self.set_flag(CARRY, result > 0xff);
+        self.set_flag(OVERFLOW, (a ^ value) & 0x80 == 0 && (a ^ high) & 0x80 != 0);
+        self.a = result as u8;
+        if self.quirks.decimal_flags {
+            self.set_nz(result as u8);
+            self.ticks += 1;
+        } else {
+            self.set_flag(ZERO, (a + value + carry) as u8 == 0);
+            self.set_flag(SIGN, high & 0x80 != 0);
+        }
+    }
+
+    // the flags are the binary ones on the nmos, only a is adjusted
+    fn sbc_decimal(&mut self) {
+        let (a, value) = (i16::from(self.a), i16::from(self.value));
+        let borrow = i16::from(!self.get_flag(CARRY));
+        let binary = a - value - borrow;
+        self.set_flag(CARRY, binary >= 0);
+        self.set_flag(OVERFLOW, (a ^ value) & (a ^ binary) & 0x80 != 0);
+        self.set_nz(binary as u8);
+        let mut low = (a & 0x0f) - (value & 0x0f) - borrow;
+        if low < 0 {
+            low = ((low - 0x06) & 0x0f) - 0x10;
+        }
+        let mut result = (a & 0xf0) - (value & 0xf0) + low;
+        if result < 0 {
+            result -= 0x60;
+        }
+        self.a = result as u8;
+        if self.quirks.decimal_flags {
+            self.set_nz(result as u8);
+            self.ticks += 1;
+        }
+    }
+
     fn jmp(&mut self) {
         self.pc = self.addr;
     }
@@ -762,99 +1990,114 @@ impl<T: AddressBusIO<u16, u8>> MOS6502<T> {
         self.set_flag(DECIMAL, true);
     }
 
-    fn beq(&mut self) {
-        if self.get_flag(ZERO) {
+    // a cycle when taken, another when the target is in another page than the
+    // next instruction
+    fn branch(&mut self, taken: bool) {
+        if taken {
+            if self.pc >> 8 != self.addr >> 8 {
+                self.ticks += 1;
+            }
             self.pc = self.addr;
             self.ticks += 1;
         }
     }
 
+    fn bra(&mut self) {
+        self.branch(true);
+    }
+
+    fn beq(&mut self) {
+        let taken = self.get_flag(ZERO);
+        self.branch(taken);
+    }
+
     fn bmi(&mut self) {
-        if self.get_flag(SIGN) {
-            self.pc = self.addr;
-            self.ticks += 1;
-        }
+        let taken = self.get_flag(SIGN);
+        self.branch(taken);
     }
 
     fn bpl(&mut self) {
-        if !self.get_flag(SIGN) {
-            self.pc = self.addr;
-            self.ticks += 1;
-        }
+        let taken = !self.get_flag(SIGN);
+        self.branch(taken);
     }
 
     fn bvc(&mut self) {
-        if !self.get_flag(OVERFLOW) {
-            self.pc = self.addr;
-            self.ticks += 1;
-        }
+        let taken = !self.get_flag(OVERFLOW);
+        self.branch(taken);
     }
 
     fn bvs(&mut self) {
-        if self.get_flag(OVERFLOW) {
-            self.pc = self.addr;
-            self.ticks += 1;
-        }
+        let taken = self.get_flag(OVERFLOW);
+        self.branch(taken);
     }
 
     fn bne(&mut self) {
-        if !self.get_flag(ZERO) {
-            self.pc = self.addr;
-            self.ticks += 1;
-        }
+        let taken = !self.get_flag(ZERO);
+        self.branch(taken);
     }
 
     fn bcs(&mut self) {
-        if self.get_flag(CARRY) {
-            self.pc = self.addr;
-            self.ticks += 1;
-        }
+        let taken = self.get_flag(CARRY);
+        self.branch(taken);
     }
 
     fn bcc(&mut self) {
-        if !self.get_flag(CARRY) {
-            self.pc = self.addr;
-            self.ticks += 1;
-        }
+        let taken = !self.get_flag(CARRY);
+        self.branch(taken);
     }
 
     fn cmp(&mut self) {
         let a = self.a;
         let value = self.value;
         self.set_flag(CARRY, a >= value);
-        self.set_flag(ZERO, a == value);
-        self.set_flag(SIGN, a < value);
+        self.set_nz(a.wrapping_sub(value));
     }
 
     fn cpx(&mut self) {
         let x = self.x;
         let value = self.value;
         self.set_flag(CARRY, x >= value);
-        self.set_flag(ZERO, x == value);
-        self.set_flag(SIGN, x < value);
+        self.set_nz(x.wrapping_sub(value));
     }
 
     fn cpy(&mut self) {
         let y = self.y;
         let value = self.value;
         self.set_flag(CARRY, y >= value);
-        self.set_flag(ZERO, y == value);
-        self.set_flag(SIGN, y < value);
+        self.set_nz(y.wrapping_sub(value));
     }
 
     fn pha(&mut self) {
-        let sp: u16 = 0x100 + u16::from(self.sp);
         let a = self.a;
-        self.write8(sp, a);
-        self.sp -= 1;
-        self.ticks += 1;
+        self.push8(a);
     }
 
     fn pla(&mut self) {
-        self.sp += 1;
-        let sp: u16 = 0x100 + u16::from(self.sp);
-        self.a = self.read8(sp);
-        self.ticks += 2;
+        self.a = self.pull8();
+        let a = self.a;
+        self.set_nz(a);
+    }
+
+    fn phx(&mut self) {
+        let x = self.x;
+        self.push8(x);
+    }
+
+    fn phy(&mut self) {
+        let y = self.y;
+        self.push8(y);
+    }
+
+    fn plx(&mut self) {
+        self.x = self.pull8();
+        let x = self.x;
+        self.set_nz(x);
+    }
+
+    fn ply(&mut self) {
+        self.y = self.pull8();
+        let y = self.y;
+        self.set_nz(y);
     }
 
     fn txs(&mut self) {
@@ -863,114 +2106,310 @@ impl<T: AddressBusIO<u16, u8>> MOS6502<T> {
 
     fn tsx(&mut self) {
         self.x = self.sp;
+        let x = self.x;
+        self.set_nz(x);
     }
 
     fn php(&mut self) {
-        let sp: u16 = 0x100 + u16::from(self.sp);
-        let status = self.status;
-        self.write8(sp, status);
-        self.sp -= 1;
-        self.ticks += 1;
+        let status = pushed(self.status, BRK);
+        self.push8(status);
     }
 
     fn plp(&mut self) {
-        self.sp += 1;
-        let sp: u16 = 0x100 + u16::from(self.sp);
-        self.status = self.read8(sp);
-        self.ticks += 2;
+        self.status = unpushed(self.pull8());
     }
 
     fn jsr(&mut self) {
-        let sp: u16 = 0x100 + u16::from(self.sp);
-        let pc = self.pc - 1;
-        let pc_high = (pc >> 8) as u8;
-        let pc_low = (pc & 0x00ff) as u8;
-        self.write8(sp, pc_high);
-        self.write8(sp - 1, pc_low);
-        self.sp -= 2;
+        let pc = self.pc.wrapping_sub(1);
+        self.push_return_address(pc);
 
         self.pc = self.addr;
-        self.ticks += 2;
     }
 
     fn brk(&mut self) {
         if self.code_breakpoint {
             self.requested_code_breakpoint = true;
         } else {
-            self.interrupt(0xfffe);
+            // the byte after it is skipped, rti returns past it
+            self.pc = self.pc.wrapping_add(1);
+            self.interrupt(0xfffe, BRK);
+            self.interrupt_breakpoint(InterruptBreakpoint::Brk);
         }
     }
 
-    fn interrupt(&mut self, address: u16) {
-        let sp: u16 = 0x100 + u16::from(self.sp);
+    // only the copy pushed by a brk has the break flag set
+    fn interrupt(&mut self, address: u16, brk: u8) {
         let pc = self.pc;
-        let pc_high = (pc >> 8) as u8;
-        let pc_low = (pc & 0x00ff) as u8;
-        self.write8(sp, pc_high);
-        self.write8(sp - 1, pc_low);
-        let status = self.status;
-        self.write8(sp - 2, status);
-        self.sp -= 3;
+        self.push_return_address(pc);
+        let status = pushed(self.status, brk);
+        self.push8(status);
+        self.set_flag(INTERRUPT, true);
+        if self.quirks.clear_decimal_on_interrupt {
+            self.set_flag(DECIMAL, false);
+        }
 
-        self.addr = self.read16(address);
+        self.addr = self.read16(self.vector(address, brk));
 
         self.pc = self.addr;
-        self.ticks += 5;
     }
 
-    fn reset(&mut self, address: u16) {
-        self.status = ALWAYS_SET | INTERRUPT;
-        self.addr = self.read16(address);
+    // a cold start: the registers and the memory of the power up state, then
+    // the reset sequence
+    pub fn power_on(&mut self) {
+        self.apply_power_up();
+        self.reset();
+    }
+
+    pub fn power_up_state(&self) -> PowerUpState {
+        self.power_up
+    }
+
+    fn apply_power_up(&mut self) {
+        let registers = self.power_up.registers;
+        self.pc = registers.pc;
+        self.a = registers.a;
+        self.x = registers.x;
+        self.y = registers.y;
+        self.sp = registers.sp;
+        self.status = registers.status;
+        self.power_up.fill_memory(&mut self.bus);
+    }
+
+    // the 7 cycles of the sequence: an interrupt with its pushes turned into
+    // reads, so the stack pointer drops by 3 with nothing written, and the
+    // registers and the other flags are left as they were
+    fn reset_from(&mut self, address: u16) {
+        if let Some(ref mut guard) = self.uninitialized_guard {
+            guard.clear();
+        }
+        self.fault = None;
+        self.halted = false;
+        self.waiting = false;
+        self.stopped = false;
+        self.nmi_pending = false;
+        self.interrupt_mask = true;
+        self.tick_state = TickState::new();
+        // every pin of the port back to an input
+        if let Some(ref mut port) = self.port {
+            port.write(0x0000, 0x00);
+        }
+        self.sp = self.sp.wrapping_sub(3);
+        self.set_flag(INTERRUPT, true);
+        if self.quirks.clear_decimal_on_interrupt {
+            self.set_flag(DECIMAL, false);
+        }
+        self.addr = self.read16(self.vector(address, 0));
+        self.interrupt_breakpoint(InterruptBreakpoint::Reset);
         self.pc = self.addr;
-        self.sp = 0xff;
-        self.a = 0;
-        self.x = 0;
-        self.y = 0;
+        self.ticks += 7;
+    }
+
+    fn wai(&mut self) {
+        self.waiting = true;
+    }
+
+    fn stp(&mut self) {
+        self.stopped = true;
     }
 
     fn rts(&mut self) {
-        self.sp += 1;
-        let sp: u16 = 0x100 + u16::from(self.sp);
-        let pc_low = u16::from(self.read8(sp));
-        let pc_high = u16::from(self.read8(sp + 1));
-        self.sp += 1;
-        self.pc = (pc_high << 8 | pc_low) + 1;
-        self.ticks += 4;
+        self.pc = self.pull_return_address().wrapping_add(1);
     }
 
     fn rti(&mut self) {
-        self.sp += 1;
-        let sp = 0x100 + u16::from(self.sp);
-        let status = self.read8(sp);
-        let pc_low = u16::from(self.read8(sp + 1));
-        let pc_high = u16::from(self.read8(sp + 2));
-        self.sp += 2;
-        self.pc = pc_high << 8 | pc_low;
+        let status = unpushed(self.pull8());
+        self.pc = self.pull_return_address();
         self.status = status;
-        self.ticks += 4;
     }
 
     fn nop(&mut self) {}
 
+    fn lax(&mut self) {
+        self.lda();
+        self.tax();
+    }
+
+    fn sax(&mut self) {
+        let (addr, value) = (self.addr, self.a & self.x);
+        self.write8(addr, value);
+    }
+
+    fn dcp(&mut self) {
+        self.dec();
+        self.cmp();
+    }
+
+    fn isc(&mut self) {
+        self.inc();
+        self.sbc();
+    }
+
+    fn slo(&mut self) {
+        self.asl();
+        self.ora();
+    }
+
+    fn rla(&mut self) {
+        self.rol();
+        self.and();
+    }
+
+    fn sre(&mut self) {
+        self.lsr();
+        self.eor();
+    }
+
+    fn rra(&mut self) {
+        self.ror();
+        self.adc();
+    }
+
+    fn anc(&mut self) {
+        self.and();
+        let sign = self.get_flag(SIGN);
+        self.set_flag(CARRY, sign);
+    }
+
+    fn alr(&mut self) {
+        self.and();
+        let a = self.a;
+        self.set_flag(CARRY, a & 0x01 != 0);
+        self.a = a >> 1;
+        self.set_nz(a >> 1);
+    }
+
+    // carry from bit 6 and overflow from bit 6 xor bit 5 of the result
+    fn arr(&mut self) {
+        let carry = if self.get_flag(CARRY) { 0x80 } else { 0 };
+        let a = ((self.a & self.value) >> 1) | carry;
+        self.a = a;
+        self.set_nz(a);
+        self.set_flag(CARRY, a & 0x40 != 0);
+        self.set_flag(OVERFLOW, ((a >> 6) ^ (a >> 5)) & 0x01 != 0);
+    }
+
+    // x = (a & x) - value, a compare that ignores the carry in
+    fn sbx(&mut self) {
+        let ax = self.a & self.x;
+        let value = self.value;
+        self.set_flag(CARRY, ax >= value);
+        self.x = ax.wrapping_sub(value);
+        let x = self.x;
+        self.set_nz(x);
+    }
+
+    fn register_illegal_opcodes(&mut self) {
+        let cpu = self;
+        illegal_opcode_table!(register_opcodes!(cpu));
+    }
+
+    // the nmos ones again for the cmos timings, then the cmos ones over them
+    fn register_cmos_opcodes(&mut self) {
+        let cpu = self;
+        opcode_table!(register_opcodes!(cpu));
+        cmos_opcode_table!(register_opcodes!(cpu));
+    }
+
+    // halts on the opcode, as a kil does on the real chip, unless the policy
+    // says otherwise
     fn invalid(&mut self) {
-        panic!(
-            "invalid opcode ${:02X} at ${:04X}",
-            self.current_opcode, self.debug_pc
-        );
+        let (pc, opcode) = (self.debug_pc, self.current_opcode);
+        match self.illegal_opcodes {
+            IllegalOpcodes::Jam | IllegalOpcodes::Execute => (),
+            IllegalOpcodes::Panic => panic!("invalid opcode ${:02X} at ${:04X}", opcode, pc),
+            IllegalOpcodes::Nop | IllegalOpcodes::Callback => {
+                if let Some(ref mut handler) = self.on_illegal_opcode {
+                    handler(pc, opcode);
+                }
+                return;
+            }
+        }
+        self.pc = self.debug_pc;
+        self.halted = true;
+        self.fault = Some(Error::InvalidOpcode {
+            pc: self.debug_pc,
+            opcode: u16::from(self.current_opcode),
+        });
     }
 }
 
 impl<T: AddressBusIO<u16, u8>> Clock for MOS6502<T> {
-    fn step(&mut self) {
+    fn step(&mut self) -> u64 {
+        // the clock keeps running on a halted cpu, and on a waiting one until
+        // an interrupt (fast_forward skips to it)
+        if self.halted || self.stopped || (self.waiting && !self.wake()) {
+            self.ticks += 1;
+            return 1;
+        }
+        if self.in_instruction() {
+            return self.finish_instruction();
+        }
+        if self.stall != 0 {
+            let stall = self.stall;
+            self.ticks += stall;
+            self.stall = 0;
+            return stall;
+        }
+        let ticks = self.ticks;
+        if self.service_interrupts() {
+            if !self.breakpoints.is_empty() {
+                self.pc_breakpoint();
+            }
+            return self.ticks - ticks;
+        }
         self.debug_pc = self.pc;
+        if let Some(ref mut recorder) = self.step_recorder {
+            recorder.begin([self.a, self.x, self.y, self.sp, self.status]);
+        }
+        if let Some(ref mut cache) = self.decode_cache {
+            cache.begin(self.debug_pc);
+        }
+        let status = self.status;
         let opcode = self.read8_from_pc();
         self.current_opcode = opcode;
-        self.opcode = self.opcodes[opcode as usize];
-        // fetch
-        (self.opcode.fetch)(self);
-        // execute
-        (self.opcode.fun)(self);
-        if self.debug {
+        self.opcode = self.lookup(opcode);
+        #[cfg(not(feature = "match_dispatch"))]
+        (self.opcode.execute)(self);
+        #[cfg(feature = "match_dispatch")]
+        opcode_table!(dispatch!(self, opcode));
+        self.ticks += u64::from(self.opcode.cycles);
+        if self.accurate_interrupts {
+            self.interrupt_mask = self.polled_mask(status);
+        }
+        if let Some(ref mut cache) = self.decode_cache {
+            cache.commit(self.debug_pc);
+        }
+        if let Some(ref mut guard) = self.uninitialized_guard {
+            if guard.commit() && guard.trap {
+                self.requested_code_breakpoint = true;
+            }
+        }
+        if let Some(ref mut recorder) = self.step_recorder {
+            let state = [self.a, self.x, self.y, self.sp, self.status];
+            recorder.end(self.debug_pc, self.pc, state);
+        }
+        if let Some(ref mut detector) = self.idle_detector {
+            let state = [self.a, self.x, self.y, self.sp, self.status];
+            detector.step(self.debug_pc, self.pc, state);
+        }
+        if !self.breakpoints.is_empty() {
+            self.pc_breakpoint();
+        }
+        #[cfg(feature = "tracing")]
+        ::tracing::trace!(
+            pc = self.debug_pc,
+            opcode = self.current_opcode,
+            name = self.opcode.name,
+            mode = self.opcode.mode,
+            a = self.a,
+            x = self.x,
+            y = self.y,
+            sp = self.sp,
+            status = self.status,
+            ticks = self.ticks,
+            "instruction"
+        );
+        if self.debugging() {
             let f_s = if self.get_flag(SIGN) { "S" } else { "-" };
             let f_v = if self.get_flag(OVERFLOW) { "V" } else { "-" };
             let f_z = if self.get_flag(ZERO) { "Z" } else { "-" };
@@ -989,7 +2428,17 @@ impl<T: AddressBusIO<u16, u8>> Clock for MOS6502<T> {
                 f_z,
                 f_c
             );
+            if let Some(ref mut trace) = self.trace {
+                trace(self.debug_pc, &self.debug_line);
+            }
         }
+        let cycles = self.ticks - ticks;
+        self.observers.emit(&CpuEvent::InstructionRetired {
+            pc: self.debug_pc,
+            opcode: u16::from(self.current_opcode),
+            cycles,
+        });
+        cycles
     }
 }
 
@@ -1001,6 +2450,24 @@ impl<T: AddressBusIO<u16, u8>> AddressBusIO<u16, u8> for MOS6502<T> {
     fn write(&mut self, address: u16, data: u8) {
         self.write8(address, data)
     }
+
+    // the guards, caches and recorders watch every access, with any of them
+    // enabled blocks take the byte path
+    fn as_contiguous(&mut self, address: u16, length: usize) -> Option<&mut [u8]> {
+        let watched = self.stack_guard.is_some()
+            || self.idle_detector.is_some()
+            || self.uninitialized_guard.is_some()
+            || self.coverage.is_some()
+            || self.step_recorder.is_some()
+            || self.access_recorder.is_some()
+            || !self.watchpoints.is_empty()
+            || self.decode_cache.is_some();
+        let watched = watched || !self.io_regions.is_empty();
+        if watched {
+            return None;
+        }
+        self.bus.as_contiguous(address, length)
+    }
 }
 
 impl<T: AddressBusIO<u16, u8>> Debug<u16, u8> for MOS6502<T> {
@@ -1013,7 +2480,7 @@ impl<T: AddressBusIO<u16, u8>> Debug<u16, u8> for MOS6502<T> {
     }
 
     fn inspect(&mut self, address: u16) -> u8 {
-        self.read(address)
+        self.peek8(address)
     }
 
     fn inject(&mut self, address: u16, data: u8) {
@@ -1041,6 +2508,90 @@ impl<T: AddressBusIO<u16, u8>> Debug<u16, u8> for MOS6502<T> {
         self.requested_code_breakpoint = false;
         requested
     }
+
+    fn disassemble(&mut self, address: u16) -> (String, u16) {
+        let (text, length) = self.disassemble_at(address);
+        (text, u16::from(length))
+    }
+
+    fn registers(&self) -> Vec<(&'static str, String)> {
+        Register::ALL
+            .iter()
+            .map(|&register| {
+                let value = self.get(register);
+                let text = if register.is_wide() {
+                    format!("${:04X}", value)
+                } else {
+                    format!("${:02X}", value)
+                };
+                (register.name(), text)
+            })
+            .collect()
+    }
+}
+
+// NV-BDIZC, uppercase when set
+pub struct Flags(pub u8);
+
+impl fmt::Display for Flags {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (bit, name) in "NV-BDIZC".chars().enumerate() {
+            let set = self.0 & (0x80 >> bit) != 0;
+            let name = match name {
+                '-' => name,
+                _ if set => name,
+                _ => name.to_ascii_lowercase(),
+            };
+            write!(f, "{}", name)?;
+        }
+        Ok(())
+    }
+}
+
+// PC=$0200 A=$00 X=$00 Y=$00 SP=$FF P=$24 nv-bdIzc CYC=0
+impl<T: AddressBusIO<u16, u8>> fmt::Display for MOS6502<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "PC=${:04X} A=${:02X} X=${:02X} Y=${:02X} SP=${:02X} P=${:02X} {} CYC={}",
+            self.pc,
+            self.a,
+            self.x,
+            self.y,
+            self.sp,
+            self.status,
+            Flags(self.status),
+            self.ticks
+        )
+    }
+}
+
+impl<T: AddressBusIO<u16, u8>> fmt::Debug for MOS6502<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("MOS6502")
+            .field("pc", &format_args!("${:04X}", self.pc))
+            .field("a", &format_args!("${:02X}", self.a))
+            .field("x", &format_args!("${:02X}", self.x))
+            .field("y", &format_args!("${:02X}", self.y))
+            .field("sp", &format_args!("${:02X}", self.sp))
+            .field(
+                "status",
+                &format_args!("${:02X} {}", self.status, Flags(self.status)),
+            )
+            .field("ticks", &self.ticks)
+            .field("fault", &self.fault)
+            .field("halted", &self.halted)
+            .field("waiting", &self.waiting)
+            .field("stopped", &self.stopped)
+            .finish()
+    }
+}
+
+// unlike the reset line of raise, it is not masked by the interrupt flag
+impl<T: AddressBusIO<u16, u8>> Reset for MOS6502<T> {
+    fn reset(&mut self) {
+        self.reset_from(0xfffc);
+    }
 }
 
 impl<T: AddressBusIO<u16, u8>> Interrupt<u16> for MOS6502<T> {
@@ -1048,21 +2599,33 @@ impl<T: AddressBusIO<u16, u8>> Interrupt<u16> for MOS6502<T> {
     // line 6: NMI $FFFA/$FFFB
     // line 40: RESET $FFFC/$FFFD
     fn raise(&mut self, line: u16) {
+        if (self.halted || self.stopped) && line != 40 {
+            return;
+        }
         match line {
             4 => {
-                if !self.get_flag(INTERRUPT) && !self.get_flag(BRK) {
-                    self.interrupt(0xfffe);
-                    // set it later so the status can be restored from the stack
-                    self.set_flag(BRK, true);
+                self.waiting = false;
+                if !self.get_flag(INTERRUPT) {
+                    self.interrupt(0xfffe, 0);
+                    self.ticks += 5;
+                    self.interrupt_breakpoint(InterruptBreakpoint::Irq);
                 }
             }
-            6 => self.interrupt(0xfffa),
+            6 => {
+                self.waiting = false;
+                self.interrupt(0xfffa, 0);
+                self.ticks += 5;
+                self.interrupt_breakpoint(InterruptBreakpoint::Nmi);
+            }
             40 => {
                 if !self.get_flag(INTERRUPT) {
-                    self.reset(0xfffc)
+                    self.reset_from(0xfffc)
                 }
             }
+            #[cfg(feature = "std")]
             _ => println!("raised interrupt on line {}", line),
+            #[cfg(not(feature = "std"))]
+            _ => (),
         }
     }
 }
@@ -0,0 +1,181 @@
+use std::collections::VecDeque;
+use std::fmt;
+
+use mos6502::MOS6502;
+use ram::Ram;
+use {AddressBusIO, Clock, Debug};
+
+// the entry point of the automation mode, where no ppu is needed
+pub const START: u16 = 0xc000;
+
+// a mapper 0 ines image, the prg banks mapped at $8000 (mirrored when there
+// is a single one) on a flat 64k
+pub fn load_rom(image: &[u8]) -> Result<Ram<u8>, String> {
+    if image.len() < 16 || &image[0..4] != b"NES\x1a" {
+        return Err("not an ines image".to_string());
+    }
+    let banks = usize::from(image[4]);
+    let mapper = image[6] >> 4 | (image[7] & 0xf0);
+    if mapper != 0 {
+        return Err(format!("unsupported mapper {}", mapper));
+    }
+    if banks == 0 || banks > 2 {
+        return Err(format!("unsupported prg size of {} banks", banks));
+    }
+    // a trainer sits between the header and the prg
+    let start = if image[6] & 0x04 != 0 { 16 + 512 } else { 16 };
+    let prg = image
+        .get(start..start + banks * 0x4000)
+        .ok_or("truncated prg")?;
+    let mut ram = Ram::new(0x10000);
+    ram.fill(prg.to_vec(), 0x8000);
+    if banks == 1 {
+        ram.fill(prg.to_vec(), 0xc000);
+    }
+    Ok(ram)
+}
+
+// the columns of a nestest log line that the core can reproduce, the ppu
+// position and the cycles are left out
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TraceLine {
+    pub pc: u16,
+    pub a: u8,
+    pub x: u8,
+    pub y: u8,
+    pub p: u8,
+    pub sp: u8,
+}
+
+fn register(line: &str, name: &str) -> Option<u8> {
+    let start = line.rfind(name)? + name.len();
+    u8::from_str_radix(line.get(start..start + 2)?, 16).ok()
+}
+
+impl TraceLine {
+    // C000  4C F5 C5  JMP $C5F5    A:00 X:00 Y:00 P:24 SP:FD PPU:  0, 21 CYC:7
+    pub fn parse(line: &str) -> Option<TraceLine> {
+        Some(TraceLine {
+            pc: u16::from_str_radix(line.get(0..4)?, 16).ok()?,
+            a: register(line, " A:")?,
+            x: register(line, " X:")?,
+            y: register(line, " Y:")?,
+            p: register(line, " P:")?,
+            sp: register(line, " SP:")?,
+        })
+    }
+
+    pub fn of<T: AddressBusIO<u16, u8>>(cpu: &MOS6502<T>) -> TraceLine {
+        TraceLine {
+            pc: cpu.pc,
+            a: cpu.a,
+            x: cpu.x,
+            y: cpu.y,
+            p: cpu.status,
+            sp: cpu.sp,
+        }
+    }
+
+    // bits 4 and 5 are not stored in the 2a03, the log always shows them as
+    // 0 and 1
+    fn matches(&self, other: &TraceLine) -> bool {
+        let normalize = |line: &TraceLine| TraceLine {
+            p: line.p & !0x10 | 0x20,
+            ..*line
+        };
+        normalize(self) == normalize(other)
+    }
+}
+
+impl fmt::Display for TraceLine {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:04X}  A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X}",
+            self.pc, self.a, self.x, self.y, self.p, self.sp
+        )
+    }
+}
+
+pub struct Divergence {
+    // 1 based, as in an editor
+    pub line: usize,
+    pub expected: String,
+    pub got: TraceLine,
+    pub disassembly: String,
+    // the log lines before the divergence, all of them matched
+    pub context: Vec<String>,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "diverged at line {}", self.line)?;
+        for line in &self.context {
+            writeln!(f, "  {}", line)?;
+        }
+        writeln!(f, "- {}", self.expected)?;
+        write!(f, "+ {}  {}", self.got, self.disassembly)
+    }
+}
+
+pub struct Nestest {
+    pub start: u16,
+    // log lines shown before the divergence
+    pub context: usize,
+    // the canonical log moves on to the undocumented opcodes at some point
+    pub max_lines: usize,
+}
+
+impl Default for Nestest {
+    fn default() -> Nestest {
+        Nestest {
+            start: START,
+            context: 8,
+            max_lines: usize::MAX,
+        }
+    }
+}
+
+impl Nestest {
+    // the rom is expected to be already on the bus, returns the number of
+    // matching lines
+    pub fn compare<T: AddressBusIO<u16, u8>>(
+        &self,
+        cpu: &mut MOS6502<T>,
+        log: &str,
+    ) -> Result<usize, Divergence> {
+        // the state on the first line of the log
+        cpu.pc = self.start;
+        cpu.a = 0;
+        cpu.x = 0;
+        cpu.y = 0;
+        cpu.sp = 0xfd;
+        cpu.status = 0x24;
+        let mut context = VecDeque::with_capacity(self.context);
+        let mut compared = 0;
+        for (index, line) in log.lines().take(self.max_lines).enumerate() {
+            let got = TraceLine::of(cpu);
+            let (disassembly, _) = cpu.disassemble(cpu.pc);
+            let matched = TraceLine::parse(line).is_some_and(|expected| expected.matches(&got));
+            // stop before an opcode the core does not implement
+            if !matched || disassembly.starts_with(".byte") {
+                return Err(Divergence {
+                    line: index + 1,
+                    expected: line.to_string(),
+                    got,
+                    disassembly,
+                    context: context.into_iter().collect(),
+                });
+            }
+            if self.context > 0 {
+                if context.len() == self.context {
+                    context.pop_front();
+                }
+                context.push_back(line.to_string());
+            }
+            cpu.step();
+            compared += 1;
+        }
+        Ok(compared)
+    }
+}
@@ -0,0 +1,87 @@
+use mos6502::{access, cycles, describe, describe_illegal, page_penalty, MOS6502};
+use AddressBusIO;
+
+// what tools need to know of an opcode without running it
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Info {
+    pub opcode: u8,
+    pub mnemonic: &'static str,
+    pub mode: &'static str,
+    // opcode included
+    pub length: u8,
+    // without the page crossing and branch penalties
+    pub cycles: u8,
+    pub page_penalty: bool,
+    // the documented opcodes, the undocumented ones only run when the
+    // builder is told to
+    pub legal: bool,
+}
+
+pub fn length(mode: &str) -> u8 {
+    match mode {
+        "implied" | "accumulator" | "invalid" => 1,
+        "absolute"
+        | "absolute_x"
+        | "absolute_y"
+        | "indirect"
+        | "indirect_absolute_x"
+        | "zeropage_relative"
+        | "immediate_zeropage"
+        | "immediate_zeropage_x" => 3,
+        "immediate_absolute" | "immediate_absolute_x" => 4,
+        "block_transfer" => 7,
+        _ => 2,
+    }
+}
+
+pub fn info(opcode: u8) -> Info {
+    let documented = describe(opcode);
+    let legal = documented.1 != "invalid";
+    let (mnemonic, mode) = if legal {
+        documented
+    } else {
+        describe_illegal(opcode)
+    };
+    // as the nmos registers them
+    let access = access(mnemonic);
+    Info {
+        opcode,
+        mnemonic,
+        mode,
+        length: length(mode),
+        cycles: cycles(mnemonic, mode, access, false),
+        page_penalty: page_penalty(mnemonic, mode, access, false),
+        legal,
+    }
+}
+
+// all of the 256, in opcode order
+pub fn all() -> impl Iterator<Item = Info> {
+    (0..=0xff).map(info)
+}
+
+pub fn find(mnemonic: &str, mode: &str) -> Option<Info> {
+    all().find(|info| info.legal && info.mnemonic == mnemonic && info.mode == mode)
+}
+
+impl<T: AddressBusIO<u16, u8>> MOS6502<T> {
+    // the opcode as this cpu runs it, with the opcodes of its variant and the
+    // undocumented ones it was built with; the others are "invalid"
+    pub fn opcode_info(&self, opcode: u8) -> Info {
+        let decoded = self.lookup(opcode);
+        let registered = decoded.mode != "invalid";
+        Info {
+            opcode,
+            mnemonic: decoded.name,
+            mode: decoded.mode,
+            length: length(decoded.mode),
+            cycles: decoded.cycles,
+            page_penalty: decoded.page_penalty,
+            legal: registered && (self.variant.is_cmos() || describe(opcode).1 != "invalid"),
+        }
+    }
+
+    pub fn opcode_table(&self) -> impl Iterator<Item = Info> + '_ {
+        (0..=0xff).map(move |opcode| self.opcode_info(opcode))
+    }
+}
@@ -0,0 +1,66 @@
+// the io port of the 6510: the direction at $0000, a bit set for an output,
+// and the data at $0001; on the c64 its bits switch the roms in and out
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ProcessorPort {
+    pub direction: u8,
+    pub data: u8,
+    // what the machine drives on the pins left as inputs, pulled up when
+    // nothing does
+    pub input: u8,
+    changed: bool,
+}
+
+impl Default for ProcessorPort {
+    fn default() -> ProcessorPort {
+        ProcessorPort::new()
+    }
+}
+
+impl ProcessorPort {
+    // every pin an input after a reset
+    pub fn new() -> ProcessorPort {
+        ProcessorPort {
+            direction: 0,
+            data: 0,
+            input: 0xff,
+            changed: false,
+        }
+    }
+
+    pub fn decodes(addr: u16) -> bool {
+        addr < 2
+    }
+
+    // the outputs from the data, the inputs as the machine drives them
+    pub fn pins(&self) -> u8 {
+        (self.data & self.direction) | (self.input & !self.direction)
+    }
+
+    pub fn read(&self, addr: u16) -> u8 {
+        if addr == 0 {
+            self.direction
+        } else {
+            self.pins()
+        }
+    }
+
+    pub fn write(&mut self, addr: u16, value: u8) {
+        let before = self.pins();
+        if addr == 0 {
+            self.direction = value;
+        } else {
+            self.data = value;
+        }
+        if self.pins() != before {
+            self.changed = true;
+        }
+    }
+
+    // whether the pins changed since the previous call, for the machine to
+    // switch its banks
+    pub fn take_change(&mut self) -> bool {
+        let changed = self.changed;
+        self.changed = false;
+        changed
+    }
+}
@@ -0,0 +1,89 @@
+use mos6502::lockstep::Registers;
+use mos6502::{ALWAYS_SET, INTERRUPT};
+use AddressBusIO;
+
+// what the memory holds when the machine is switched on
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Fill {
+    // as the bus has it
+    Untouched,
+    Value(u8),
+    // runs of run bytes of first then second, as the dram of many machines
+    // comes up
+    Alternating { first: u8, second: u8, run: u16 },
+    // the same garbage for the same seed
+    Random(u32),
+}
+
+// the registers and the memory a cpu starts with, set by build and by
+// power_on; the reset sequence alone leaves them as they were
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct PowerUpState {
+    pub registers: Registers,
+    pub fill: Fill,
+    // from start to end, both included
+    pub start: u16,
+    pub end: u16,
+}
+
+impl Default for PowerUpState {
+    fn default() -> PowerUpState {
+        PowerUpState::new()
+    }
+}
+
+impl PowerUpState {
+    pub fn new() -> PowerUpState {
+        PowerUpState {
+            registers: Registers {
+                pc: 0,
+                a: 0,
+                x: 0,
+                y: 0,
+                sp: 0xff,
+                status: ALWAYS_SET | INTERRUPT,
+            },
+            fill: Fill::Untouched,
+            start: 0x0000,
+            end: 0x0000,
+        }
+    }
+
+    pub fn fill(mut self, fill: Fill, start: u16, end: u16) -> PowerUpState {
+        self.fill = fill;
+        self.start = start;
+        self.end = end;
+        self
+    }
+
+    // straight to the bus, no guard takes it for a write of the program
+    pub fn fill_memory<T: AddressBusIO<u16, u8>>(&self, bus: &mut T) {
+        // a xorshift, its state cannot be 0
+        let mut state = match self.fill {
+            Fill::Untouched => return,
+            Fill::Random(0) => 0x2545_f491,
+            Fill::Random(seed) => seed,
+            _ => 0,
+        };
+        for address in self.start..=self.end {
+            let value = match self.fill {
+                Fill::Value(value) => value,
+                Fill::Alternating { first, second, run } => {
+                    let index = (address - self.start) / run.max(1);
+                    if index & 1 == 0 {
+                        first
+                    } else {
+                        second
+                    }
+                }
+                _ => {
+                    state ^= state << 13;
+                    state ^= state >> 17;
+                    state ^= state << 5;
+                    state as u8
+                }
+            };
+            bus.write(address, value);
+        }
+    }
+}
@@ -0,0 +1,340 @@
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use mos6502::MOS6502;
+use utils::json::{self, Json};
+use {AddressBusIO, Clock};
+
+// the registers and the memory the vector cares about, before or after the
+// instruction
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct State {
+    pub pc: u16,
+    pub s: u8,
+    pub a: u8,
+    pub x: u8,
+    pub y: u8,
+    pub p: u8,
+    pub ram: Vec<(u16, u8)>,
+}
+
+// a single bus access, in the order the cpu does them
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Cycle {
+    pub address: u16,
+    pub value: u8,
+    pub write: bool,
+}
+
+impl fmt::Display for Cycle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let access = if self.write { "write" } else { "read" };
+        write!(f, "{} ${:02X} at ${:04X}", access, self.value, self.address)
+    }
+}
+
+pub struct ProcessorTest {
+    pub name: String,
+    pub initial: State,
+    pub expected: State,
+    pub cycles: Vec<Cycle>,
+}
+
+fn number<T: Copy>(json: &Json, max: u64, convert: fn(u64) -> T) -> Result<T, String> {
+    match json.as_u64() {
+        Some(number) if number <= max => Ok(convert(number)),
+        _ => Err(format!("invalid number {:?}", json)),
+    }
+}
+
+fn byte(json: &Json) -> Result<u8, String> {
+    number(json, 0xff, |number| number as u8)
+}
+
+fn word(json: &Json) -> Result<u16, String> {
+    number(json, 0xffff, |number| number as u16)
+}
+
+fn field<'a>(json: &'a Json, key: &str) -> Result<&'a Json, String> {
+    json.get(key).ok_or_else(|| format!("missing {}", key))
+}
+
+fn array<'a>(json: &'a Json, key: &str) -> Result<&'a [Json], String> {
+    field(json, key)?
+        .as_array()
+        .ok_or_else(|| format!("{} is not an array", key))
+}
+
+fn state(json: &Json) -> Result<State, String> {
+    let ram = array(json, "ram")?
+        .iter()
+        .map(|cell| match cell.as_array() {
+            Some([address, value]) => Ok((word(address)?, byte(value)?)),
+            _ => Err(format!("invalid ram cell {:?}", cell)),
+        })
+        .collect::<Result<_, String>>()?;
+    Ok(State {
+        pc: word(field(json, "pc")?)?,
+        s: byte(field(json, "s")?)?,
+        a: byte(field(json, "a")?)?,
+        x: byte(field(json, "x")?)?,
+        y: byte(field(json, "y")?)?,
+        p: byte(field(json, "p")?)?,
+        ram,
+    })
+}
+
+fn cycle(json: &Json) -> Result<Cycle, String> {
+    match json.as_array() {
+        Some([address, value, access]) => Ok(Cycle {
+            address: word(address)?,
+            value: byte(value)?,
+            write: match access.as_str() {
+                Some("read") => false,
+                Some("write") => true,
+                _ => return Err(format!("invalid access {:?}", access)),
+            },
+        }),
+        _ => Err(format!("invalid cycle {:?}", json)),
+    }
+}
+
+fn test(json: &Json) -> Result<ProcessorTest, String> {
+    let name = field(json, "name")?
+        .as_str()
+        .ok_or("name is not a string")?
+        .to_string();
+    let parsed = (|| {
+        Ok(ProcessorTest {
+            name: name.clone(),
+            initial: state(field(json, "initial")?)?,
+            expected: state(field(json, "final")?)?,
+            cycles: array(json, "cycles")?
+                .iter()
+                .map(cycle)
+                .collect::<Result<_, String>>()?,
+        })
+    })();
+    parsed.map_err(|error: String| format!("{}: {}", name, error))
+}
+
+// a file of the per opcode vectors, an array of tests
+pub fn parse(text: &str) -> Result<Vec<ProcessorTest>, String> {
+    json::parse(text)?
+        .as_array()
+        .ok_or("expected an array of tests")?
+        .iter()
+        .map(test)
+        .collect()
+}
+
+pub fn load<P: AsRef<Path>>(path: P) -> Result<Vec<ProcessorTest>, String> {
+    let text = fs::read_to_string(path.as_ref())
+        .map_err(|error| format!("{}: {}", path.as_ref().display(), error))?;
+    parse(&text)
+}
+
+// the flat 64k the vectors assume, logging every access
+pub struct TestBus {
+    memory: Vec<u8>,
+    pub cycles: Vec<Cycle>,
+}
+
+impl Default for TestBus {
+    fn default() -> TestBus {
+        TestBus::new()
+    }
+}
+
+impl TestBus {
+    pub fn new() -> TestBus {
+        TestBus {
+            memory: vec![0; 0x10000],
+            cycles: Vec::new(),
+        }
+    }
+
+    // without logging, for setting up and checking the memory
+    pub fn peek(&self, address: u16) -> u8 {
+        self.memory[usize::from(address)]
+    }
+
+    pub fn poke(&mut self, address: u16, value: u8) {
+        self.memory[usize::from(address)] = value;
+    }
+}
+
+impl AddressBusIO<u16, u8> for TestBus {
+    fn read(&mut self, address: u16) -> u8 {
+        let value = self.peek(address);
+        self.cycles.push(Cycle {
+            address,
+            value,
+            write: false,
+        });
+        value
+    }
+
+    fn write(&mut self, address: u16, value: u8) {
+        self.poke(address, value);
+        self.cycles.push(Cycle {
+            address,
+            value,
+            write: true,
+        });
+    }
+}
+
+// what a cpu (or a variant wrapping one) needs to run the vectors, the
+// memory is set and checked through the bus
+pub trait TestCpu {
+    fn set_registers(&mut self, state: &State);
+    // ram is left empty
+    fn registers(&self) -> State;
+    fn bus(&mut self) -> &mut TestBus;
+    // a whole instruction
+    fn step(&mut self);
+}
+
+impl TestCpu for MOS6502<TestBus> {
+    fn set_registers(&mut self, state: &State) {
+        self.pc = state.pc;
+        self.sp = state.s;
+        self.a = state.a;
+        self.x = state.x;
+        self.y = state.y;
+        self.status = state.p;
+    }
+
+    fn registers(&self) -> State {
+        State {
+            pc: self.pc,
+            s: self.sp,
+            a: self.a,
+            x: self.x,
+            y: self.y,
+            p: self.status,
+            ram: Vec::new(),
+        }
+    }
+
+    fn bus(&mut self) -> &mut TestBus {
+        self.bus_mut()
+    }
+
+    fn step(&mut self) {
+        Clock::step(self);
+    }
+}
+
+pub struct Failure {
+    pub name: String,
+    pub differences: Vec<String>,
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+        for difference in &self.differences {
+            write!(f, "\n  {}", difference)?;
+        }
+        Ok(())
+    }
+}
+
+fn compare<T: PartialEq + fmt::UpperHex>(
+    differences: &mut Vec<String>,
+    name: &str,
+    got: T,
+    expected: T,
+) {
+    if got != expected {
+        differences.push(format!(
+            "{}: got ${:02X}, expected ${:02X}",
+            name, got, expected
+        ));
+    }
+}
+
+// the cycles are optional, as not every core does the dummy accesses
+pub fn run<C: TestCpu>(
+    cpu: &mut C,
+    test: &ProcessorTest,
+    check_cycles: bool,
+) -> Result<(), Failure> {
+    cpu.set_registers(&test.initial);
+    for &(address, value) in &test.initial.ram {
+        cpu.bus().poke(address, value);
+    }
+    cpu.bus().cycles.clear();
+
+    cpu.step();
+
+    let mut differences = Vec::new();
+    let got = cpu.registers();
+    compare(&mut differences, "pc", got.pc, test.expected.pc);
+    compare(&mut differences, "s", got.s, test.expected.s);
+    compare(&mut differences, "a", got.a, test.expected.a);
+    compare(&mut differences, "x", got.x, test.expected.x);
+    compare(&mut differences, "y", got.y, test.expected.y);
+    compare(&mut differences, "p", got.p, test.expected.p);
+    for &(address, value) in &test.expected.ram {
+        let name = format!("${:04X}", address);
+        compare(&mut differences, &name, cpu.bus().peek(address), value);
+    }
+    if check_cycles {
+        let cycles = &cpu.bus().cycles;
+        let length = cycles.len().max(test.cycles.len());
+        for index in 0..length {
+            match (cycles.get(index), test.cycles.get(index)) {
+                (Some(got), Some(expected)) if got == expected => (),
+                (Some(got), Some(expected)) => {
+                    differences.push(format!("cycle {}: {}, expected {}", index, got, expected))
+                }
+                (Some(got), None) => differences.push(format!("cycle {}: extra {}", index, got)),
+                (None, Some(expected)) => {
+                    differences.push(format!("cycle {}: missing {}", index, expected))
+                }
+                (None, None) => (),
+            }
+        }
+    }
+
+    // the next test does not see what this one left behind
+    for &(address, _) in test.initial.ram.iter().chain(&test.expected.ram) {
+        cpu.bus().poke(address, 0);
+    }
+    for index in 0..cpu.bus().cycles.len() {
+        let address = cpu.bus().cycles[index].address;
+        cpu.bus().poke(address, 0);
+    }
+
+    if differences.is_empty() {
+        Ok(())
+    } else {
+        Err(Failure {
+            name: test.name.clone(),
+            differences,
+        })
+    }
+}
+
+pub struct Report {
+    pub passed: usize,
+    pub failures: Vec<Failure>,
+}
+
+pub fn run_all<C: TestCpu>(cpu: &mut C, tests: &[ProcessorTest], check_cycles: bool) -> Report {
+    let mut report = Report {
+        passed: 0,
+        failures: Vec::new(),
+    };
+    for test in tests {
+        match run(cpu, test, check_cycles) {
+            Ok(()) => report.passed += 1,
+            Err(failure) => report.failures.push(failure),
+        }
+    }
+    report
+}
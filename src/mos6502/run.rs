@@ -0,0 +1,76 @@
+use mos6502::breakpoints::{Breakpoint, WatchHit};
+use mos6502::MOS6502;
+use {AddressBusIO, Clock};
+
+// why run or run_until_pc gave back control
+#[derive(Clone, PartialEq, Debug)]
+pub enum Stop {
+    Reached,
+    // an instruction jumping or branching to itself, at pc
+    Trap(u16),
+    // a kil or a stp, the fault (if any) is left to take_fault
+    Halted,
+    // the next instruction is at an enabled breakpoint
+    Breakpoint(Breakpoint),
+    // an access to an enabled watchpoint, without a handler for them
+    Watchpoint(WatchHit),
+    // a brk or an interrupt breakpoint, take_interrupt_breakpoint tells which
+    Requested,
+    // the cycle budget ran out first
+    Limit,
+}
+
+impl<T: AddressBusIO<u16, u8>> MOS6502<T> {
+    // whole instructions until at least cycles have gone by, returns how many
+    // more than that the last one took
+    pub fn run_for_cycles(&mut self, cycles: u64) -> u64 {
+        let mut elapsed = 0;
+        while elapsed < cycles {
+            elapsed += self.step();
+        }
+        elapsed - cycles
+    }
+
+    // steps until the next instruction is the one at target, without looking
+    // at the current one so a loop can be run back to its start
+    pub fn run_until_pc(&mut self, target: u16, limit: u64) -> Stop {
+        self.run_until(Some(target), limit)
+    }
+
+    // as run_until_pc with no target, the breakpoint the run starts on is
+    // not hit again
+    pub fn run(&mut self, limit: u64) -> Stop {
+        self.run_until(None, limit)
+    }
+
+    fn run_until(&mut self, target: Option<u16>, limit: u64) -> Stop {
+        let mut elapsed = 0;
+        while elapsed < limit {
+            let pc = self.pc;
+            // a stall or a wai leave the pc where it is
+            let idle = self.stall != 0 || self.waiting;
+            elapsed += self.step();
+            if self.halted || self.stopped {
+                return Stop::Halted;
+            }
+            // the request is taken, as is_code_breakpoint_requested would
+            if self.requested_code_breakpoint {
+                self.requested_code_breakpoint = false;
+                if let Some(hit) = self.hit_watchpoint.take() {
+                    return Stop::Watchpoint(hit);
+                }
+                return match self.hit_breakpoint.take() {
+                    Some(breakpoint) => Stop::Breakpoint(breakpoint),
+                    None => Stop::Requested,
+                };
+            }
+            if Some(self.pc) == target {
+                return Stop::Reached;
+            }
+            if self.pc == pc && !idle && !self.waiting {
+                return Stop::Trap(pc);
+            }
+        }
+        Stop::Limit
+    }
+}
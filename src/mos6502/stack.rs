@@ -0,0 +1,70 @@
+use alloc::vec::Vec;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum StackViolation {
+    // sp wrapped from $00 to $FF on a push
+    Overflow { pc: u16 },
+    // sp wrapped from $FF to $00 on a pull
+    Underflow { pc: u16 },
+    // sp went below the configured threshold
+    Threshold { pc: u16, sp: u8 },
+    // a pushed return address was written before being pulled back
+    Smash { pc: u16, address: u16 },
+}
+
+pub struct StackGuard {
+    threshold: u8,
+    // stack offsets holding return addresses not yet pulled
+    return_slots: Vec<u8>,
+    violations: Vec<StackViolation>,
+}
+
+impl StackGuard {
+    pub fn new(threshold: u8) -> StackGuard {
+        StackGuard {
+            threshold,
+            return_slots: Vec::new(),
+            violations: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, pc: u16, sp: u8) {
+        if sp == 0x00 {
+            self.violations.push(StackViolation::Overflow { pc });
+        }
+        let new_sp = sp.wrapping_sub(1);
+        if sp >= self.threshold && new_sp < self.threshold {
+            self.violations
+                .push(StackViolation::Threshold { pc, sp: new_sp });
+        }
+    }
+
+    pub fn pull(&mut self, pc: u16, sp: u8) {
+        if sp == 0xff {
+            self.violations.push(StackViolation::Underflow { pc });
+        }
+    }
+
+    pub fn return_address_pushed(&mut self, sp: u8) {
+        // high byte at sp + 2, low byte at sp + 1 (sp is already decremented)
+        self.return_slots.push(sp.wrapping_add(1));
+        self.return_slots.push(sp.wrapping_add(2));
+    }
+
+    // stack is where the stack page starts, $2100 on the huc6280
+    pub fn write(&mut self, pc: u16, sp: u8, stack: u16, address: u16) {
+        if !(stack..=stack + 0xff).contains(&address) {
+            return;
+        }
+        // anything at or below sp has already been pulled (or was never pushed)
+        self.return_slots.retain(|slot| *slot > sp);
+        let offset = (address & 0xff) as u8;
+        if self.return_slots.contains(&offset) {
+            self.violations.push(StackViolation::Smash { pc, address });
+        }
+    }
+
+    pub fn take_violations(&mut self) -> Vec<StackViolation> {
+        self.violations.split_off(0)
+    }
+}
@@ -1,7 +1,15 @@
-use mos6502::{CARRY, MOS6502, SIGN, ZERO};
+use mos6502::asm::assemble;
+use mos6502::coverage::SelfModification;
+use mos6502::idle::IdleLoop;
+use mos6502::run::Stop;
+use mos6502::stack::StackViolation;
+use mos6502::uninit::UninitializedRead;
+use mos6502::{InterruptBreakpoint, BRK, CARRY, DECIMAL, INTERRUPT, MOS6502, OVERFLOW, SIGN, ZERO};
 use ram::Ram;
 use AddressBusIO;
 use Clock;
+use Debug;
+use Interrupt;
 
 #[test]
 fn test_adc_immediate() {
@@ -216,3 +224,2488 @@ fn test_ror_accumulator_carry() {
     assert_eq!(cpu.get_flag(ZERO), false);
     assert_eq!(cpu.get_flag(SIGN), true);
 }
+
+#[test]
+fn test_register_wrap() {
+    // inx, iny, dey, dex, inc $10, dec $11
+    let mut ram = Ram::new(1024);
+    ram.fill(vec![0xe8, 0xc8, 0x88, 0xca, 0xe6, 0x10, 0xc6, 0x11], 0);
+    ram.fill(vec![0xff, 0x00], 0x10);
+    let mut cpu = MOS6502::new(ram);
+    cpu.x = 0xff;
+    cpu.y = 0xff;
+    cpu.step();
+    cpu.step();
+    assert_eq!((cpu.x, cpu.y), (0x00, 0x00));
+    assert!(cpu.get_flag(ZERO));
+    cpu.step();
+    cpu.step();
+    assert_eq!((cpu.x, cpu.y), (0xff, 0xff));
+    assert!(cpu.get_flag(SIGN));
+    cpu.step();
+    cpu.step();
+    assert_eq!((cpu.read(0x10), cpu.read(0x11)), (0x00, 0xff));
+}
+
+#[test]
+fn test_address_wrap() {
+    let mut ram = Ram::new(0x10000);
+    // lda $f0,x, lda $ffff,y, lda ($ff),y
+    ram.fill(vec![0xb5, 0xf0, 0xb9, 0xff, 0xff, 0xb1, 0xff], 0x0200);
+    // the pointer at $ff takes its high byte from $00
+    ram.fill(vec![0x03, 0x55, 0x66], 0x0000);
+    ram.fill(vec![0x04], 0x00ff);
+    ram.fill(vec![0x77], 0x0306);
+    let mut cpu = MOS6502::new(ram);
+    cpu.pc = 0x0200;
+    cpu.x = 0x11;
+    cpu.y = 0x02;
+    cpu.step();
+    assert_eq!(cpu.a, 0x55);
+    cpu.step();
+    assert_eq!(cpu.a, 0x55);
+    cpu.step();
+    assert_eq!(cpu.a, 0x77);
+}
+
+#[test]
+fn test_set_overflow() {
+    let mut ram = Ram::new(0x10000);
+    // clv, clv
+    ram.fill(vec![0xb8, 0xb8], 0x0200);
+    let mut cpu = MOS6502::new(ram);
+    cpu.pc = 0x0200;
+    cpu.so(true);
+    assert_ne!(cpu.status & OVERFLOW, 0);
+    cpu.step();
+    // held, no new edge
+    cpu.so(true);
+    assert_eq!(cpu.status & OVERFLOW, 0);
+    cpu.so(false);
+    assert_eq!(cpu.status & OVERFLOW, 0);
+    cpu.so(true);
+    assert_ne!(cpu.status & OVERFLOW, 0);
+    cpu.step();
+    assert_eq!(cpu.status & OVERFLOW, 0);
+}
+
+#[test]
+fn test_stall() {
+    let mut ram = Ram::new(0x10000);
+    // pha, nop
+    ram.fill(vec![0x48, 0xea], 0x0200);
+    let mut cpu = MOS6502::new(ram);
+    cpu.pc = 0x0200;
+    cpu.a = 0x42;
+    cpu.stall(3);
+    assert_eq!(cpu.step(), 3);
+    assert_eq!(cpu.step(), 3);
+    assert!(!cpu.is_stalled());
+
+    // the write of pha goes on, the fetch after it waits
+    cpu.pc = 0x0200;
+    cpu.tick();
+    cpu.tick();
+    cpu.stall(2);
+    cpu.tick();
+    assert_eq!(cpu.read(0x01fe), 0x42);
+    cpu.tick();
+    cpu.tick();
+    assert_eq!(cpu.pc, 0x0201);
+    assert!(!cpu.is_stalled());
+    cpu.tick();
+    assert_eq!(cpu.pc, 0x0202);
+}
+
+#[test]
+fn test_indirect_pointer_wrap() {
+    // sta ($fe,x) with the pointer at $ff, its high byte from $00, under step
+    // and tick
+    for &ticked in &[false, true] {
+        let mut ram = Ram::new(0x10000);
+        ram.fill(vec![0x81, 0xfe], 0x0200);
+        ram.fill(vec![0x12], 0x0000);
+        ram.fill(vec![0x34], 0x00ff);
+        let mut cpu = MOS6502::new(ram);
+        cpu.pc = 0x0200;
+        cpu.a = 0x56;
+        cpu.x = 0x01;
+        let cycles = if ticked {
+            cpu.tick();
+            1 + cpu.finish_instruction()
+        } else {
+            cpu.step()
+        };
+        assert_eq!(cycles, 6);
+        assert_eq!(cpu.read(0x1234), 0x56);
+    }
+}
+
+#[test]
+fn test_indexed_by_register() {
+    let mut ram = Ram::new(0x10000);
+    // lda ($20,x), stx $30,y
+    ram.fill(vec![0xa1, 0x20, 0x96, 0x30], 0x0200);
+    ram.fill(vec![0x00, 0x12], 0x0024);
+    ram.fill(vec![0x99], 0x1200);
+    let mut cpu = MOS6502::new(ram);
+    cpu.pc = 0x0200;
+    cpu.x = 0x04;
+    cpu.y = 0x05;
+    cpu.step();
+    assert_eq!(cpu.a, 0x99);
+    cpu.step();
+    assert_eq!(cpu.read(0x0035), 0x04);
+    assert_eq!(cpu.read(0x0034), 0x00);
+}
+
+#[test]
+fn test_pc_wrap() {
+    let mut ram = Ram::new(0x10000);
+    // a nop at the top of memory runs into $0000, then bpl -3
+    ram.fill(vec![0xea], 0xffff);
+    ram.fill(vec![0x10, 0xfb], 0x0000);
+    let mut cpu = MOS6502::new(ram);
+    cpu.pc = 0xffff;
+    cpu.step();
+    assert_eq!(cpu.pc, 0x0000);
+    cpu.step();
+    assert_eq!(cpu.pc, 0xfffd);
+}
+
+#[test]
+fn test_stack_guard_threshold_and_overflow() {
+    let mut ram = Ram::new(1024);
+    // pha, pha
+    ram.fill(vec![0x48, 0x48], 0);
+    let mut cpu = MOS6502::new(ram);
+    cpu.enable_stack_guard(0xfe);
+    cpu.step();
+    assert!(cpu.take_stack_violations().is_empty());
+    cpu.step();
+    assert!(cpu.take_stack_violations() == vec![StackViolation::Threshold { pc: 1, sp: 0xfd }]);
+    cpu.sp = 0x00;
+    cpu.pc = 0;
+    cpu.step();
+    assert!(cpu.take_stack_violations() == vec![StackViolation::Overflow { pc: 0 }]);
+    assert_eq!(cpu.sp, 0xff);
+}
+
+#[test]
+fn test_stack_guard_smash() {
+    use mos6502::builder::Variant;
+
+    let mut ram = Ram::new(1024);
+    // jsr $0010
+    ram.fill(vec![0x20, 0x10, 0x00], 0);
+    // sta $01ff, rts
+    ram.fill(vec![0x8d, 0xff, 0x01, 0x60], 0x10);
+    let mut cpu = MOS6502::new(ram);
+    cpu.enable_stack_guard(0x00);
+    cpu.step();
+    cpu.step();
+    assert!(
+        cpu.take_stack_violations()
+            == vec![StackViolation::Smash {
+                pc: 0x10,
+                address: 0x01ff
+            }]
+    );
+    cpu.step();
+    assert!(cpu.take_stack_violations().is_empty());
+
+    // the stack of the huc6280 is at $2100
+    let mut ram = Ram::new(0x10000);
+    // jsr $0010
+    ram.fill(vec![0x20, 0x10, 0x00], 0);
+    // sta $01ff, sta $21ff
+    ram.fill(vec![0x8d, 0xff, 0x01, 0x8d, 0xff, 0x21], 0x10);
+    let mut cpu = MOS6502::builder(ram)
+        .variant(Variant::HuC6280)
+        .pc(0x0000)
+        .build();
+    cpu.enable_stack_guard(0x00);
+    cpu.step();
+    cpu.step();
+    assert!(cpu.take_stack_violations().is_empty());
+    cpu.step();
+    assert!(
+        cpu.take_stack_violations()
+            == vec![StackViolation::Smash {
+                pc: 0x13,
+                address: 0x21ff
+            }]
+    );
+}
+
+#[test]
+fn test_idle_branch_to_self() {
+    let mut ram = Ram::new(1024);
+    // clv, bvc *
+    ram.fill(vec![0xb8, 0x50, 0xfe], 0);
+    let mut cpu = MOS6502::new(ram);
+    cpu.enable_idle_detection(16);
+    cpu.step();
+    cpu.step();
+    assert!(!cpu.is_idle());
+    cpu.step();
+    assert!(cpu.is_idle());
+    assert!(cpu.take_idle_loop() == Some(IdleLoop { start: 1, end: 1 }));
+    assert!(cpu.take_idle_loop().is_none());
+}
+
+#[test]
+fn test_idle_poll_loop_with_progress() {
+    let mut ram = Ram::new(1024);
+    // loop: inc $10, lda $10, bne loop
+    ram.fill(vec![0xe6, 0x10, 0xa5, 0x10, 0xd0, 0xfa], 0);
+    let mut cpu = MOS6502::new(ram);
+    cpu.enable_idle_detection(16);
+    for _ in 0..30 {
+        cpu.step();
+        assert!(!cpu.is_idle());
+    }
+}
+
+#[test]
+fn test_interrupt_breakpoint_nmi() {
+    let mut ram = Ram::new(0x10000);
+    ram.fill(vec![0x00, 0x80], 0xfffa);
+    let mut cpu = MOS6502::new(ram);
+    cpu.set_interrupt_breakpoint(InterruptBreakpoint::Irq, true);
+    cpu.raise(6);
+    assert!(cpu.take_interrupt_breakpoint().is_none());
+    cpu.set_interrupt_breakpoint(InterruptBreakpoint::Nmi, true);
+    cpu.raise(6);
+    assert!(cpu.take_interrupt_breakpoint() == Some(InterruptBreakpoint::Nmi));
+    assert!(cpu.is_code_breakpoint_requested());
+    assert_eq!(cpu.pc, 0x8000);
+}
+
+#[test]
+fn test_interrupt_breakpoint_brk() {
+    let mut ram = Ram::new(0x10000);
+    ram.fill(vec![0x00, 0x90], 0xfffe);
+    let mut cpu = MOS6502::new(ram);
+    cpu.set_interrupt_breakpoint(InterruptBreakpoint::Brk, true);
+    cpu.step();
+    assert!(cpu.take_interrupt_breakpoint() == Some(InterruptBreakpoint::Brk));
+    assert_eq!(cpu.pc, 0x9000);
+}
+
+#[test]
+fn test_interrupt_lines() {
+    let mut ram = Ram::new(0x10000);
+    // cli, nop, nop; the handlers are rti
+    ram.fill(vec![0x58, 0xea, 0xea], 0x0200);
+    ram.fill(vec![0x40], 0x8000);
+    ram.fill(vec![0x40], 0x9000);
+    ram.fill(vec![0x00, 0x80, 0x00, 0x00, 0x00, 0x90], 0xfffa);
+    let mut cpu = MOS6502::new(ram);
+    cpu.pc = 0x0200;
+    // masked until cli
+    cpu.irq(true);
+    cpu.nmi();
+    assert_eq!(cpu.pc, 0x0200);
+    assert_eq!(cpu.step(), 7);
+    assert_eq!(cpu.pc, 0x8000);
+    cpu.step();
+    assert_eq!(cpu.pc, 0x0200);
+    cpu.step();
+    assert_eq!(cpu.step(), 7);
+    assert_eq!(cpu.pc, 0x9000);
+    // taken again after rti for as long as it is held
+    cpu.step();
+    assert_eq!(cpu.pc, 0x0201);
+    cpu.step();
+    assert_eq!(cpu.pc, 0x9000);
+    cpu.step();
+    cpu.irq(false);
+    cpu.step();
+    assert_eq!(cpu.pc, 0x0202);
+}
+
+#[test]
+fn test_accurate_interrupts() {
+    let machine = |program: Vec<u8>, accurate: bool| {
+        let mut ram = Ram::new(0x10000);
+        ram.fill(program, 0x0200);
+        ram.fill(vec![0x00, 0x03], 0xfffe);
+        ram.fill(vec![0x00, 0x04], 0xfffa);
+        MOS6502::builder(ram)
+            .accurate_interrupts(accurate)
+            .pc(0x0200)
+            .build()
+    };
+
+    // cli, nop, nop with the irq held: the nop after cli still runs
+    for &(accurate, pc) in &[(false, 0x0300), (true, 0x0202)] {
+        let mut cpu = machine(vec![0x58, 0xea, 0xea], accurate);
+        cpu.irq(true);
+        cpu.step();
+        cpu.step();
+        assert_eq!(cpu.pc, pc);
+    }
+
+    // beq to the next instruction, the irq comes on its second cycle and
+    // waits for the nop after it
+    let mut cpu = machine(vec![0xf0, 0x00, 0xea], true);
+    cpu.status &= !INTERRUPT;
+    cpu.status |= ZERO;
+    cpu.tick();
+    cpu.tick();
+    cpu.irq(true);
+    assert_eq!(cpu.finish_instruction(), 1);
+    cpu.tick();
+    assert_eq!(cpu.finish_instruction(), 1);
+    assert_eq!(cpu.pc, 0x0203);
+    cpu.tick();
+    cpu.finish_instruction();
+    assert_eq!(cpu.pc, 0x0300);
+
+    // an nmi during a brk takes it over, the pushed break bit stays
+    let mut cpu = machine(vec![0x00, 0x00], true);
+    cpu.tick();
+    cpu.tick();
+    cpu.nmi();
+    cpu.finish_instruction();
+    assert_eq!(cpu.pc, 0x0400);
+    assert_ne!(cpu.read(0x01fd) & BRK, 0);
+}
+
+#[test]
+fn test_halt() {
+    use Reset;
+
+    let mut ram = Ram::new(0x10000);
+    // kil
+    ram.fill(vec![0x12], 0x0200);
+    ram.fill(vec![0x00, 0x03, 0x00, 0x02], 0xfffa);
+    let mut cpu = MOS6502::new(ram);
+    cpu.pc = 0x0200;
+    cpu.step();
+    assert!(cpu.is_halted());
+    assert!(cpu.take_fault().is_some());
+    // nothing runs, not even an nmi
+    cpu.nmi();
+    cpu.raise(6);
+    assert_eq!(cpu.step(), 1);
+    assert_eq!(cpu.pc, 0x0200);
+    assert!(cpu.take_fault().is_none());
+    assert!(cpu.try_step().is_err());
+    cpu.reset();
+    assert!(!cpu.is_halted());
+    assert_eq!(cpu.pc, 0x0200);
+}
+
+#[test]
+fn test_status_pushes() {
+    use mos6502::Register;
+
+    let machine = |program: Vec<u8>| {
+        let mut ram = Ram::new(0x10000);
+        ram.fill(program, 0x0200);
+        ram.fill(vec![0x00, 0x03, 0x00, 0x03, 0x00, 0x03], 0xfffa);
+        let mut cpu = MOS6502::new(ram);
+        cpu.pc = 0x0200;
+        cpu
+    };
+
+    // the live status never has the break bit, the interrupt flag is clear
+    // so the irq is taken
+    for &live in &[0x20, 0x21, 0xeb] {
+        let pushes = [(0x01ff, 0x08, live | 0x30), (0x01fd, 0x00, live | 0x30)];
+        for &(address, opcode, expected) in &pushes {
+            let mut cpu = machine(vec![opcode, 0x00]);
+            cpu.status = live;
+            cpu.step();
+            assert_eq!(cpu.read(address), expected, "${:02X}", opcode);
+        }
+        for &line in &[4, 6] {
+            let mut cpu = machine(vec![]);
+            cpu.status = live;
+            cpu.raise(line);
+            assert_eq!(cpu.read(0x01fd), live, "line {}", line);
+        }
+    }
+
+    // plp and rti drop the break bit and keep the unused one set
+    for &(pulled, status) in &[
+        (0x00, 0x20),
+        (0x10, 0x20),
+        (0x30, 0x20),
+        (0xdf, 0xef),
+        (0xff, 0xef),
+    ] {
+        let mut cpu = machine(vec![0x28]);
+        cpu.sp = 0xfe;
+        cpu.write(0x01ff, pulled);
+        cpu.step();
+        assert_eq!(cpu.status, status);
+
+        let mut cpu = machine(vec![0x40]);
+        cpu.sp = 0xfc;
+        cpu.write(0x01fd, pulled);
+        cpu.step();
+        assert_eq!(cpu.status, status);
+    }
+
+    let mut cpu = machine(vec![]);
+    cpu.set(Register::P, 0xff);
+    assert_eq!(cpu.status, 0xef);
+}
+
+#[test]
+fn test_brk_rti() {
+    let mut ram = Ram::new(0x10000);
+    // brk and its signature byte, then nop; the handler is rti
+    ram.fill(vec![0x00, 0xff, 0xea], 0x0200);
+    ram.fill(vec![0x40], 0x9000);
+    ram.fill(vec![0x00, 0x90], 0xfffe);
+    let mut cpu = MOS6502::new(ram);
+    cpu.pc = 0x0200;
+    cpu.status = CARRY | 0x20;
+    cpu.step();
+    assert_eq!(cpu.pc, 0x9000);
+    assert_eq!(cpu.sp, 0xfc);
+    // pc + 2 and the status with the break flag
+    assert_eq!(cpu.read(0x01ff), 0x02);
+    assert_eq!(cpu.read(0x01fe), 0x02);
+    assert_eq!(cpu.read(0x01fd), CARRY | 0x30);
+    assert!(cpu.get_flag(INTERRUPT));
+    cpu.step();
+    assert_eq!(cpu.pc, 0x0202);
+    assert_eq!(cpu.status, CARRY | 0x20);
+
+    // an irq pushes it without
+    cpu.raise(4);
+    assert_eq!(cpu.read(0x01fd), CARRY | 0x20);
+    assert_eq!(cpu.pc, 0x9000);
+    // and is masked until the handler returns
+    cpu.raise(4);
+    assert_eq!(cpu.sp, 0xfc);
+}
+
+#[test]
+fn test_uninitialized_read() {
+    // the range reversed too
+    for &(start, end) in &[(0x0200, 0x02ff), (0x02ff, 0x0200)] {
+        let mut ram = Ram::new(1024);
+        // sta $0200, lda $0200, lda $0201, lda $0201
+        ram.fill(
+            vec![
+                0x8d, 0x00, 0x02, 0xad, 0x00, 0x02, 0xad, 0x01, 0x02, 0xad, 0x01, 0x02,
+            ],
+            0,
+        );
+        let mut cpu = MOS6502::new(ram);
+        cpu.enable_uninitialized_guard(start, end, true);
+        cpu.step();
+        cpu.step();
+        assert!(cpu.take_uninitialized_reads().is_empty());
+        assert!(!cpu.is_code_breakpoint_requested());
+        cpu.step();
+        assert!(
+            cpu.take_uninitialized_reads()
+                == vec![UninitializedRead {
+                    pc: 6,
+                    address: 0x0201
+                }]
+        );
+        assert!(cpu.is_code_breakpoint_requested());
+        cpu.step();
+        assert!(cpu.take_uninitialized_reads().is_empty());
+    }
+}
+
+#[test]
+fn test_store_does_not_read() {
+    // ram with a log of every read
+    struct Reads(Ram<u8>, Vec<u16>);
+
+    impl AddressBusIO<u16, u8> for Reads {
+        fn read(&mut self, address: u16) -> u8 {
+            self.1.push(address);
+            AddressBusIO::read(&mut self.0, address)
+        }
+        fn write(&mut self, address: u16, value: u8) {
+            AddressBusIO::write(&mut self.0, address, value)
+        }
+    }
+
+    let mut ram = Ram::new(0x10000);
+    // sta $0300 / stx $30,y / sty ($10),y / lda $0300 / inc $0300 / jmp $0400
+    ram.fill(
+        vec![
+            0x8d, 0x00, 0x03, 0x96, 0x30, 0x91, 0x10, 0xad, 0x00, 0x03, 0xee, 0x00, 0x03, 0x4c,
+            0x00, 0x04,
+        ],
+        0,
+    );
+    let mut cpu = MOS6502::new(Reads(ram, Vec::new()));
+    cpu.a = 0x42;
+    for _ in 0..3 {
+        cpu.step();
+    }
+    // only the opcodes, the operands and the ($10) pointer, never the targets
+    assert_eq!(cpu.bus.1, vec![0, 1, 2, 3, 4, 5, 6, 0x10, 0x11]);
+    cpu.bus.1.clear();
+    cpu.step();
+    assert_eq!(cpu.a, 0x42);
+    cpu.step();
+    assert_eq!(cpu.bus.1, vec![7, 8, 9, 0x0300, 10, 11, 12, 0x0300]);
+    cpu.bus.1.clear();
+    cpu.step();
+    assert_eq!(cpu.pc, 0x0400);
+    assert_eq!(cpu.bus.1, vec![13, 14, 15]);
+}
+
+#[test]
+fn test_decode_cache() {
+    struct Reads(Ram<u8>, Vec<u16>);
+
+    impl AddressBusIO<u16, u8> for Reads {
+        fn read(&mut self, address: u16) -> u8 {
+            self.1.push(address);
+            AddressBusIO::read(&mut self.0, address)
+        }
+        fn write(&mut self, address: u16, value: u8) {
+            AddressBusIO::write(&mut self.0, address, value)
+        }
+    }
+
+    let mut ram = Ram::new(1024);
+    // lda #$00 / clc / adc #$01 / sta $01 / jmp $0000, patching its own lda
+    ram.fill(
+        vec![0xa9, 0x00, 0x18, 0x69, 0x01, 0x85, 0x01, 0x4c, 0x00, 0x00],
+        0,
+    );
+    let mut cpu = MOS6502::new(Reads(ram, Vec::new()));
+    cpu.enable_decode_cache();
+    for _ in 0..5 {
+        cpu.step();
+    }
+    assert_eq!(cpu.bus.1, (0..10).collect::<Vec<u16>>());
+    cpu.bus.1.clear();
+    for _ in 0..5 {
+        cpu.step();
+    }
+    // only the patched lda is fetched again
+    assert_eq!(cpu.bus.1, vec![0, 1]);
+    assert_eq!(cpu.a, 2);
+    {
+        let cache = cpu.decode_cache().unwrap();
+        assert_eq!((cache.hits(), cache.misses()), (4, 6));
+    }
+
+    cpu.bus.0.fill(vec![0x10], 1);
+    cpu.invalidate_decode_cache(0x0001, 0x0001);
+    cpu.step();
+    assert_eq!(cpu.a, 0x10);
+}
+
+#[test]
+fn test_self_modification() {
+    let mut ram = Ram::new(1024);
+    // lda #$ea, sta $0000, sta $0010
+    ram.fill(vec![0xa9, 0xea, 0x8d, 0x00, 0x00, 0x8d, 0x10, 0x00], 0);
+    let mut cpu = MOS6502::new(ram);
+    cpu.enable_coverage();
+    cpu.step();
+    cpu.step();
+    cpu.step();
+    assert!(
+        cpu.take_self_modifications()
+            == vec![SelfModification {
+                pc: 2,
+                address: 0x0000
+            }]
+    );
+    assert_eq!(cpu.coverage().unwrap().executed_count(), 8);
+    assert!(!cpu.coverage().unwrap().is_executed(0x0010));
+}
+
+#[test]
+fn test_step_diff() {
+    let mut ram = Ram::new(1024);
+    // lda #$00, sta $0200
+    ram.fill(vec![0xa9, 0x00, 0x8d, 0x00, 0x02], 0);
+    let mut cpu = MOS6502::new(ram);
+    cpu.a = 0x10;
+    cpu.enable_step_diffs();
+    cpu.step();
+    let diff = cpu.take_step_diff().unwrap();
+    assert!(diff.registers == vec![("A", 0x10, 0x00)]);
+    assert_eq!(diff.flags_set, ZERO);
+    assert!(diff.writes.is_empty());
+    cpu.step();
+    let diff = cpu.take_step_diff().unwrap();
+    assert!(diff.registers.is_empty());
+    assert!(diff.writes == vec![(0x0200, 0x00)]);
+    assert_eq!(diff.to_string(), "[0002] -> 0005 $0200=00");
+}
+
+#[test]
+fn test_assemble() {
+    let source = "
+        screen = $0200
+        .org $0600
+    start:
+        ldx #0          ; comment
+    loop:
+        lda message,x
+        beq done
+        sta screen,x
+        sta $10,x
+        inx
+        jmp loop
+    done:
+        asl a
+        jmp (vector)
+    vector:
+        .word start
+    message:
+        .byte \"hi\", 0, <message, >message
+    ";
+    let assembly = assemble(source).unwrap();
+    assert_eq!(assembly.origin, 0x0600);
+    assert_eq!(assembly.labels["done"], 0x0610);
+    assert_eq!(
+        &assembly.bytes[0..17],
+        &[
+            0xa2, 0x00, 0xbd, 0x16, 0x06, 0xf0, 0x09, 0x9d, 0x00, 0x02, 0x95, 0x10, 0xe8, 0x4c,
+            0x02, 0x06, 0x0a
+        ]
+    );
+    assert_eq!(
+        &assembly.bytes[17..],
+        &[0x6c, 0x14, 0x06, 0x00, 0x06, b'h', b'i', 0x00, 0x16, 0x06]
+    );
+
+    let mut ram = Ram::new(0x10000);
+    ram.fill(assembly.bytes, 0x0600);
+    let mut cpu = MOS6502::new(ram);
+    cpu.pc = 0x0600;
+    for _ in 0..20 {
+        cpu.step();
+    }
+    assert_eq!(cpu.inspect(0x0201), b'i');
+
+    assert_eq!(
+        assemble("lda #$100").err().unwrap(),
+        "line 1: value 256 does not fit in a byte"
+    );
+    assert_eq!(
+        assemble("nop\n bne far\n.org $0200\nfar:").err().unwrap(),
+        "line 2: branch out of range (509)"
+    );
+    assert!(assemble("ldx $10,x").is_err());
+    assert!(assemble("foo").is_err());
+}
+
+#[test]
+fn test_cmp_sign_from_difference() {
+    let mut ram = Ram::new(1024);
+    // cmp #$f0, cmp #$10
+    ram.fill(vec![0xc9, 0xf0, 0xc9, 0x10], 0);
+    let mut cpu = MOS6502::new(ram);
+    cpu.a = 0x10;
+    cpu.step();
+    assert!(!cpu.get_flag(CARRY));
+    assert!(!cpu.get_flag(SIGN));
+    cpu.step();
+    assert!(cpu.get_flag(CARRY));
+    assert!(cpu.get_flag(ZERO));
+    assert!(!cpu.get_flag(SIGN));
+}
+
+#[test]
+fn test_debug_line() {
+    let mut ram = Ram::new(1024);
+    // lda $10
+    ram.fill(vec![0xa5, 0x10], 0);
+    let mut cpu = MOS6502::new(ram);
+    cpu.step();
+    assert_eq!(cpu.debug_line, "");
+    cpu.pc = 0;
+    cpu.debug = true;
+    cpu.step();
+    if cfg!(feature = "strip_debug") {
+        assert_eq!(cpu.debug_line, "");
+    } else {
+        assert!(cpu.debug_line.starts_with("lda $10 [A=$00"));
+    }
+}
+
+#[test]
+fn test_bus_accessors() {
+    let mut ram = Ram::new(0x10000);
+    // sta $10
+    ram.fill(vec![0x85, 0x10], 0x0200);
+    let mut cpu = MOS6502::builder(ram).pc(0x0200).build();
+    cpu.a = 0x42;
+    cpu.bus_mut().write(0x0202, 0xea);
+    cpu.step();
+    assert_eq!(cpu.bus().cells()[0x10], 0x42);
+    let mut ram = cpu.into_bus();
+    assert_eq!(ram.read(0x0202_u16), 0xea);
+}
+
+#[test]
+fn test_disassemble_at() {
+    let mut ram = Ram::new(0x10000);
+    // lda $1234,x, bne -4, then an illegal opcode
+    ram.fill(vec![0xbd, 0x34, 0x12, 0xd0, 0xfc, 0x02], 0x0200);
+    let mut cpu = MOS6502::new(ram);
+    cpu.enable_uninitialized_guard(0x0000, 0xffff, true);
+    assert_eq!(cpu.disassemble_at(0x0200), ("lda $1234,X".to_string(), 3));
+    assert_eq!(cpu.disassemble_at(0x0203), ("bne $0201".to_string(), 2));
+    assert_eq!(cpu.disassemble_at(0x0205), (".byte $02".to_string(), 1));
+    assert!(cpu.take_uninitialized_reads().is_empty());
+    assert_eq!((cpu.pc, cpu.ticks), (0, 0));
+}
+
+#[test]
+fn test_opcode_metadata() {
+    use mos6502::opcodes::{self, Info};
+
+    assert_eq!(
+        opcodes::info(0xbd),
+        Info {
+            opcode: 0xbd,
+            mnemonic: "lda",
+            mode: "absolute_x",
+            length: 3,
+            cycles: 4,
+            page_penalty: true,
+            legal: true,
+        }
+    );
+    assert_eq!(opcodes::info(0x0a).mnemonic, "asl");
+    assert!(!opcodes::info(0x02).legal);
+    // the undocumented ones with the cycles they run in
+    let dcp = opcodes::info(0xdf);
+    assert_eq!(
+        (dcp.mnemonic, dcp.cycles, dcp.page_penalty),
+        ("dcp", 7, false)
+    );
+    let nop = opcodes::info(0x1c);
+    assert_eq!(
+        (nop.mnemonic, nop.cycles, nop.page_penalty),
+        ("nop", 4, true)
+    );
+    assert_eq!(opcodes::all().filter(|info| info.legal).count(), 151);
+    assert_eq!(
+        opcodes::find("jmp", "indirect").map(|info| info.opcode),
+        Some(0x6c)
+    );
+    assert_eq!(opcodes::find("jmp", "immediate"), None);
+}
+
+#[test]
+fn test_opcode_table() {
+    use mos6502::builder::{IllegalOpcodes, Variant};
+    use mos6502::opcodes;
+    let cpu = MOS6502::new(Ram::new(0x10000));
+    for info in opcodes::all().filter(|info| info.legal) {
+        assert_eq!(cpu.opcode_info(info.opcode), info);
+    }
+    assert_eq!(cpu.opcode_table().filter(|info| info.legal).count(), 151);
+    assert_eq!(cpu.opcode_info(0x80).mode, "invalid");
+    let cmos = MOS6502::builder(Ram::new(0x10000))
+        .variant(Variant::Cmos)
+        .build();
+    let bra = cmos.opcode_info(0x80);
+    assert_eq!((bra.mnemonic, bra.mode, bra.length), ("bra", "relative", 2));
+    assert!(bra.legal);
+    let undocumented = MOS6502::builder(Ram::new(0x10000))
+        .illegal_opcodes(IllegalOpcodes::Execute)
+        .build();
+    let lax = undocumented.opcode_info(0xa7);
+    assert_eq!((lax.mnemonic, lax.cycles, lax.legal), ("lax", 3, false));
+    for info in opcodes::all() {
+        let registered = undocumented.opcode_info(info.opcode);
+        assert_eq!(
+            (registered.cycles, registered.page_penalty),
+            (info.cycles, info.page_penalty)
+        );
+    }
+    // the nmos family has the same undocumented ones
+    for &variant in &[Variant::Ricoh2A03, Variant::Mos6510] {
+        let undocumented = MOS6502::builder(Ram::new(0x10000))
+            .variant(variant)
+            .illegal_opcodes(IllegalOpcodes::Execute)
+            .build();
+        assert!(!undocumented.opcode_info(0xa7).legal, "{:?}", variant);
+        assert!(undocumented.opcode_info(0xa5).legal, "{:?}", variant);
+    }
+}
+
+#[test]
+fn test_instruction_iterator() {
+    let mut ram = Ram::new(0x10000);
+    // lda #$01 / sta $1000 / .byte $02
+    ram.fill(vec![0xa9, 0x01, 0x8d, 0x00, 0x10, 0x02], 0x0200);
+    let mut cpu = MOS6502::builder(ram).pc(0x0200).build();
+    let executed: Vec<_> = cpu.iter_instructions().take(10).collect();
+    assert_eq!(executed.len(), 3);
+    assert_eq!(executed[0].operands(), &[0x01]);
+    assert_eq!(executed[1].to_string(), "$0202: 8D 00 10 sta (4 cycles)");
+    assert_eq!((executed[2].pc, executed[2].opcode), (0x0205, 0x02));
+    assert!(cpu.take_fault().is_some());
+    // halted until a reset
+    assert_eq!(cpu.iter_instructions().next(), None);
+}
+
+#[test]
+fn test_run_helpers() {
+    let mut ram = Ram::new(0x10000);
+    // ldx #$03 / dex / bne -3 / jmp * / .byte $02
+    ram.fill(
+        vec![0xa2, 0x03, 0xca, 0xd0, 0xfd, 0x4c, 0x05, 0x02, 0x02],
+        0x0200,
+    );
+    let mut cpu = MOS6502::builder(ram).pc(0x0200).build();
+    // ldx, dex and the taken bne
+    assert_eq!(cpu.run_for_cycles(5), 2);
+    assert_eq!(cpu.pc, 0x0202);
+    assert_eq!(cpu.run_until_pc(0x0205, 1000), Stop::Reached);
+    assert_eq!(cpu.x, 0);
+    assert_eq!(cpu.run_until_pc(0x1234, 1000), Stop::Trap(0x0205));
+    cpu.pc = 0x0200;
+    assert_eq!(cpu.run_until_pc(0x1234, 4), Stop::Limit);
+    assert_eq!(cpu.pc, 0x0203);
+    cpu.pc = 0x0208;
+    assert_eq!(cpu.run_until_pc(0x1234, 1000), Stop::Halted);
+}
+
+#[test]
+fn test_breakpoints() {
+    let mut ram = Ram::new(0x10000);
+    // ldx #$03 / dex / bne -3 / jmp *
+    ram.fill(vec![0xa2, 0x03, 0xca, 0xd0, 0xfd, 0x4c, 0x05, 0x02], 0x0200);
+    let mut cpu = MOS6502::builder(ram).pc(0x0200).build();
+    cpu.add_breakpoint(0x0202);
+    cpu.add_breakpoint(0x0205);
+    assert!(cpu.enable_breakpoint(0x0205, false));
+    assert!(!cpu.enable_breakpoint(0x1234, true));
+    let stop = cpu.run(1000);
+    assert_eq!(cpu.pc, 0x0202);
+    match stop {
+        Stop::Breakpoint(breakpoint) => {
+            assert_eq!((breakpoint.address, breakpoint.hits), (0x0202, 1))
+        }
+        _ => panic!("{:?}", stop),
+    }
+    // resumed from it, once more each time round the loop
+    assert!(matches!(cpu.run(1000), Stop::Breakpoint(_)));
+    assert!(matches!(cpu.run(1000), Stop::Breakpoint(_)));
+    assert_eq!((cpu.x, cpu.breakpoints()[0].hits), (0x01, 3));
+    assert!(cpu.remove_breakpoint(0x0202));
+    assert_eq!(cpu.run(1000), Stop::Trap(0x0205));
+    // step reports them as code breakpoints
+    cpu.enable_breakpoint(0x0205, true);
+    cpu.step();
+    assert!(cpu.is_code_breakpoint_requested());
+    assert_eq!(
+        cpu.take_breakpoint().map(|breakpoint| breakpoint.address),
+        Some(0x0205)
+    );
+}
+
+#[test]
+fn test_conditional_breakpoints() {
+    use mos6502::condition::Condition;
+
+    let mut ram = Ram::new(0x10000);
+    // ldx #$06 / dex / bne -3 / jmp *
+    ram.fill(vec![0xa2, 0x06, 0xca, 0xd0, 0xfd, 0x4c, 0x05, 0x02], 0x0200);
+    ram.fill(vec![0x80], 0x2002);
+    let mut cpu = MOS6502::builder(ram).pc(0x0200).build();
+    let condition = Condition::parse("[0x2002] & 0x80 != 0 && x < 3 || a == $10").unwrap();
+    cpu.x = 0x06;
+    assert!(!condition.test(&mut cpu));
+    cpu.a = 0x10;
+    assert!(condition.test(&mut cpu));
+    cpu.a = 0;
+    assert_eq!(
+        Condition::parse("(x + 2) * 3 - -1")
+            .unwrap()
+            .evaluate(&mut cpu),
+        25
+    );
+    cpu.x = 0;
+    assert!(Condition::parse("!z && c == 0").unwrap().test(&mut cpu));
+    assert!(Condition::parse("x >").is_err());
+    assert!(Condition::parse("q == 1").is_err());
+    assert!(Condition::parse("[2002").is_err());
+
+    cpu.add_conditional_breakpoint(0x0203, "x == 2").unwrap();
+    match cpu.run(1000) {
+        Stop::Breakpoint(breakpoint) => {
+            assert_eq!(
+                breakpoint.condition.map(|c| c.to_string()),
+                Some("x == 2".to_string())
+            );
+        }
+        stop => panic!("{:?}", stop),
+    }
+    assert_eq!((cpu.pc, cpu.x), (0x0203, 0x02));
+    assert_eq!(cpu.breakpoints()[0].hits, 1);
+    assert_eq!(cpu.run(1000), Stop::Trap(0x0205));
+    assert!(cpu.add_conditional_breakpoint(0x0203, "x ==").is_err());
+}
+
+#[test]
+fn test_watchpoints() {
+    use mos6502::breakpoints::{Watch, WatchHit};
+    use std::sync::{Arc, Mutex};
+
+    let mut ram = Ram::new(0x10000);
+    // lda #$42 / sta $10 / lda $10 / inc $11 / jmp *
+    ram.fill(
+        vec![
+            0xa9, 0x42, 0x85, 0x10, 0xa5, 0x10, 0xe6, 0x11, 0x4c, 0x08, 0x02,
+        ],
+        0x0200,
+    );
+    let mut cpu = MOS6502::builder(ram).pc(0x0200).build();
+    cpu.add_watchpoint(0x0010, 0x0011, Watch::Write);
+    let write = WatchHit {
+        pc: 0x0202,
+        address: 0x0010,
+        value: 0x42,
+        write: true,
+    };
+    assert_eq!(cpu.run(1000), Stop::Watchpoint(write));
+    // the instruction is done, the read of lda is not watched
+    assert_eq!(cpu.pc, 0x0204);
+    match cpu.run(1000) {
+        Stop::Watchpoint(hit) => {
+            assert_eq!((hit.pc, hit.address, hit.value), (0x0206, 0x0011, 0x01))
+        }
+        stop => panic!("{:?}", stop),
+    }
+    // the write back of the old value is a dummy access
+    assert_eq!(cpu.watchpoints()[0].hits, 2);
+
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let logged = Arc::clone(&seen);
+    cpu.on_watchpoint(move |hit| logged.lock().unwrap().push((hit.address, hit.write)));
+    cpu.add_watchpoint(0x0010, 0x0011, Watch::Read);
+    cpu.pc = 0x0204;
+    assert_eq!(cpu.run(1000), Stop::Trap(0x0208));
+    assert_eq!(
+        *seen.lock().unwrap(),
+        vec![(0x0010, false), (0x0011, false)]
+    );
+    assert!(cpu.remove_watchpoint(0x0010, 0x0011));
+}
+
+#[test]
+fn test_step_traced() {
+    let mut ram = Ram::new(0x10000);
+    // lda $1000 / inc $1000,x / sta ($10),y / tax
+    ram.fill(
+        vec![0xad, 0x00, 0x10, 0xfe, 0xff, 0x0f, 0x91, 0x10, 0xaa],
+        0x0200,
+    );
+    ram.fill(vec![0x41], 0x1000);
+    ram.fill(vec![0x00, 0x30], 0x0010);
+    let mut cpu = MOS6502::builder(ram).pc(0x0200).build();
+    cpu.x = 0x01;
+    cpu.y = 0x02;
+    let load = cpu.step_traced().unwrap();
+    assert_eq!((load.name, load.mode, load.cycles), ("lda", "absolute", 4));
+    assert_eq!(
+        (load.address, load.read, load.written),
+        (Some(0x1000), Some(0x41), None)
+    );
+    let modify = cpu.step_traced().unwrap();
+    assert_eq!(modify.operands(), &[0xff, 0x0f]);
+    assert_eq!(modify.address, Some(0x1000));
+    assert_eq!(
+        (modify.read, modify.written, modify.cycles),
+        (Some(0x41), Some(0x42), 7)
+    );
+    let store = cpu.step_traced().unwrap();
+    assert_eq!(
+        (store.address, store.read, store.written),
+        (Some(0x3002), None, Some(0x41))
+    );
+    let implied = cpu.step_traced().unwrap();
+    assert_eq!(
+        (implied.address, implied.read, implied.written),
+        (None, None, None)
+    );
+}
+
+#[test]
+fn test_step_traced_interrupts() {
+    use mos6502::builder::Variant;
+
+    // wai, then lda #$42 at the nmi vector
+    let mut ram = Ram::new(0x10000);
+    ram.fill(vec![0xcb], 0x0200);
+    ram.fill(vec![0xa9, 0x42], 0x0300);
+    ram.fill(vec![0x00, 0x03], 0xfffa);
+    let mut cpu = MOS6502::builder(ram)
+        .variant(Variant::Cmos)
+        .pc(0x0200)
+        .build();
+    assert_eq!(cpu.step_traced().unwrap().name, "wai");
+    // nothing runs while it waits
+    assert_eq!(cpu.step_traced(), None);
+    cpu.nmi();
+    let handler = cpu.step_traced().unwrap();
+    assert_eq!(
+        (handler.pc, handler.name, handler.cycles),
+        (0x0300, "lda", 9)
+    );
+    assert_eq!((cpu.a, cpu.pc, cpu.waiting), (0x42, 0x0302, false));
+
+    // the stall goes first, then the interrupt and its first instruction
+    let mut ram = Ram::new(0x10000);
+    ram.fill(vec![0xa9, 0x17], 0x0200);
+    ram.fill(vec![0xa9, 0x42], 0x0300);
+    ram.fill(vec![0x00, 0x03], 0xfffa);
+    let mut cpu = MOS6502::builder(ram).pc(0x0200).build();
+    cpu.stall(4);
+    cpu.nmi();
+    let ticks = cpu.ticks;
+    assert_eq!(cpu.step_traced(), None);
+    assert_eq!((cpu.ticks - ticks, cpu.pc), (4, 0x0200));
+    let handler = cpu.step_traced().unwrap();
+    assert_eq!(
+        (handler.pc, handler.name, handler.cycles),
+        (0x0300, "lda", 9)
+    );
+    assert_eq!((cpu.a, cpu.pc), (0x42, 0x0302));
+}
+
+#[test]
+fn test_disassembler() {
+    use mos6502::disasm::{disassemble, disassemble_range};
+
+    // lda $1234,x / bne $0201 / .byte $02 / jmp, cut short
+    let bytes = [0xbd, 0x34, 0x12, 0xd0, 0xfc, 0x02, 0x4c, 0x00];
+    let lines = disassemble(&bytes, 0x0200);
+    let listing: Vec<String> = lines.iter().map(|line| line.to_string()).collect();
+    assert_eq!(
+        listing,
+        vec![
+            "$0200: BD 34 12  lda $1234,X",
+            "$0203: D0 FC     bne $0201",
+            "$0205: 02        .byte $02",
+            "$0206: 4C        .byte $4C",
+            "$0207: 00        brk",
+        ]
+    );
+    assert_eq!(lines[1].bytes(), &[0xd0, 0xfc]);
+
+    let mut ram = Ram::new(0x10000);
+    ram.fill(bytes.to_vec(), 0x0200);
+    assert_eq!(
+        disassemble_range(&mut ram, 0x0200, 0x0204),
+        lines[..2].to_vec()
+    );
+}
+
+#[test]
+fn test_decode() {
+    use mos6502::builder::Variant;
+
+    let mut ram = Ram::new(0x10000);
+    // lda $1234,x / .byte $02 / bra $0200
+    ram.fill(vec![0xbd, 0x34, 0x12, 0x02, 0x80, 0xfa], 0x0200);
+    let mut cpu = MOS6502::builder(ram).pc(0x0200).build();
+    let instruction = cpu.decode(0x0200);
+    assert_eq!(
+        (instruction.mnemonic, instruction.mode),
+        ("lda", "absolute_x")
+    );
+    assert_eq!(instruction.operands(), &[0x34, 0x12]);
+    assert_eq!((instruction.length, instruction.word()), (3, 0x1234));
+    let invalid = cpu.decode(0x0203);
+    assert_eq!((invalid.mode, invalid.length), ("invalid", 1));
+    assert!(invalid.operands().is_empty());
+    // nothing ran
+    assert_eq!((cpu.pc, cpu.ticks), (0x0200, 0));
+
+    // the opcodes of the variant
+    let ram = cpu.into_bus();
+    let mut cpu = MOS6502::builder(ram).variant(Variant::Cmos).build();
+    assert_eq!(cpu.decode(0x0204).mnemonic, "bra");
+}
+
+#[test]
+fn test_register_access() {
+    use mos6502::Register;
+
+    let mut cpu = MOS6502::new(Ram::new(0x10000));
+    for (index, &register) in Register::ALL.iter().enumerate() {
+        cpu.set(register, 0x1210 + index as u16);
+    }
+    assert_eq!(cpu.pc, 0x1210);
+    assert_eq!(
+        [cpu.a, cpu.x, cpu.y, cpu.sp, cpu.status],
+        [0x11, 0x12, 0x13, 0x14, 0x25]
+    );
+    assert_eq!(cpu.get(Register::Y), 0x13);
+    assert_eq!(Register::from_name("sp"), Some(Register::SP));
+    assert_eq!(Register::from_name("q"), None);
+    assert_eq!(cpu.registers()[4], ("SP", "$14".to_string()));
+}
+
+#[test]
+fn test_display() {
+    let mut ram = Ram::new(0x10000);
+    // sec / lda #$80
+    ram.fill(vec![0x38, 0xa9, 0x80], 0x0200);
+    let mut cpu = MOS6502::builder(ram).pc(0x0200).build();
+    cpu.step();
+    cpu.step();
+    assert_eq!(
+        format!("{}", cpu),
+        "PC=$0203 A=$80 X=$00 Y=$00 SP=$FF P=$A5 Nv-bdIzC CYC=4"
+    );
+    assert!(format!("{:?}", cpu).starts_with("MOS6502 { pc: $0203, a: $80,"));
+}
+
+#[test]
+fn test_reset() {
+    use Reset;
+
+    let mut ram = Ram::new(0x10000);
+    ram.fill(vec![0x00, 0x03], 0xfffc);
+    // cli / lda #$01 / pha / .byte $02
+    ram.fill(vec![0x58, 0xa9, 0x01, 0x48, 0x02], 0x0300);
+    let mut cpu = MOS6502::new(ram);
+    cpu.reset();
+    assert_eq!((cpu.pc, cpu.sp, cpu.ticks), (0x0300, 0xfc, 7));
+    for _ in 0..4 {
+        cpu.step();
+    }
+    assert!(cpu.take_fault().is_some());
+    // with the interrupts masked again, the registers are kept
+    cpu.reset();
+    assert_eq!(
+        [cpu.pc, u16::from(cpu.a), u16::from(cpu.sp)],
+        [0x0300, 1, 0xf8]
+    );
+    assert_eq!(cpu.status, 0x24);
+    assert_eq!(cpu.take_fault(), None);
+}
+
+#[test]
+fn test_step_cycles() {
+    let mut ram = Ram::new(0x10000);
+    // lda #$01 / sta $1000 / jsr $0300
+    ram.fill(vec![0xa9, 0x01, 0x8d, 0x00, 0x10, 0x20, 0x00, 0x03], 0x0200);
+    let mut cpu = MOS6502::builder(ram).pc(0x0200).build();
+    let cycles = [cpu.step(), cpu.step(), cpu.step()];
+    assert_eq!(cycles, [2, 4, 6]);
+    assert_eq!(cpu.ticks, 12);
+}
+
+#[test]
+fn test_observers() {
+    use events::CpuEvent;
+    use std::sync::{Arc, Mutex};
+
+    let mut ram = Ram::new(0x10000);
+    // sta $d000, sta $0200, brk
+    ram.fill(vec![0x8d, 0x00, 0xd0, 0x8d, 0x00, 0x02, 0x00], 0x0400);
+    ram.fill(vec![0x00, 0x90], 0xfffe);
+    let mut cpu = MOS6502::builder(ram).pc(0x0400).build();
+    cpu.a = 0x42;
+    cpu.trace_io_region(0xd000, 0xd3ff);
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let seen = Arc::clone(&events);
+    cpu.observers()
+        .subscribe(move |event| seen.lock().unwrap().push(*event));
+    for _ in 0..3 {
+        cpu.step();
+    }
+    assert_eq!(
+        *events.lock().unwrap(),
+        vec![
+            CpuEvent::IoWrite {
+                address: 0xd000,
+                value: 0x42
+            },
+            CpuEvent::InstructionRetired {
+                pc: 0x0400,
+                opcode: 0x8d,
+                cycles: 4
+            },
+            CpuEvent::InstructionRetired {
+                pc: 0x0403,
+                opcode: 0x8d,
+                cycles: 4
+            },
+            CpuEvent::InterruptTaken { handler: 0x9000 },
+            CpuEvent::InstructionRetired {
+                pc: 0x0406,
+                opcode: 0x00,
+                cycles: 7
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_indirect_jump() {
+    let mut ram = Ram::new(0x10000);
+    // jmp ($0300), jmp ($03ff)
+    ram.fill(vec![0x6c, 0x00, 0x03], 0x0200);
+    ram.fill(vec![0x6c, 0xff, 0x03], 0x1234);
+    ram.fill(vec![0x34, 0x12], 0x0300);
+    ram.fill(vec![0x78, 0x56], 0x03ff);
+    let mut cpu = MOS6502::new(ram);
+    cpu.pc = 0x0200;
+    assert_eq!(cpu.step(), 5);
+    assert_eq!(cpu.pc, 0x1234);
+    // the high byte comes from $0300, not $0400, on the nmos
+    cpu.step();
+    assert_eq!(cpu.pc, 0x3478);
+}
+
+#[test]
+fn test_illegal_opcodes() {
+    use mos6502::builder::IllegalOpcodes;
+    use mos6502::opcodes;
+
+    let program = vec![
+        0xa7, 0x10, // lax $10
+        0x87, 0x11, // sax $11
+        0xc7, 0x12, // dcp $12
+        0x07, 0x13, // slo $13
+        0x0b, 0x80, // anc #$80
+        0x4b, 0x03, // alr #$03
+        0xcb, 0x01, // sbx #$01
+        0x1c, 0xff, 0xff, // nop $ffff,x
+        0x02, // kil
+    ];
+    let mut ram = Ram::new(0x10000);
+    ram.fill(program.clone(), 0x0200);
+    ram.fill(vec![0x8f, 0x00, 0x90, 0x81], 0x0010);
+    let mut cpu = MOS6502::builder(ram)
+        .illegal_opcodes(IllegalOpcodes::Execute)
+        .pc(0x0200)
+        .build();
+    cpu.x = 0xf0;
+    cpu.step();
+    assert_eq!((cpu.a, cpu.x), (0x8f, 0x8f));
+    cpu.a = 0x0f;
+    cpu.step();
+    assert_eq!(cpu.read(0x0011), 0x0f);
+    // $90 - 1 compared to a
+    cpu.step();
+    assert_eq!(cpu.read(0x0012), 0x8f);
+    assert!(!cpu.get_flag(CARRY));
+    // $81 << 1 ored into a, the carry out of the shift
+    cpu.step();
+    assert_eq!((cpu.read(0x0013), cpu.a), (0x02, 0x0f));
+    assert!(cpu.get_flag(CARRY));
+    cpu.a = 0xff;
+    cpu.step();
+    assert_eq!(cpu.a, 0x80);
+    assert!(cpu.get_flag(CARRY) && cpu.get_flag(SIGN));
+    cpu.a = 0xff;
+    cpu.step();
+    assert_eq!(cpu.a, 0x01);
+    assert!(cpu.get_flag(CARRY));
+    cpu.a = 0x0f;
+    cpu.step();
+    assert_eq!(cpu.x, 0x0e);
+    cpu.step();
+    assert_eq!(cpu.pc, 0x0211);
+    assert_eq!(cpu.take_fault(), None);
+    cpu.step();
+    assert!(cpu.take_fault().is_some());
+    assert_eq!(cpu.disassemble_at(0x0200).0, "lax $10");
+    assert_eq!(opcodes::info(0xa7).mnemonic, "lax");
+    assert!(!opcodes::info(0xa7).legal);
+
+    // they jam unless asked for
+    let mut ram = Ram::new(0x10000);
+    ram.fill(program, 0x0200);
+    let mut cpu = MOS6502::builder(ram).pc(0x0200).build();
+    cpu.step();
+    assert!(cpu.take_fault().is_some());
+}
+
+#[test]
+fn test_illegal_opcode_callback() {
+    use std::sync::{Arc, Mutex};
+
+    let mut ram = Ram::new(0x10000);
+    // .byte $02 / .byte $f2 / nop
+    ram.fill(vec![0x02, 0xf2, 0xea], 0x0200);
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let logged = Arc::clone(&seen);
+    let mut cpu = MOS6502::builder(ram)
+        .on_illegal_opcode(move |pc, opcode| logged.lock().unwrap().push((pc, opcode)))
+        .pc(0x0200)
+        .build();
+    for _ in 0..3 {
+        cpu.step();
+    }
+    assert_eq!(cpu.pc, 0x0203);
+    assert!(!cpu.is_halted());
+    assert_eq!(*seen.lock().unwrap(), vec![(0x0200, 0x02), (0x0201, 0xf2)]);
+}
+
+#[test]
+#[should_panic(expected = "invalid opcode $02 at $0200")]
+fn test_illegal_opcode_panic() {
+    use mos6502::builder::IllegalOpcodes;
+
+    let mut ram = Ram::new(0x10000);
+    ram.fill(vec![0x02], 0x0200);
+    let mut cpu = MOS6502::builder(ram)
+        .illegal_opcodes(IllegalOpcodes::Panic)
+        .pc(0x0200)
+        .build();
+    cpu.step();
+}
+
+#[test]
+fn test_jam_cycles() {
+    use mos6502::builder::IllegalOpcodes;
+
+    // lda #$01 / .byte $02
+    for &policy in &[IllegalOpcodes::Jam, IllegalOpcodes::Nop] {
+        for &ticked in &[false, true] {
+            let mut ram = Ram::new(0x10000);
+            ram.fill(vec![0xa9, 0x01, 0x02], 0x0200);
+            let mut cpu = MOS6502::builder(ram)
+                .illegal_opcodes(policy)
+                .pc(0x0200)
+                .build();
+            let cycles: Vec<u64> = (0..2)
+                .map(|_| {
+                    if ticked {
+                        cpu.tick();
+                        1 + cpu.finish_instruction()
+                    } else {
+                        cpu.step()
+                    }
+                })
+                .collect();
+            assert_eq!(cycles, vec![2, 2], "{:?} ticked {}", policy, ticked);
+            let jammed = policy == IllegalOpcodes::Jam;
+            assert_eq!(cpu.is_halted(), jammed);
+            assert_eq!(cpu.pc, if jammed { 0x0202 } else { 0x0203 });
+        }
+    }
+}
+
+#[test]
+fn test_builder() {
+    use alloc::sync::{Arc, Mutex};
+    use mos6502::builder::{IllegalOpcodes, Quirks, Variant};
+
+    let mut ram = Ram::new(0x10000);
+    // jmp ($10ff) / .byte $02 / brk
+    ram.fill(vec![0x6c, 0xff, 0x10, 0x02, 0x00], 0x0200);
+    ram.fill(vec![0x03], 0x10ff);
+    ram.fill(vec![0x02], 0x1000);
+    ram.fill(vec![0x04], 0x1100);
+    let mut cpu = MOS6502::builder(ram)
+        .variant(Variant::Nmos)
+        .illegal_opcodes(IllegalOpcodes::Nop)
+        .pc(0x0200)
+        .build();
+    assert_eq!(cpu.quirks(), Quirks::of(Variant::Nmos));
+    // the high byte from the same page
+    cpu.step();
+    assert_eq!(cpu.pc, 0x0203);
+    cpu.step();
+    assert_eq!(cpu.pc, 0x0204);
+    assert_eq!(cpu.take_fault(), None);
+
+    let lines = Arc::new(Mutex::new(Vec::new()));
+    let traced = Arc::clone(&lines);
+    let mut ram = Ram::new(0x10000);
+    // sed / brk
+    ram.fill(vec![0xf8, 0x00], 0x0200);
+    let mut cpu = MOS6502::builder(ram)
+        .variant(Variant::Cmos)
+        .pc(0x0200)
+        .trace(move |pc, line: &str| traced.lock().unwrap().push((pc, line.to_string())))
+        .build();
+    cpu.step();
+    cpu.step();
+    assert!(!cpu.get_flag(DECIMAL));
+    if !cfg!(feature = "strip_debug") {
+        let lines = lines.lock().unwrap();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[1].0, 0x0201);
+        assert!(lines[0].1.starts_with("sed"));
+    }
+}
+
+#[test]
+fn test_cmos() {
+    use mos6502::builder::Variant;
+
+    let machine = |program: Vec<u8>, variant| {
+        let mut ram = Ram::new(0x10000);
+        ram.fill(program, 0x0200);
+        ram.fill(vec![0x00, 0x03], 0x0020);
+        ram.fill(vec![0x99], 0x0300);
+        ram.fill(vec![0x00, 0x05], 0x0402);
+        MOS6502::builder(ram).variant(variant).pc(0x0200).build()
+    };
+    let program = vec![
+        0xa9, 0x55, // lda #$55
+        0x64, 0x10, // stz $10
+        0x04, 0x10, // tsb $10
+        0xa9, 0x0f, // lda #$0f
+        0x14, 0x10, // trb $10
+        0xa2, 0x07, // ldx #$07
+        0xda, // phx
+        0x7a, // ply
+        0x80, 0x02, // bra +2
+        0x00, 0x00, // brk
+        0xb2, 0x20, // lda ($20)
+        0x89, 0x00, // bit #$00
+        0x1a, // inc a
+        0xa2, 0x02, // ldx #$02
+        0x7c, 0x00, 0x04, // jmp ($0400,x)
+    ];
+    // the same under step and tick
+    let mut ticks = Vec::new();
+    for &ticked in &[false, true] {
+        let mut cpu = machine(program.clone(), Variant::Cmos);
+        for _ in 0..14 {
+            if ticked {
+                cpu.tick();
+                cpu.finish_instruction();
+            } else {
+                cpu.step();
+            }
+            if cpu.pc == 0x0206 {
+                assert_eq!(cpu.peek8(0x0010), 0x55);
+                assert!(cpu.get_flag(ZERO));
+            }
+            // bit #imm leaves n alone
+            if cpu.pc == 0x0216 {
+                assert!(cpu.get_flag(ZERO) && cpu.get_flag(SIGN));
+            }
+        }
+        assert_eq!(cpu.peek8(0x0010), 0x50);
+        assert_eq!(cpu.y, 0x07);
+        assert_eq!(cpu.a, 0x9a);
+        assert_eq!(cpu.pc, 0x0500);
+        ticks.push(cpu.ticks);
+    }
+    assert_eq!(ticks[0], ticks[1]);
+
+    // sed / clc / lda #$99 / adc #$01, z from the decimal result on the cmos
+    let decimal = vec![0xf8, 0x18, 0xa9, 0x99, 0x69, 0x01];
+    for &(variant, zero, cycles) in &[(Variant::Cmos, true, 3), (Variant::Nmos, false, 2)] {
+        let mut cpu = machine(decimal.clone(), variant);
+        for _ in 0..3 {
+            cpu.step();
+        }
+        assert_eq!(cpu.step(), cycles);
+        assert_eq!(cpu.a, 0x00);
+        assert!(cpu.get_flag(CARRY));
+        assert_eq!(cpu.get_flag(ZERO), zero);
+        // and as many under tick
+        let mut ticked = machine(decimal.clone(), variant);
+        for _ in 0..3 {
+            ticked.tick();
+            ticked.finish_instruction();
+        }
+        ticked.tick();
+        assert_eq!(ticked.finish_instruction() + 1, cycles);
+        assert_eq!((ticked.a, ticked.ticks), (0x00, cpu.ticks));
+    }
+}
+
+#[test]
+fn test_bit_instructions() {
+    use mos6502::builder::Variant;
+
+    let program = vec![
+        0x87, 0x10, // smb0 $10
+        0x0f, 0x10, 0x03, // bbr0 $10,+3
+        0x8f, 0x10, 0x01, // bbs0 $10,+1
+        0x00, // brk
+        0x07, 0x10, // rmb0 $10
+        0x0f, 0x10, 0xf2, // bbr0 $10,-14
+    ];
+    for &ticked in &[false, true] {
+        let mut ram = Ram::new(0x10000);
+        ram.fill(program.clone(), 0x0200);
+        let mut cpu = MOS6502::builder(ram)
+            .variant(Variant::Cmos)
+            .pc(0x0200)
+            .build();
+        assert_eq!(cpu.disassemble_at(0x0202).0, "bbr0 $10,$0208");
+        let mut cycles = Vec::new();
+        for _ in 0..5 {
+            let ticks = cpu.ticks;
+            if ticked {
+                cpu.tick();
+                cpu.finish_instruction();
+            } else {
+                cpu.step();
+            }
+            cycles.push(cpu.ticks - ticks);
+        }
+        // a taken branch costs a cycle more
+        assert_eq!(cycles, vec![5, 5, 6, 5, 6]);
+        assert_eq!(cpu.peek8(0x0010), 0x00);
+        assert_eq!(cpu.pc, 0x0200);
+    }
+}
+
+#[test]
+fn test_wait_and_stop() {
+    use mos6502::builder::Variant;
+    use Reset;
+
+    let machine = || {
+        let mut ram = Ram::new(0x10000);
+        // wai / lda #$01 / stp
+        ram.fill(vec![0xcb, 0xa9, 0x01, 0xdb], 0x0200);
+        ram.fill(vec![0x00, 0x02], 0xfffc);
+        ram.fill(vec![0x00, 0x03], 0xfffe);
+        MOS6502::builder(ram)
+            .variant(Variant::Cmos)
+            .pc(0x0200)
+            .build()
+    };
+
+    let mut cpu = machine();
+    assert_eq!(cpu.step(), 3);
+    assert!(cpu.is_waiting() && cpu.is_idle());
+    assert_eq!(cpu.step(), 1);
+    assert_eq!(cpu.pc, 0x0201);
+    // a masked irq only wakes it up
+    cpu.irq(true);
+    assert_eq!(cpu.step(), 2);
+    assert_eq!(cpu.a, 0x01);
+    assert_eq!(cpu.step(), 3);
+    assert!(cpu.is_stopped());
+    cpu.nmi();
+    assert_eq!(cpu.step(), 1);
+    assert_eq!(cpu.pc, 0x0204);
+    cpu.reset();
+    assert!(!cpu.is_stopped());
+    assert_eq!(cpu.pc, 0x0200);
+
+    // under tick, an irq let through is taken
+    let mut cpu = machine();
+    cpu.status &= !INTERRUPT;
+    cpu.tick();
+    assert_eq!(cpu.finish_instruction(), 2);
+    for _ in 0..10 {
+        cpu.tick();
+    }
+    assert!(cpu.is_waiting() && !cpu.in_instruction());
+    cpu.irq(true);
+    cpu.tick();
+    assert_eq!(cpu.finish_instruction(), 6);
+    assert_eq!(cpu.pc, 0x0300);
+}
+
+#[test]
+fn test_processor_port() {
+    use mos6502::builder::Variant;
+
+    let mut ram = Ram::new(0x10000);
+    // lda #$07 / sta $00 / lda #$05 / sta $01 / lda $01
+    ram.fill(
+        vec![0xa9, 0x07, 0x85, 0x00, 0xa9, 0x05, 0x85, 0x01, 0xa5, 0x01],
+        0x0200,
+    );
+    let mut cpu = MOS6502::builder(ram)
+        .variant(Variant::Mos6510)
+        .pc(0x0200)
+        .build();
+    for _ in 0..4 {
+        cpu.step();
+    }
+    {
+        let port = cpu.processor_port_mut().unwrap();
+        // the inputs are pulled up
+        assert_eq!(port.pins(), 0xfd);
+        assert!(port.take_change());
+        assert!(!port.take_change());
+        port.input = 0x00;
+    }
+    cpu.step();
+    assert_eq!(cpu.a, 0x05);
+    // nothing reaches the memory under it
+    assert_eq!(cpu.bus_mut().read(0x0001), 0x00);
+
+    let mut ram = Ram::new(0x10000);
+    ram.fill(vec![0xa9, 0x05, 0x85, 0x01], 0x0200);
+    let mut cpu = MOS6502::builder(ram).pc(0x0200).build();
+    cpu.step();
+    cpu.step();
+    assert!(cpu.processor_port().is_none());
+    assert_eq!(cpu.bus_mut().read(0x0001), 0x05);
+}
+
+#[test]
+fn test_ricoh_binary_only() {
+    use mos6502::builder::Variant;
+
+    // sed / clc / lda #$09 / adc #$01 / sec / lda #$10 / sbc #$01
+    let program = vec![
+        0xf8, 0x18, 0xa9, 0x09, 0x69, 0x01, 0x38, 0xa9, 0x10, 0xe9, 0x01,
+    ];
+    for &(variant, sum, difference) in &[
+        (Variant::Nmos, 0x10, 0x09),
+        (Variant::Ricoh2A03, 0x0a, 0x0f),
+    ] {
+        let mut ram = Ram::new(0x10000);
+        ram.fill(program.clone(), 0x0200);
+        let mut cpu = MOS6502::builder(ram).variant(variant).pc(0x0200).build();
+        for _ in 0..4 {
+            cpu.step();
+        }
+        assert_eq!(cpu.a, sum);
+        for _ in 0..3 {
+            cpu.step();
+        }
+        assert_eq!(cpu.a, difference);
+        // the flag itself is still there
+        assert!(cpu.get_flag(DECIMAL));
+    }
+}
+
+#[test]
+fn test_cmos_timings() {
+    use mos6502::builder::Variant;
+
+    // ldx #$01 / asl $0300,x / asl $03ff,x / inc $0300,x / jmp ($0400)
+    let program = vec![
+        0xa2, 0x01, 0x1e, 0x00, 0x03, 0x1e, 0xff, 0x03, 0xfe, 0x00, 0x03, 0x6c, 0x00, 0x04,
+    ];
+    let nmos = [2, 7, 7, 7, 5];
+    let cmos = [2, 6, 7, 7, 6];
+    for &(variant, expected) in &[(Variant::Nmos, nmos), (Variant::Cmos, cmos)] {
+        for &ticked in &[false, true] {
+            let mut ram = Ram::new(0x10000);
+            ram.fill(program.clone(), 0x0200);
+            ram.fill(vec![0x41], 0x0301);
+            let mut cpu = MOS6502::builder(ram).variant(variant).pc(0x0200).build();
+            let cycles: Vec<u64> = (0..5)
+                .map(|_| {
+                    if ticked {
+                        cpu.tick();
+                        1 + cpu.finish_instruction()
+                    } else {
+                        cpu.step()
+                    }
+                })
+                .collect();
+            assert_eq!(cycles, expected, "{:?} ticked {}", variant, ticked);
+            assert_eq!((cpu.peek8(0x0301), cpu.pc), (0x83, 0x0000));
+        }
+    }
+}
+
+#[test]
+fn test_cmos_undefined_nops() {
+    use mos6502::builder::Variant;
+
+    // $03 / $02 #$ff / $44 $10 / $f4 $10 / $5c $1234 / $dc $1234 / lda #$01
+    let program = vec![
+        0x03, 0x02, 0xff, 0x44, 0x10, 0xf4, 0x10, 0x5c, 0x34, 0x12, 0xdc, 0x34, 0x12, 0xa9, 0x01,
+    ];
+    let pcs = [0x0201, 0x0203, 0x0205, 0x0207, 0x020a, 0x020d, 0x020f];
+    for &ticked in &[false, true] {
+        let mut ram = Ram::new(0x10000);
+        ram.fill(program.clone(), 0x0200);
+        let mut cpu = MOS6502::builder(ram)
+            .variant(Variant::Cmos)
+            .pc(0x0200)
+            .build();
+        let steps: Vec<(u64, u16)> = (0..7)
+            .map(|_| {
+                let cycles = if ticked {
+                    cpu.tick();
+                    1 + cpu.finish_instruction()
+                } else {
+                    cpu.step()
+                };
+                (cycles, cpu.pc)
+            })
+            .collect();
+        let cycles = [1, 2, 3, 4, 8, 4, 2];
+        let expected: Vec<(u64, u16)> = cycles.iter().cloned().zip(pcs.iter().cloned()).collect();
+        assert_eq!(steps, expected, "ticked {}", ticked);
+        assert_eq!(cpu.opcode_info(0x03).mnemonic, "nop");
+        assert!(!cpu.halted);
+        assert_eq!(cpu.a, 0x01);
+    }
+}
+
+#[test]
+fn test_functional_harness() {
+    use mos6502::functional::FunctionalTest;
+
+    let run = |source: &str| {
+        let program = assemble(source).unwrap();
+        let mut ram = Ram::new(0x10000);
+        ram.fill(program.bytes, usize::from(program.origin));
+        let harness = FunctionalTest {
+            success: program.labels["done"],
+            ..FunctionalTest::default()
+        };
+        harness.run(&mut MOS6502::new(ram))
+    };
+
+    let passed = run("
+        .org $0400
+        lda #$01
+        sta $0200
+done:   jmp done
+    ");
+    assert_eq!(passed.ok(), Some(3));
+
+    let trap = run("
+        .org $0400
+        lda #$07
+        sta $0200
+        ldx #$00
+trap:   beq trap
+done:   jmp done
+    ")
+    .err()
+    .unwrap();
+    assert_eq!(trap.pc, 0x0407);
+    assert_eq!(trap.test_case, 0x07);
+    assert!(!trap.timed_out);
+    assert_eq!(trap.trace.len(), 4);
+    assert!(trap.trace[3].starts_with("$0407: beq $0407"));
+}
+
+// the binary is not distributed with the sources
+#[cfg(feature = "functional_test")]
+#[test]
+fn test_klaus_dormann_functional() {
+    use mos6502::functional::FunctionalTest;
+    use std::env;
+    use std::fs;
+
+    let filename =
+        env::var("FUNCTIONAL_TEST").unwrap_or_else(|_| "6502_functional_test.bin".to_string());
+    let image = fs::read(&filename).unwrap_or_else(|err| panic!("{}: {}", filename, err));
+    let mut ram = Ram::new(0x10000);
+    ram.fill(image, 0);
+    if let Err(trap) = FunctionalTest::default().run(&mut MOS6502::new(ram)) {
+        panic!("{}", trap);
+    }
+}
+
+#[test]
+fn test_processor_tests_runner() {
+    use mos6502::processor_tests::{self, TestBus};
+
+    let tests = processor_tests::parse(
+        r#"[
+        {
+            "name": "a9 23 lda",
+            "initial": {"pc": 512, "s": 253, "a": 0, "x": 0, "y": 0, "p": 36,
+                        "ram": [[512, 169], [513, 35]]},
+            "final": {"pc": 514, "s": 253, "a": 35, "x": 0, "y": 0, "p": 36,
+                      "ram": [[512, 169], [513, 35]]},
+            "cycles": [[512, 169, "read"], [513, 35, "read"]]
+        },
+        {
+            "name": "85 10 sta",
+            "initial": {"pc": 512, "s": 253, "a": 7, "x": 0, "y": 0, "p": 36,
+                        "ram": [[512, 133], [513, 16]]},
+            "final": {"pc": 514, "s": 253, "a": 8, "x": 0, "y": 0, "p": 36,
+                      "ram": [[16, 7]]},
+            "cycles": [[512, 133, "read"], [513, 16, "read"], [16, 8, "write"]]
+        }
+    ]"#,
+    )
+    .unwrap();
+    assert_eq!(tests.len(), 2);
+    assert_eq!(tests[1].cycles[2].address, 0x10);
+    assert!(tests[1].cycles[2].write);
+
+    let mut cpu = MOS6502::new(TestBus::new());
+    let report = processor_tests::run_all(&mut cpu, &tests, true);
+    assert_eq!(report.passed, 1);
+    assert_eq!(report.failures.len(), 1);
+    assert_eq!(report.failures[0].name, "85 10 sta");
+    assert_eq!(
+        report.failures[0].differences,
+        vec![
+            "a: got $07, expected $08",
+            "cycle 2: write $07 at $0010, expected write $08 at $0010",
+        ]
+    );
+    // nothing left behind for the next vector
+    assert_eq!(cpu.bus().peek(0x10), 0);
+
+    assert!(processor_tests::parse(r#"[{"name": "broken"}]"#).is_err());
+}
+
+// the vectors are not distributed with the sources: $PROCESSOR_TESTS is the
+// directory with the 00.json to ff.json files
+#[cfg(feature = "processor_tests")]
+#[test]
+fn test_processor_tests() {
+    use mos6502::processor_tests::{self, TestBus};
+    use std::env;
+
+    let directory = env::var("PROCESSOR_TESTS").unwrap_or_else(|_| "6502/v1".to_string());
+    let mut cpu = MOS6502::new(TestBus::new());
+    let mut failed = 0;
+    for code in 0..=0xffu8 {
+        cpu.bus_mut().poke(0, code);
+        // the undocumented opcodes are not implemented
+        if cpu.disassemble(0).0.starts_with(".byte") {
+            continue;
+        }
+        cpu.bus_mut().poke(0, 0);
+        let path = format!("{}/{:02x}.json", directory, code);
+        let tests = processor_tests::load(&path).unwrap();
+        let report = processor_tests::run_all(&mut cpu, &tests, false);
+        if let Some(failure) = report.failures.first() {
+            println!(
+                "{}: {} failed, first {}",
+                path,
+                report.failures.len(),
+                failure
+            );
+            failed += report.failures.len();
+        }
+    }
+    assert_eq!(failed, 0);
+}
+
+#[test]
+fn test_nestest_log_comparison() {
+    use mos6502::nestest::{self, Nestest};
+
+    // lda #$01, ldx #$02, jmp $C000 at the start of a single prg bank
+    let mut image = b"NES\x1a\x01\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00".to_vec();
+    let mut prg = vec![0; 0x4000];
+    prg[0..7].copy_from_slice(&[0xa9, 0x01, 0xa2, 0x02, 0x4c, 0x00, 0xc0]);
+    image.extend(prg);
+    let ram = nestest::load_rom(&image).unwrap();
+    let mut cpu = MOS6502::new(ram);
+    assert_eq!(cpu.inspect(0x8004), 0x4c);
+    assert_eq!(cpu.inspect(0xc004), 0x4c);
+
+    let log = "\
+C000  A9 01     LDA #$01                        A:00 X:00 Y:00 P:24 SP:FD PPU:  0, 21 CYC:7
+C002  A2 02     LDX #$02                        A:01 X:00 Y:00 P:24 SP:FD PPU:  0, 27 CYC:9
+C004  4C 00 C0  JMP $C000                       A:01 X:02 Y:00 P:24 SP:FD PPU:  0, 33 CYC:11
+C000  A9 01     LDA #$01                        A:01 X:02 Y:00 P:24 SP:FD PPU:  0, 42 CYC:14";
+    assert_eq!(Nestest::default().compare(&mut cpu, log).ok(), Some(4));
+
+    let diverging = log.replace("A:01 X:02", "A:01 X:03");
+    let divergence = Nestest {
+        context: 1,
+        ..Nestest::default()
+    }
+    .compare(&mut cpu, &diverging)
+    .err()
+    .unwrap();
+    assert_eq!(divergence.line, 3);
+    assert_eq!(divergence.got.x, 0x02);
+    assert_eq!(divergence.disassembly, "jmp $C000");
+    assert_eq!(divergence.context.len(), 1);
+    assert!(divergence.context[0].starts_with("C002"));
+
+    assert!(nestest::load_rom(b"not a rom").is_err());
+}
+
+// the rom and its log are not distributed with the sources
+#[cfg(feature = "nestest")]
+#[test]
+fn test_nestest() {
+    use mos6502::builder::Variant;
+    use mos6502::nestest::{self, Nestest};
+    use std::env;
+    use std::fs;
+
+    let rom = env::var("NESTEST_ROM").unwrap_or_else(|_| "nestest.nes".to_string());
+    let log = env::var("NESTEST_LOG").unwrap_or_else(|_| "nestest.log".to_string());
+    let image = fs::read(&rom).unwrap_or_else(|err| panic!("{}: {}", rom, err));
+    let log = fs::read_to_string(&log).unwrap_or_else(|err| panic!("{}: {}", log, err));
+    let mut cpu = MOS6502::builder(nestest::load_rom(&image).unwrap())
+        .variant(Variant::Ricoh2A03)
+        .build();
+    // the documented opcodes end where the log reaches the first nop $04
+    let harness = Nestest {
+        max_lines: 5003,
+        ..Nestest::default()
+    };
+    if let Err(divergence) = harness.compare(&mut cpu, &log) {
+        panic!("{}", divergence);
+    }
+}
+
+#[test]
+fn test_lockstep_random_programs() {
+    use mos6502::lockstep::{Lockstep, ReferenceCpu, Registers};
+
+    // straight line code past the zeropage looping back to the start, from a
+    // fixed seed
+    let mut seed = 0x2545_f491u32;
+    let mut random = || {
+        seed ^= seed << 13;
+        seed ^= seed >> 17;
+        seed ^= seed << 5;
+        seed as u8
+    };
+    let opcodes = [
+        0x69, 0xe9, 0x29, 0x49, 0xa9, 0xa2, 0xa0, 0xc9, 0x65, 0x85, 0x86, 0xe6,
+    ];
+    let mut program = Vec::new();
+    while program.len() < 0x200 {
+        program.push(opcodes[usize::from(random()) % opcodes.len()]);
+        program.push(random());
+    }
+    program.extend(&[0x4c, 0x00, 0x04]);
+    let machine = || {
+        let mut ram = Ram::new(0x10000);
+        ram.fill(program.clone(), 0x0400);
+        let mut cpu = MOS6502::new(ram);
+        cpu.pc = 0x0400;
+        cpu
+    };
+
+    let mut reference = machine();
+    let passed = Lockstep {
+        max_steps: 2000,
+        ..Lockstep::default()
+    }
+    .run(&mut machine(), &mut reference);
+    assert_eq!(passed.ok(), Some(2000));
+
+    // a reference with the carry stuck at 0
+    struct Stuck(MOS6502<Ram<u8>>);
+    impl ReferenceCpu for Stuck {
+        fn registers(&self) -> Registers {
+            Registers {
+                status: self.0.status & !CARRY,
+                ..ReferenceCpu::registers(&self.0)
+            }
+        }
+
+        fn step(&mut self) -> Vec<(u16, u8)> {
+            ReferenceCpu::step(&mut self.0)
+        }
+    }
+
+    let divergence = Lockstep::default()
+        .run(&mut machine(), &mut Stuck(machine()))
+        .err()
+        .unwrap();
+    assert_ne!(divergence.got.status & CARRY, 0);
+    assert!(divergence.step > 0);
+    assert!(divergence.disassembly.is_some());
+}
+
+#[test]
+fn test_arithmetic_flags() {
+    use mos6502::flags::{self, Case, Operation};
+
+    let mut cpu = MOS6502::new(Ram::new(0x10000));
+    for &(operation, decimal) in &[
+        (Operation::Adc, false),
+        (Operation::Adc, true),
+        (Operation::Sbc, false),
+        (Operation::Sbc, true),
+        (Operation::Cmp, false),
+        (Operation::Cpx, false),
+        (Operation::Cpy, false),
+    ] {
+        let mismatches = flags::check(&mut cpu, flags::all_cases(operation, decimal), 0x0200);
+        if let Some(mismatch) = mismatches.first() {
+            panic!("{} mismatches, first {}", mismatches.len(), mismatch);
+        }
+    }
+
+    // $80 - $01 overflows, $7F - $FF too
+    let sbc = |register, value| {
+        let case = Case {
+            operation: Operation::Sbc,
+            register,
+            value,
+            carry: true,
+            overflow: false,
+            decimal: false,
+        };
+        flags::expected(&case).overflow
+    };
+    assert!(sbc(0x80, 0x01));
+    assert!(sbc(0x7f, 0xff));
+    assert!(!sbc(0x7f, 0x01));
+
+    // 58 + 46 with the carry in = 105 and 12 - 21 = 91 with a borrow
+    let decimal = |operation, register, value, carry| {
+        flags::expected(&Case {
+            operation,
+            register,
+            value,
+            carry,
+            overflow: false,
+            decimal: true,
+        })
+    };
+    let sum = decimal(Operation::Adc, 0x58, 0x46, true);
+    assert_eq!((sum.register, sum.carry), (0x05, true));
+    let difference = decimal(Operation::Sbc, 0x12, 0x21, true);
+    assert_eq!((difference.register, difference.carry), (0x91, false));
+
+    assert_eq!(
+        flags::random_cases(Operation::Adc, true, 1, 100).count(),
+        100
+    );
+}
+
+#[test]
+fn test_cycle_timing() {
+    use mos6502::builder::IllegalOpcodes;
+    use mos6502::timing::{self, Condition};
+
+    assert_eq!(Condition::PageCross.expected(0xbd), 5);
+    // stores pay for the page cross anyway
+    assert_eq!(Condition::PageCross.expected(0x9d), 5);
+    assert_eq!(Condition::BranchTakenPageCross.expected(0xd0), 4);
+
+    if let Some(discrepancy) = timing::validate().first() {
+        panic!("{}", discrepancy);
+    }
+
+    // the undocumented ones cost what the documented ones of their kind do
+    let mut ram = Ram::new(0x10000);
+    ram.fill(vec![0xdf, 0x00, 0x10, 0xb3, 0x00, 0xb3, 0x00], 0x0200);
+    ram.fill(vec![0xff, 0x10], 0x0000);
+    let mut cpu = MOS6502::builder(ram)
+        .illegal_opcodes(IllegalOpcodes::Execute)
+        .pc(0x0200)
+        .build();
+    // dcp $1000,x, then lax ($00),y across a page and without
+    assert_eq!(cpu.step(), 7);
+    cpu.y = 1;
+    assert_eq!(cpu.step(), 6);
+    cpu.y = 0;
+    assert_eq!(cpu.step(), 5);
+}
+
+#[test]
+fn test_tick() {
+    use mos6502::timing;
+    use testbus::TestBus;
+
+    // inc $10 a bus cycle at a time, with the write of the old value
+    let mut bus = TestBus::new();
+    bus.expect_read(0x0000, 0xe6)
+        .expect_read(0x0001, 0x10)
+        .expect_read(0x0010, 0x41)
+        .expect_write(0x0010, 0x41)
+        .expect_write(0x0010, 0x42);
+    let mut cpu = MOS6502::new(bus);
+    for left in (0..5).rev() {
+        cpu.tick();
+        assert_eq!(cpu.bus().remaining().len(), left);
+    }
+    assert!(!cpu.in_instruction());
+    assert_eq!(cpu.ticks, 5);
+    cpu.bus().finish();
+
+    // step finishes the instruction tick is in
+    let mut ram = Ram::new(0x10000);
+    ram.fill(vec![0xa9, 0x42, 0x8d, 0x00, 0x02, 0xea], 0x0000);
+    let mut cpu = MOS6502::new(ram);
+    cpu.tick();
+    assert!(cpu.in_instruction());
+    assert_eq!(cpu.step(), 1);
+    assert_eq!(cpu.a, 0x42);
+    assert_eq!(cpu.step(), 4);
+    assert_eq!(cpu.read(0x0200), 0x42);
+
+    // cycle exact for every documented opcode
+    if let Some(discrepancy) = timing::validate_ticked().first() {
+        panic!("{}", discrepancy);
+    }
+
+    // an irq taken between instructions goes as brk does
+    let mut ram = Ram::new(0x10000);
+    ram.fill(vec![0xea, 0xea], 0x0200);
+    ram.fill(vec![0x00, 0x03], 0xfffe);
+    let mut cpu = MOS6502::new(ram);
+    cpu.pc = 0x0200;
+    cpu.status &= !INTERRUPT;
+    cpu.irq(true);
+    cpu.tick();
+    assert_eq!(cpu.finish_instruction(), 6);
+    assert_eq!(cpu.pc, 0x0300);
+    assert_eq!(cpu.read(0x01ff), 0x02);
+    assert_eq!(cpu.read(0x01fe), 0x00);
+    assert_eq!(cpu.read(0x01fd) & BRK, 0);
+}
+
+#[cfg(feature = "tracing")]
+#[test]
+fn test_tracing_events() {
+    use std::fmt;
+    use std::sync::{Arc, Mutex};
+    use tracing;
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata, Subscriber};
+
+    struct Messages(Arc<Mutex<Vec<String>>>);
+
+    struct Message<'a>(&'a mut String);
+
+    impl<'a> Visit for Message<'a> {
+        fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+            self.0.push_str(&format!("{}={:?} ", field.name(), value));
+        }
+    }
+
+    impl Subscriber for Messages {
+        fn enabled(&self, _metadata: &Metadata) -> bool {
+            true
+        }
+        fn new_span(&self, _span: &Attributes) -> Id {
+            Id::from_u64(1)
+        }
+        fn record(&self, _span: &Id, _values: &Record) {}
+        fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+        fn event(&self, event: &Event) {
+            let mut line = String::new();
+            event.record(&mut Message(&mut line));
+            self.0.lock().unwrap().push(line);
+        }
+        fn enter(&self, _span: &Id) {}
+        fn exit(&self, _span: &Id) {}
+    }
+
+    let mut ram = Ram::new(0x10000);
+    // lda #$42, sta $d000, sta $0200, brk
+    ram.fill(
+        vec![0xa9, 0x42, 0x8d, 0x00, 0xd0, 0x8d, 0x00, 0x02, 0x00],
+        0,
+    );
+    ram.fill(vec![0x00, 0x30], 0xfffe);
+    let mut cpu = MOS6502::new(ram);
+    cpu.trace_io_region(0xd000, 0xd3ff);
+
+    let messages = Arc::new(Mutex::new(Vec::new()));
+    tracing::subscriber::with_default(Messages(messages.clone()), || {
+        for _ in 0..4 {
+            cpu.step();
+        }
+    });
+    let messages = messages.lock().unwrap();
+    assert_eq!(messages.len(), 6);
+    assert!(messages[0].starts_with("message=instruction pc=0 opcode=169 name=\"lda\""));
+    assert_eq!(messages[1], "message=io write pc=2 address=53248 value=66 ");
+    assert!(messages[4].starts_with("message=interrupt kind=Brk pc=8 handler=12288"));
+    assert_eq!(
+        messages.iter().filter(|m| m.contains("io write")).count(),
+        1
+    );
+}
+
+#[cfg(feature = "dynarec")]
+#[test]
+fn test_dynarec() {
+    use mos6502::dynarec::Dynarec;
+
+    let program = assemble(
+        "
+        ldx #$05
+        lda #$81
+        sec
+loop:   asl a
+        rol a
+        ror a
+        eor #$55
+        tay
+        dex
+        sta $80,x
+        cpx #$00
+        bne loop
+patch:  lda #$00
+        clc
+        adc #$01
+        sta patch+1
+        cmp #$06
+        bne patch
+done:   jmp done
+        ",
+    )
+    .unwrap();
+    let done = program.labels["done"];
+    let mut cpus: Vec<MOS6502<Ram<u8>>> = (0..2)
+        .map(|_| {
+            let mut ram = Ram::new(0x10000);
+            ram.fill(program.bytes.clone(), 0);
+            MOS6502::new(ram)
+        })
+        .collect();
+    let mut dynarec = Dynarec::new().unwrap();
+
+    while cpus[0].pc != done {
+        cpus[0].step();
+    }
+    while cpus[1].pc != done {
+        dynarec.step(&mut cpus[1]).unwrap();
+    }
+
+    let (interpreted, compiled) = cpus.split_at_mut(1);
+    let (interpreted, compiled) = (&mut interpreted[0], &mut compiled[0]);
+    assert_eq!(
+        [
+            interpreted.a,
+            interpreted.x,
+            interpreted.y,
+            interpreted.status
+        ],
+        [compiled.a, compiled.x, compiled.y, compiled.status]
+    );
+    assert_eq!(interpreted.ticks, compiled.ticks);
+    for address in 0x80..0x86 {
+        assert_eq!(interpreted.inspect(address), compiled.inspect(address));
+    }
+    // the self-modifying lda is recompiled, then left to the interpreter
+    assert_eq!(compiled.a, 6);
+    assert!(dynarec.compiled_instructions() > dynarec.interpreted_instructions());
+}
+
+#[cfg(feature = "dynarec")]
+#[test]
+fn test_dynarec_cmos() {
+    use mos6502::builder::Variant;
+    use mos6502::dynarec::Dynarec;
+
+    // lda #$05 / inc a / inc a / dec a / two one cycle nops / jmp *
+    let program = vec![0xa9, 0x05, 0x1a, 0x1a, 0x3a, 0x03, 0x0b, 0x4c, 0x07, 0x02];
+    let mut cpus: Vec<MOS6502<Ram<u8>>> = (0..2)
+        .map(|_| {
+            let mut ram = Ram::new(0x10000);
+            ram.fill(program.clone(), 0x0200);
+            MOS6502::builder(ram)
+                .variant(Variant::Cmos)
+                .pc(0x0200)
+                .build()
+        })
+        .collect();
+    let mut dynarec = Dynarec::new().unwrap();
+
+    while cpus[0].pc != 0x0207 {
+        cpus[0].step();
+    }
+    while cpus[1].pc != 0x0207 {
+        dynarec.step(&mut cpus[1]).unwrap();
+    }
+    assert_eq!(dynarec.compiled_instructions(), 6);
+    assert_eq!(
+        (cpus[1].a, cpus[1].status, cpus[1].ticks),
+        (cpus[0].a, cpus[0].status, cpus[0].ticks)
+    );
+    assert_eq!((cpus[1].a, cpus[1].ticks), (0x06, 10));
+}
+
+#[test]
+fn test_huc6280() {
+    use Reset;
+
+    // the first bank is at $E000 with mpr7 at $00, the vectors at its end
+    let mut ram: Ram<u8> = Ram::new(0x0020_0000);
+    ram.fill(
+        vec![
+            0xa9, 0xf8, // lda #$f8
+            0x53, 0x02, // tam #$02
+            0xa9, 0x01, // lda #$01
+            0x85, 0x10, // sta $10
+            0xa2, 0x10, // ldx #$10
+            0xa9, 0x05, // lda #$05
+            0xf4, // set
+            0x09, 0x02, // ora #$02
+            0x43, 0x02, // tma #$02
+            0x73, 0x00, 0x30, 0x00, 0x31, 0x04, 0x00, // tii $3000,$3100,$0004
+            0x83, 0x04, 0x10, // tst #$04,$10
+            0x48, // pha
+        ],
+        0x0000,
+    );
+    ram.fill(vec![0x04, 0x03, 0x02, 0x01], 0x001f_1000);
+    ram.fill(vec![0x00, 0xe1, 0x00, 0xe0], 0x1ffc);
+    let mut cpu = MOS6502::huc6280(ram);
+    cpu.reset();
+    assert_eq!(cpu.pc, 0xe000);
+    for _ in 0..4 {
+        cpu.step();
+    }
+    // the zeropage is at $2000, through mpr1
+    assert_eq!(cpu.mmu().mpr[1], 0xf8);
+    assert_eq!(cpu.mmu_mut().bus_mut().read(0x001f_0010), 0x01);
+    cpu.step();
+    cpu.step();
+    // the byte at x instead of a
+    assert_eq!(cpu.step(), 7);
+    assert_eq!(cpu.a, 0x05);
+    assert_eq!(cpu.mmu_mut().bus_mut().read(0x001f_0010), 0x03);
+    cpu.step();
+    assert_eq!(cpu.a, 0xf8);
+    assert_eq!(cpu.step(), 17 + 6 * 4);
+    for (offset, &value) in [0x04, 0x03, 0x02, 0x01].iter().enumerate() {
+        assert_eq!(
+            cpu.mmu_mut().bus_mut().read(0x001f_1100 + offset as u32),
+            value
+        );
+    }
+    assert_eq!(cpu.step(), 7);
+    assert!(cpu.get_flag(ZERO));
+    let sp = cpu.sp;
+    cpu.step();
+    let stacked = 0x001f_0100 + u32::from(sp);
+    assert_eq!(cpu.mmu_mut().bus_mut().read(stacked), 0xf8);
+    // nmi at $FFFC
+    cpu.nmi();
+    cpu.step();
+    assert_eq!(cpu.pc, 0xe100);
+    assert_eq!(cpu.disassemble_at(0xe011).0, "tii $3000,$3100,$0004");
+}
+
+#[test]
+fn test_huc6280_tick() {
+    use Reset;
+
+    let program = vec![
+        0xa9, 0xf8, // lda #$f8
+        0x53, 0x02, // tam #$02
+        0xa2, 0x10, // ldx #$10
+        0xf4, // set
+        0x09, 0x02, // ora #$02
+        0x73, 0x00, 0x30, 0x00, 0x31, 0x04, 0x00, // tii $3000,$3100,$0004
+        0x83, 0x04, 0x10, // tst #$04,$10
+        0x22, // sax
+        0xd4, // csh
+        0xe8, // inx
+    ];
+    let run = |ticked: bool| {
+        let mut ram: Ram<u8> = Ram::new(0x0020_0000);
+        ram.fill(program.clone(), 0x0000);
+        ram.fill(vec![0x04, 0x03, 0x02, 0x01], 0x001f_1000);
+        ram.fill(vec![0x00, 0xe0], 0x1ffe);
+        let mut cpu = MOS6502::huc6280(ram);
+        cpu.reset();
+        let cycles: Vec<u64> = (0..9)
+            .map(|_| {
+                if ticked {
+                    cpu.tick();
+                    1 + cpu.finish_instruction()
+                } else {
+                    cpu.step()
+                }
+            })
+            .collect();
+        let copied = cpu.mmu_mut().bus_mut().read(0x001f_1103);
+        (cycles, cpu.pc, cpu.a, cpu.x, copied, cpu.mmu().high_speed)
+    };
+    let stepped = run(false);
+    assert_eq!(stepped.0, vec![2, 5, 2, 7, 17 + 6 * 4, 7, 3, 3, 2]);
+    assert_eq!(run(true), stepped);
+}
+
+#[test]
+fn test_power_up_state() {
+    use mos6502::lockstep::Registers;
+    use mos6502::power::{Fill, PowerUpState};
+
+    let mut power_up = PowerUpState::new().fill(
+        Fill::Alternating {
+            first: 0x00,
+            second: 0xff,
+            run: 4,
+        },
+        0x0000,
+        0x07ff,
+    );
+    power_up.registers = Registers {
+        pc: 0x0200,
+        a: 0x55,
+        x: 0xaa,
+        y: 0x01,
+        sp: 0x00,
+        status: 0x34,
+    };
+    let mut ram = Ram::new(0x10000);
+    ram.fill(vec![0x00, 0x03], 0xfffc);
+    let mut cpu = MOS6502::builder(ram).power_up(power_up).build();
+    assert_eq!(Registers::of(&cpu), power_up.registers);
+    assert_eq!(cpu.bus_mut().read(0x0003), 0x00);
+    assert_eq!(cpu.bus_mut().read(0x0004), 0xff);
+    assert_eq!(cpu.bus_mut().read(0x0800), 0x00);
+
+    cpu.a = 0x00;
+    cpu.bus_mut().write(0x0004, 0x12);
+    cpu.power_on();
+    assert_eq!((cpu.a, cpu.x, cpu.sp, cpu.pc), (0x55, 0xaa, 0xfd, 0x0300));
+    assert_eq!(cpu.bus_mut().read(0x0004), 0xff);
+
+    // the same seed, the same garbage
+    let random = PowerUpState::new().fill(Fill::Random(7), 0x0000, 0x00ff);
+    let mut first = Ram::new(0x100);
+    let mut second = Ram::new(0x100);
+    random.fill_memory(&mut first);
+    random.fill_memory(&mut second);
+    assert!(first.cells() == second.cells());
+    assert!(first.cells().iter().any(|&byte| byte != first.cells()[0]));
+}
+
+#[test]
+fn test_pull_and_transfer_flags() {
+    let mut ram = Ram::new(0x10000);
+    // lda #$80 / pha / lda #$01 / pla / ldx #$00 / txs / ldx #$01 / tsx
+    ram.fill(
+        vec![
+            0xa9, 0x80, 0x48, 0xa9, 0x01, 0x68, 0xa2, 0x00, 0x9a, 0xa2, 0x01, 0xba,
+        ],
+        0x0200,
+    );
+    let mut cpu = MOS6502::builder(ram).pc(0x0200).build();
+    for _ in 0..4 {
+        cpu.step();
+    }
+    assert_eq!(cpu.a, 0x80);
+    assert!(cpu.get_flag(SIGN) && !cpu.get_flag(ZERO));
+    for _ in 0..4 {
+        cpu.step();
+    }
+    assert_eq!(cpu.x, 0x00);
+    assert!(cpu.get_flag(ZERO) && !cpu.get_flag(SIGN));
+}
+
+#[test]
+fn test_tick_accumulator_mode() {
+    let mut ram = Ram::new(0x10000);
+    // lda #$41 / asl a / ror a
+    ram.fill(vec![0xa9, 0x41, 0x0a, 0x6a], 0x0200);
+    let mut cpu = MOS6502::builder(ram).pc(0x0200).build();
+    cpu.tick();
+    cpu.finish_instruction();
+    cpu.tick();
+    cpu.finish_instruction();
+    assert_eq!(cpu.a, 0x82);
+    cpu.tick();
+    cpu.finish_instruction();
+    assert_eq!(cpu.a, 0x41);
+    assert_eq!(cpu.ticks, 6);
+}
@@ -0,0 +1,174 @@
+use alloc::vec::Vec;
+use core::fmt;
+
+use mos6502::{CARRY, MOS6502, OVERFLOW, SIGN, ZERO};
+use ram::Ram;
+use {AddressBusIO, Clock};
+
+// cycles of the documented nmos opcodes without penalties, 0 for the others
+#[rustfmt::skip]
+pub const CYCLES: [u8; 256] = [
+    7, 6, 0, 0, 0, 3, 5, 0, 3, 2, 2, 0, 0, 4, 6, 0,
+    2, 5, 0, 0, 0, 4, 6, 0, 2, 4, 0, 0, 0, 4, 7, 0,
+    6, 6, 0, 0, 3, 3, 5, 0, 4, 2, 2, 0, 4, 4, 6, 0,
+    2, 5, 0, 0, 0, 4, 6, 0, 2, 4, 0, 0, 0, 4, 7, 0,
+    6, 6, 0, 0, 0, 3, 5, 0, 3, 2, 2, 0, 3, 4, 6, 0,
+    2, 5, 0, 0, 0, 4, 6, 0, 2, 4, 0, 0, 0, 4, 7, 0,
+    6, 6, 0, 0, 0, 3, 5, 0, 4, 2, 2, 0, 5, 4, 6, 0,
+    2, 5, 0, 0, 0, 4, 6, 0, 2, 4, 0, 0, 0, 4, 7, 0,
+    0, 6, 0, 0, 3, 3, 3, 0, 2, 0, 2, 0, 4, 4, 4, 0,
+    2, 6, 0, 0, 4, 4, 4, 0, 2, 5, 2, 0, 0, 5, 0, 0,
+    2, 6, 2, 0, 3, 3, 3, 0, 2, 2, 2, 0, 4, 4, 4, 0,
+    2, 5, 0, 0, 4, 4, 4, 0, 2, 4, 2, 0, 4, 4, 4, 0,
+    2, 6, 0, 0, 3, 3, 5, 0, 2, 2, 2, 0, 4, 4, 6, 0,
+    2, 5, 0, 0, 0, 4, 6, 0, 2, 4, 0, 0, 0, 4, 7, 0,
+    2, 6, 0, 0, 3, 3, 5, 0, 2, 2, 2, 0, 4, 4, 6, 0,
+    2, 5, 0, 0, 0, 4, 6, 0, 2, 4, 0, 0, 0, 4, 7, 0,
+];
+
+// the reads through an indexed mode, one more cycle when the index crosses a
+// page (stores and read modify writes always take it)
+pub fn page_penalty(code: u8) -> bool {
+    match code & 0x1f {
+        0x11 | 0x19 | 0x1d => code != 0x91 && code != 0x99 && code != 0x9d,
+        0x1c | 0x1e => code == 0xbc || code == 0xbe,
+        _ => false,
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Condition {
+    Plain,
+    PageCross,
+    BranchNotTaken,
+    BranchTaken,
+    BranchTakenPageCross,
+}
+
+impl Condition {
+    pub fn expected(self, code: u8) -> u8 {
+        let base = CYCLES[usize::from(code)];
+        match self {
+            Condition::Plain | Condition::BranchNotTaken => base,
+            Condition::PageCross => base + u8::from(page_penalty(code)),
+            Condition::BranchTaken => base + 1,
+            Condition::BranchTakenPageCross => base + 2,
+        }
+    }
+}
+
+pub struct Discrepancy {
+    pub opcode: u8,
+    pub name: &'static str,
+    pub mode: &'static str,
+    pub condition: Condition,
+    pub expected: u8,
+    pub got: u64,
+}
+
+impl fmt::Display for Discrepancy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "${:02X} {} {} ({:?}): expected {} cycles, got {}",
+            self.opcode, self.name, self.mode, self.condition, self.expected, self.got
+        )
+    }
+}
+
+// the status flag deciding a branch and the value taking it
+fn branch_flag(code: u8) -> (u8, bool) {
+    let flag = [SIGN, OVERFLOW, CARRY, ZERO][usize::from(code >> 6)];
+    (flag, code & 0x20 != 0)
+}
+
+// what the index registers, the operand and the pointer in the zeropage are
+// set to: no page crossed unless asked, the effective address is $1000 or
+// $1100
+fn measure(code: u8, condition: Condition, ticked: bool) -> u64 {
+    let mut cpu = MOS6502::new(Ram::new(0x10000));
+    let mode = cpu.lookup(code).mode;
+    let cross = condition == Condition::PageCross;
+    let (low, high) = match mode {
+        "indirect_x" | "indirect_y" => (0x00, 0x00),
+        _ if cross => (0xff, 0x10),
+        _ => (0x00, 0x10),
+    };
+    cpu.write(0x0000, if cross { 0xff } else { 0x00 });
+    cpu.write(0x0001, 0x10);
+    let index = u8::from(cross);
+    cpu.x = index;
+    cpu.y = index;
+    if mode == "indirect_x" {
+        cpu.x = 0;
+    }
+
+    let start = match condition {
+        Condition::BranchTakenPageCross => 0x02f0,
+        _ => 0x0200,
+    };
+    let (low, high) = match condition {
+        Condition::BranchTaken => (0x10, 0x00),
+        Condition::BranchTakenPageCross => (0x20, 0x00),
+        _ => (low, high),
+    };
+    if mode == "relative" {
+        let (flag, taken) = branch_flag(code);
+        let set = taken != (condition == Condition::BranchNotTaken);
+        cpu.set_flag(flag, set);
+    }
+    cpu.write(start, code);
+    cpu.write(start + 1, low);
+    cpu.write(start + 2, high);
+    cpu.pc = start;
+    if ticked {
+        cpu.tick();
+        1 + cpu.finish_instruction()
+    } else {
+        cpu.step()
+    }
+}
+
+// every documented opcode, under the conditions its addressing mode has
+pub fn validate() -> Vec<Discrepancy> {
+    discrepancies(false)
+}
+
+// the same running the opcodes a cycle at a time with tick
+pub fn validate_ticked() -> Vec<Discrepancy> {
+    discrepancies(true)
+}
+
+fn discrepancies(ticked: bool) -> Vec<Discrepancy> {
+    let table = MOS6502::new(Ram::<u8>::new(0));
+    let mut discrepancies = Vec::new();
+    for code in 0..=0xffu8 {
+        if CYCLES[usize::from(code)] == 0 {
+            continue;
+        }
+        let opcode = table.lookup(code);
+        let conditions: &[Condition] = match opcode.mode {
+            "relative" => &[
+                Condition::BranchNotTaken,
+                Condition::BranchTaken,
+                Condition::BranchTakenPageCross,
+            ],
+            "absolute_x" | "absolute_y" | "indirect_y" => &[Condition::Plain, Condition::PageCross],
+            _ => &[Condition::Plain],
+        };
+        for &condition in conditions {
+            let (expected, got) = (condition.expected(code), measure(code, condition, ticked));
+            if u64::from(expected) != got {
+                discrepancies.push(Discrepancy {
+                    opcode: code,
+                    name: opcode.name,
+                    mode: opcode.mode,
+                    condition,
+                    expected,
+                    got,
+                });
+            }
+        }
+    }
+    discrepancies
+}
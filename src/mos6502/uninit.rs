@@ -0,0 +1,86 @@
+use alloc::vec::Vec;
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct UninitializedRead {
+    pub pc: u16,
+    pub address: u16,
+}
+
+const UNWRITTEN: u8 = 0;
+const WRITTEN: u8 = 1;
+const REPORTED: u8 = 2;
+
+// tracks an inclusive range of ram, each never-written byte is reported only
+// on its first read to avoid flooding the log from polling loops
+pub struct UninitializedGuard {
+    start: u16,
+    cells: Vec<u8>,
+    pub trap: bool,
+    // reads of the current instruction, held back until it completes
+    pending: Vec<UninitializedRead>,
+    reads: Vec<UninitializedRead>,
+}
+
+impl UninitializedGuard {
+    // the ends in either order
+    pub fn new(start: u16, end: u16, trap: bool) -> UninitializedGuard {
+        let (start, end) = (start.min(end), start.max(end));
+        UninitializedGuard {
+            start,
+            cells: vec![UNWRITTEN; usize::from(end - start) + 1],
+            trap,
+            pending: Vec::new(),
+            reads: Vec::new(),
+        }
+    }
+
+    fn index(&self, address: u16) -> Option<usize> {
+        if address < self.start {
+            return None;
+        }
+        let index = usize::from(address - self.start);
+        if index < self.cells.len() {
+            Some(index)
+        } else {
+            None
+        }
+    }
+
+    pub fn clear(&mut self) {
+        for cell in self.cells.iter_mut() {
+            *cell = UNWRITTEN;
+        }
+    }
+
+    pub fn mark_initialized(&mut self, start: u16, end: u16) {
+        for address in start..=end {
+            self.write(address);
+        }
+    }
+
+    pub fn write(&mut self, address: u16) {
+        if let Some(index) = self.index(address) {
+            self.cells[index] = WRITTEN;
+        }
+    }
+
+    pub fn read(&mut self, pc: u16, address: u16) {
+        if let Some(index) = self.index(address) {
+            if self.cells[index] == UNWRITTEN {
+                self.cells[index] = REPORTED;
+                self.pending.push(UninitializedRead { pc, address });
+            }
+        }
+    }
+
+    // returns true when something has been reported
+    pub fn commit(&mut self) -> bool {
+        let reported = !self.pending.is_empty();
+        self.reads.append(&mut self.pending);
+        reported
+    }
+
+    pub fn take_reads(&mut self) -> Vec<UninitializedRead> {
+        self.reads.split_off(0)
+    }
+}
@@ -1,5 +1,6 @@
-use std::cmp;
-use {Address, AddressBusIO, As, Data};
+use alloc::vec::Vec;
+use core::cmp;
+use {Address, AddressBusIO, Data};
 
 pub struct Ram<T: Data> {
     cells: Vec<T>,
@@ -18,14 +19,95 @@ impl<T: Data> Ram<T> {
             self.cells[i] = data[index];
         }
     }
+
+    // the contents without a bus access, through a shared reference
+    pub fn cells(&self) -> &[T] {
+        &self.cells
+    }
+}
+
+impl<T: Address, U: Data> AddressBusIO<T, U> for Ram<U> {
+    fn read(&mut self, address: T) -> U {
+        self.cells[address.to_usize()]
+    }
+
+    fn write(&mut self, address: T, value: U) {
+        self.cells[address.to_usize()] = value;
+    }
+
+    fn as_contiguous(&mut self, address: T, length: usize) -> Option<&mut [U]> {
+        let start = address.to_usize();
+        self.cells.get_mut(start..start.checked_add(length)?)
+    }
+}
+
+// the same as Ram in an array, small machines and test rigs can live on the
+// stack without allocating
+pub struct FixedRam<T: Data, const N: usize> {
+    cells: [T; N],
+}
+
+impl<T: Data, const N: usize> Default for FixedRam<T, N> {
+    fn default() -> FixedRam<T, N> {
+        FixedRam::new()
+    }
 }
 
-impl<T: Address + As<usize>, U: Data> AddressBusIO<T, U> for Ram<U> {
+impl<T: Data, const N: usize> FixedRam<T, N> {
+    pub fn new() -> FixedRam<T, N> {
+        FixedRam {
+            cells: [T::zero(); N],
+        }
+    }
+
+    pub fn fill(&mut self, data: &[T], offset: usize) {
+        let offset = cmp::min(offset, N);
+        let length = cmp::min(data.len(), N - offset);
+        self.cells[offset..offset + length].copy_from_slice(&data[..length]);
+    }
+
+    pub fn cells(&self) -> &[T] {
+        &self.cells
+    }
+}
+
+// every value of an unsigned address type indexes N cells, known at compile
+// time so the branch folds away
+#[cfg(feature = "unchecked")]
+fn covers<T: Address>(n: usize) -> bool {
+    T::min_value() == T::zero() && T::max_value().to_usize() < n
+}
+
+impl<T: Address, U: Data, const N: usize> AddressBusIO<T, U> for FixedRam<U, N> {
     fn read(&mut self, address: T) -> U {
-        self.cells[address.as_()]
+        let index = address.to_usize();
+        #[cfg(feature = "unchecked")]
+        {
+            if covers::<T>(N) {
+                debug_assert!(index < N);
+                // safety: covers, index is at most the address maximum
+                return unsafe { *self.cells.get_unchecked(index) };
+            }
+        }
+        self.cells[index]
     }
 
     fn write(&mut self, address: T, value: U) {
-        self.cells[address.as_()] = value;
+        let index = address.to_usize();
+        #[cfg(feature = "unchecked")]
+        {
+            if covers::<T>(N) {
+                debug_assert!(index < N);
+                // safety: as in read
+                unsafe { *self.cells.get_unchecked_mut(index) = value };
+                return;
+            }
+        }
+        self.cells[index] = value;
+    }
+
+    fn as_contiguous(&mut self, address: T, length: usize) -> Option<&mut [U]> {
+        let start = address.to_usize();
+        self.cells.get_mut(start..start.checked_add(length)?)
     }
 }
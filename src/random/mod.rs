@@ -1,27 +1,42 @@
-use rand;
-use rand::distributions::{Distribution, Standard};
+use sources::{EntropySource, HostEntropy};
 use {Address, AddressBusIO, Data};
 
-#[derive(Default)]
-pub struct Random<T: Data> {
+// a write draws the next value, reads return it
+pub struct Random<T: Data, E: EntropySource = HostEntropy> {
     value: T,
+    entropy: E,
+}
+
+impl<T: Data> Default for Random<T> {
+    fn default() -> Random<T> {
+        Random::new()
+    }
 }
 
 impl<T: Data> Random<T> {
     pub fn new() -> Random<T> {
-        Random { value: T::zero() }
+        Random::with_entropy(HostEntropy)
+    }
+}
+
+impl<T: Data, E: EntropySource> Random<T, E> {
+    // a SeededEntropy for reproducible runs
+    pub fn with_entropy(entropy: E) -> Random<T, E> {
+        Random {
+            value: T::zero(),
+            entropy,
+        }
     }
 }
 
-impl<T: Address, U: Data> AddressBusIO<T, U> for Random<U>
-where
-    Standard: Distribution<U>,
-{
+impl<T: Address, U: Data, E: EntropySource> AddressBusIO<T, U> for Random<U, E> {
     fn read(&mut self, _address: T) -> U {
         self.value
     }
 
     fn write(&mut self, _address: T, _value: U) {
-        self.value = rand::random::<U>();
+        // the low bits, for cells up to 64 bits
+        let mask = U::max_value().to_u64().unwrap_or(u64::MAX);
+        self.value = U::from(self.entropy.next_u64() & mask).unwrap();
     }
 }
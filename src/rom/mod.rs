@@ -1,17 +1,31 @@
-use {Address, AddressBusIO, As, Data};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
 
+use {Address, AddressBusIO, Data};
+
+// clones share the image, machines running the same rom side by side do not
+// copy it
+#[derive(Clone)]
 pub struct Rom<T: Data> {
-    cells: Vec<T>,
+    cells: Arc<[T]>,
 }
 
 impl<T: Data> Rom<T> {
     pub fn new(data: Vec<T>) -> Rom<T> {
+        Rom { cells: data.into() }
+    }
+
+    pub fn shared(data: Arc<[T]>) -> Rom<T> {
         Rom { cells: data }
     }
+
+    pub fn image(&self) -> Arc<[T]> {
+        self.cells.clone()
+    }
 }
 
-impl<T: Address + As<usize>, U: Data> AddressBusIO<T, U> for Rom<U> {
+impl<T: Address, U: Data> AddressBusIO<T, U> for Rom<U> {
     fn read(&mut self, address: T) -> U {
-        self.cells[address.as_()]
+        self.cells[address.to_usize()]
     }
 }
@@ -0,0 +1,91 @@
+use alloc::rc::Rc;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+
+// a device with a notion of time, the cycle counts are the cpu ones
+pub trait Timed {
+    // the cycle of the next timer expiry, scanline interrupt or anything else
+    // that can wake a waiting cpu up, None when nothing is pending
+    fn next_event(&self) -> Option<u64>;
+    // catch up to cycle in one go, where nothing is supposed to happen (the
+    // event itself is left to the normal stepping)
+    fn skip_to(&mut self, cycle: u64);
+}
+
+// fires every period cycles, a vblank or a scanline counter
+pub struct Periodic {
+    period: u64,
+    next: u64,
+}
+
+impl Periodic {
+    pub fn new(period: u64) -> Periodic {
+        Periodic {
+            period: period.max(1),
+            next: period.max(1),
+        }
+    }
+
+    // true once the period elapsed, now is the cpu cycle count
+    pub fn update(&mut self, now: u64) -> bool {
+        if now < self.next {
+            return false;
+        }
+        while self.next <= now {
+            self.next += self.period;
+        }
+        true
+    }
+}
+
+impl Timed for Periodic {
+    fn next_event(&self) -> Option<u64> {
+        Some(self.next)
+    }
+
+    // everything follows from the cpu cycles
+    fn skip_to(&mut self, _cycle: u64) {}
+}
+
+#[derive(Default)]
+pub struct Scheduler {
+    devices: Vec<Rc<RefCell<dyn Timed>>>,
+}
+
+impl Scheduler {
+    pub fn new() -> Scheduler {
+        Scheduler {
+            devices: Vec::new(),
+        }
+    }
+
+    pub fn add(&mut self, device: Rc<RefCell<dyn Timed>>) {
+        self.devices.push(device);
+    }
+
+    pub fn next_event(&self) -> Option<u64> {
+        self.devices
+            .iter()
+            .filter_map(|device| device.borrow().next_event())
+            .min()
+    }
+
+    // moves every device to the cycle right before the next event (at most
+    // limit), returns the cycle the cpu can jump to
+    pub fn fast_forward(&mut self, now: u64, limit: u64) -> u64 {
+        let target = match self.next_event() {
+            Some(event) => event.saturating_sub(1).min(limit),
+            None => limit,
+        };
+        if target <= now {
+            return now;
+        }
+        for device in &self.devices {
+            device.borrow_mut().skip_to(target);
+        }
+        target
+    }
+}
+
+#[cfg(test)]
+mod tests;
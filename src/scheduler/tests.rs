@@ -0,0 +1,69 @@
+use alloc::rc::Rc;
+use core::cell::RefCell;
+use scheduler::{Periodic, Scheduler, Timed};
+
+struct Countdown {
+    now: u64,
+    expires: u64,
+}
+
+impl Timed for Countdown {
+    fn next_event(&self) -> Option<u64> {
+        Some(self.expires).filter(|expires| *expires > self.now)
+    }
+
+    fn skip_to(&mut self, cycle: u64) {
+        self.now = cycle;
+    }
+}
+
+#[test]
+fn test_next_event() {
+    let mut scheduler = Scheduler::new();
+    assert_eq!(scheduler.next_event(), None);
+    assert_eq!(scheduler.fast_forward(10, 100), 100);
+    let vblank = Rc::new(RefCell::new(Periodic::new(1000)));
+    scheduler.add(vblank.clone());
+    scheduler.add(Rc::new(RefCell::new(Countdown {
+        now: 0,
+        expires: 300,
+    })));
+    assert_eq!(scheduler.next_event(), Some(300));
+    assert!(!vblank.borrow_mut().update(999));
+    assert!(vblank.borrow_mut().update(1000));
+    assert_eq!(vblank.borrow().next_event(), Some(2000));
+}
+
+#[cfg(feature = "mos6502")]
+#[test]
+fn test_fast_forward_idle_cpu() {
+    use mos6502::MOS6502;
+    use ram::Ram;
+    use Clock;
+
+    let mut ram = Ram::new(0x10000);
+    // loop: jmp loop
+    ram.fill(vec![0x4c, 0x00, 0x02], 0x200);
+    let mut cpu = MOS6502::new(ram);
+    cpu.pc = 0x200;
+    cpu.enable_idle_detection(16);
+
+    let countdown = Rc::new(RefCell::new(Countdown {
+        now: 0,
+        expires: 5000,
+    }));
+    let mut scheduler = Scheduler::new();
+    scheduler.add(countdown.clone());
+
+    assert!(!cpu.fast_forward(&mut scheduler, 10000));
+    cpu.step();
+    cpu.step();
+    assert!(cpu.is_idle());
+    assert!(cpu.fast_forward(&mut scheduler, 10000));
+    assert_eq!(cpu.ticks, 4999);
+    assert_eq!(countdown.borrow().now, 4999);
+    // already right before the event
+    assert!(!cpu.fast_forward(&mut scheduler, 10000));
+    cpu.step();
+    assert_eq!(cpu.pc, 0x200);
+}
@@ -0,0 +1,117 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use utils::random;
+
+// where the pacing gets the time from, the host clock or a replayed one
+pub trait TimeSource: Send {
+    // since an arbitrary start, never going back
+    fn now(&mut self) -> Duration;
+    fn sleep(&mut self, duration: Duration);
+}
+
+pub struct HostClock {
+    start: Instant,
+}
+
+impl Default for HostClock {
+    fn default() -> HostClock {
+        HostClock::new()
+    }
+}
+
+impl HostClock {
+    pub fn new() -> HostClock {
+        HostClock {
+            start: Instant::now(),
+        }
+    }
+}
+
+impl TimeSource for HostClock {
+    fn now(&mut self) -> Duration {
+        self.start.elapsed()
+    }
+
+    fn sleep(&mut self, duration: Duration) {
+        thread::sleep(duration);
+    }
+}
+
+// time only moves when slept through or advanced, the clones share it so a
+// test can move the clock of a pacer it handed over
+#[derive(Clone, Default)]
+pub struct ManualClock {
+    nanoseconds: Arc<AtomicU64>,
+}
+
+impl ManualClock {
+    pub fn new() -> ManualClock {
+        ManualClock::default()
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        self.nanoseconds
+            .fetch_add(duration.as_nanos() as u64, Ordering::Relaxed);
+    }
+}
+
+impl TimeSource for ManualClock {
+    fn now(&mut self) -> Duration {
+        Duration::from_nanos(self.nanoseconds.load(Ordering::Relaxed))
+    }
+
+    fn sleep(&mut self, duration: Duration) {
+        self.advance(duration);
+    }
+}
+
+// where the random devices get their values from
+pub trait EntropySource: Send {
+    fn next_u64(&mut self) -> u64;
+
+    fn fill(&mut self, bytes: &mut [u8]) {
+        for chunk in bytes.chunks_mut(8) {
+            let value = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&value[..chunk.len()]);
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct HostEntropy;
+
+impl EntropySource for HostEntropy {
+    fn next_u64(&mut self) -> u64 {
+        random()
+    }
+}
+
+// xorshift64*, the same seed gives the same values on every host
+#[derive(Clone)]
+pub struct SeededEntropy {
+    state: u64,
+}
+
+impl SeededEntropy {
+    pub fn new(seed: u64) -> SeededEntropy {
+        SeededEntropy {
+            // 0 would stay 0 forever
+            state: seed.max(1),
+        }
+    }
+}
+
+impl EntropySource for SeededEntropy {
+    fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,28 @@
+use sources::{EntropySource, ManualClock, SeededEntropy, TimeSource};
+use std::time::Duration;
+
+#[test]
+fn seeded_entropy_repeats() {
+    let mut first = SeededEntropy::new(42);
+    let mut second = SeededEntropy::new(42);
+    let values: Vec<u64> = (0..4).map(|_| first.next_u64()).collect();
+    assert_eq!(
+        values,
+        (0..4).map(|_| second.next_u64()).collect::<Vec<_>>()
+    );
+    assert_ne!(values[0], values[1]);
+
+    let mut bytes = [0; 11];
+    SeededEntropy::new(42).fill(&mut bytes);
+    assert_eq!(&bytes[0..8], &values[0].to_le_bytes());
+    assert_eq!(&bytes[8..11], &values[1].to_le_bytes()[0..3]);
+}
+
+#[test]
+fn manual_clock_is_shared() {
+    let clock = ManualClock::new();
+    let mut handed_over = clock.clone();
+    handed_over.sleep(Duration::from_millis(5));
+    clock.advance(Duration::from_millis(1));
+    assert_eq!(handed_over.now(), Duration::from_millis(6));
+}
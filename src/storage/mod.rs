@@ -6,7 +6,7 @@ use std::io::SeekFrom;
 use std::io::Write;
 use std::path::Path;
 
-use {Address, AddressBusBlockIO, As};
+use {Address, AddressBusBlockIO};
 
 pub struct BlockDevice {
     file: File,
@@ -40,9 +40,9 @@ impl BlockDevice {
     }
 }
 
-impl<T: Address + As<usize>> AddressBusBlockIO<T, u8> for BlockDevice {
+impl<T: Address> AddressBusBlockIO<T, u8> for BlockDevice {
     fn read(&mut self, address: T, buffer: &mut [u8]) {
-        let offset = (address.as_() * self.block_size) as u64;
+        let offset = (address.to_usize() * self.block_size) as u64;
         if offset + self.block_size as u64 >= self.max_size {
             return;
         }
@@ -51,7 +51,7 @@ impl<T: Address + As<usize>> AddressBusBlockIO<T, u8> for BlockDevice {
     }
 
     fn write(&mut self, address: T, buffer: &[u8]) {
-        let offset = (address.as_() * self.block_size) as u64;
+        let offset = (address.to_usize() * self.block_size) as u64;
         if offset + self.block_size as u64 >= self.max_size {
             return;
         }
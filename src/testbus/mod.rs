@@ -0,0 +1,101 @@
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+use core::fmt;
+use {Address, AddressBusIO, Data};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Access<T: Address, U: Data> {
+    // the value handed back to the cpu
+    Read(T, U),
+    Write(T, U),
+}
+
+impl<T: Address, U: Data> fmt::Display for Access<T, U> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Access::Read(address, value) => write!(f, "read ${:X} from ${:X}", value, address),
+            Access::Write(address, value) => write!(f, "write ${:X} to ${:X}", value, address),
+        }
+    }
+}
+
+// plays back a script of the exact bus traffic expected from the cpu and
+// panics on the first access that does not follow it
+pub struct TestBus<T: Address, U: Data> {
+    script: VecDeque<Access<T, U>>,
+    done: usize,
+}
+
+impl<T: Address, U: Data> Default for TestBus<T, U> {
+    fn default() -> TestBus<T, U> {
+        TestBus::new()
+    }
+}
+
+impl<T: Address, U: Data> TestBus<T, U> {
+    pub fn new() -> TestBus<T, U> {
+        TestBus {
+            script: VecDeque::new(),
+            done: 0,
+        }
+    }
+
+    pub fn expect_read(&mut self, address: T, value: U) -> &mut TestBus<T, U> {
+        self.script.push_back(Access::Read(address, value));
+        self
+    }
+
+    pub fn expect_write(&mut self, address: T, value: U) -> &mut TestBus<T, U> {
+        self.script.push_back(Access::Write(address, value));
+        self
+    }
+
+    pub fn remaining(&self) -> Vec<Access<T, U>> {
+        self.script.iter().cloned().collect()
+    }
+
+    // panics if part of the script was not played
+    pub fn finish(&self) {
+        if let Some(next) = self.script.front() {
+            panic!(
+                "bus access {} never happened: expected {} ({} more left)",
+                self.done,
+                next,
+                self.script.len() - 1
+            );
+        }
+    }
+
+    fn next(&mut self, access: Access<T, U>) -> Access<T, U> {
+        let expected = match self.script.pop_front() {
+            Some(expected) => expected,
+            None => panic!("bus access {} is not scripted: {}", self.done, access),
+        };
+        let matched = match (expected, access) {
+            (Access::Read(expected, _), Access::Read(address, _)) => expected == address,
+            (Access::Write(..), Access::Write(..)) => expected == access,
+            _ => false,
+        };
+        if !matched {
+            panic!(
+                "bus access {} was {}, expected {}",
+                self.done, access, expected
+            );
+        }
+        self.done += 1;
+        expected
+    }
+}
+
+impl<T: Address, U: Data> AddressBusIO<T, U> for TestBus<T, U> {
+    fn read(&mut self, address: T) -> U {
+        match self.next(Access::Read(address, U::zero())) {
+            Access::Read(_, value) => value,
+            Access::Write(..) => unreachable!(),
+        }
+    }
+
+    fn write(&mut self, address: T, value: U) {
+        self.next(Access::Write(address, value));
+    }
+}
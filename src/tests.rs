@@ -62,3 +62,181 @@ fn address_bus_io_u64_u32() {
     bus.write(0xffffffffaabbccdd, 0xaabbccdd);
     assert_eq!(bus.read(0xaabbccddffaaffbb), 0);
 }
+
+#[test]
+fn bus_widths() {
+    use BusWidth;
+
+    // the address after the last byte of a page of the width
+    fn next_page<T: Address>(address: T) -> T {
+        let page = T::from_usize(1).wrapping_shl(T::BITS / 2);
+        (address & !(page - T::one())).wrapping_add(page)
+    }
+
+    assert_eq!(next_page(0x12_u8), 0x20);
+    assert_eq!(next_page(0xff12_u16), 0x0000);
+    assert_eq!(next_page(0x1234_5678_u32), 0x1235_0000);
+    assert_eq!(u8::from_usize(0x1ff), 0xff);
+    assert_eq!(0xfffe_u16.to_usize(), 0xfffe);
+}
+
+#[test]
+fn contiguous_blocks() {
+    use memcontroller::MemoryController;
+    use ram::Ram;
+    use {read_block, write_block};
+
+    let mut ram = Ram::new(0x100);
+    let mut device: TestAddressBusIO<u16, u8> = TestAddressBusIO::default();
+    let mut controller = MemoryController::new();
+    controller.map(0x1000, 0x10ff, &mut ram);
+    controller.map(0x2000, 0x20ff, &mut device);
+    controller.mirror(0x3000, 0x30ff, 0x1000);
+
+    write_block(&mut controller, 0x10f0, &[1, 2, 3]);
+    assert_eq!(
+        controller.as_contiguous(0x30f0, 3),
+        Some(&mut [1, 2, 3][..])
+    );
+    // past the end of the mapping or not memory at all
+    assert!(controller.as_contiguous(0x10f0, 0x20).is_none());
+    assert!(controller.as_contiguous(0x2000, 1).is_none());
+
+    let mut buffer = [0xff; 2];
+    read_block(&mut controller, 0x2000, &mut buffer);
+    assert_eq!(buffer, [0, 0]);
+    read_block(&mut controller, 0x10f1, &mut buffer);
+    assert_eq!(buffer, [2, 3]);
+}
+
+#[cfg(feature = "mos6502")]
+#[test]
+fn faults_instead_of_panics() {
+    use memcontroller::MemoryController;
+    use mos6502::MOS6502;
+    use ram::Ram;
+    use Error;
+
+    let mut ram = Ram::new(0x10000);
+    ram.fill(vec![0xea, 0x02], 0x0200);
+    let mut cpu = MOS6502::new(ram);
+    cpu.pc = 0x0200;
+    assert_eq!(cpu.try_step(), Ok(()));
+    let fault = Error::InvalidOpcode {
+        pc: 0x0201,
+        opcode: 0x02,
+    };
+    assert_eq!(cpu.try_step(), Err(fault.clone()));
+    // jammed on the opcode
+    assert_eq!(cpu.pc, 0x0201);
+    assert_eq!(cpu.try_step(), Err(fault));
+
+    let mut ram = Ram::new(0x100);
+    let mut controller: MemoryController<u16, u8> = MemoryController::new();
+    controller.map(0x1000, 0x10ff, &mut ram);
+    controller.write(0x1000, 1);
+    assert_eq!(controller.take_unmapped(), None);
+    assert_eq!(controller.read(0x2000), 0);
+    controller.write(0x3000, 1);
+    assert_eq!(
+        controller.take_unmapped(),
+        Some(Error::UnmappedAddress(0x2000))
+    );
+    assert_eq!(controller.take_unmapped(), None);
+}
+
+#[cfg(feature = "mos6502")]
+#[test]
+fn fixed_ram_on_the_stack() {
+    use mos6502::MOS6502;
+    use ram::FixedRam;
+    use Clock;
+
+    let mut ram: FixedRam<u8, 0x200> = FixedRam::new();
+    // lda #$2a, sta $0180 (on the stack page)
+    ram.fill(&[0xa9, 0x2a, 0x8d, 0x80, 0x01], 0);
+    let mut cpu = MOS6502::new(ram);
+    cpu.step();
+    cpu.step();
+    assert_eq!(cpu.read(0x0180), 0x2a);
+    assert_eq!(cpu.as_contiguous(0x01ff, 1), Some(&mut [0][..]));
+    assert!(cpu.as_contiguous(0x01ff, 2).is_none());
+}
+
+#[test]
+fn rom_images_are_shared() {
+    use alloc::sync::Arc;
+    use rom::Rom;
+
+    let image: Arc<[u8]> = vec![1, 2, 3].into();
+    let mut first: Rom<u8> = Rom::shared(image.clone());
+    let mut second = first.clone();
+    assert!(Arc::ptr_eq(&first.image(), &image));
+    assert!(Arc::ptr_eq(&second.image(), &image));
+    assert_eq!(first.read(1u16), 2);
+    assert_eq!(second.read(2u16), 3);
+}
+
+#[cfg(feature = "mos6502")]
+#[test]
+fn scripted_bus_traffic() {
+    use mos6502::MOS6502;
+    use testbus::{Access, TestBus};
+    use Clock;
+
+    // inc $10, the dummy write of the old value is left out by default
+    let mut bus = TestBus::new();
+    bus.expect_read(0x0000, 0xe6)
+        .expect_read(0x0001, 0x10)
+        .expect_read(0x0010, 0x41)
+        .expect_write(0x0010, 0x42)
+        .expect_read(0x0002, 0xea);
+    let mut cpu = MOS6502::new(bus);
+    cpu.step();
+    assert_eq!(cpu.bus().remaining(), vec![Access::Read(0x0002, 0xea)]);
+    cpu.step();
+    cpu.bus().finish();
+}
+
+#[cfg(feature = "mos6502")]
+#[test]
+fn scripted_dummy_accesses() {
+    use mos6502::MOS6502;
+    use testbus::TestBus;
+    use Clock;
+
+    // inc $10, lda $10ff,x across a page and sta $2000,x without crossing
+    let mut bus = TestBus::new();
+    bus.expect_read(0x0000, 0xe6)
+        .expect_read(0x0001, 0x10)
+        .expect_read(0x0010, 0x41)
+        .expect_write(0x0010, 0x41)
+        .expect_write(0x0010, 0x42)
+        .expect_read(0x0002, 0xbd)
+        .expect_read(0x0003, 0xff)
+        .expect_read(0x0004, 0x10)
+        .expect_read(0x1000, 0x00)
+        .expect_read(0x1100, 0x07)
+        .expect_read(0x0005, 0x9d)
+        .expect_read(0x0006, 0x01)
+        .expect_read(0x0007, 0x20)
+        .expect_read(0x2002, 0x00)
+        .expect_write(0x2002, 0x07);
+    let mut cpu = MOS6502::builder(bus).dummy_accesses(true).build();
+    cpu.x = 1;
+    for _ in 0..3 {
+        cpu.step();
+    }
+    cpu.bus().finish();
+}
+
+#[test]
+#[should_panic(expected = "bus access 1 was write $1 to $10, expected write $2 to $10")]
+fn scripted_bus_mismatch() {
+    use testbus::TestBus;
+
+    let mut bus: TestBus<u16, u8> = TestBus::new();
+    bus.expect_read(0x00, 0x01).expect_write(0x10, 0x02);
+    assert_eq!(bus.read(0x00), 0x01);
+    bus.write(0x10, 0x01);
+}
@@ -2,7 +2,7 @@ use std::sync::{Arc, Mutex};
 extern crate chrono;
 extern crate timer;
 
-use {Address, AddressBusIO, Data, Interrupt};
+use {Address, AddressBusIO, Data, Interrupt, Reset};
 
 pub struct SimpleTimer<T: Data, U: Address> {
     counter: Arc<Mutex<T>>,
@@ -33,6 +33,14 @@ impl<T: Data, U: Address> SimpleTimer<T, U> {
     }
 }
 
+// stops the countdown, the interrupt stays connected
+impl<T: Data, U: Address> Reset for SimpleTimer<T, U> {
+    fn reset(&mut self) {
+        *self.guard.lock().unwrap() = None;
+        *self.counter.lock().unwrap() = T::zero();
+    }
+}
+
 impl<T: Address, U: Data> AddressBusIO<T, U> for SimpleTimer<U, T> {
     fn read(&mut self, _address: T) -> U {
         *self.counter.lock().unwrap()
@@ -5,7 +5,7 @@ use std::process;
 use std::sync::mpsc::{channel, Receiver, Sender};
 use std::thread;
 
-use AddressBusIO;
+use {AddressBusIO, Reset};
 
 pub struct UnixTerm {
     stdout: Stdout,
@@ -55,6 +55,13 @@ impl UnixTerm {
     }
 }
 
+impl Reset for UnixTerm {
+    fn reset(&mut self) {
+        self.last_stdout = 0;
+        self.last_stderr = 0;
+    }
+}
+
 impl AddressBusIO<u8, u8> for UnixTerm {
     fn read(&mut self, address: u8) -> u8 {
         // wake up thread
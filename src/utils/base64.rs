@@ -0,0 +1,42 @@
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+pub fn encode(data: &[u8]) -> String {
+    let mut text = String::with_capacity(data.len().div_ceil(3) * 4);
+    for group in data.chunks(3) {
+        let value = u32::from(group[0]) << 16
+            | u32::from(*group.get(1).unwrap_or(&0)) << 8
+            | u32::from(*group.get(2).unwrap_or(&0));
+        for i in 0..4 {
+            if i <= group.len() {
+                text.push(ALPHABET[(value >> (18 - i * 6)) as usize & 0x3f] as char);
+            } else {
+                text.push('=');
+            }
+        }
+    }
+    text
+}
+
+// None on anything that is not padded standard base64
+pub fn decode(text: &str) -> Option<Vec<u8>> {
+    let text = text.trim().as_bytes();
+    if text.len() & 3 != 0 {
+        return None;
+    }
+    let mut data = Vec::with_capacity(text.len() / 4 * 3);
+    for group in text.chunks(4) {
+        let padding = group.iter().rev().take_while(|c| **c == b'=').count();
+        if padding > 2 {
+            return None;
+        }
+        let mut value = 0u32;
+        for c in &group[..4 - padding] {
+            let index = ALPHABET.iter().position(|a| a == c)?;
+            value = value << 6 | index as u32;
+        }
+        value <<= 6 * padding as u32;
+        let bytes = [(value >> 16) as u8, (value >> 8) as u8, value as u8];
+        data.extend_from_slice(&bytes[..3 - padding]);
+    }
+    Some(data)
+}
@@ -0,0 +1,209 @@
+// just enough json for test vectors and configuration files
+#[derive(Clone, PartialEq, Debug)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    // in file order
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match *self {
+            Json::Object(ref members) => members
+                .iter()
+                .find(|member| member.0 == key)
+                .map(|member| &member.1),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Json]> {
+        match *self {
+            Json::Array(ref items) => Some(items),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match *self {
+            Json::String(ref text) => Some(text),
+            _ => None,
+        }
+    }
+
+    // only integral numbers
+    pub fn as_u64(&self) -> Option<u64> {
+        match *self {
+            Json::Number(number) if number >= 0.0 && number.fract() == 0.0 => Some(number as u64),
+            _ => None,
+        }
+    }
+}
+
+struct Parser<'a> {
+    text: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, message: &str) -> String {
+        format!("{} at offset {}", message, self.offset)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.offset < self.text.len() && self.text[self.offset].is_ascii_whitespace() {
+            self.offset += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<u8> {
+        self.skip_whitespace();
+        self.text.get(self.offset).cloned()
+    }
+
+    fn expect(&mut self, token: &str) -> Result<(), String> {
+        if self.text[self.offset..].starts_with(token.as_bytes()) {
+            self.offset += token.len();
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected {}", token)))
+        }
+    }
+
+    fn value(&mut self) -> Result<Json, String> {
+        match self.peek() {
+            Some(b'{') => self.object(),
+            Some(b'[') => self.array(),
+            Some(b'"') => self.string().map(Json::String),
+            Some(b't') => self.expect("true").map(|_| Json::Bool(true)),
+            Some(b'f') => self.expect("false").map(|_| Json::Bool(false)),
+            Some(b'n') => self.expect("null").map(|_| Json::Null),
+            Some(_) => self.number(),
+            None => Err(self.error("unexpected end")),
+        }
+    }
+
+    fn object(&mut self) -> Result<Json, String> {
+        self.expect("{")?;
+        let mut members = Vec::new();
+        if self.peek() == Some(b'}') {
+            self.offset += 1;
+            return Ok(Json::Object(members));
+        }
+        loop {
+            if self.peek() != Some(b'"') {
+                return Err(self.error("expected a key"));
+            }
+            let key = self.string()?;
+            self.skip_whitespace();
+            self.expect(":")?;
+            members.push((key, self.value()?));
+            match self.peek() {
+                Some(b',') => self.offset += 1,
+                Some(b'}') => {
+                    self.offset += 1;
+                    return Ok(Json::Object(members));
+                }
+                _ => return Err(self.error("expected , or }")),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Json, String> {
+        self.expect("[")?;
+        let mut items = Vec::new();
+        if self.peek() == Some(b']') {
+            self.offset += 1;
+            return Ok(Json::Array(items));
+        }
+        loop {
+            items.push(self.value()?);
+            match self.peek() {
+                Some(b',') => self.offset += 1,
+                Some(b']') => {
+                    self.offset += 1;
+                    return Ok(Json::Array(items));
+                }
+                _ => return Err(self.error("expected , or ]")),
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.expect("\"")?;
+        let mut text = Vec::new();
+        loop {
+            let c = *self
+                .text
+                .get(self.offset)
+                .ok_or_else(|| self.error("unterminated string"))?;
+            self.offset += 1;
+            match c {
+                b'"' => break,
+                b'\\' => {
+                    let escape = *self
+                        .text
+                        .get(self.offset)
+                        .ok_or_else(|| self.error("unterminated string"))?;
+                    self.offset += 1;
+                    let unescaped = match escape {
+                        b'n' => '\n',
+                        b't' => '\t',
+                        b'r' => '\r',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'u' => {
+                            let digits = self
+                                .text
+                                .get(self.offset..self.offset + 4)
+                                .and_then(|digits| std::str::from_utf8(digits).ok())
+                                .and_then(|digits| u32::from_str_radix(digits, 16).ok())
+                                .ok_or_else(|| self.error("invalid unicode escape"))?;
+                            self.offset += 4;
+                            // surrogate pairs are not needed so far
+                            std::char::from_u32(digits).unwrap_or('\u{fffd}')
+                        }
+                        c => c as char,
+                    };
+                    let mut buffer = [0; 4];
+                    text.extend_from_slice(unescaped.encode_utf8(&mut buffer).as_bytes());
+                }
+                c => text.push(c),
+            }
+        }
+        String::from_utf8(text).map_err(|_| self.error("invalid utf-8"))
+    }
+
+    fn number(&mut self) -> Result<Json, String> {
+        let start = self.offset;
+        while self.offset < self.text.len()
+            && matches!(
+                self.text[self.offset],
+                b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9'
+            )
+        {
+            self.offset += 1;
+        }
+        std::str::from_utf8(&self.text[start..self.offset])
+            .ok()
+            .and_then(|number| number.parse().ok())
+            .map(Json::Number)
+            .ok_or_else(|| self.error("invalid value"))
+    }
+}
+
+pub fn parse(text: &str) -> Result<Json, String> {
+    let mut parser = Parser {
+        text: text.as_bytes(),
+        offset: 0,
+    };
+    let value = parser.value()?;
+    if parser.peek().is_some() {
+        return Err(parser.error("trailing characters"));
+    }
+    Ok(value)
+}
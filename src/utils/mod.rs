@@ -1,6 +1,18 @@
+use rand::distributions::{Distribution, Standard};
 use std::num::ParseIntError;
 use Address;
 
+#[cfg(target_arch = "wasm32")]
+use rand::prng::XorShiftRng;
+#[cfg(target_arch = "wasm32")]
+use rand::{Rng, SeedableRng};
+#[cfg(target_arch = "wasm32")]
+use std::cell::RefCell;
+
+pub mod base64;
+pub mod json;
+pub mod png;
+
 pub fn to_number<T: Address<FromStrRadixErr = ParseIntError>>(
     string: &str,
 ) -> Result<T, ParseIntError> {
@@ -26,3 +38,44 @@ pub fn to_number<T: Address<FromStrRadixErr = ParseIntError>>(
 
     T::from_str_radix(string, 10)
 }
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn random<T>() -> T
+where
+    Standard: Distribution<T>,
+{
+    ::rand::random()
+}
+
+// rand has no entropy source on wasm32-unknown-unknown, the browser (when
+// available) seeds a generator instead
+#[cfg(all(target_arch = "wasm32", feature = "web"))]
+fn seed() -> [u8; 16] {
+    extern crate js_sys;
+    let mut seed = [0; 16];
+    for byte in seed.iter_mut() {
+        *byte = (js_sys::Math::random() * 256.0) as u8;
+    }
+    seed
+}
+
+#[cfg(all(target_arch = "wasm32", not(feature = "web")))]
+fn seed() -> [u8; 16] {
+    [0x5a; 16]
+}
+
+#[cfg(target_arch = "wasm32")]
+thread_local! {
+    static GENERATOR: RefCell<XorShiftRng> = RefCell::new(XorShiftRng::from_seed(seed()));
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn random<T>() -> T
+where
+    Standard: Distribution<T>,
+{
+    GENERATOR.with(|generator| generator.borrow_mut().gen())
+}
+
+#[cfg(test)]
+mod tests;
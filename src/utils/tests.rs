@@ -0,0 +1,47 @@
+use utils::json::{self, Json};
+use utils::{base64, png};
+
+#[test]
+fn base64_roundtrip() {
+    assert_eq!(base64::encode(b"impostor"), "aW1wb3N0b3I=");
+    assert_eq!(base64::encode(b"chip8"), "Y2hpcDg=");
+    assert_eq!(base64::encode(b"6502"), "NjUwMg==");
+    assert_eq!(base64::decode("aW1wb3N0b3I="), Some(b"impostor".to_vec()));
+    assert_eq!(base64::decode("NjUwMg=="), Some(b"6502".to_vec()));
+    assert_eq!(base64::decode("NjUwMg="), None);
+    assert_eq!(base64::decode("Nj!wMg=="), None);
+}
+
+#[test]
+fn png_encode() {
+    let png = png::encode(2, 1, &[255, 0, 0, 0, 0, 255]);
+    assert_eq!(&png[0..8], b"\x89PNG\r\n\x1a\n");
+    // IHDR length, type, width and height
+    assert_eq!(&png[8..24], b"\0\0\0\x0dIHDR\0\0\0\x02\0\0\0\x01");
+    // IHDR crc, computed by zlib
+    assert_eq!(&png[29..33], &[0x7b, 0x40, 0xe8, 0xdd]);
+    assert_eq!(&png[png.len() - 12..], b"\0\0\0\0IEND\xae\x42\x60\x82");
+}
+
+#[test]
+fn json_parse() {
+    let value =
+        json::parse(r#"{"name": "a9 \"x\"", "list": [1, -2.5, true, null], "empty": {}}"#).unwrap();
+    assert_eq!(value.get("name").and_then(Json::as_str), Some("a9 \"x\""));
+    assert_eq!(
+        value.get("list"),
+        Some(&Json::Array(vec![
+            Json::Number(1.0),
+            Json::Number(-2.5),
+            Json::Bool(true),
+            Json::Null,
+        ]))
+    );
+    assert_eq!(value.get("empty"), Some(&Json::Object(vec![])));
+    assert_eq!(
+        value.get("list").unwrap().as_array().unwrap()[0].as_u64(),
+        Some(1)
+    );
+    assert!(json::parse("[1, 2").is_err());
+    assert!(json::parse("[1] 2").is_err());
+}
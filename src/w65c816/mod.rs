@@ -0,0 +1,1464 @@
+use {AddressBusIO, Clock, Interrupt, Reset};
+
+pub const CARRY: u8 = 0x01;
+pub const ZERO: u8 = 0x02;
+pub const INTERRUPT: u8 = 0x04;
+pub const DECIMAL: u8 = 0x08;
+// x in native mode, 8 bit index registers; the break bit in emulation mode
+pub const INDEX: u8 = 0x10;
+pub const BREAK: u8 = 0x10;
+// m, an 8 bit accumulator and memory
+pub const MEMORY: u8 = 0x20;
+pub const OVERFLOW: u8 = 0x40;
+pub const SIGN: u8 = 0x80;
+
+// what decides the size of the operand
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Width {
+    // the m flag
+    Memory,
+    // the x flag
+    Index,
+    Byte,
+    Word,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Access {
+    Read,
+    Write,
+    Modify,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Decoded {
+    pub name: &'static str,
+    pub mode: &'static str,
+    width: Width,
+    access: Access,
+    // with 8 bit registers and the direct page on a page boundary
+    cycles: u8,
+}
+
+// every opcode of the chip is documented, the match in dispatch is checked
+// for all of them by the compiler
+macro_rules! opcode_table {
+    ($callback:ident!($($arguments:tt)*)) => {
+        $callback!($($arguments)*;
+            adc: 0x61 direct_indirect_x, 0x63 stack_relative, 0x65 direct,
+                0x67 direct_indirect_long, 0x69 immediate, 0x6d absolute, 0x6f absolute_long,
+                0x71 direct_indirect_y, 0x72 direct_indirect, 0x73 stack_relative_indirect_y,
+                0x75 direct_x, 0x77 direct_indirect_long_y, 0x79 absolute_y, 0x7d absolute_x,
+                0x7f absolute_long_x;
+            and: 0x21 direct_indirect_x, 0x23 stack_relative, 0x25 direct,
+                0x27 direct_indirect_long, 0x29 immediate, 0x2d absolute, 0x2f absolute_long,
+                0x31 direct_indirect_y, 0x32 direct_indirect, 0x33 stack_relative_indirect_y,
+                0x35 direct_x, 0x37 direct_indirect_long_y, 0x39 absolute_y, 0x3d absolute_x,
+                0x3f absolute_long_x;
+            asl: 0x06 direct, 0x0a accumulator, 0x0e absolute, 0x16 direct_x, 0x1e absolute_x;
+            bcc: 0x90 relative;
+            bcs: 0xb0 relative;
+            beq: 0xf0 relative;
+            bit: 0x24 direct, 0x2c absolute, 0x34 direct_x, 0x3c absolute_x, 0x89 immediate;
+            bmi: 0x30 relative;
+            bne: 0xd0 relative;
+            bpl: 0x10 relative;
+            bra: 0x80 relative;
+            brk: 0x00 immediate;
+            brl: 0x82 relative_long;
+            bvc: 0x50 relative;
+            bvs: 0x70 relative;
+            clc: 0x18 implied;
+            cld: 0xd8 implied;
+            cli: 0x58 implied;
+            clv: 0xb8 implied;
+            cmp: 0xc1 direct_indirect_x, 0xc3 stack_relative, 0xc5 direct,
+                0xc7 direct_indirect_long, 0xc9 immediate, 0xcd absolute, 0xcf absolute_long,
+                0xd1 direct_indirect_y, 0xd2 direct_indirect, 0xd3 stack_relative_indirect_y,
+                0xd5 direct_x, 0xd7 direct_indirect_long_y, 0xd9 absolute_y, 0xdd absolute_x,
+                0xdf absolute_long_x;
+            cop: 0x02 immediate;
+            cpx: 0xe0 immediate, 0xe4 direct, 0xec absolute;
+            cpy: 0xc0 immediate, 0xc4 direct, 0xcc absolute;
+            dec: 0x3a accumulator, 0xc6 direct, 0xce absolute, 0xd6 direct_x, 0xde absolute_x;
+            dex: 0xca implied;
+            dey: 0x88 implied;
+            eor: 0x41 direct_indirect_x, 0x43 stack_relative, 0x45 direct,
+                0x47 direct_indirect_long, 0x49 immediate, 0x4d absolute, 0x4f absolute_long,
+                0x51 direct_indirect_y, 0x52 direct_indirect, 0x53 stack_relative_indirect_y,
+                0x55 direct_x, 0x57 direct_indirect_long_y, 0x59 absolute_y, 0x5d absolute_x,
+                0x5f absolute_long_x;
+            inc: 0x1a accumulator, 0xe6 direct, 0xee absolute, 0xf6 direct_x, 0xfe absolute_x;
+            inx: 0xe8 implied;
+            iny: 0xc8 implied;
+            jml: 0x5c absolute_long, 0xdc absolute_indirect_long;
+            jmp: 0x4c absolute, 0x6c absolute_indirect, 0x7c absolute_indirect_x;
+            jsl: 0x22 absolute_long;
+            jsr: 0x20 absolute, 0xfc absolute_indirect_x;
+            lda: 0xa1 direct_indirect_x, 0xa3 stack_relative, 0xa5 direct,
+                0xa7 direct_indirect_long, 0xa9 immediate, 0xad absolute, 0xaf absolute_long,
+                0xb1 direct_indirect_y, 0xb2 direct_indirect, 0xb3 stack_relative_indirect_y,
+                0xb5 direct_x, 0xb7 direct_indirect_long_y, 0xb9 absolute_y, 0xbd absolute_x,
+                0xbf absolute_long_x;
+            ldx: 0xa2 immediate, 0xa6 direct, 0xae absolute, 0xb6 direct_y, 0xbe absolute_y;
+            ldy: 0xa0 immediate, 0xa4 direct, 0xac absolute, 0xb4 direct_x, 0xbc absolute_x;
+            lsr: 0x46 direct, 0x4a accumulator, 0x4e absolute, 0x56 direct_x, 0x5e absolute_x;
+            mvn: 0x54 block_move;
+            mvp: 0x44 block_move;
+            nop: 0xea implied;
+            ora: 0x01 direct_indirect_x, 0x03 stack_relative, 0x05 direct,
+                0x07 direct_indirect_long, 0x09 immediate, 0x0d absolute, 0x0f absolute_long,
+                0x11 direct_indirect_y, 0x12 direct_indirect, 0x13 stack_relative_indirect_y,
+                0x15 direct_x, 0x17 direct_indirect_long_y, 0x19 absolute_y, 0x1d absolute_x,
+                0x1f absolute_long_x;
+            pea: 0xf4 immediate;
+            pei: 0xd4 direct;
+            per: 0x62 relative_long;
+            pha: 0x48 implied;
+            phb: 0x8b implied;
+            phd: 0x0b implied;
+            phk: 0x4b implied;
+            php: 0x08 implied;
+            phx: 0xda implied;
+            phy: 0x5a implied;
+            pla: 0x68 implied;
+            plb: 0xab implied;
+            pld: 0x2b implied;
+            plp: 0x28 implied;
+            plx: 0xfa implied;
+            ply: 0x7a implied;
+            rep: 0xc2 immediate;
+            rol: 0x26 direct, 0x2a accumulator, 0x2e absolute, 0x36 direct_x, 0x3e absolute_x;
+            ror: 0x66 direct, 0x6a accumulator, 0x6e absolute, 0x76 direct_x, 0x7e absolute_x;
+            rti: 0x40 implied;
+            rtl: 0x6b implied;
+            rts: 0x60 implied;
+            sbc: 0xe1 direct_indirect_x, 0xe3 stack_relative, 0xe5 direct,
+                0xe7 direct_indirect_long, 0xe9 immediate, 0xed absolute, 0xef absolute_long,
+                0xf1 direct_indirect_y, 0xf2 direct_indirect, 0xf3 stack_relative_indirect_y,
+                0xf5 direct_x, 0xf7 direct_indirect_long_y, 0xf9 absolute_y, 0xfd absolute_x,
+                0xff absolute_long_x;
+            sec: 0x38 implied;
+            sed: 0xf8 implied;
+            sei: 0x78 implied;
+            sep: 0xe2 immediate;
+            sta: 0x81 direct_indirect_x, 0x83 stack_relative, 0x85 direct,
+                0x87 direct_indirect_long, 0x8d absolute, 0x8f absolute_long,
+                0x91 direct_indirect_y, 0x92 direct_indirect, 0x93 stack_relative_indirect_y,
+                0x95 direct_x, 0x97 direct_indirect_long_y, 0x99 absolute_y, 0x9d absolute_x,
+                0x9f absolute_long_x;
+            stp: 0xdb implied;
+            stx: 0x86 direct, 0x8e absolute, 0x96 direct_y;
+            sty: 0x84 direct, 0x8c absolute, 0x94 direct_x;
+            stz: 0x64 direct, 0x74 direct_x, 0x9c absolute, 0x9e absolute_x;
+            tax: 0xaa implied;
+            tay: 0xa8 implied;
+            tcd: 0x5b implied;
+            tcs: 0x1b implied;
+            tdc: 0x7b implied;
+            trb: 0x14 direct, 0x1c absolute;
+            tsb: 0x04 direct, 0x0c absolute;
+            tsc: 0x3b implied;
+            tsx: 0xba implied;
+            txa: 0x8a implied;
+            txs: 0x9a implied;
+            txy: 0x9b implied;
+            tya: 0x98 implied;
+            tyx: 0xbb implied;
+            wai: 0xcb implied;
+            wdm: 0x42 immediate;
+            xba: 0xeb implied;
+            xce: 0xfb implied;
+        )
+    };
+}
+
+macro_rules! dispatch {
+    ($cpu:ident, $opcode:expr; $($name:ident: $($code:literal $mode:ident),+;)*) => {
+        match $opcode {
+            $($(
+                $code => {
+                    $cpu.$mode();
+                    $cpu.$name();
+                }
+            )+)*
+        }
+    };
+}
+
+macro_rules! describe {
+    ($code:ident; $($name:ident: $($op:literal $mode:ident),+;)*) => {
+        match $code {
+            $($(
+                $op => (stringify!($name), stringify!($mode)),
+            )+)*
+        }
+    };
+}
+
+const fn describe(code: u8) -> (&'static str, &'static str) {
+    opcode_table!(describe!(code))
+}
+
+fn width(name: &str) -> Width {
+    match name {
+        "ldx" | "ldy" | "stx" | "sty" | "cpx" | "cpy" | "inx" | "iny" | "dex" | "dey" | "phx"
+        | "phy" | "plx" | "ply" | "tax" | "tay" | "tsx" | "txy" | "tyx" => Width::Index,
+        "rep" | "sep" | "brk" | "cop" | "wdm" => Width::Byte,
+        "pea" | "pei" => Width::Word,
+        _ => Width::Memory,
+    }
+}
+
+fn access(name: &str) -> Access {
+    match name {
+        "sta" | "stx" | "sty" | "stz" => Access::Write,
+        "asl" | "lsr" | "rol" | "ror" | "inc" | "dec" | "trb" | "tsb" => Access::Modify,
+        _ => Access::Read,
+    }
+}
+
+// from the datasheet; the 16 bit operands, the direct page off a page
+// boundary, the page crosses and the native mode add theirs as they happen
+fn cycles(name: &str, mode: &str, access: Access) -> u8 {
+    match (name, mode) {
+        ("brk", _) | ("cop", _) | ("mvn", _) | ("mvp", _) => return 7,
+        ("jsr", "absolute") | ("rts", _) | ("rtl", _) | ("rti", _) => return 6,
+        ("jsr", _) | ("jsl", _) => return 8,
+        ("jmp", "absolute") => return 3,
+        ("jml", "absolute_long") | ("brl", _) | ("phd", _) => return 4,
+        ("jmp", "absolute_indirect") | ("pea", _) | ("pld", _) => return 5,
+        ("jmp", _) | ("jml", _) | ("pei", _) | ("per", _) => return 6,
+        ("pha", _) | ("phb", _) | ("phk", _) | ("php", _) | ("phx", _) | ("phy", _) => return 3,
+        ("pla", _) | ("plb", _) | ("plp", _) | ("plx", _) | ("ply", _) => return 4,
+        ("rep", _) | ("sep", _) | ("xba", _) | ("wai", _) | ("stp", _) => return 3,
+        (_, "implied") | (_, "accumulator") | (_, "immediate") | (_, "relative") => return 2,
+        _ => (),
+    }
+    let read = match mode {
+        "direct" => 3,
+        "direct_x" | "direct_y" | "absolute" | "absolute_x" | "absolute_y" | "stack_relative" => 4,
+        "direct_indirect" | "direct_indirect_y" | "absolute_long" | "absolute_long_x" => 5,
+        "direct_indirect_x" | "direct_indirect_long" | "direct_indirect_long_y" => 6,
+        _ => 7,
+    };
+    // the index is always added before a write
+    let indexed = matches!(mode, "absolute_x" | "absolute_y" | "direct_indirect_y");
+    match access {
+        Access::Read => read,
+        Access::Write => read + u8::from(indexed),
+        Access::Modify => read + 2 + u8::from(indexed),
+    }
+}
+
+pub struct W65C816<T: AddressBusIO<u32, u8>> {
+    bus: T,
+
+    // c, b is its high byte
+    pub a: u16,
+    pub x: u16,
+    pub y: u16,
+    pub sp: u16,
+    // the direct page
+    pub dp: u16,
+    pub pc: u16,
+    // the banks of the program and of the data
+    pub pbr: u8,
+    pub dbr: u8,
+    pub status: u8,
+    // the 6502 mode of a reset, left with xce
+    pub emulation: bool,
+
+    pub ticks: u64,
+
+    // the effective address, 24 bit
+    addr: u32,
+    // the operand is in the direct page or on the stack, its high byte wraps
+    // within the bank
+    wraps: bool,
+    accumulator: bool,
+    decoded: Decoded,
+    opcodes: [Decoded; 256],
+
+    waiting: bool,
+    stopped: bool,
+    irq_line: bool,
+    nmi_pending: bool,
+}
+
+impl<T: AddressBusIO<u32, u8>> W65C816<T> {
+    pub fn new(bus: T) -> W65C816<T> {
+        let mut opcodes = [Decoded {
+            name: "",
+            mode: "",
+            width: Width::Byte,
+            access: Access::Read,
+            cycles: 0,
+        }; 256];
+        for (code, decoded) in opcodes.iter_mut().enumerate() {
+            let (name, mode) = describe(code as u8);
+            let access = access(name);
+            *decoded = Decoded {
+                name,
+                mode,
+                width: width(name),
+                access,
+                cycles: cycles(name, mode, access),
+            };
+        }
+        W65C816 {
+            bus,
+            a: 0,
+            x: 0,
+            y: 0,
+            sp: 0x01ff,
+            dp: 0,
+            pc: 0,
+            pbr: 0,
+            dbr: 0,
+            status: MEMORY | INDEX | INTERRUPT,
+            emulation: true,
+            ticks: 0,
+            addr: 0,
+            wraps: false,
+            accumulator: false,
+            decoded: opcodes[0xea],
+            opcodes,
+            waiting: false,
+            stopped: false,
+            irq_line: false,
+            nmi_pending: false,
+        }
+    }
+
+    pub fn decode(&self, code: u8) -> Decoded {
+        self.opcodes[code as usize]
+    }
+
+    pub fn bus(&self) -> &T {
+        &self.bus
+    }
+
+    pub fn bus_mut(&mut self) -> &mut T {
+        &mut self.bus
+    }
+
+    pub fn get_flag(&self, flag: u8) -> bool {
+        self.status & flag != 0
+    }
+
+    pub fn set_flag(&mut self, flag: u8, enabled: bool) {
+        if enabled {
+            self.status |= flag;
+        } else {
+            self.status &= !flag;
+        }
+    }
+
+    // the address of the next instruction, with its bank
+    pub fn program_counter(&self) -> u32 {
+        (u32::from(self.pbr) << 16) | u32::from(self.pc)
+    }
+
+    pub fn is_waiting(&self) -> bool {
+        self.waiting
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.stopped
+    }
+
+    // taken by the next step, the irq stays asserted until released
+    pub fn irq(&mut self, asserted: bool) {
+        self.irq_line = asserted;
+    }
+
+    pub fn nmi(&mut self) {
+        self.nmi_pending = true;
+    }
+
+    fn read8(&mut self, addr: u32) -> u8 {
+        self.bus.read(addr & 0x00ff_ffff)
+    }
+
+    fn write8(&mut self, addr: u32, value: u8) {
+        self.bus.write(addr & 0x00ff_ffff, value)
+    }
+
+    // a pointer in bank 0, the direct page and the vectors
+    fn read16_bank0(&mut self, addr: u16) -> u16 {
+        let low = u16::from(self.read8(u32::from(addr)));
+        let high = u16::from(self.read8(u32::from(addr.wrapping_add(1))));
+        (high << 8) | low
+    }
+
+    fn read24_bank0(&mut self, addr: u16) -> u32 {
+        let word = u32::from(self.read16_bank0(addr));
+        let bank = u32::from(self.read8(u32::from(addr.wrapping_add(2))));
+        (bank << 16) | word
+    }
+
+    fn fetch8(&mut self) -> u8 {
+        let addr = self.program_counter();
+        self.pc = self.pc.wrapping_add(1);
+        self.read8(addr)
+    }
+
+    fn fetch16(&mut self) -> u16 {
+        let low = u16::from(self.fetch8());
+        let high = u16::from(self.fetch8());
+        (high << 8) | low
+    }
+
+    fn fetch24(&mut self) -> u32 {
+        let word = u32::from(self.fetch16());
+        (u32::from(self.fetch8()) << 16) | word
+    }
+
+    fn memory_wide(&self) -> bool {
+        !self.get_flag(MEMORY)
+    }
+
+    fn index_wide(&self) -> bool {
+        !self.get_flag(INDEX)
+    }
+
+    // whether the operand of the instruction is 16 bit
+    fn wide(&self) -> bool {
+        match self.decoded.width {
+            Width::Memory => self.memory_wide(),
+            Width::Index => self.index_wide(),
+            Width::Byte => false,
+            Width::Word => true,
+        }
+    }
+
+    fn next(&self, addr: u32) -> u32 {
+        if self.wraps {
+            (addr & 0x00ff_0000) | (addr.wrapping_add(1) & 0xffff)
+        } else {
+            addr.wrapping_add(1)
+        }
+    }
+
+    // the operand, and a cycle for its high byte when it was not counted
+    fn load(&mut self) -> u16 {
+        let addr = self.addr;
+        let low = u16::from(self.read8(addr));
+        if !self.wide() {
+            return low;
+        }
+        if self.decoded.width != Width::Word {
+            self.ticks += 1;
+        }
+        let next = self.next(addr);
+        (u16::from(self.read8(next)) << 8) | low
+    }
+
+    fn store(&mut self, value: u16) {
+        let addr = self.addr;
+        self.write8(addr, value as u8);
+        if self.wide() {
+            self.ticks += 1;
+            let next = self.next(addr);
+            self.write8(next, (value >> 8) as u8);
+        }
+    }
+
+    // a read modify write works on a when in the accumulator mode
+    fn modify_operand(&mut self) -> u16 {
+        if self.accumulator {
+            self.a
+        } else {
+            self.load()
+        }
+    }
+
+    fn write_back(&mut self, value: u16) {
+        let wide = self.wide();
+        if self.accumulator {
+            self.set_a(value);
+        } else {
+            self.store(value);
+        }
+        self.set_nz(value, wide);
+    }
+
+    fn set_nz(&mut self, value: u16, wide: bool) {
+        let (mask, sign) = if wide {
+            (0xffff, 0x8000)
+        } else {
+            (0x00ff, 0x0080)
+        };
+        self.set_flag(ZERO, value & mask == 0);
+        self.set_flag(SIGN, value & sign != 0);
+    }
+
+    // b is kept with an 8 bit accumulator
+    fn set_a(&mut self, value: u16) {
+        if self.memory_wide() {
+            self.a = value;
+        } else {
+            self.a = (self.a & 0xff00) | (value & 0x00ff);
+        }
+    }
+
+    fn load_a(&mut self, value: u16) {
+        self.set_a(value);
+        let wide = self.memory_wide();
+        self.set_nz(value, wide);
+    }
+
+    // the high bytes of x and y are zero with 8 bit index registers
+    fn index(&self, value: u16) -> u16 {
+        if self.index_wide() {
+            value
+        } else {
+            value & 0x00ff
+        }
+    }
+
+    fn load_x(&mut self, value: u16) {
+        self.x = self.index(value);
+        let (x, wide) = (self.x, self.index_wide());
+        self.set_nz(x, wide);
+    }
+
+    fn load_y(&mut self, value: u16) {
+        self.y = self.index(value);
+        let (y, wide) = (self.y, self.index_wide());
+        self.set_nz(y, wide);
+    }
+
+    // m and x are set for good in the emulation mode
+    fn fix_status(&mut self) {
+        if self.emulation {
+            self.status |= MEMORY | INDEX;
+            self.sp = 0x0100 | (self.sp & 0x00ff);
+        }
+        if !self.index_wide() {
+            self.x &= 0x00ff;
+            self.y &= 0x00ff;
+        }
+    }
+
+    fn push8(&mut self, value: u8) {
+        let sp = self.sp;
+        self.write8(u32::from(sp), value);
+        self.sp = if self.emulation {
+            0x0100 | (sp.wrapping_sub(1) & 0x00ff)
+        } else {
+            sp.wrapping_sub(1)
+        };
+    }
+
+    fn pull8(&mut self) -> u8 {
+        self.sp = if self.emulation {
+            0x0100 | (self.sp.wrapping_add(1) & 0x00ff)
+        } else {
+            self.sp.wrapping_add(1)
+        };
+        let sp = self.sp;
+        self.read8(u32::from(sp))
+    }
+
+    fn push16(&mut self, value: u16) {
+        self.push8((value >> 8) as u8);
+        self.push8(value as u8);
+    }
+
+    fn pull16(&mut self) -> u16 {
+        let low = u16::from(self.pull8());
+        (u16::from(self.pull8()) << 8) | low
+    }
+
+    // a register as wide as its flag says, a cycle more when 16 bit
+    fn push_register(&mut self, value: u16, wide: bool) {
+        if wide {
+            self.ticks += 1;
+            self.push16(value);
+        } else {
+            self.push8(value as u8);
+        }
+    }
+
+    fn pull_register(&mut self, wide: bool) -> u16 {
+        if wide {
+            self.ticks += 1;
+            self.pull16()
+        } else {
+            u16::from(self.pull8())
+        }
+    }
+
+    // the emulation mode vector is the 6502 one; the native mode pushes the
+    // program bank too, and brk and an irq get their own vectors
+    fn interrupt(&mut self, native: u16, emulation: u16, software: bool) {
+        if !self.emulation {
+            let pbr = self.pbr;
+            self.push8(pbr);
+            self.ticks += 1;
+        }
+        let pc = self.pc;
+        self.push16(pc);
+        let status = if self.emulation && !software {
+            self.status & !BREAK
+        } else {
+            self.status
+        };
+        self.push8(status);
+        self.set_flag(INTERRUPT, true);
+        self.set_flag(DECIMAL, false);
+        self.pbr = 0;
+        let vector = if self.emulation { emulation } else { native };
+        self.pc = self.read16_bank0(vector);
+    }
+
+    // an irq wakes a waiting cpu up even when masked, it just goes on with
+    // the next instruction then
+    fn wake(&mut self) -> bool {
+        if self.nmi_pending || self.irq_line {
+            self.waiting = false;
+        }
+        !self.waiting
+    }
+
+    fn service_interrupts(&mut self) -> bool {
+        if self.nmi_pending {
+            self.nmi_pending = false;
+            self.interrupt(0xffea, 0xfffa, false);
+        } else if self.irq_line && !self.get_flag(INTERRUPT) {
+            self.interrupt(0xffee, 0xfffe, false);
+        } else {
+            return false;
+        }
+        self.ticks += 7;
+        true
+    }
+
+    fn implied(&mut self) {}
+
+    fn accumulator(&mut self) {
+        self.accumulator = true;
+    }
+
+    fn immediate(&mut self) {
+        self.addr = self.program_counter();
+        self.wraps = true;
+        let length = if self.wide() { 2 } else { 1 };
+        self.pc = self.pc.wrapping_add(length);
+    }
+
+    // a cycle more when the direct page is off a page boundary
+    fn direct_page(&mut self, index: u16) -> u16 {
+        let offset = u16::from(self.fetch8());
+        if self.dp & 0x00ff != 0 {
+            self.ticks += 1;
+        } else if self.emulation && index != 0 {
+            // the old zeropage wrap, only with the page aligned
+            return self.dp | (offset.wrapping_add(index) & 0x00ff);
+        }
+        self.dp.wrapping_add(offset).wrapping_add(index)
+    }
+
+    fn set_direct(&mut self, addr: u16) {
+        self.addr = u32::from(addr);
+        self.wraps = true;
+    }
+
+    fn direct(&mut self) {
+        let addr = self.direct_page(0);
+        self.set_direct(addr);
+    }
+
+    fn direct_x(&mut self) {
+        let x = self.x;
+        let addr = self.direct_page(x);
+        self.set_direct(addr);
+    }
+
+    fn direct_y(&mut self) {
+        let y = self.y;
+        let addr = self.direct_page(y);
+        self.set_direct(addr);
+    }
+
+    fn data_bank(&self, addr: u16) -> u32 {
+        (u32::from(self.dbr) << 16) | u32::from(addr)
+    }
+
+    // the index crosses banks, and costs a cycle when it crosses a page or
+    // is 16 bit, for the reads; writes always pay it
+    fn indexed(&mut self, base: u32, index: u16) {
+        self.addr = base.wrapping_add(u32::from(index)) & 0x00ff_ffff;
+        self.wraps = false;
+        let crossed = base & 0xffff_ff00 != self.addr & 0xffff_ff00;
+        if self.decoded.access == Access::Read && (crossed || self.index_wide()) {
+            self.ticks += 1;
+        }
+    }
+
+    fn direct_indirect(&mut self) {
+        let pointer = self.direct_page(0);
+        let addr = self.read16_bank0(pointer);
+        self.addr = self.data_bank(addr);
+        self.wraps = false;
+    }
+
+    fn direct_indirect_x(&mut self) {
+        let x = self.x;
+        let pointer = self.direct_page(x);
+        let addr = self.read16_bank0(pointer);
+        self.addr = self.data_bank(addr);
+        self.wraps = false;
+    }
+
+    fn direct_indirect_y(&mut self) {
+        let pointer = self.direct_page(0);
+        let addr = self.read16_bank0(pointer);
+        let (base, y) = (self.data_bank(addr), self.y);
+        self.indexed(base, y);
+    }
+
+    fn direct_indirect_long(&mut self) {
+        let pointer = self.direct_page(0);
+        self.addr = self.read24_bank0(pointer);
+        self.wraps = false;
+    }
+
+    fn direct_indirect_long_y(&mut self) {
+        let pointer = self.direct_page(0);
+        self.addr = self.read24_bank0(pointer).wrapping_add(u32::from(self.y)) & 0x00ff_ffff;
+        self.wraps = false;
+    }
+
+    // jmp and jsr only take the low word, in the program bank
+    fn absolute(&mut self) {
+        let addr = self.fetch16();
+        self.addr = self.data_bank(addr);
+        self.wraps = false;
+    }
+
+    fn absolute_x(&mut self) {
+        let addr = self.fetch16();
+        let (base, x) = (self.data_bank(addr), self.x);
+        self.indexed(base, x);
+    }
+
+    fn absolute_y(&mut self) {
+        let addr = self.fetch16();
+        let (base, y) = (self.data_bank(addr), self.y);
+        self.indexed(base, y);
+    }
+
+    fn absolute_long(&mut self) {
+        self.addr = self.fetch24();
+        self.wraps = false;
+    }
+
+    fn absolute_long_x(&mut self) {
+        self.addr = self.fetch24().wrapping_add(u32::from(self.x)) & 0x00ff_ffff;
+        self.wraps = false;
+    }
+
+    fn stack_relative(&mut self) {
+        let offset = u16::from(self.fetch8());
+        let addr = self.sp.wrapping_add(offset);
+        self.set_direct(addr);
+    }
+
+    fn stack_relative_indirect_y(&mut self) {
+        let offset = u16::from(self.fetch8());
+        let pointer = self.sp.wrapping_add(offset);
+        let addr = self.read16_bank0(pointer);
+        self.addr = self.data_bank(addr).wrapping_add(u32::from(self.y)) & 0x00ff_ffff;
+        self.wraps = false;
+    }
+
+    // the targets of the branches stay in the program bank
+    fn relative(&mut self) {
+        let offset = self.fetch8() as i8;
+        self.addr = u32::from(self.pc.wrapping_add(offset as u16));
+    }
+
+    fn relative_long(&mut self) {
+        let offset = self.fetch16();
+        self.addr = u32::from(self.pc.wrapping_add(offset));
+    }
+
+    fn absolute_indirect(&mut self) {
+        let pointer = self.fetch16();
+        self.addr = u32::from(self.read16_bank0(pointer));
+    }
+
+    // the table is in the program bank
+    fn absolute_indirect_x(&mut self) {
+        let pointer = self.fetch16().wrapping_add(self.x);
+        let bank = u32::from(self.pbr) << 16;
+        let low = u16::from(self.read8(bank | u32::from(pointer)));
+        let high = u16::from(self.read8(bank | u32::from(pointer.wrapping_add(1))));
+        self.addr = u32::from((high << 8) | low);
+    }
+
+    fn absolute_indirect_long(&mut self) {
+        let pointer = self.fetch16();
+        self.addr = self.read24_bank0(pointer);
+    }
+
+    // the destination bank then the source one
+    fn block_move(&mut self) {
+        let destination = u32::from(self.fetch8());
+        let source = u32::from(self.fetch8());
+        self.addr = (destination << 8) | source;
+    }
+
+    // adc and sbc, sbc adds the complement; in decimal mode each digit is
+    // adjusted in turn and v comes before the adjust of the top one
+    fn add(&mut self, data: u16, subtract: bool) {
+        let wide = self.memory_wide();
+        let (mask, digits) = if wide { (0xffff, 4) } else { (0x00ff, 2) };
+        let a = u32::from(self.a & mask);
+        let data = u32::from(data & mask);
+        let mut carry = u32::from(self.get_flag(CARRY));
+        let decimal = self.get_flag(DECIMAL);
+        let mut result = if decimal { 0 } else { a + data + carry };
+        let mut top = 0;
+        for digit in 0..digits {
+            if !decimal {
+                break;
+            }
+            let shift = 4 * digit;
+            let nibble = 0x0f << shift;
+            let limit = (0x10 << shift) - 1;
+            result = (a & nibble) + (data & nibble) + (carry << shift) + (result & (limit >> 4));
+            if digit == digits - 1 {
+                top = shift;
+                break;
+            }
+            if subtract && result <= limit {
+                result = result.wrapping_sub(0x06 << shift);
+            } else if !subtract && result > (0x0a << shift) - 1 {
+                result += 0x06 << shift;
+            }
+            carry = u32::from(result > limit);
+        }
+        let sign = if wide { 0x8000 } else { 0x80 };
+        self.set_flag(OVERFLOW, !(a ^ data) & (a ^ result) & sign != 0);
+        if decimal {
+            let limit = (0x10 << top) - 1;
+            if subtract && result <= limit {
+                result = result.wrapping_sub(0x06 << top);
+            } else if !subtract && result > (0x0a << top) - 1 {
+                result += 0x06 << top;
+            }
+        }
+        self.set_flag(CARRY, result > u32::from(mask));
+        self.load_a(result as u16);
+    }
+
+    fn adc(&mut self) {
+        let value = self.load();
+        self.add(value, false);
+    }
+
+    fn sbc(&mut self) {
+        let value = self.load();
+        self.add(!value, true);
+    }
+
+    fn and(&mut self) {
+        let value = self.load() & self.a;
+        self.load_a(value);
+    }
+
+    fn ora(&mut self) {
+        let value = self.load() | self.a;
+        self.load_a(value);
+    }
+
+    fn eor(&mut self) {
+        let value = self.load() ^ self.a;
+        self.load_a(value);
+    }
+
+    // bit #imm only sets z
+    fn bit(&mut self) {
+        let value = self.load();
+        let wide = self.memory_wide();
+        let sign = if wide { 0x8000 } else { 0x80 };
+        let mask = if wide { 0xffff } else { 0x00ff };
+        self.set_flag(ZERO, value & self.a & mask == 0);
+        if self.decoded.mode != "immediate" {
+            self.set_flag(SIGN, value & sign != 0);
+            self.set_flag(OVERFLOW, value & (sign >> 1) != 0);
+        }
+    }
+
+    fn compare(&mut self, register: u16) {
+        let value = self.load();
+        let wide = self.wide();
+        let mask = if wide { 0xffff } else { 0x00ff };
+        let register = register & mask;
+        self.set_flag(CARRY, register >= value);
+        self.set_nz(register.wrapping_sub(value), wide);
+    }
+
+    fn cmp(&mut self) {
+        let a = self.a;
+        self.compare(a);
+    }
+
+    fn cpx(&mut self) {
+        let x = self.x;
+        self.compare(x);
+    }
+
+    fn cpy(&mut self) {
+        let y = self.y;
+        self.compare(y);
+    }
+
+    fn lda(&mut self) {
+        let value = self.load();
+        self.load_a(value);
+    }
+
+    fn ldx(&mut self) {
+        let value = self.load();
+        self.load_x(value);
+    }
+
+    fn ldy(&mut self) {
+        let value = self.load();
+        self.load_y(value);
+    }
+
+    fn sta(&mut self) {
+        let a = self.a;
+        self.store(a);
+    }
+
+    fn stx(&mut self) {
+        let x = self.x;
+        self.store(x);
+    }
+
+    fn sty(&mut self) {
+        let y = self.y;
+        self.store(y);
+    }
+
+    fn stz(&mut self) {
+        self.store(0);
+    }
+
+    fn top_bit(&self) -> u16 {
+        if self.wide() {
+            0x8000
+        } else {
+            0x0080
+        }
+    }
+
+    fn asl(&mut self) {
+        let value = self.modify_operand();
+        let top = self.top_bit();
+        self.set_flag(CARRY, value & top != 0);
+        self.write_back(value << 1);
+    }
+
+    fn lsr(&mut self) {
+        let value = self.modify_operand() & if self.wide() { 0xffff } else { 0x00ff };
+        self.set_flag(CARRY, value & 1 != 0);
+        self.write_back(value >> 1);
+    }
+
+    fn rol(&mut self) {
+        let value = self.modify_operand();
+        let top = self.top_bit();
+        let carry = u16::from(self.get_flag(CARRY));
+        self.set_flag(CARRY, value & top != 0);
+        self.write_back((value << 1) | carry);
+    }
+
+    fn ror(&mut self) {
+        let value = self.modify_operand() & if self.wide() { 0xffff } else { 0x00ff };
+        let carry = if self.get_flag(CARRY) {
+            self.top_bit()
+        } else {
+            0
+        };
+        self.set_flag(CARRY, value & 1 != 0);
+        self.write_back((value >> 1) | carry);
+    }
+
+    fn inc(&mut self) {
+        let value = self.modify_operand();
+        self.write_back(value.wrapping_add(1));
+    }
+
+    fn dec(&mut self) {
+        let value = self.modify_operand();
+        self.write_back(value.wrapping_sub(1));
+    }
+
+    // z from the bits a and memory have in common, then those bits cleared
+    // or set in memory
+    fn trb(&mut self) {
+        let value = self.load();
+        let mask = if self.wide() { 0xffff } else { 0x00ff };
+        self.set_flag(ZERO, value & self.a & mask == 0);
+        let a = self.a;
+        self.store(value & !a);
+    }
+
+    fn tsb(&mut self) {
+        let value = self.load();
+        let mask = if self.wide() { 0xffff } else { 0x00ff };
+        self.set_flag(ZERO, value & self.a & mask == 0);
+        let a = self.a;
+        self.store(value | a);
+    }
+
+    fn inx(&mut self) {
+        let x = self.x.wrapping_add(1);
+        self.load_x(x);
+    }
+
+    fn iny(&mut self) {
+        let y = self.y.wrapping_add(1);
+        self.load_y(y);
+    }
+
+    fn dex(&mut self) {
+        let x = self.x.wrapping_sub(1);
+        self.load_x(x);
+    }
+
+    fn dey(&mut self) {
+        let y = self.y.wrapping_sub(1);
+        self.load_y(y);
+    }
+
+    fn tax(&mut self) {
+        let a = self.a;
+        self.load_x(a);
+    }
+
+    fn tay(&mut self) {
+        let a = self.a;
+        self.load_y(a);
+    }
+
+    fn txa(&mut self) {
+        let x = self.x;
+        self.load_a(x);
+    }
+
+    fn tya(&mut self) {
+        let y = self.y;
+        self.load_a(y);
+    }
+
+    fn txy(&mut self) {
+        let x = self.x;
+        self.load_y(x);
+    }
+
+    fn tyx(&mut self) {
+        let y = self.y;
+        self.load_x(y);
+    }
+
+    fn tsx(&mut self) {
+        let sp = self.sp;
+        self.load_x(sp);
+    }
+
+    fn txs(&mut self) {
+        self.sp = self.x;
+        self.fix_status();
+    }
+
+    // the 16 bit transfers, whatever the width of a
+    fn tcd(&mut self) {
+        self.dp = self.a;
+        let dp = self.dp;
+        self.set_nz(dp, true);
+    }
+
+    fn tdc(&mut self) {
+        self.a = self.dp;
+        let a = self.a;
+        self.set_nz(a, true);
+    }
+
+    fn tcs(&mut self) {
+        self.sp = self.a;
+        self.fix_status();
+    }
+
+    fn tsc(&mut self) {
+        self.a = self.sp;
+        let a = self.a;
+        self.set_nz(a, true);
+    }
+
+    // n and z from the new low byte
+    fn xba(&mut self) {
+        self.a = self.a.rotate_left(8);
+        let a = self.a;
+        self.set_nz(a, false);
+    }
+
+    // the carry and the emulation mode swap
+    fn xce(&mut self) {
+        let carry = self.get_flag(CARRY);
+        let emulation = self.emulation;
+        self.set_flag(CARRY, emulation);
+        self.emulation = carry;
+        self.fix_status();
+    }
+
+    fn rep(&mut self) {
+        let value = self.load() as u8;
+        self.status &= !value;
+        self.fix_status();
+    }
+
+    fn sep(&mut self) {
+        let value = self.load() as u8;
+        self.status |= value;
+        self.fix_status();
+    }
+
+    fn pha(&mut self) {
+        let (a, wide) = (self.a, self.memory_wide());
+        self.push_register(a, wide);
+    }
+
+    fn phx(&mut self) {
+        let (x, wide) = (self.x, self.index_wide());
+        self.push_register(x, wide);
+    }
+
+    fn phy(&mut self) {
+        let (y, wide) = (self.y, self.index_wide());
+        self.push_register(y, wide);
+    }
+
+    fn pla(&mut self) {
+        let wide = self.memory_wide();
+        let value = self.pull_register(wide);
+        self.load_a(value);
+    }
+
+    fn plx(&mut self) {
+        let wide = self.index_wide();
+        let value = self.pull_register(wide);
+        self.load_x(value);
+    }
+
+    fn ply(&mut self) {
+        let wide = self.index_wide();
+        let value = self.pull_register(wide);
+        self.load_y(value);
+    }
+
+    // the break bit reads as set in the emulation mode
+    fn php(&mut self) {
+        let status = self.status;
+        self.push8(status);
+    }
+
+    fn plp(&mut self) {
+        self.status = self.pull8();
+        self.fix_status();
+    }
+
+    fn phb(&mut self) {
+        let dbr = self.dbr;
+        self.push8(dbr);
+    }
+
+    fn plb(&mut self) {
+        self.dbr = self.pull8();
+        let dbr = u16::from(self.dbr);
+        self.set_nz(dbr, false);
+    }
+
+    fn phd(&mut self) {
+        let dp = self.dp;
+        self.push16(dp);
+    }
+
+    fn pld(&mut self) {
+        self.dp = self.pull16();
+        let dp = self.dp;
+        self.set_nz(dp, true);
+    }
+
+    fn phk(&mut self) {
+        let pbr = self.pbr;
+        self.push8(pbr);
+    }
+
+    fn pea(&mut self) {
+        let value = self.load();
+        self.push16(value);
+    }
+
+    // the word at the direct page address
+    fn pei(&mut self) {
+        let value = self.load();
+        self.push16(value);
+    }
+
+    fn per(&mut self) {
+        let addr = self.addr as u16;
+        self.push16(addr);
+    }
+
+    // a cycle more when taken, and another for a page crossed in the
+    // emulation mode
+    fn branch(&mut self, taken: bool) {
+        if taken {
+            let target = self.addr as u16;
+            self.ticks += 1;
+            if self.emulation && self.pc & 0xff00 != target & 0xff00 {
+                self.ticks += 1;
+            }
+            self.pc = target;
+        }
+    }
+
+    fn bcc(&mut self) {
+        let taken = !self.get_flag(CARRY);
+        self.branch(taken);
+    }
+
+    fn bcs(&mut self) {
+        let taken = self.get_flag(CARRY);
+        self.branch(taken);
+    }
+
+    fn beq(&mut self) {
+        let taken = self.get_flag(ZERO);
+        self.branch(taken);
+    }
+
+    fn bne(&mut self) {
+        let taken = !self.get_flag(ZERO);
+        self.branch(taken);
+    }
+
+    fn bmi(&mut self) {
+        let taken = self.get_flag(SIGN);
+        self.branch(taken);
+    }
+
+    fn bpl(&mut self) {
+        let taken = !self.get_flag(SIGN);
+        self.branch(taken);
+    }
+
+    fn bvc(&mut self) {
+        let taken = !self.get_flag(OVERFLOW);
+        self.branch(taken);
+    }
+
+    fn bvs(&mut self) {
+        let taken = self.get_flag(OVERFLOW);
+        self.branch(taken);
+    }
+
+    fn bra(&mut self) {
+        self.branch(true);
+    }
+
+    fn brl(&mut self) {
+        self.pc = self.addr as u16;
+    }
+
+    fn jmp(&mut self) {
+        self.pc = self.addr as u16;
+    }
+
+    fn jml(&mut self) {
+        self.pbr = (self.addr >> 16) as u8;
+        self.pc = self.addr as u16;
+    }
+
+    fn jsr(&mut self) {
+        let pc = self.pc.wrapping_sub(1);
+        self.push16(pc);
+        self.pc = self.addr as u16;
+    }
+
+    fn jsl(&mut self) {
+        let (pbr, pc) = (self.pbr, self.pc.wrapping_sub(1));
+        self.push8(pbr);
+        self.push16(pc);
+        self.jml();
+    }
+
+    fn rts(&mut self) {
+        self.pc = self.pull16().wrapping_add(1);
+    }
+
+    fn rtl(&mut self) {
+        self.pc = self.pull16().wrapping_add(1);
+        self.pbr = self.pull8();
+    }
+
+    fn rti(&mut self) {
+        self.status = self.pull8();
+        self.fix_status();
+        self.pc = self.pull16();
+        if !self.emulation {
+            self.pbr = self.pull8();
+            self.ticks += 1;
+        }
+    }
+
+    // the byte after brk and cop is skipped, rti returns past it
+    fn brk(&mut self) {
+        self.interrupt(0xffe6, 0xfffe, true);
+    }
+
+    fn cop(&mut self) {
+        self.interrupt(0xffe4, 0xfff4, true);
+    }
+
+    // a byte per run, going back to itself until a wraps to $FFFF; the data
+    // bank is left on the destination
+    fn block(&mut self, step: u16) {
+        let (destination, source) = ((self.addr >> 8) as u8, self.addr as u8);
+        let from = (u32::from(source) << 16) | u32::from(self.x);
+        let value = self.read8(from);
+        let to = (u32::from(destination) << 16) | u32::from(self.y);
+        self.write8(to, value);
+        self.dbr = destination;
+        self.x = self.index(self.x.wrapping_add(step));
+        self.y = self.index(self.y.wrapping_add(step));
+        self.a = self.a.wrapping_sub(1);
+        if self.a != 0xffff {
+            self.pc = self.pc.wrapping_sub(3);
+        }
+    }
+
+    fn mvn(&mut self) {
+        self.block(1);
+    }
+
+    fn mvp(&mut self) {
+        self.block(0xffff);
+    }
+
+    fn clc(&mut self) {
+        self.set_flag(CARRY, false);
+    }
+
+    fn cld(&mut self) {
+        self.set_flag(DECIMAL, false);
+    }
+
+    fn cli(&mut self) {
+        self.set_flag(INTERRUPT, false);
+    }
+
+    fn clv(&mut self) {
+        self.set_flag(OVERFLOW, false);
+    }
+
+    fn sec(&mut self) {
+        self.set_flag(CARRY, true);
+    }
+
+    fn sed(&mut self) {
+        self.set_flag(DECIMAL, true);
+    }
+
+    fn sei(&mut self) {
+        self.set_flag(INTERRUPT, true);
+    }
+
+    fn nop(&mut self) {}
+
+    // reserved for a future extension, a two byte nop
+    fn wdm(&mut self) {}
+
+    fn wai(&mut self) {
+        self.waiting = true;
+    }
+
+    fn stp(&mut self) {
+        self.stopped = true;
+    }
+}
+
+impl<T: AddressBusIO<u32, u8>> Clock for W65C816<T> {
+    fn step(&mut self) -> u64 {
+        // the clock keeps running on a stopped or waiting cpu
+        if self.stopped || (self.waiting && !self.wake()) {
+            self.ticks += 1;
+            return 1;
+        }
+        let ticks = self.ticks;
+        if self.service_interrupts() {
+            return self.ticks - ticks;
+        }
+        let opcode = self.fetch8();
+        self.decoded = self.opcodes[opcode as usize];
+        self.accumulator = false;
+        opcode_table!(dispatch!(self, opcode));
+        self.ticks += u64::from(self.decoded.cycles);
+        self.ticks - ticks
+    }
+}
+
+// the emulation mode, with 8 bit registers and the stack in page 1
+impl<T: AddressBusIO<u32, u8>> Reset for W65C816<T> {
+    fn reset(&mut self) {
+        self.emulation = true;
+        self.status |= MEMORY | INDEX | INTERRUPT;
+        self.status &= !DECIMAL;
+        self.fix_status();
+        self.dp = 0;
+        self.pbr = 0;
+        self.dbr = 0;
+        self.waiting = false;
+        self.stopped = false;
+        self.nmi_pending = false;
+        self.pc = self.read16_bank0(0xfffc);
+        self.ticks += 7;
+    }
+}
+
+impl<T: AddressBusIO<u32, u8>> Interrupt<u32> for W65C816<T> {
+    // line 4: IRQ, line 6: NMI, line 40: RESET, as the 6502 ones
+    fn raise(&mut self, line: u32) {
+        if self.stopped && line != 40 {
+            return;
+        }
+        match line {
+            4 => {
+                self.waiting = false;
+                if !self.get_flag(INTERRUPT) {
+                    self.interrupt(0xffee, 0xfffe, false);
+                    self.ticks += 7;
+                }
+            }
+            6 => {
+                self.waiting = false;
+                self.interrupt(0xffea, 0xfffa, false);
+                self.ticks += 7;
+            }
+            40 => self.reset(),
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,168 @@
+use ram::Ram;
+use w65c816::{BREAK, CARRY, W65C816};
+use {AddressBusIO, Clock, Reset};
+
+// the program at $8000 of bank 0 and the reset vector to it
+fn cpu(program: Vec<u8>) -> W65C816<Ram<u8>> {
+    let mut ram = Ram::new(0x0100_0000);
+    ram.fill(vec![0x00, 0x80], 0xfffc);
+    ram.fill(program, 0x8000);
+    let mut cpu = W65C816::new(ram);
+    cpu.reset();
+    cpu
+}
+
+#[test]
+fn test_native_mode() {
+    let mut cpu = cpu(vec![
+        0x18, // clc
+        0xfb, // xce
+        0xc2, 0x30, // rep #$30
+        0xa9, 0x34, 0x12, // lda #$1234
+        0x8d, 0x00, 0x20, // sta $2000
+        0xe2, 0x20, // sep #$20
+        0xa9, 0xff, // lda #$ff
+    ]);
+    assert!(cpu.emulation);
+    let cycles: Vec<u64> = (0..5).map(|_| cpu.step()).collect();
+    assert_eq!(cycles, vec![2, 2, 3, 3, 5]);
+    assert!(!cpu.emulation && cpu.get_flag(CARRY));
+    assert_eq!(cpu.bus_mut().read(0x2000), 0x34);
+    assert_eq!(cpu.bus_mut().read(0x2001), 0x12);
+    // b is kept with an 8 bit accumulator
+    cpu.step();
+    assert_eq!(cpu.step(), 2);
+    assert_eq!(cpu.a, 0x12ff);
+}
+
+#[test]
+fn test_long_addressing() {
+    let mut cpu = cpu(vec![
+        0xa9, 0x56, // lda #$56
+        0x8f, 0x56, 0x34, 0x12, // sta $123456
+        0xa2, 0x02, // ldx #$02
+        0xa9, 0x00, // lda #$00
+        0xbf, 0x54, 0x34, 0x12, // lda $123454,x
+        0xa9, 0x12, // lda #$12
+        0x48, // pha
+        0xab, // plb
+        0xad, 0x56, 0x34, // lda $3456
+    ]);
+    cpu.step();
+    assert_eq!(cpu.step(), 5);
+    assert_eq!(cpu.bus_mut().read(0x0012_3456), 0x56);
+    cpu.step();
+    cpu.step();
+    assert_eq!(cpu.step(), 5);
+    assert_eq!(cpu.a, 0x56);
+    for _ in 0..4 {
+        cpu.step();
+    }
+    // through the data bank
+    assert_eq!(cpu.dbr, 0x12);
+    assert_eq!(cpu.a, 0x56);
+}
+
+#[test]
+fn test_block_move() {
+    let mut cpu = cpu(vec![
+        0x18, 0xfb, // clc, xce
+        0xc2, 0x30, // rep #$30
+        0xa2, 0x00, 0x10, // ldx #$1000
+        0xa0, 0x00, 0x20, // ldy #$2000
+        0xa9, 0x02, 0x00, // lda #$0002
+        0x54, 0x01, 0x00, // mvn $01,$00
+        0xea, // nop
+    ]);
+    cpu.bus_mut().write(0x1000, 0xaa);
+    cpu.bus_mut().write(0x1001, 0xbb);
+    cpu.bus_mut().write(0x1002, 0xcc);
+    for _ in 0..6 {
+        cpu.step();
+    }
+    // a byte a step, the count in a
+    assert_eq!(cpu.pc, 0x800d);
+    assert_eq!(cpu.step() + cpu.step() + cpu.step(), 21);
+    assert_eq!(cpu.pc, 0x8010);
+    assert_eq!(cpu.a, 0xffff);
+    assert_eq!((cpu.x, cpu.y, cpu.dbr), (0x1003, 0x2003, 0x01));
+    assert_eq!(cpu.bus_mut().read(0x0001_2000), 0xaa);
+    assert_eq!(cpu.bus_mut().read(0x0001_2002), 0xcc);
+}
+
+#[test]
+fn test_decimal_mode() {
+    let mut cpu = cpu(vec![
+        0xf8, // sed
+        0x38, // sec
+        0xa9, 0x10, // lda #$10
+        0xe9, 0x01, // sbc #$01
+        0x18, 0xfb, // clc, xce
+        0xc2, 0x20, // rep #$20
+        0x18, // clc
+        0xa9, 0x99, 0x19, // lda #$1999
+        0x69, 0x01, 0x00, // adc #$0001
+        0x69, 0x00, 0x80, // adc #$8000
+    ]);
+    for _ in 0..4 {
+        cpu.step();
+    }
+    assert_eq!(cpu.a, 0x09);
+    assert!(cpu.get_flag(CARRY));
+    for _ in 0..6 {
+        cpu.step();
+    }
+    assert_eq!(cpu.a, 0x2000);
+    assert!(!cpu.get_flag(CARRY));
+    cpu.step();
+    assert_eq!(cpu.a, 0x0000);
+    assert!(cpu.get_flag(CARRY));
+}
+
+#[test]
+fn test_interrupts() {
+    let mut cpu = cpu(vec![
+        0x00, 0xea, // brk
+    ]);
+    cpu.bus_mut().write(0xfffe, 0x00);
+    cpu.bus_mut().write(0xffff, 0x90);
+    cpu.bus_mut().write(0xffe6, 0x00);
+    cpu.bus_mut().write(0xffe7, 0xa0);
+    assert_eq!(cpu.step(), 7);
+    assert_eq!(cpu.pc, 0x9000);
+    assert_eq!(cpu.sp, 0x01fc);
+    assert_eq!(cpu.bus_mut().read(0x01ff), 0x80);
+    assert_eq!(cpu.bus_mut().read(0x01fe), 0x02);
+    assert!(cpu.bus_mut().read(0x01fd) & BREAK != 0);
+
+    // the stack stays in page 1 in the emulation mode
+    cpu.sp = 0x0100;
+    cpu.pc = 0x8000;
+    cpu.step();
+    assert_eq!(cpu.sp, 0x01fd);
+
+    // the native mode pushes the program bank and has a vector for brk
+    cpu.emulation = false;
+    cpu.sp = 0x0100;
+    cpu.pc = 0x8000;
+    assert_eq!(cpu.step(), 8);
+    assert_eq!(cpu.pc, 0xa000);
+    assert_eq!(cpu.sp, 0x00fc);
+}
+
+#[test]
+fn test_long_subroutines() {
+    let mut cpu = cpu(vec![
+        0x22, 0x00, 0x00, 0x02, // jsl $020000
+        0xea, // nop
+    ]);
+    cpu.bus_mut().write(0x0002_0000, 0x6b); // rtl
+    assert_eq!(cpu.step(), 8);
+    assert_eq!((cpu.pbr, cpu.pc), (0x02, 0x0000));
+    assert_eq!(cpu.program_counter(), 0x0002_0000);
+    assert_eq!(cpu.bus_mut().read(0x01ff), 0x00);
+    assert_eq!(cpu.bus_mut().read(0x01fe), 0x80);
+    assert_eq!(cpu.bus_mut().read(0x01fd), 0x03);
+    assert_eq!(cpu.step(), 6);
+    assert_eq!((cpu.pbr, cpu.pc), (0x00, 0x8004));
+}
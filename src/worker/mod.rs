@@ -0,0 +1,162 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+
+use {Address, AddressBusIO, Clock, Data};
+
+// a video or audio chip stepped once per cycle on its own thread
+pub trait Chip<T: Address, U: Data>: AddressBusIO<T, U> + Clock + Send + 'static {}
+
+impl<T: Address, U: Data, C: AddressBusIO<T, U> + Clock + Send + 'static> Chip<T, U> for C {}
+
+struct Slice<T: Address, U: Data> {
+    cycles: u64,
+    writes: Vec<(u64, T, U)>,
+}
+
+struct State<T: Address, U: Data, C: Chip<T, U>> {
+    // taken by the thread while it runs a slice
+    chip: Option<C>,
+    slice: Option<Slice<T, U>>,
+    writes: Vec<(u64, T, U)>,
+    quit: bool,
+}
+
+struct Shared<T: Address, U: Data, C: Chip<T, U>> {
+    state: Mutex<State<T, U, C>>,
+    changed: Condvar,
+    // where the cpu is in the slice, stamped on every write
+    cycle: AtomicU64,
+}
+
+impl<T: Address, U: Data, C: Chip<T, U>> Shared<T, U, C> {
+    fn idle(&self) -> MutexGuard<'_, State<T, U, C>> {
+        let mut state = self.state.lock().unwrap();
+        while state.chip.is_none() || state.slice.is_some() {
+            state = self.changed.wait(state).unwrap();
+        }
+        state
+    }
+}
+
+fn run<T: Address, U: Data, C: Chip<T, U>>(shared: &Shared<T, U, C>) {
+    loop {
+        let (mut chip, slice) = {
+            let mut state = shared.state.lock().unwrap();
+            while state.slice.is_none() && !state.quit {
+                state = shared.changed.wait(state).unwrap();
+            }
+            if state.quit {
+                return;
+            }
+            (state.chip.take().unwrap(), state.slice.take().unwrap())
+        };
+        let mut writes = slice.writes.into_iter().peekable();
+        let mut cycle = 0;
+        while cycle < slice.cycles {
+            while let Some(&(_, address, value)) = writes.peek().filter(|write| write.0 <= cycle) {
+                chip.write(address, value);
+                writes.next();
+            }
+            // a chip taking several cycles a step sees the writes late
+            cycle += chip.step().max(1);
+        }
+        // stamped past the end of the slice
+        for (_, address, value) in writes {
+            chip.write(address, value);
+        }
+        shared.state.lock().unwrap().chip = Some(chip);
+        shared.changed.notify_all();
+    }
+}
+
+// runs a chip one slice (a scanline or a frame) behind the cpu: the writes
+// of a slice are replayed at the same cycles once the cpu reaches the
+// barrier, so the result does not depend on the thread scheduling
+pub struct Worker<T: Address, U: Data, C: Chip<T, U>> {
+    shared: Arc<Shared<T, U, C>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl<T: Address, U: Data, C: Chip<T, U>> Worker<T, U, C> {
+    pub fn new(chip: C) -> Worker<T, U, C> {
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State {
+                chip: Some(chip),
+                slice: None,
+                writes: Vec::new(),
+                quit: false,
+            }),
+            changed: Condvar::new(),
+            cycle: AtomicU64::new(0),
+        });
+        let thread = {
+            let shared = shared.clone();
+            thread::spawn(move || run(&*shared))
+        };
+        Worker {
+            shared,
+            thread: Some(thread),
+        }
+    }
+
+    // the cpu side of the chip, to be mapped on the bus
+    pub fn port(&self) -> Port<T, U, C> {
+        Port {
+            shared: self.shared.clone(),
+        }
+    }
+
+    // cycles since the start of the current slice
+    pub fn set_cycle(&self, cycle: u64) {
+        self.shared.cycle.store(cycle, Ordering::Relaxed);
+    }
+
+    // the barrier: waits for the previous slice and starts this one in the
+    // background, the cycle goes back to 0
+    pub fn run(&mut self, cycles: u64) {
+        let mut state = self.shared.idle();
+        let writes = state.writes.drain(..).collect();
+        state.slice = Some(Slice { cycles, writes });
+        self.set_cycle(0);
+        self.shared.changed.notify_all();
+    }
+
+    // waits for the running slice, for rendering or saving the chip
+    pub fn with<R, F: FnOnce(&mut C) -> R>(&mut self, f: F) -> R {
+        let mut state = self.shared.idle();
+        f(state.chip.as_mut().unwrap())
+    }
+}
+
+impl<T: Address, U: Data, C: Chip<T, U>> Drop for Worker<T, U, C> {
+    fn drop(&mut self) {
+        self.shared.state.lock().unwrap().quit = true;
+        self.shared.changed.notify_all();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+// writes are queued for the next slice, reads see the chip as it was at the
+// end of the previous one
+pub struct Port<T: Address, U: Data, C: Chip<T, U>> {
+    shared: Arc<Shared<T, U, C>>,
+}
+
+impl<T: Address, U: Data, C: Chip<T, U>> AddressBusIO<T, U> for Port<T, U, C> {
+    fn read(&mut self, address: T) -> U {
+        let mut state = self.shared.idle();
+        state.chip.as_mut().unwrap().read(address)
+    }
+
+    fn write(&mut self, address: T, value: U) {
+        let cycle = self.shared.cycle.load(Ordering::Relaxed);
+        let mut state = self.shared.state.lock().unwrap();
+        state.writes.push((cycle, address, value));
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,52 @@
+use worker::Worker;
+use {AddressBusIO, Clock};
+
+#[derive(Default)]
+struct Counter {
+    cycles: u64,
+    writes: Vec<(u64, u8)>,
+}
+
+impl AddressBusIO<u16, u8> for Counter {
+    fn read(&mut self, _address: u16) -> u8 {
+        self.cycles as u8
+    }
+
+    fn write(&mut self, _address: u16, value: u8) {
+        self.writes.push((self.cycles, value));
+    }
+}
+
+impl Clock for Counter {
+    fn step(&mut self) -> u64 {
+        self.cycles += 1;
+        1
+    }
+}
+
+#[test]
+fn test_worker_replays_writes_at_their_cycle() {
+    let mut worker = Worker::new(Counter::default());
+    let mut port = worker.port();
+    let mut seen = Vec::new();
+    for slice in 0..4 {
+        for cycle in 0..10 {
+            worker.set_cycle(cycle);
+            if cycle % 3 == 0 {
+                port.write(0, slice * 10 + cycle as u8);
+            }
+        }
+        seen.push(port.read(0));
+        worker.run(10);
+    }
+    // reads see the end of the previous slice
+    assert_eq!(seen, vec![0, 10, 20, 30]);
+    worker.with(|counter| {
+        assert_eq!(counter.cycles, 40);
+        assert_eq!(counter.writes.len(), 16);
+        assert!(counter
+            .writes
+            .iter()
+            .all(|&(cycle, value)| cycle == u64::from(value)));
+    });
+}